@@ -0,0 +1,6 @@
+fn main() {
+    // Node resolves the symbols of its API when it loads the bindings, which some linkers need
+    // to be told about
+    #[cfg(feature = "node")]
+    napi_build::setup();
+}
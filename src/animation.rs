@@ -0,0 +1,110 @@
+use crate::cli_app::Args;
+use crate::error::StringArtError;
+#[cfg(feature = "gif")]
+use crate::frame_sink::GifSink;
+use crate::geometry::Point;
+#[cfg(feature = "gif")]
+use crate::imagery::RefImage;
+use crate::optimum::Constraints;
+use crate::style;
+use crate::style::Data;
+use crate::template;
+use crate::term;
+use image::DynamicImage;
+
+/// The arguments for one frame of several, with each output filepath numbered by the frame
+pub fn frame_args(args: &Args, frame: usize, image: DynamicImage) -> Args {
+    Args {
+        output_filepaths: args
+            .output_filepaths
+            .iter()
+            .map(|f| template::per_frame(f))
+            .collect(),
+        svg_filepath: args.svg_filepath.as_deref().map(template::per_frame),
+        data_filepath: args.data_filepath.as_deref().map(template::per_frame),
+        gif_filepath: args.gif_filepath.as_deref().map(template::per_frame),
+        video_filepath: args.video_filepath.as_deref().map(template::per_frame),
+        instructions_filepath: args
+            .instructions_filepath
+            .as_deref()
+            .map(template::per_frame),
+        event_log_filepath: args.event_log_filepath.as_deref().map(template::per_frame),
+        simulate_filepath: args.simulate_filepath.as_deref().map(template::per_frame),
+        explain_filepath: args.explain_filepath.as_deref().map(template::per_frame),
+        milestones_filepath: args.milestones_filepath.as_deref().map(template::per_frame),
+        frame: Some(frame),
+        image,
+        frames: Vec::new(),
+        ..args.clone()
+    }
+}
+
+/// Create string art for each frame of an animated input. Each frame starts from the strings of
+/// the previous frame, so consecutive frames stay similar.
+pub fn animate(pin_locations: Vec<Point>, args: Args) -> Result<Vec<Data>, StringArtError> {
+    let mut datas: Vec<Data> = Vec::new();
+    for (i, (image, _)) in args.frames.iter().enumerate() {
+        term::info(
+            args.verbosity,
+            &format!("Frame {} of {}", i + 1, args.frames.len()),
+        );
+        let frame_args = frame_args(&args, i, image.clone());
+        let starting_segments = datas
+            .last()
+            .map(|d| d.line_segments.clone())
+            .unwrap_or_default();
+        datas.push(style::color_on_custom(
+            pin_locations.clone(),
+            frame_args,
+            starting_segments,
+            &Constraints::default(),
+            Vec::new(),
+        )?);
+    }
+
+    #[cfg(feature = "gif")]
+    if let (Some(filepath), Some(last)) = (&args.animation_filepath, datas.last()) {
+        let mut sink = GifSink::create(&template::expand(filepath, last))?;
+        for (data, (_, delay)) in datas.iter().zip(&args.frames) {
+            sink.add(RefImage::from(data).color(), *delay)?;
+        }
+    }
+
+    Ok(datas)
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::cli_app::Cli;
+    use crate::pins;
+    use clap::Parser;
+    use image::Delay;
+
+    #[test]
+    fn test_animate_renders_each_frame() {
+        let cli = Cli::parse_from(["string_art", "-i", "in.gif", "-c", "4", "-m", "3"]);
+        let frame = || {
+            let mut i = DynamicImage::new_rgb8(10, 10).to_rgb8();
+            i.enumerate_pixels_mut()
+                .filter(|(x, y, _)| x == y)
+                .for_each(|(_, _, p)| *p = image::Rgb([255; 3]));
+            (
+                DynamicImage::ImageRgb8(i),
+                Delay::from_numer_denom_ms(100, 1),
+            )
+        };
+        let mut args = Args::with_image(cli, frame().0);
+        args.frames = vec![frame(), frame()];
+        let pins = pins::generate(&args.pin_arrangement, args.pin_count, 10, 10);
+
+        let datas = animate(pins, args).unwrap();
+
+        assert_eq!(
+            vec![Some(0), Some(1)],
+            datas.iter().map(|d| d.args.frame).collect::<Vec<_>>()
+        );
+        // The second frame is identical, so starting from the first frame's strings is optimal
+        assert_eq!(datas[0].line_segments, datas[1].line_segments);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::error::StringArtError;
+use crate::provenance;
+use crate::serde::Serialize;
+use crate::style::Data;
+use crate::template;
+use std::io::{Seek, Write};
+use zip::result::{ZipError, ZipResult};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+/// One file in the archive, as listed in its manifest
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ManifestEntry {
+    pub name: String,
+    /// What the file is, such as `output` or `instructions`
+    pub kind: &'static str,
+    pub bytes: usize,
+    pub sha256: String,
+}
+
+/// Lists the files of an archive, and where the run came from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Manifest {
+    pub version: String,
+    pub input_sha256: Option<String>,
+    pub files: Vec<ManifestEntry>,
+}
+
+/// Every file the finished runs were asked to write, with what each one is. Files that were not
+/// written, such as a gif when gifs are not supported, are left out.
+pub fn artifacts(datas: &[Data]) -> Vec<(&'static str, String)> {
+    let mut artifacts: Vec<(&'static str, String)> = Vec::new();
+    for data in datas {
+        for (kind, filepath) in data.args.outputs() {
+            let filepath = template::expand(filepath, data);
+            if std::path::Path::new(&filepath).is_file()
+                && artifacts.iter().all(|(_, other)| other != &filepath)
+            {
+                artifacts.push((kind, filepath));
+            }
+        }
+    }
+    artifacts
+}
+
+/// Bundle every file written by the finished runs into a zip archive, along with a
+/// `manifest.json` describing them
+pub fn write(datas: &[Data], archive_filepath: &str) -> Result<(), StringArtError> {
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut entries = Vec::new();
+    for (kind, filepath) in artifacts(datas) {
+        if kind == "archive" {
+            continue;
+        }
+        let bytes = std::fs::read(&filepath).map_err(|source| StringArtError::Read {
+            filepath: filepath.clone(),
+            source,
+        })?;
+        let name = std::path::Path::new(&filepath)
+            .file_name()
+            .map_or(filepath.clone(), |name| name.to_string_lossy().into_owned());
+        if files.iter().any(|(other, _)| other == &name) {
+            continue;
+        }
+        entries.push(ManifestEntry {
+            name: name.clone(),
+            kind,
+            bytes: bytes.len(),
+            sha256: provenance::hex(&provenance::sha256(&bytes)),
+        });
+        files.push((name, bytes));
+    }
+    let manifest = Manifest {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        input_sha256: datas
+            .first()
+            .and_then(|data| data.provenance.input_sha256.clone()),
+        files: entries,
+    };
+    files.push((
+        "manifest.json".to_owned(),
+        serde_json::to_vec_pretty(&manifest)
+            .map_err(|error| StringArtError::encode(archive_filepath, error))?,
+    ));
+
+    let write = |error| StringArtError::write(archive_filepath, error);
+    let file = std::fs::File::create(archive_filepath).map_err(write)?;
+    let mut writer = zip(std::io::BufWriter::new(file), &files).map_err(|error| match error {
+        ZipError::Io(error) => write(error),
+        error => StringArtError::encode(archive_filepath, error),
+    })?;
+    writer.flush().map_err(write)
+}
+
+/// Write a zip archive of the named files to `writer`, each compressed with deflate. Every file
+/// is dated 1980-01-01, the earliest date a zip file can hold, so archives of the same files are
+/// identical.
+fn zip<W: Write + Seek>(writer: W, files: &[(String, Vec<u8>)]) -> ZipResult<W> {
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(DateTime::default());
+    let mut zip = ZipWriter::new(writer);
+    for (name, bytes) in files {
+        zip.start_file(name, options)?;
+        zip.write_all(bytes)?;
+    }
+    zip.finish()
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use std::io::{Cursor, Read};
+
+    /// Read the files back in the order they were written
+    fn unzip(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+        (0..archive.len())
+            .map(|i| {
+                let mut file = archive.by_index(i).unwrap();
+                assert_eq!(CompressionMethod::Deflated, file.compression());
+                let mut bytes = Vec::new();
+                file.read_to_end(&mut bytes).unwrap();
+                (file.name().to_owned(), bytes)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_zip_round_trip() {
+        let files = vec![
+            ("a.txt".to_owned(), b"string art".to_vec()),
+            ("empty".to_owned(), Vec::new()),
+            ("b.bin".to_owned(), (0..=255u8).cycle().take(5000).collect()),
+        ];
+        let archive = zip(Cursor::new(Vec::new()), &files).unwrap().into_inner();
+        assert_eq!(files, unzip(&archive));
+        // The same files always make the same archive
+        assert_eq!(
+            archive,
+            zip(Cursor::new(Vec::new()), &files).unwrap().into_inner()
+        );
+    }
+
+    #[test]
+    fn test_write_archive_with_manifest() {
+        let dir = std::env::temp_dir();
+        let output = dir.join("string_art_archive_output.png");
+        let instructions = dir.join("string_art_archive_instructions.txt");
+        let archive = dir.join("string_art_archive.zip");
+        std::fs::write(&output, b"not really a png").unwrap();
+        std::fs::write(&instructions, b"wind the strings").unwrap();
+
+        let mut data = Data::example(&[]);
+        data.args.output_filepaths = vec![output.to_str().unwrap().to_owned()];
+        data.args.instructions_filepath = Some(instructions.to_str().unwrap().to_owned());
+        data.args.data_filepath = Some(dir.join("not_written.json").to_str().unwrap().to_owned());
+        write(&[data], archive.to_str().unwrap()).unwrap();
+        let files = unzip(&std::fs::read(&archive).unwrap());
+        [output, instructions, archive]
+            .iter()
+            .for_each(|path| std::fs::remove_file(path).unwrap());
+
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            vec![
+                "string_art_archive_output.png",
+                "string_art_archive_instructions.txt",
+                "manifest.json",
+            ],
+            names
+        );
+        let manifest: serde_json::Value = serde_json::from_slice(&files[2].1).unwrap();
+        assert_eq!("instructions", manifest["files"][1]["kind"]);
+        assert_eq!(16, manifest["files"][1]["bytes"]);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::cli_app::Args;
+use crate::error::StringArtError;
+use crate::optimum::Constraints;
+use crate::pins;
+use crate::pins::Arrangement;
+use crate::style;
+use crate::style::Data;
+use crate::template;
+use crate::term;
+
+/// Run a short optimization for every pin arrangement and pin count, so their scores can be
+/// compared. Each run saves a preview to the output filepaths, but nothing else.
+pub fn benchmark(args: &Args) -> Result<Vec<Data>, StringArtError> {
+    let pin_counts = match args.compare_pin_counts.is_empty() {
+        true => vec![args.pin_count],
+        false => args.compare_pin_counts.clone(),
+    };
+    let width = args.image.width();
+    let height = args.image.height();
+
+    let mut datas = Vec::new();
+    for arrangement in Arrangement::ALL {
+        for pin_count in &pin_counts {
+            if args.cancellation.is_cancelled() {
+                return Ok(datas);
+            }
+            term::info(
+                args.verbosity,
+                &format!("Comparing {:?} with {} pins", arrangement, pin_count),
+            );
+            let run_args = Args {
+                output_filepaths: args
+                    .output_filepaths
+                    .iter()
+                    .map(|filepath| template::per_comparison(filepath))
+                    .collect(),
+                output_scales: args.output_scales.clone(),
+                max_strings: usize::min(args.max_strings, args.preview_strings),
+                pin_count: *pin_count,
+                pin_arrangement: arrangement.clone(),
+                ..args.without_outputs()
+            };
+            let pin_locations = pins::from_args(&run_args, width, height);
+            datas.push(style::color_on_custom(
+                pin_locations,
+                run_args,
+                Vec::new(),
+                &Constraints::default(),
+                Vec::new(),
+            )?);
+        }
+    }
+    Ok(datas)
+}
+
+/// A table of each run's results, best first
+pub fn table(datas: &[Data]) -> String {
+    let mut datas: Vec<&Data> = datas.iter().collect();
+    datas.sort_by_key(|data| data.final_score);
+    let rows = datas.iter().map(|data| {
+        let previews: Vec<String> = data
+            .args
+            .output_filepaths
+            .iter()
+            .map(|filepath| template::expand(filepath, data))
+            .collect();
+        format!(
+            "{:<12} {:>6} {:>8} {:>16} {:>8.1}  {}",
+            format!("{:?}", data.args.pin_arrangement).to_lowercase(),
+            data.pin_locations.len(),
+            data.line_segments.len(),
+            data.final_score,
+            data.quality.psnr,
+            previews.join(", ")
+        )
+    });
+    std::iter::once(format!(
+        "{:<12} {:>6} {:>8} {:>16} {:>8}  {}",
+        "arrangement", "pins", "strings", "score", "psnr", "preview"
+    ))
+    .chain(rows)
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::cli_app::Cli;
+    use clap::Parser;
+
+    fn args(extra_args: &[&str]) -> Args {
+        let cli = Cli::parse_from(
+            ["string_art", "-i", "in.png", "--compare-arrangements"]
+                .iter()
+                .chain(extra_args),
+        );
+        let mut image = image::RgbImage::new(20, 20);
+        image
+            .enumerate_pixels_mut()
+            .for_each(|(x, y, p)| *p = image::Rgb([if (x + y) % 5 == 0 { 255 } else { 0 }; 3]));
+        Args::with_image(cli, image::DynamicImage::ImageRgb8(image))
+    }
+
+    #[test]
+    fn test_compare_runs_every_arrangement_and_pin_count() {
+        let datas = benchmark(&args(&[
+            "--compare-pin-counts",
+            "8,16",
+            "--preview-strings",
+            "20",
+        ]))
+        .unwrap();
+        assert_eq!(12, datas.len());
+        assert!(datas.iter().all(|data| data.line_segments.len() <= 20));
+        assert_eq!(
+            vec![Arrangement::Perimeter, Arrangement::Perimeter],
+            datas[..2]
+                .iter()
+                .map(|data| data.args.pin_arrangement.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_table_lists_best_first() {
+        let output = std::env::temp_dir().join("string_art_compare.png");
+        let output = output.to_str().unwrap();
+        let datas = benchmark(&args(&[
+            "-c",
+            "12",
+            "--preview-strings",
+            "20",
+            "-o",
+            output,
+        ]))
+        .unwrap();
+        let table = table(&datas);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(7, lines.len());
+        assert!(lines[0].starts_with("arrangement"));
+        let best = datas.iter().map(|data| data.final_score).min().unwrap();
+        assert!(lines[1].contains(&best.to_string()));
+        datas.iter().for_each(|data| {
+            let preview = template::expand(&data.args.output_filepaths[0], data);
+            assert!(table.contains(&preview));
+            std::fs::remove_file(preview).unwrap();
+        });
+    }
+}
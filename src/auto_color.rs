@@ -1,17 +1,19 @@
+#[cfg(feature = "cli")]
 use crate::cli_app::Cli;
 use crate::image::DynamicImage;
 use crate::imagery::Rgb;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::collections::HashSet;
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AutoColor {
     pub auto_fg_count: usize,
     pub manual_foregrounds: HashSet<Rgb>,
     pub manual_background: Option<Rgb>,
 }
 
+#[cfg(feature = "cli")]
 impl From<&Cli> for AutoColor {
     fn from(value: &Cli) -> Self {
         AutoColor {
@@ -0,0 +1,285 @@
+use crate::cli_app::{Args, Cli};
+use crate::control::{self, Cancellation, Pause};
+use crate::serde::Deserialize;
+use crate::string_art;
+use crate::term;
+use clap::Parser;
+use serde_json::{Map, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Create string art for every image listed in a manifest, as one queued job.
+///
+/// The manifest is a JSON file, compressed or not, like:
+///
+/// {"defaults": {"pin-count": 300, "max-strings": 4000},
+///  "images": [{"input-filepath": "a.jpg", "output-filepath": "a.png"},
+///             {"input-filepath": "b.jpg", "output-filepath": "b.png", "max-strings": 2000}]}
+///
+/// Each key is a long option of `string_art`, without the leading `--`. An image's own options
+/// replace the defaults of the same name. Flags take `true` or `false`, and options given more
+/// than once take a list.
+#[derive(Parser, Debug)]
+#[command(name = "string_art batch", version, max_term_width(100))]
+pub struct BatchCli {
+    /// The manifest listing the images and their options.
+    #[arg(short, long, value_name("FILEPATH"))]
+    pub manifest: String,
+
+    /// How many images to work on at once. The threads are split evenly between them.
+    #[arg(short, long, default_value("1"), value_parser = clap::value_parser!(u64).range(1..))]
+    pub jobs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    defaults: Map<String, Value>,
+    images: Vec<Map<String, Value>>,
+}
+
+impl Manifest {
+    /// The command line for each image: the defaults, with the image's own options in their
+    /// place
+    pub fn command_lines(&self) -> Result<Vec<Vec<String>>, String> {
+        self.images
+            .iter()
+            .map(|image| {
+                let mut options = self.defaults.clone();
+                options.extend(image.clone());
+                options.iter().try_fold(
+                    vec!["string_art".to_owned()],
+                    |mut command_line, (name, value)| {
+                        command_line.extend(option_args(name, value)?);
+                        Ok(command_line)
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// The command line arguments for one option of the manifest
+fn option_args(name: &str, value: &Value) -> Result<Vec<String>, String> {
+    let option = format!("--{}", name);
+    match value {
+        Value::Null | Value::Bool(false) => Ok(Vec::new()),
+        Value::Bool(true) => Ok(vec![option]),
+        Value::String(string) => Ok(vec![option, string.clone()]),
+        Value::Number(number) => Ok(vec![option, number.to_string()]),
+        Value::Array(values) => values.iter().try_fold(Vec::new(), |mut args, value| {
+            match value {
+                Value::Array(_) | Value::Object(_) => {
+                    return Err(format!(
+                        "The values of \"{}\" can't be lists or objects",
+                        name
+                    ))
+                }
+                _ => args.extend(option_args(name, value)?),
+            }
+            Ok(args)
+        }),
+        Value::Object(_) => Err(format!("The value of \"{}\" can't be an object", name)),
+    }
+}
+
+/// Run `string_art batch`. Every image's options are checked before any image is started.
+pub fn batch() {
+    // Skip the program name, so `batch` is treated as the name of the command
+    let cli = BatchCli::parse_from(std::env::args().skip(1));
+    let fail = |message: String| -> ! {
+        clap::Command::new("string_art batch")
+            .error(clap::error::ErrorKind::ValueValidation, message)
+            .exit()
+    };
+    let manifest: Manifest =
+        string_art::read_data(&cli.manifest).unwrap_or_else(|error| fail(error.to_string()));
+    let command_lines = manifest
+        .command_lines()
+        .unwrap_or_else(|message| fail(message));
+
+    let mut clis = Vec::new();
+    let mut errors = Vec::new();
+    for (i, command_line) in command_lines.into_iter().enumerate() {
+        match Cli::try_parse_from(command_line) {
+            Ok(cli) => clis.push((i, cli)),
+            Err(error) => errors.push(format!("Image {}: {}", i + 1, error)),
+        }
+    }
+    if !errors.is_empty() {
+        fail(errors.join("\n"))
+    }
+
+    // One handler for the whole queue, so Ctrl-C stops the image being made and every image
+    // after it
+    let cancellation = Cancellation::default();
+    let pause = Pause::default();
+    control::cancel_on_interrupt(&cancellation);
+    control::pause_on_signal(&pause);
+    let total = clis.len();
+    let unfinished = run_queue(clis, cli.jobs as usize, &cancellation, &pause);
+    if unfinished > 0 {
+        term::error(&format!("{} of {} images were not made", unfinished, total));
+        std::process::exit(1)
+    }
+}
+
+/// Make each image, `jobs` at a time, sharing `cancellation` and `pause` between them. Returns
+/// how many images failed or were never started because the queue was cancelled.
+fn run_queue(
+    clis: Vec<(usize, Cli)>,
+    jobs: usize,
+    cancellation: &Cancellation,
+    pause: &Pause,
+) -> usize {
+    let total = clis.len();
+    let queue = Mutex::new(clis.into_iter());
+    let unfinished = AtomicUsize::new(0);
+    let jobs = usize::min(jobs, usize::max(1, total));
+    let threads = usize::max(1, rayon::current_num_threads() / jobs);
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .expect("Unable to start the threads");
+                while let Some((i, cli)) = queue.lock().unwrap().next() {
+                    if cancellation.is_cancelled() {
+                        unfinished.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    println!("Image {} of {}: {}", i + 1, total, cli.input_filepath);
+                    let started_at = Instant::now();
+                    // Report a failed image and carry on with the rest
+                    if let Err(error) = pool.install(|| {
+                        Args::try_from(cli).and_then(|mut args| {
+                            args.cancellation = cancellation.clone();
+                            args.pause = pause.clone();
+                            string_art::run(args, started_at)
+                        })
+                    }) {
+                        term::error(&format!("Image {} of {}: {}", i + 1, total, error));
+                        unfinished.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    unfinished.into_inner()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn manifest(json: Value) -> Manifest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_images_override_the_defaults() {
+        let manifest = manifest(serde_json::json!({
+            "defaults": {"max-strings": 4000, "pin-count": 300},
+            "images": [
+                {"input-filepath": "a.jpg"},
+                {"input-filepath": "b.jpg", "max-strings": 2000},
+            ],
+        }));
+        assert_eq!(
+            Ok(vec![
+                vec![
+                    "string_art",
+                    "--input-filepath",
+                    "a.jpg",
+                    "--max-strings",
+                    "4000",
+                    "--pin-count",
+                    "300"
+                ],
+                vec![
+                    "string_art",
+                    "--input-filepath",
+                    "b.jpg",
+                    "--max-strings",
+                    "2000",
+                    "--pin-count",
+                    "300"
+                ],
+            ]
+            .into_iter()
+            .map(|line| line.into_iter().map(String::from).collect::<Vec<_>>())
+            .collect()),
+            manifest.command_lines()
+        );
+    }
+
+    #[test]
+    fn test_option_values() {
+        let args = |value: Value| option_args("foreground-color", &value);
+        assert_eq!(Ok(vec!["--foreground-color".to_owned()]), args(true.into()));
+        assert_eq!(Ok(Vec::new()), args(false.into()));
+        assert_eq!(Ok(Vec::new()), args(Value::Null));
+        assert_eq!(
+            Ok(vec![
+                "--foreground-color",
+                "#FF0000",
+                "--foreground-color",
+                "#00FF00"
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect()),
+            args(serde_json::json!(["#FF0000", "#00FF00"]))
+        );
+        assert!(args(serde_json::json!({"r": 255})).is_err());
+        assert!(args(serde_json::json!([["#FF0000"]])).is_err());
+    }
+
+    #[test]
+    fn test_command_lines_parse() {
+        let manifest = manifest(serde_json::json!({
+            "defaults": {"pad-to-square": true, "foreground-color": ["#FF0000", "#FFFFFF"]},
+            "images": [{"input-filepath": "a.jpg", "max-strings": 10}],
+        }));
+        let cli = Cli::try_parse_from(&manifest.command_lines().unwrap()[0]).unwrap();
+        assert_eq!("a.jpg", cli.input_filepath);
+        assert_eq!(10, cli.max_strings);
+        assert_eq!(2, cli.foreground_color.unwrap().len());
+    }
+
+    #[test]
+    fn test_failed_images_are_counted() {
+        let clis = ["/nonexistent/a.png", "/nonexistent/b.png"]
+            .iter()
+            .map(|input| Cli::parse_from(["string_art", "--input-filepath", input]))
+            .enumerate()
+            .collect();
+        assert_eq!(
+            2,
+            run_queue(clis, 2, &Cancellation::default(), &Pause::default())
+        );
+    }
+
+    #[test]
+    fn test_cancelled_queue_starts_no_images() {
+        let filepath = std::env::temp_dir().join("string_art_batch_cancelled.png");
+        let filepath = filepath.to_str().unwrap();
+        image::RgbImage::new(8, 8).save(filepath).unwrap();
+        let output = std::env::temp_dir().join("string_art_batch_cancelled_output.png");
+        let cli = Cli::parse_from([
+            "string_art",
+            "--input-filepath",
+            filepath,
+            "--output-filepath",
+            output.to_str().unwrap(),
+        ]);
+        let cancellation = Cancellation::default();
+        cancellation.cancel();
+        let unfinished = run_queue(vec![(0, cli)], 1, &cancellation, &Pause::default());
+        std::fs::remove_file(filepath).unwrap();
+        assert_eq!(1, unfinished);
+        assert!(!output.exists());
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    string_art::gui::gui();
+}
@@ -0,0 +1,101 @@
+use crate::imagery::Rgb;
+use crate::serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// How the light of overlapping strings adds up
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Blend {
+    /// Add the sRGB values of the strings, which makes overlaps too dark
+    Srgb,
+    /// Add the light of the strings, converting to linear light and back to sRGB to show it
+    Linear,
+}
+
+impl core::str::FromStr for Blend {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "srgb" => Ok(Blend::Srgb),
+            "linear" => Ok(Blend::Linear),
+            _ => Err(format!("Invalid blend: \"{}\"", string)),
+        }
+    }
+}
+
+impl Blend {
+    /// The sRGB color in the space strings are blended in
+    pub fn decode(self, rgb: Rgb) -> Rgb {
+        match self {
+            Blend::Srgb => rgb,
+            Blend::Linear => map(rgb, |c| linear(c).round() as i64),
+        }
+    }
+
+    /// The color, blended in this space, back in sRGB
+    pub fn encode(self, rgb: Rgb) -> Rgb {
+        match self {
+            Blend::Srgb => rgb,
+            Blend::Linear => map(rgb, |c| srgb(c as f64).round() as i64),
+        }
+    }
+}
+
+fn map(rgb: Rgb, f: impl Fn(i64) -> i64) -> Rgb {
+    Rgb::new(f(rgb.r), f(rgb.g), f(rgb.b))
+}
+
+/// The linear light of an 8-bit sRGB channel, on the same 0 to 255 scale. Looked up, since
+/// every pixel of every string is converted.
+pub fn linear(channel: i64) -> f64 {
+    static LINEAR: OnceLock<[f64; 256]> = OnceLock::new();
+    let table = LINEAR.get_or_init(|| {
+        std::array::from_fn(|c| {
+            255.0
+                * match c as f64 / 255.0 {
+                    c if c <= 0.04045 => c / 12.92,
+                    c => ((c + 0.055) / 1.055).powf(2.4),
+                }
+        })
+    });
+    table[channel.clamp(0, 255) as usize]
+}
+
+/// The 8-bit sRGB channel with the linear light `light`, on the same 0 to 255 scale
+fn srgb(light: f64) -> f64 {
+    255.0
+        * match (light / 255.0).clamp(0.0, 1.0) {
+            l if l <= 0.0031308 => l * 12.92,
+            l => 1.055 * l.powf(1.0 / 2.4) - 0.055,
+        }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        // Dark channels share the few smallest values of linear light, so only lighter ones
+        // come back exactly
+        for c in [0, 50, 128, 200, 255] {
+            let rgb = Rgb::new(c, c, c);
+            assert_eq!(
+                rgb,
+                Blend::Linear.encode(Blend::Linear.decode(rgb)),
+                "{}",
+                c
+            );
+        }
+    }
+
+    #[test]
+    fn test_half_the_light_is_lighter_than_half_the_value() {
+        // Two strings that each give half the light of white add up to white in linear light,
+        // but half of white's sRGB value is much less than half of its light
+        assert_eq!(
+            Rgb::new(188, 188, 188),
+            Blend::Linear.encode(Rgb::new(128, 128, 128))
+        );
+        assert_eq!(Rgb::new(128, 0, 0), Blend::Srgb.encode(Rgb::new(128, 0, 0)));
+    }
+}
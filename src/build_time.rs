@@ -0,0 +1,69 @@
+use crate::serde::{Deserialize, Serialize};
+use crate::style::Data;
+
+/// How long winding the finished work by hand is expected to take
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct BuildTime {
+    pub strings: usize,
+    /// How many times the thread switches to another color, in threading order
+    pub color_changes: usize,
+    pub seconds: f64,
+}
+
+impl BuildTime {
+    /// Estimate the build time from --seconds-per-string and --seconds-per-color-change
+    pub fn estimate(data: &Data) -> Self {
+        let strings = data.line_segments.len();
+        let color_changes = data
+            .line_segments
+            .windows(2)
+            .filter(|pair| pair[0].2 != pair[1].2)
+            .count();
+        Self {
+            strings,
+            color_changes,
+            seconds: strings as f64 * data.args.seconds_per_string
+                + color_changes as f64 * data.args.seconds_per_color_change,
+        }
+    }
+}
+
+impl std::fmt::Display for BuildTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let minutes = (self.seconds / 60.0).round() as u64;
+        write!(f, "{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::imagery::Rgb;
+
+    #[test]
+    fn test_estimate() {
+        let mut data = Data::example(&[
+            "--seconds-per-string",
+            "10",
+            "--seconds-per-color-change",
+            "300",
+        ]);
+        let white = data.line_segments[0];
+        let red = (white.0, white.1, Rgb::new(255, 0, 0));
+        data.line_segments = vec![white, white, red, red, white];
+
+        let build_time = BuildTime::estimate(&data);
+        assert_eq!(5, build_time.strings);
+        assert_eq!(2, build_time.color_changes);
+        assert_eq!(650.0, build_time.seconds);
+    }
+
+    #[test]
+    fn test_display() {
+        let build_time = BuildTime {
+            seconds: 7.0 * 3600.0 + 5.0 * 60.0 + 20.0,
+            ..BuildTime::default()
+        };
+        assert_eq!("7h 05m", build_time.to_string());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::cli_app::Args;
+#[cfg(feature = "cli")]
+use crate::cli_app::Cli;
+#[cfg(feature = "cli")]
+use crate::error::{open_image, StringArtError};
+use crate::image::DynamicImage;
+use crate::imagery::RefImage;
+use crate::lab::ScoreSpace;
+use crate::metrics::Quality;
+use crate::style;
+#[cfg(feature = "cli")]
+use crate::term;
+#[cfg(feature = "cli")]
+use clap::Parser;
+
+/// Report how close an image made some other way, such as a rendering from another tool or a
+/// photo of the finished piece, is to the target. The target is preprocessed and scored the same
+/// way as for a run with the same options.
+#[cfg(feature = "cli")]
+#[derive(Parser, Debug)]
+#[command(name = "string_art compare", version, max_term_width(100))]
+pub struct CompareCli {
+    /// The target image, as it would be given to --input-filepath.
+    #[arg(short, long, value_name("FILEPATH"))]
+    pub input: String,
+
+    /// The image to score. It is resized to match the preprocessed target.
+    #[arg(short, long, value_name("FILEPATH"))]
+    pub candidate: String,
+
+    /// Options of the run to score like, given after `--`, such as `-- --crop 10%,10%,80%,80%
+    /// --weight-filepath weights.png`. Options that preprocess the target, choose the colors,
+    /// or weight the error change the score.
+    #[arg(last = true, value_name("OPTIONS"))]
+    pub options: Vec<String>,
+}
+
+#[cfg(feature = "cli")]
+impl CompareCli {
+    /// Preprocess the target and score the candidate against it
+    pub fn report(&self) -> Result<Report, StringArtError> {
+        let cli = Cli::try_parse_from(
+            ["string_art", "--input-filepath", &self.input]
+                .into_iter()
+                .chain(self.options.iter().map(String::as_str)),
+        )?;
+        let args = Args::try_from(cli)?;
+        let candidate = open_image(&self.candidate)?.resize_exact(
+            args.image.width(),
+            args.image.height(),
+            image::imageops::FilterType::Triangle,
+        );
+        Ok(Report::new(&args, &candidate))
+    }
+}
+
+/// Run `string_art compare`, printing how close the candidate is to the target
+#[cfg(feature = "cli")]
+pub fn compare() {
+    // Skip the program name, so `compare` is treated as the name of the command
+    let cli = CompareCli::parse_from(std::env::args().skip(1));
+    match cli.report() {
+        Ok(report) => println!("{}", report),
+        Err(error) => {
+            term::error(&error.to_string());
+            std::process::exit(1)
+        }
+    }
+}
+
+/// How close an image made some other way is to the target, scored like the strings of a run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Report {
+    /// The score of the empty board, before any strings are wound
+    pub blank_score: i64,
+    /// Lower is better, and comparable with the final score of a run on the same target
+    pub score: i64,
+    pub quality: Quality,
+}
+
+impl Report {
+    /// Score `candidate`, which must already be the size of the preprocessed target. The
+    /// weighting options and the mask count the error of each pixel as they do in a run.
+    pub fn new(args: &Args, candidate: &DynamicImage) -> Self {
+        let difference = RefImage::from(&args.image)
+            .decoded(args.blend)
+            .negated()
+            .add_ref_image(&RefImage::from(candidate).decoded(args.blend));
+        let difference = match args.score_space {
+            ScoreSpace::Rgb => difference,
+            ScoreSpace::Lab => difference.in_lab(&args.image, args.blend),
+        };
+        let difference = match style::weights(args) {
+            Some(weights) => difference.with_weights(weights),
+            None => difference,
+        };
+        Self {
+            blank_score: style::weighted_blank(args).score(),
+            score: difference.score(),
+            quality: Quality::between(candidate, &args.image),
+        }
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Blank score: {} (lower is better)", self.blank_score)?;
+        writeln!(f, "Score      : {}", self.score)?;
+        writeln!(f, "PSNR       : {:.2} dB", self.quality.psnr)?;
+        write!(f, "SSIM       : {:.4}", self.quality.ssim)
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+
+    fn args() -> Args {
+        let cli = crate::cli_app::Cli::parse_from(["string_art", "-i", "in.png", "-b", "#000000"]);
+        let mut image = image::RgbImage::new(4, 4);
+        image.put_pixel(1, 1, image::Rgb([255; 3]));
+        Args::with_image(cli, DynamicImage::ImageRgb8(image))
+    }
+
+    #[test]
+    fn test_the_target_scores_perfectly() {
+        let args = args();
+        let report = Report::new(&args, &args.image);
+        assert_eq!(0, report.score);
+        assert!(report.blank_score > 0);
+        assert_eq!(1.0, report.quality.ssim);
+    }
+
+    #[test]
+    fn test_a_blank_candidate_scores_like_the_empty_board() {
+        let args = args();
+        let report = Report::new(&args, &DynamicImage::new_rgb8(4, 4));
+        assert_eq!(report.blank_score, report.score);
+    }
+
+    #[test]
+    fn test_the_weight_map_counts_like_a_run() {
+        let filepath = std::env::temp_dir().join("string_art_candidate_weights.png");
+        let filepath = filepath.to_str().unwrap();
+        // Only the bright pixel of the target counts, three times over
+        let mut weights = image::GrayImage::new(4, 4);
+        weights.put_pixel(1, 1, image::Luma([3]));
+        weights.save(filepath).unwrap();
+        let cli = crate::cli_app::Cli::parse_from([
+            "string_art",
+            "-i",
+            "in.png",
+            "-b",
+            "#000000",
+            "--weight-filepath",
+            filepath,
+        ]);
+        let args = Args::with_image(cli, args().image).with_images_from_options();
+        std::fs::remove_file(filepath).unwrap();
+        let args = args.unwrap();
+
+        let report = Report::new(&args, &DynamicImage::new_rgb8(4, 4));
+        assert_eq!(3 * 3 * 255 * 255, report.blank_score);
+        assert_eq!(report.blank_score, report.score);
+        let data = crate::style::color_on_custom(
+            crate::pins::from_args(&args, 4, 4),
+            args.clone(),
+            Vec::new(),
+            &crate::optimum::Constraints::default(),
+            Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(data.initial_score, report.blank_score);
+    }
+
+    #[test]
+    fn test_compare_preprocesses_the_target() {
+        let dir = std::env::temp_dir();
+        let input = dir.join("string_art_compare_input.png");
+        let candidate = dir.join("string_art_compare_candidate.png");
+        let mut target = image::RgbImage::new(8, 8);
+        target.put_pixel(0, 0, image::Rgb([255; 3]));
+        target.save(&input).unwrap();
+        // The target cropped to its right half, which is black
+        image::RgbImage::new(2, 4).save(&candidate).unwrap();
+        let cli = |options: &[&str]| {
+            CompareCli::parse_from(
+                [
+                    "string_art compare",
+                    "--input",
+                    input.to_str().unwrap(),
+                    "--candidate",
+                    candidate.to_str().unwrap(),
+                    "--",
+                ]
+                .iter()
+                .chain(options),
+            )
+        };
+        let cropped = cli(&["--crop", "50%,0%,50%,100%", "-b", "#000000"]).report();
+        let uncropped = cli(&["-b", "#000000"]).report();
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&candidate).unwrap();
+        assert_eq!(0, cropped.unwrap().score);
+        assert!(uncropped.unwrap().score > 0);
+    }
+
+    #[test]
+    fn test_compare_reports_a_missing_candidate() {
+        let input = std::env::temp_dir().join("string_art_compare_missing.png");
+        image::RgbImage::new(4, 4).save(&input).unwrap();
+        let report = CompareCli::parse_from([
+            "string_art compare",
+            "--input",
+            input.to_str().unwrap(),
+            "--candidate",
+            "/nonexistent/candidate.png",
+        ])
+        .report();
+        std::fs::remove_file(&input).unwrap();
+        assert!(matches!(report, Err(StringArtError::Read { .. })));
+    }
+}
@@ -0,0 +1,308 @@
+use crate::imagery::Rgb;
+use crate::imagery::Rgbf;
+
+/// How many pixels are scored at once
+pub const LANES: usize = 8;
+
+/// How a `RefImage` stores each pixel. Smaller representations save memory on large images,
+/// and single channel representations score brightness alone.
+///
+/// Every representation scores a pixel on the same scale as `Rgb`: the sum of the squared
+/// channels, so scores can be compared no matter how the image is stored.
+pub trait Channel: Copy + Send + Sync + PartialEq + std::fmt::Debug + 'static {
+    const ZERO: Self;
+
+    fn from_rgb(rgb: Rgb) -> Self;
+
+    /// The color a string adds to a pixel. Representations that hold whole levels round it, so
+    /// a string too faint to change a pixel by itself adds nothing.
+    fn from_rgbf(rgbf: Rgbf) -> Self {
+        Self::from_rgb(Rgb::from(rgbf))
+    }
+
+    fn to_rgb(self) -> Rgb;
+
+    fn plus(self, other: Self) -> Self;
+
+    fn negated(self) -> Self;
+
+    fn score(self) -> i64;
+
+    /// The total change in weighted score as `LANES` pixels go from `before` to `after`.
+    /// Unused lanes hold `ZERO` before and after.
+    fn score_changes(before: &[Self; LANES], after: &[Self; LANES], weights: &[i64; LANES]) -> i64 {
+        (0..LANES)
+            .map(|i| (after[i].score() - before[i].score()) * weights[i])
+            .sum()
+    }
+}
+
+impl Channel for Rgb {
+    const ZERO: Self = Rgb::BLACK;
+
+    fn from_rgb(rgb: Rgb) -> Self {
+        rgb
+    }
+
+    fn to_rgb(self) -> Rgb {
+        self
+    }
+
+    fn plus(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn negated(self) -> Self {
+        -self
+    }
+
+    fn score(self) -> i64 {
+        self.r * self.r + self.g * self.g + self.b * self.b
+    }
+
+    /// Packs each channel of the pixels together, so the compiler can score every lane with
+    /// the same instructions
+    fn score_changes(before: &[Self; LANES], after: &[Self; LANES], weights: &[i64; LANES]) -> i64 {
+        let packed = |pixels: &[Self; LANES]| {
+            [
+                pixels.map(|c| c.r),
+                pixels.map(|c| c.g),
+                pixels.map(|c| c.b),
+            ]
+        };
+        let (before, after) = (packed(before), packed(after));
+        let mut changes = [0; LANES];
+        for (a, b) in before.iter().zip(after.iter()) {
+            for lane in 0..LANES {
+                // b² - a², with one multiplication
+                changes[lane] += (b[lane] - a[lane]) * (b[lane] + a[lane]);
+            }
+        }
+        (0..LANES).map(|lane| changes[lane] * weights[lane]).sum()
+    }
+}
+
+/// Triple channel representations. Pixels of a `RefImage` hold differences between colors, so
+/// each channel must be signed; `i16` is the smallest that fits.
+macro_rules! triple_channel {
+    ($t:ty) => {
+        impl Channel for [$t; 3] {
+            const ZERO: Self = [0 as $t; 3];
+
+            fn from_rgb(rgb: Rgb) -> Self {
+                [rgb.r as $t, rgb.g as $t, rgb.b as $t]
+            }
+
+            fn from_rgbf(rgbf: Rgbf) -> Self {
+                [
+                    <$t>::from_level(rgbf.r),
+                    <$t>::from_level(rgbf.g),
+                    <$t>::from_level(rgbf.b),
+                ]
+            }
+
+            fn to_rgb(self) -> Rgb {
+                Rgb::new(
+                    self[0].round_to_i64(),
+                    self[1].round_to_i64(),
+                    self[2].round_to_i64(),
+                )
+            }
+
+            fn plus(self, other: Self) -> Self {
+                [
+                    self[0].saturating_plus(other[0]),
+                    self[1].saturating_plus(other[1]),
+                    self[2].saturating_plus(other[2]),
+                ]
+            }
+
+            fn negated(self) -> Self {
+                [
+                    self[0].saturating_negated(),
+                    self[1].saturating_negated(),
+                    self[2].saturating_negated(),
+                ]
+            }
+
+            fn score(self) -> i64 {
+                self.iter().map(|c| c.squared()).sum::<f64>().round() as i64
+            }
+        }
+    };
+}
+
+/// Single channel representations store the mean of the three channels
+macro_rules! single_channel {
+    ($t:ty) => {
+        impl Channel for $t {
+            const ZERO: Self = 0 as $t;
+
+            fn from_rgb(rgb: Rgb) -> Self {
+                ((rgb.r + rgb.g + rgb.b) as f64 / 3.0) as $t
+            }
+
+            fn from_rgbf(rgbf: Rgbf) -> Self {
+                <$t>::from_level((rgbf.r + rgbf.g + rgbf.b) / 3.0)
+            }
+
+            fn to_rgb(self) -> Rgb {
+                let value = self.round_to_i64();
+                Rgb::new(value, value, value)
+            }
+
+            fn plus(self, other: Self) -> Self {
+                self.saturating_plus(other)
+            }
+
+            fn negated(self) -> Self {
+                self.saturating_negated()
+            }
+
+            fn score(self) -> i64 {
+                (3.0 * self.squared()).round() as i64
+            }
+        }
+    };
+}
+
+/// Arithmetic shared by the numeric types a channel can be stored as
+trait Number: Copy {
+    /// A level, rounded to a whole one unless fractions can be stored
+    fn from_level(level: f64) -> Self;
+    fn saturating_plus(self, other: Self) -> Self;
+    fn saturating_negated(self) -> Self;
+    fn round_to_i64(self) -> i64;
+    fn squared(self) -> f64;
+}
+
+macro_rules! integer_number {
+    ($t:ty) => {
+        impl Number for $t {
+            fn from_level(level: f64) -> Self {
+                level.round() as $t
+            }
+
+            fn saturating_plus(self, other: Self) -> Self {
+                self.saturating_add(other)
+            }
+
+            fn saturating_negated(self) -> Self {
+                self.saturating_neg()
+            }
+
+            fn round_to_i64(self) -> i64 {
+                self as i64
+            }
+
+            fn squared(self) -> f64 {
+                let n = self as f64;
+                n * n
+            }
+        }
+    };
+}
+
+integer_number!(i16);
+integer_number!(i32);
+
+impl Number for f32 {
+    fn from_level(level: f64) -> Self {
+        level as f32
+    }
+
+    fn saturating_plus(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn saturating_negated(self) -> Self {
+        -self
+    }
+
+    fn round_to_i64(self) -> i64 {
+        self.round() as i64
+    }
+
+    fn squared(self) -> f64 {
+        let n = self as f64;
+        n * n
+    }
+}
+
+triple_channel!(i16);
+triple_channel!(i32);
+triple_channel!(f32);
+single_channel!(i16);
+single_channel!(i32);
+single_channel!(f32);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn grey() -> Rgb {
+        Rgb::new(-100, -100, -100)
+    }
+
+    #[test]
+    fn test_triple_channels_score_like_rgb() {
+        let color = Rgb::new(-10, 20, 255);
+        assert_eq!(color.score(), <[i16; 3]>::from_rgb(color).score());
+        assert_eq!(color.score(), <[i32; 3]>::from_rgb(color).score());
+        assert_eq!(color.score(), <[f32; 3]>::from_rgb(color).score());
+    }
+
+    #[test]
+    fn test_single_channels_score_like_grey_rgb() {
+        assert_eq!(grey().score(), i16::from_rgb(grey()).score());
+        assert_eq!(grey().score(), i32::from_rgb(grey()).score());
+        assert_eq!(grey().score(), f32::from_rgb(grey()).score());
+    }
+
+    #[test]
+    fn test_single_channel_is_the_mean() {
+        assert_eq!(
+            Rgb::new(20, 20, 20),
+            i32::from_rgb(Rgb::new(0, 30, 30)).to_rgb()
+        );
+    }
+
+    #[test]
+    fn test_packed_score_changes_match_each_pixel() {
+        let before = std::array::from_fn(|i| Rgb::new(i as i64 * 30 - 100, -50, 7));
+        let after = before.map(|c| c + Rgb::new(20, -3, 255));
+        let weights = std::array::from_fn(|i| i as i64 % 3);
+        let each_pixel: i64 = (0..LANES)
+            .map(|i| (after[i].score() - before[i].score()) * weights[i])
+            .sum();
+        assert_eq!(each_pixel, Rgb::score_changes(&before, &after, &weights));
+    }
+
+    #[test]
+    fn test_plus_saturates() {
+        assert_eq!(i16::MAX, i16::MAX.plus(1));
+        assert_eq!([i16::MIN, 0, 2], [i16::MIN, 0, 1].plus([-1, 0, 1]));
+    }
+
+    #[test]
+    fn test_negated_saturates() {
+        assert_eq!(i16::MAX, i16::MIN.negated());
+        assert_eq!(i32::MAX, i32::MIN.negated());
+        assert_eq!([i16::MAX, 0, -1], [i16::MIN, 0, 1].negated());
+    }
+
+    #[test]
+    fn test_only_floats_keep_fractions() {
+        let faint = Rgbf::new(0.4, -0.6, 1.5);
+        assert_eq!(Rgb::new(0, -1, 2), Rgb::from_rgbf(faint));
+        assert_eq!([0, -1, 2], <[i16; 3]>::from_rgbf(faint));
+        assert_eq!([0.4, -0.6, 1.5], <[f32; 3]>::from_rgbf(faint));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let color = Rgb::new(-10, 20, 255);
+        assert_eq!(color, <[f32; 3]>::from_rgb(color).to_rgb());
+        assert_eq!(-color, <[i16; 3]>::from_rgb(color).negated().to_rgb());
+    }
+}
@@ -1,27 +1,118 @@
+#[cfg(feature = "cli")]
 use crate::{
-    auto_color::{fg_and_bg, AutoColor},
-    imagery::Rgb,
-    pins::PinArrangement,
+    auto_color::fg_and_bg,
+    memory::{self, Estimate},
+    preprocess::{auto_trim, preprocess},
+    term,
 };
+use crate::{
+    auto_color::AutoColor,
+    blend::Blend,
+    control::{Cancellation, Pause, Progress},
+    error::{open_image, StringArtError},
+    geometry::{Length, Point},
+    imagery::{Gradient, Pen, Rgb, Shade},
+    lab::ScoreSpace,
+    optimizer::Strategy,
+    pins::{Arrangement, Direction},
+    preprocess::{Crop, DenoiseFilter, Equalize, Rotation},
+    realistic::RenderStyle,
+    style::{DrawOrder, Style},
+    sweep::Sweep,
+};
+#[cfg(feature = "cli")]
 use clap::{builder::ArgPredicate, error::ErrorKind, Parser};
+#[cfg(feature = "cli")]
 use image::io::Reader as ImageReader;
-use serde::Serialize;
-use std::{collections::HashSet, str::FromStr};
+use image::Delay;
+#[cfg(all(feature = "cli", feature = "gif"))]
+use image::{codecs::gif::GifDecoder, AnimationDecoder};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+#[cfg(feature = "cli")]
+use std::str::FromStr;
 
-const DEFAULT_BG: &str = "#000000";
-const DEFAULT_FG: &str = "#FFFFFF";
+#[cfg(feature = "cli")]
+pub const DEFAULT_BG: &str = "#000000";
+#[cfg(feature = "cli")]
+pub const DEFAULT_FG: &str = "#FFFFFF";
+
+/// How long each frame is shown when the input is a directory of images
+#[cfg(feature = "cli")]
+const DEFAULT_FRAME_DELAY_MS: u32 = 100;
 
 /// The validated arguments passed in by the user
+#[cfg(feature = "cli")]
 #[derive(Debug, Clone, PartialEq, Serialize, Parser)]
-#[command(version, about, long_about = None, max_term_width(100))]
+#[command(
+    version,
+    about,
+    long_about = None,
+    max_term_width(100),
+    after_help("To check a data file, run `string_art validate --data-filepath FILEPATH`. To run many \
+images from a manifest, run `string_art batch --manifest FILEPATH`. To compare two data files, run \
+`string_art diff BEFORE AFTER`. To render the images of a data file again, run `string_art replay \
+DATA`. To score an image made some other way against the target, run `string_art compare --input \
+FILEPATH --candidate FILEPATH`.")
+)]
 pub struct Cli {
     /// Path to the image that will be rendered with strings.
+    ///
+    /// If this is an animated gif or a directory of images, each frame is rendered with strings,
+    /// starting from the strings of the previous frame. Each frame gets its own output files,
+    /// named using the `{frame}` placeholder (which is added to filepaths that lack it).
     #[arg(short = 'i', long)]
     pub input_filepath: String,
 
-    /// Location to save generated string image.
+    /// Location to save generated string image. Can be specified multiple times to save several
+    /// images, each at the size given by the matching --output-scale.
+    ///
+    /// Output filepaths may contain placeholders that are filled in after the run: `{pins}`,
+    /// `{strings}`, `{score}`, `{initial_score}`, `{colors}`, `{arrangement}`, `{alpha}`,
+    /// `{step}`, and `{seconds}`. For example: `art_{pins}_{strings}_{score}.png`.
     #[arg(short = 'o', long)]
-    pub output_filepath: Option<String>,
+    pub output_filepath: Vec<String>,
+
+    /// How much to scale each string image relative to the input image. The first scale applies
+    /// to the first --output-filepath, the second to the second, and so on. Images without a
+    /// matching scale are saved at the size of the input image.
+    #[arg(long, value_parser = positive_f64)]
+    pub output_scale: Vec<f64>,
+
+    /// Overwrite files that already exist. Without this, the run stops before it starts if any
+    /// of the files it would write already exist.
+    #[arg(long)]
+    pub force: bool,
+
+    /// How to draw the string images. `flat` shows what the optimizer sees, while `realistic`
+    /// draws each string as a shaded thread with a shadow, for presentation-quality previews.
+    #[arg(long, default_value("flat"))]
+    pub render_style: RenderStyle,
+
+    /// The order strings are drawn in the string images: `colors` (the order the foreground
+    /// colors were given), `darkest-first`, `lightest-first`, or `threading` (the order the
+    /// strings were placed). Where strings cross, the ones drawn later end up on top in
+    /// `realistic` images. `flat` images add every string's color together, so the order does
+    /// not change them.
+    #[arg(long, default_value("threading"))]
+    pub draw_order: DrawOrder,
+
+    /// Location to save the strings as an SVG, with one line per string in its color. Unlike
+    /// the string images, it can be scaled to any size without blurring, and it prints at
+    /// --physical-width-mm when that is given.
+    #[arg(long, value_name("FILEPATH"))]
+    pub svg_filepath: Option<String>,
+
+    /// How wide to draw each string in the SVG, in pixels of the input image.
+    #[arg(
+        long,
+        value_name("PIXELS"),
+        value_parser = positive_f64,
+        default_value("1"),
+        requires("svg_filepath")
+    )]
+    pub svg_stroke_width: f64,
 
     /// Location to save image of pin locations.
     #[arg(short = 'p', long)]
@@ -33,14 +124,312 @@ pub struct Cli {
     #[arg(short = 'd', long)]
     pub data_filepath: Option<String>,
 
+    /// Indent the data file so it is easy to read.
+    #[arg(long, conflicts_with("data_compact"))]
+    pub data_pretty: bool,
+
+    /// Write the data file without any whitespace. This is the default.
+    #[arg(long)]
+    pub data_compact: bool,
+
+    /// Compress the data file with gzip.
+    #[arg(long)]
+    pub data_gzip: bool,
+
+    /// Location to save step by step instructions for winding the strings, as a text file.
+    #[arg(long, value_name("FILEPATH"))]
+    pub instructions_filepath: Option<String>,
+
+    /// Leave out the instructions' steps for tying off a thread wherever the next string does
+    /// not start where the last one ended.
+    #[arg(long, requires("instructions_filepath"))]
+    pub no_tie_off_markers: bool,
+
+    /// Leave out the instructions' steps for changing to a thread of another color.
+    #[arg(long, requires("instructions_filepath"))]
+    pub no_color_change_markers: bool,
+
+    /// Suggest taking a photo to compare with the preview every N strings in the instructions.
+    #[arg(
+        long,
+        value_name("N"),
+        requires("instructions_filepath"),
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub checkpoint_every: Option<u64>,
+
+    /// Location to save a zip archive of every file the run writes, such as the string images,
+    /// pins image, data file, instructions, and gifs, along with a `manifest.json` listing them.
+    /// Handy for handing a complete build kit to someone else.
+    #[arg(long, value_name("FILEPATH"))]
+    pub archive_filepath: Option<String>,
+
+    /// Reorder the strings of each color to shorten the distance the thread has to be carried
+    /// between the end of one string and the start of the next. How much shorter it got is saved
+    /// in the data file, and printed with -vv. The strings of each color are grouped together,
+    /// so `threading` draw order changes which strings end up on top in `realistic` images.
+    #[arg(long)]
+    pub minimize_travel: bool,
+
+    /// Reorder the strings of each color so they can be wound with one continuous thread, each
+    /// string starting at the pin the last one ended at. Where that isn't possible, as few
+    /// bridging strings as possible are added between the pieces. With -vv, print how many.
+    #[arg(long, conflicts_with("minimize_travel"))]
+    pub continuous_path: bool,
+
+    /// Location to save an image of the strings placed so far whenever the run is paused. Send
+    /// the process SIGUSR1 to pause, and again to resume.
+    #[arg(long, value_name("FILEPATH"))]
+    pub snapshot_filepath: Option<String>,
+
     /// Location to save a gif of the creation process.
     #[arg(short = 'g', long)]
     pub gif_filepath: Option<String>,
 
+    /// Add a frame to the gif each time this many strings have been placed or removed, instead
+    /// of after every batch of strings. Fewer frames make a smaller gif of a large work. The
+    /// video gets the same frames.
+    #[arg(
+        long,
+        value_name("N"),
+        requires("gif_filepath"),
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub gif_strings_per_frame: Option<u64>,
+
+    /// How long each frame of the gif is shown, in milliseconds. By default frames have no delay,
+    /// so viewers show them as fast as they allow.
+    #[arg(long, value_name("MS"), requires("gif_filepath"))]
+    pub gif_frame_delay: Option<u32>,
+
+    /// Encode the gif with one palette of shades from the background to each foreground color,
+    /// instead of a palette for each frame. The gif is many times smaller, but strings that
+    /// blend into other colors are drawn in the nearest shade.
+    #[arg(long, requires("gif_filepath"))]
+    pub gif_palette: bool,
+
+    /// Location to save a video of the creation process, which is much smaller than the gif for
+    /// long runs. `.mp4`, `.m4v`, and `.mov` files are encoded with H.264, and `.webm` files
+    /// with VP9. Needs ffmpeg on the PATH, and string_art built with the `video` feature.
+    #[arg(long, value_name("FILEPATH"))]
+    pub video_filepath: Option<String>,
+
+    /// Frames per second of the video.
+    #[arg(
+        long,
+        value_name("FPS"),
+        default_value("30"),
+        requires("video_filepath"),
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub video_fps: u32,
+
+    /// Location to save a grid of labeled snapshots taken as the strings reach each of the
+    /// --milestones, ending with the finished strings. A compact alternative to the gif.
+    #[arg(long, value_name("FILEPATH"))]
+    pub milestones_filepath: Option<String>,
+
+    /// String counts at which to take the snapshots for --milestones-filepath, separated by
+    /// commas.
+    #[arg(long, value_delimiter(','), default_value("500,1000,2000,4000"))]
+    pub milestones: Vec<usize>,
+
+    /// Location to save a gif of every finished frame when the input is animated.
+    #[arg(long, value_name("FILEPATH"))]
+    pub animation_filepath: Option<String>,
+
+    /// An image to morph the input image into. Strings are found for both images, and each
+    /// output is saved for both, numbered with `{frame}` as for an animated input.
+    #[arg(long, value_name("FILEPATH"))]
+    pub morph_to: Option<String>,
+
+    /// Location to save a gif of the strings of the input image being taken down, a few at a
+    /// time, while the strings of the --morph-to image are put up.
+    #[arg(long, value_name("FILEPATH"), requires("morph_to"))]
+    pub morph_filepath: Option<String>,
+
+    /// Location to save an image explaining how well each part of the finished work matches
+    /// the input image. Red shows the error that remains, and green shows the error that the
+    /// strings removed.
+    #[arg(long, value_name("FILEPATH"))]
+    pub explain_filepath: Option<String>,
+
+    /// Location to save an image simulating how the finished work looks from
+    /// --simulate-distance away.
+    #[arg(long, value_name("FILEPATH"))]
+    pub simulate_filepath: Option<String>,
+
+    /// Distance in meters from which to view the finished work when saving
+    /// --simulate-filepath. Requires --physical-width-mm.
+    #[arg(
+        long,
+        value_parser = positive_f64,
+        requires_all(["physical_width_mm", "simulate_filepath"])
+    )]
+    pub simulate_distance: Option<f64>,
+
+    /// Width of the physical finished work in millimeters. PNG and TIFF string images record
+    /// their resolution from it, so they print at exactly this width.
+    #[arg(long, value_parser = positive_f64)]
+    pub physical_width_mm: Option<f64>,
+
+    /// Seconds it takes to wind one string by hand, for estimating the build time.
+    #[arg(long, value_parser = positive_f64, default_value("10"))]
+    pub seconds_per_string: f64,
+
+    /// Seconds it takes to tie off one thread and start another color, for estimating the build
+    /// time.
+    #[arg(long, value_parser = positive_f64, default_value("180"))]
+    pub seconds_per_color_change: f64,
+
+    /// Interactively edit the strings in this data file: delete or lock strings, then improve
+    /// the rest around those edits. Use the same input image and preprocessing options as the
+    /// run that created the data file.
+    #[arg(long, value_name("FILEPATH"))]
+    pub edit_from: Option<String>,
+
+    /// Start from the strings in this data file, and keep improving them with these options:
+    /// more strings, other colors, or tighter constraints. The strings stay on the data file's
+    /// pins, and --max-strings counts them too. Use the same input image and preprocessing
+    /// options as the run that created the data file.
+    #[arg(
+        long,
+        value_name("FILEPATH"),
+        conflicts_with_all(["edit_from", "morph_to", "ensemble"])
+    )]
+    pub continue_from: Option<String>,
+
+    /// Resume a run that was interrupted with Ctrl-C from the data file it wrote, placing
+    /// strings until there are --max-strings. Give the same input image and options as the
+    /// interrupted run. Unless --data-filepath is given, the resumed run is saved to the same
+    /// data file, so it can be interrupted and resumed again.
+    #[arg(
+        long,
+        value_name("FILEPATH"),
+        conflicts_with_all(["edit_from", "continue_from", "morph_to", "ensemble"])
+    )]
+    pub resume: Option<String>,
+
     /// The maximum number of strings in the finished work.
     #[arg(short = 'm', long, default_value(usize::MAX.to_string()), hide_default_value(true))]
     pub max_strings: usize,
 
+    /// Stop placing strings after about this many seconds, and finish the run with the strings
+    /// placed so far. The outputs are still written, and the data file notes that the run
+    /// timed out.
+    #[arg(long, value_name("SECONDS"), value_parser = positive_f64)]
+    pub time_limit: Option<f64>,
+
+    /// Stop adding strings once the best one would lower the score by less than this percentage
+    /// of the current score, like `0.01`. Stops the run when more strings barely help, instead
+    /// of guessing --max-strings.
+    #[arg(long, value_name("PERCENT"), value_parser = percent)]
+    pub min_improvement: Option<f64>,
+
+    /// The most passes the `greedy-batch` optimizer makes of adding strings and then removing
+    /// them. Some images keep adding and removing the same strings for a long time. The data
+    /// file records how many passes were made.
+    #[arg(long, value_name("N"), value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_passes: Option<u64>,
+
+    /// What each string costs, in the units of the score. A string is only added when it lowers
+    /// the score by more than its cost, so the run stops once more strings give too little back,
+    /// instead of only at --max-strings.
+    #[arg(long, value_name("SCORE"), default_value("0"), value_parser = clap::value_parser!(i64).range(0..))]
+    pub string_cost: i64,
+
+    /// The most strings that may cross any one pixel. Physical thread stacks up and bulges where
+    /// too many strings cross.
+    #[arg(long)]
+    pub max_overlap: Option<u32>,
+
+    /// The smallest angle in degrees between two strings leaving the same pin. Strings that are
+    /// nearly parallel where they leave a pin lie on top of each other around the nail.
+    #[arg(long, value_name("DEGREES"), value_parser = positive_f64)]
+    pub min_pin_angle: Option<f64>,
+
+    /// The shortest string that may be placed, in pixels. Short strings between neighboring
+    /// pins add noise around the frame and use up the strings for little gain.
+    #[arg(long, value_name("PIXELS"), value_parser = positive_f64)]
+    pub min_string_length: Option<f64>,
+
+    /// How to choose strings. `greedy-batch` adds and removes the best strings in batches.
+    /// `greedy-walk` winds one continuous thread from pin to pin. `annealing` explores random
+    /// changes before settling down. `beam` keeps several candidate sets of strings at once.
+    #[arg(long, default_value("greedy-batch"))]
+    pub optimizer: Strategy,
+
+    /// The index of the pin the `greedy-walk` thread starts from, such as the pin nearest a
+    /// tie-off hook. Pins are numbered as in the data file, starting from 0.
+    #[arg(long, value_name("INDEX"))]
+    pub start_pin: Option<usize>,
+
+    /// How many random changes the `annealing` optimizer tries.
+    #[arg(long, default_value("100000"))]
+    pub annealing_steps: usize,
+
+    /// How many candidate sets of strings the `beam` optimizer keeps.
+    #[arg(long, default_value("4"))]
+    pub beam_width: usize,
+
+    /// Run the optimizer against the brightness of the image alone, then give each string the
+    /// foreground color that fits best where it lies. Much faster with several foreground
+    /// colors, at some cost to how well the colors match.
+    #[arg(long)]
+    pub luminance_first: bool,
+
+    /// After the optimizer finishes, refine the strings with up to this many passes of swaps:
+    /// each swap removes a string and adds the best replacement at once, and is kept only if it
+    /// improves the image. Slow, since every swap searches every possible string.
+    #[arg(long, value_name("N"))]
+    pub swap_passes: Option<usize>,
+
+    /// When adding and removing strings stalls before --max-strings is reached, try up to this
+    /// many rounds of removing a random few strings and searching again. Each round is kept
+    /// only if it improves the image. Ignored by the `greedy-walk` optimizer.
+    #[arg(long, value_name("N"))]
+    pub perturb_rounds: Option<usize>,
+
+    /// The percentage of strings each --perturb-rounds round removes.
+    #[arg(long, default_value("5"), value_name("PERCENT"), value_parser = percent)]
+    pub perturb_percent: f64,
+
+    /// Seeds the choice of strings --perturb-rounds removes in place of --seed, so the rest of
+    /// the run can be repeated while the strings removed change.
+    #[arg(long)]
+    pub perturb_seed: Option<u64>,
+
+    /// Run this many optimizations in parallel, each using a different random sample of the
+    /// pins, then merge them by keeping the best combination of their strings. Ignored when the
+    /// input is animated.
+    #[arg(long, value_name("N"), value_parser = clap::value_parser!(u64).range(2..))]
+    pub ensemble: Option<u64>,
+
+    /// Instead of creating the string art, run a short optimization with each pin arrangement
+    /// and print a table comparing their scores, to help choose a frame layout. Each run saves
+    /// a preview to the --output-filepath, which gets the `{arrangement}` and `{pins}`
+    /// placeholders if it lacks them.
+    #[arg(long)]
+    pub compare_arrangements: bool,
+
+    /// Pin counts to try with each arrangement when comparing arrangements, separated by commas.
+    /// Uses --pin-count when not given.
+    #[arg(long, value_delimiter(','), requires("compare_arrangements"))]
+    pub compare_pin_counts: Vec<u32>,
+
+    /// Instead of creating the string art, run a short optimization with every combination of
+    /// these parameter values and save a labeled contact sheet of the results to the
+    /// --output-filepath. Separate parameters with semicolons and values with commas, like
+    /// `string_alpha=0.05,0.1,0.2;pin_count=150,250`. Parameters that can be swept are
+    /// `string_alpha`, `step_size`, `pin_count`, `pin_arrangement`, `max_strings`, and
+    /// `max_overlap`.
+    #[arg(long, value_name("PARAMETERS"), requires("output_filepath"))]
+    pub sweep: Option<Sweep>,
+
+    /// How many strings each run may use when comparing arrangements or sweeping parameters.
+    #[arg(long, default_value("500"))]
+    pub preview_strings: usize,
+
     /// Used when calculating a string's antialiasing. Smaller values -> finer antialiasing.
     #[arg(short = 's', long, default_value("1.0"))]
     pub step_size: f64,
@@ -49,14 +438,58 @@ pub struct Cli {
     #[arg(short = 'a', long, default_value("0.2"))]
     pub string_alpha: f64,
 
+    /// Keep the fraction of a color level each string adds to a pixel, so that very thin strings
+    /// (a low --string-alpha) still add up instead of rounding away. Takes 12 more bytes per
+    /// pixel.
+    #[arg(long)]
+    pub float_accumulation: bool,
+
     /// How many pins should be used in creating the image (approximately).
     #[arg(short = 'c', long, default_value("200"))]
     pub pin_count: u32,
 
     /// Should the pins be arranged on the image's perimeter, or in a grid across the entire image,
-    /// or in the largest possible centered circle, or scattered randomly?
+    /// or in the largest possible centered circle, or scattered randomly, or on several
+    /// concentric circles (`rings`), or scattered randomly but evenly, never too close together
+    /// (`poisson`)?
     #[arg(short = 'r', long, default_value("perimeter"))]
-    pub pin_arrangement: PinArrangement,
+    pub pin_arrangement: Arrangement,
+
+    /// How far in from the edges of the image to keep the pins, to leave clearance for the nails
+    /// of a physical frame. Either a number of pixels or a percentage of the image's shorter
+    /// side, like `2%`. The arrangement is fit into the smaller area.
+    #[arg(long, value_name("LENGTH"), default_value("0"))]
+    pub pin_margin: Length,
+
+    /// Leave out any pin closer than this many pixels to another, in any arrangement. A warning
+    /// says how many pins were placed when it's fewer than --pin-count.
+    #[arg(long, value_name("PIXELS"), value_parser(positive_f64))]
+    pub min_pin_spacing: Option<f64>,
+
+    /// Where pin 0 of the `circle` arrangement goes, in degrees clockwise from 12 o'clock.
+    #[arg(
+        long,
+        value_name("DEGREES"),
+        default_value("0"),
+        allow_negative_numbers(true)
+    )]
+    pub circle_start_angle: f64,
+
+    /// Which way the pins of the `circle` arrangement are numbered: `clockwise` or
+    /// `counter-clockwise`. Match this and `--circle-start-angle` to the numbering of a
+    /// pre-drilled hoop.
+    #[arg(long, default_value("clockwise"))]
+    pub circle_direction: Direction,
+
+    /// How many concentric circles the `rings` arrangement spreads the pins across. The inner
+    /// rings put pins near the center, for detail there like the face of a portrait.
+    #[arg(
+        long,
+        value_name("N"),
+        default_value("3"),
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub ring_count: u32,
 
     /// An RGB color in hex format `#RRGGBB` specifying the color of the background.
     #[arg(
@@ -87,72 +520,768 @@ pub struct Cli {
     #[arg(short = 'u', long)]
     pub auto_color: Option<usize>,
 
+    /// How to choose the colors: `standard` (the foreground colors), `cmy` (cyan, magenta, and
+    /// yellow strings on a white background, mixing like translucent threads), or `cmyk` (`cmy`
+    /// with black strings too). With `cmy` and `cmyk`, the image is separated into a layer for
+    /// each color, and each layer's strings are placed separately.
+    #[arg(
+        long,
+        default_value("standard"),
+        conflicts_with_all(["foreground_color", "background_color", "auto_color"])
+    )]
+    pub style: Style,
+
+    /// Use strings that fade from one color to another along their length, given as
+    /// `#RRGGBB,#RRGGBB`, instead of the foreground colors. Each string may run either way, so
+    /// it starts with either color and ends with the other.
+    #[arg(
+        long,
+        value_name("FROM,TO"),
+        conflicts_with_all([
+            "foreground_color",
+            "auto_color",
+            "style",
+            "minimize_travel",
+            "continuous_path"
+        ])
+    )]
+    pub gradient_strings: Option<Gradient>,
+
+    /// Reduce each color channel of the input image to this many tonal levels before creating
+    /// the string art. A handful of levels often yields cleaner, bolder results.
+    #[arg(long, value_parser = clap::value_parser!(u16).range(2..=256))]
+    pub posterize: Option<u16>,
+
+    /// Dither the input image to the foreground and background colors with Floyd-Steinberg
+    /// error diffusion before creating the string art. Tones become how densely the foreground
+    /// color is spread, which strings match more naturally than smooth gradients. Only used
+    /// with a single foreground color.
+    #[arg(long, conflicts_with("auto_color"))]
+    pub dither: bool,
+
+    /// Only use this rectangle of the input image, given as `x,y,width,height`. Each value is
+    /// either a number of pixels or a percentage of the image's size, like `10%,0,80%,100%`.
+    #[arg(long)]
+    pub crop: Option<Crop>,
+
+    /// Crop away plain borders of the input image, such as scanner margins or letterboxing, so
+    /// pins and strings aren't spent on them. A border is any rows and columns along the edges
+    /// that are all the color of the top left pixel, and the data file records what is kept as
+    /// the crop. Happens after --crop.
+    #[arg(long)]
+    pub auto_trim: bool,
+
+    /// Rotate the input image clockwise by 90, 180, or 270 degrees.
+    #[arg(long)]
+    pub rotate: Option<Rotation>,
+
+    /// Flip the input image horizontally.
+    #[arg(long)]
+    pub flip_h: bool,
+
+    /// Flip the input image vertically.
+    #[arg(long)]
+    pub flip_v: bool,
+
+    /// Letterbox the input image to a square, filling the empty space with this RGB color in
+    /// hex format `#RRGGBB`. Uses the background color when no color is given.
+    #[arg(long, value_name("HEX CODE"), num_args(0..=1))]
+    pub pad_to_square: Option<Option<Rgb>>,
+
+    /// Extend the canvas past the edges of the input image by this many pixels, or by this
+    /// percentage of the image's width and height, so pins are placed outside the photo and
+    /// strings cross it at shallower angles. The margin is filled with the background color.
+    #[arg(long, value_name("PX OR %"))]
+    pub pin_overscan: Option<Length>,
+
+    /// Smooth away noise in the input image before creating the string art, so strings aren't
+    /// wasted reproducing speckle. The strength is the radius of the filter in pixels.
+    #[arg(long)]
+    pub denoise: Option<u32>,
+
+    /// Which filter --denoise uses. `median` is good at removing speckle, while `bilateral`
+    /// smooths gently while keeping edges sharp.
+    #[arg(long, default_value("median"))]
+    pub denoise_filter: DenoiseFilter,
+
+    /// Stretch the contrast of the input image before creating the string art. `global` uses the
+    /// histogram of the entire image, while `clahe` adapts to each region of the image.
+    #[arg(long)]
+    pub equalize: Option<Equalize>,
+
+    /// Path to a photo of the board the strings will be wound on. Output images show the strings
+    /// on top of this photo instead of on the background color.
+    #[arg(long, value_name("FILEPATH"))]
+    pub canvas_image: Option<String>,
+
+    /// Choose strings to match the input image when drawn on top of --canvas-image, instead of
+    /// on top of the background color.
+    #[arg(long, requires("canvas_image"))]
+    pub canvas_target: bool,
+
+    /// Path to an image marking where strings may not pass, such as a hole or a mounted object.
+    /// Strings never cross the light pixels of the mask.
+    #[arg(long, value_name("FILEPATH"))]
+    pub exclusion_mask: Option<String>,
+
+    /// Path to an image whose black pixels hide parts of the input image, such as a border to
+    /// keep clean or a damaged area of a photo. Hidden pixels don't count toward the score, and
+    /// strings that lie mostly on them aren't placed. The mask is resized to match the input
+    /// image.
+    #[arg(long, value_name("FILEPATH"))]
+    pub mask_filepath: Option<String>,
+
+    /// Count the error of detailed regions of the input image more, so they get more strings than
+    /// smooth gradients. Where the brightness varies the most, a pixel counts 1 + STRENGTH times
+    /// as much as where it is flat. Scores are scaled up when this is used.
+    #[arg(long, value_name("STRENGTH"), value_parser(positive_f64))]
+    pub contrast_weighting: Option<f64>,
+
+    /// Count the error near edges of the input image more, so fine detail like facial features
+    /// isn't smeared. Edges are found with a Sobel filter, and on the strongest edge a pixel
+    /// counts 1 + STRENGTH times as much as far from any edge. Scores are scaled up when this is
+    /// used.
+    #[arg(long, value_name("STRENGTH"), value_parser(positive_f64))]
+    pub edge_weighting: Option<f64>,
+
+    /// How the error of each pixel is measured. `rgb` is the squared difference of the red,
+    /// green, and blue channels, which counts blue too much and green too little. `lab` is the
+    /// squared ΔE of CIELAB, which counts differences as they look, for better color strings
+    /// at some cost in speed.
+    #[arg(long, value_name("SPACE"), default_value("rgb"))]
+    pub score_space: ScoreSpace,
+
+    /// How the light of overlapping strings adds up. `srgb` adds the color values directly,
+    /// which makes overlaps look too dark. `linear` adds the light of the strings, as real
+    /// threads do, and converts back to sRGB to show it.
+    #[arg(long, default_value("srgb"))]
+    pub blend: Blend,
+
+    /// Path to a grayscale image of how much each part of the input image matters, such as
+    /// light faces and eyes over a dark background. The error of each pixel counts as much as
+    /// the brightness of the weight map there, so black areas don't count at all. The map is
+    /// resized to match the input image. Scores are scaled up when this is used.
+    #[arg(long, value_name("FILEPATH"))]
+    pub weight_filepath: Option<String>,
+
+    /// Seeds everything chosen at random: the pins of the `random` and `poisson` arrangements,
+    /// the changes the `annealing` optimizer tries, the pins each --ensemble member samples,
+    /// and the strings --perturb-rounds removes unless --perturb-seed is given.
+    /// Runs with the same inputs and seed give the same strings. Without it, each run is
+    /// different.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// In verbose mode, only print every Nth string that is added or removed.
+    #[arg(long, value_name("N"), default_value("1"), value_parser = clap::value_parser!(u64).range(1..))]
+    pub log_every: u64,
+
+    /// In verbose mode, print at most one added or removed string per this many seconds.
+    #[arg(long, value_name("SECONDS"), value_parser(positive_f64))]
+    pub log_interval: Option<f64>,
+
+    /// Location to save every string that is added or removed, as one JSON object per line.
+    #[arg(long, value_name("FILEPATH"))]
+    pub event_log_filepath: Option<String>,
+
+    /// Keep the run within about this many megabytes of memory. The memory needed for the image
+    /// and pins is estimated before the run, and the image is scaled down if it would not fit.
+    /// The most memory used is reported at the end.
+    #[arg(long, value_name("MB"), value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_memory_mb: Option<u64>,
+
+    /// Print a compact summary when the run finishes: the strings of each color, how much the
+    /// score improved, how long each phase took, the length of thread, and the size of each
+    /// file written.
+    #[arg(long)]
+    pub stats: bool,
+
     /// Output debugging messages. Pass multiple times for more verbose logging.
     #[arg(short = 'v', long, action(clap::ArgAction::Count))]
     pub verbose: u8,
 }
 
-pub fn parse_args() -> Args {
-    Cli::parse().into()
+#[cfg(feature = "cli")]
+pub fn positive_f64(string: &str) -> Result<f64, String> {
+    string
+        .parse::<f64>()
+        .ok()
+        .filter(|f| *f > 0.0)
+        .ok_or_else(|| format!("Expected a positive number but got \"{}\"", string))
+}
+
+/// A random number generator seeded by `seed`, or by the system when there is no seed
+pub fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+#[cfg(feature = "cli")]
+fn percent(string: &str) -> Result<f64, String> {
+    string
+        .parse::<f64>()
+        .ok()
+        .filter(|f| *f > 0.0 && *f <= 100.0)
+        .ok_or_else(|| {
+            format!(
+                "Expected a percentage above 0 and up to 100 but got \"{}\"",
+                string
+            )
+        })
+}
+
+#[cfg(feature = "cli")]
+pub fn parse_args() -> Result<Args, StringArtError> {
+    Args::try_from(Cli::parse())
+}
+
+/// An error with the options found after they were parsed, reported like clap's own errors
+#[cfg(feature = "cli")]
+pub fn arg_error(kind: ErrorKind, message: impl std::fmt::Display) -> StringArtError {
+    StringArtError::Args(clap::Error::raw(kind, message))
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Args {
     pub input_filepath: String,
-    pub output_filepath: Option<String>,
+    pub output_filepaths: Vec<String>,
+    pub output_scales: Vec<f64>,
+    pub force: bool,
+    pub render_style: RenderStyle,
+    pub draw_order: DrawOrder,
+    pub svg_filepath: Option<String>,
+    pub svg_stroke_width: f64,
     pub pins_filepath: Option<String>,
     pub data_filepath: Option<String>,
+    pub data_pretty: bool,
+    pub data_gzip: bool,
+    pub instructions_filepath: Option<String>,
+    pub no_tie_off_markers: bool,
+    pub no_color_change_markers: bool,
+    pub checkpoint_every: Option<u64>,
+    pub archive_filepath: Option<String>,
+    pub minimize_travel: bool,
+    pub continuous_path: bool,
     pub gif_filepath: Option<String>,
+    pub gif_strings_per_frame: Option<u64>,
+    pub gif_frame_delay: Option<u32>,
+    pub gif_palette: bool,
+    pub video_filepath: Option<String>,
+    pub video_fps: u32,
+    pub snapshot_filepath: Option<String>,
+    pub milestones_filepath: Option<String>,
+    pub milestones: Vec<usize>,
+    pub animation_filepath: Option<String>,
+    pub morph_to: Option<String>,
+    pub morph_filepath: Option<String>,
+    pub explain_filepath: Option<String>,
+    pub simulate_filepath: Option<String>,
+    pub simulate_distance: Option<f64>,
+    pub physical_width_mm: Option<f64>,
+    pub seconds_per_string: f64,
+    pub seconds_per_color_change: f64,
+    pub edit_from: Option<String>,
+    pub continue_from: Option<String>,
+    pub resume: Option<String>,
     pub max_strings: usize,
+    pub time_limit: Option<f64>,
+    pub min_improvement: Option<f64>,
+    pub max_passes: Option<usize>,
+    pub string_cost: i64,
+    pub max_overlap: Option<u32>,
+    pub min_pin_angle: Option<f64>,
+    pub min_string_length: Option<f64>,
+    pub optimizer: Strategy,
+    pub start_pin: Option<usize>,
+    pub annealing_steps: usize,
+    pub beam_width: usize,
+    pub luminance_first: bool,
+    pub swap_passes: Option<usize>,
+    pub perturb_rounds: Option<usize>,
+    pub perturb_percent: f64,
+    pub perturb_seed: Option<u64>,
+    pub ensemble: Option<u64>,
+    pub compare_arrangements: bool,
+    pub compare_pin_counts: Vec<u32>,
+    pub sweep: Option<Sweep>,
+    pub preview_strings: usize,
     pub step_size: f64,
     pub string_alpha: f64,
+    pub float_accumulation: bool,
     pub pin_count: u32,
-    pub pin_arrangement: PinArrangement,
+    pub pin_arrangement: Arrangement,
+    pub pin_margin: Length,
+    pub min_pin_spacing: Option<f64>,
+    pub circle_start_angle: f64,
+    pub circle_direction: Direction,
+    pub ring_count: u32,
     pub auto_color: Option<AutoColor>,
-    pub foreground_colors: HashSet<Rgb>,
+    pub style: Style,
+    pub gradient_strings: Option<Gradient>,
+    /// In the order they were given, followed by any automatically chosen colors
+    pub foreground_colors: Vec<Rgb>,
     pub background_color: Rgb,
+    pub crop: Option<Crop>,
+    pub auto_trim: bool,
+    pub rotate: Option<Rotation>,
+    pub flip_h: bool,
+    pub flip_v: bool,
+    pub pad_to_square: Option<Rgb>,
+    pub pin_overscan: Option<Length>,
+    pub posterize: Option<u16>,
+    pub dither: bool,
+    pub equalize: Option<Equalize>,
+    pub denoise: Option<u32>,
+    pub denoise_filter: DenoiseFilter,
+    pub canvas_image: Option<String>,
+    pub canvas_target: bool,
+    pub exclusion_mask: Option<String>,
+    pub mask_filepath: Option<String>,
+    pub contrast_weighting: Option<f64>,
+    pub edge_weighting: Option<f64>,
+    pub score_space: ScoreSpace,
+    pub blend: Blend,
+    pub weight_filepath: Option<String>,
+    pub seed: Option<u64>,
+    pub log_every: u64,
+    pub log_interval: Option<f64>,
+    pub event_log_filepath: Option<String>,
+    pub max_memory_mb: Option<u64>,
+    pub stats: bool,
     pub verbosity: u8,
+    /// Which frame of an animated input is being rendered
+    pub frame: Option<usize>,
     #[serde(skip)]
     pub image: image::DynamicImage,
+    /// The canvas image, resized to match the input image
+    #[serde(skip)]
+    pub canvas: Option<image::DynamicImage>,
+    /// The --morph-to image, preprocessed like the input image
+    #[serde(skip)]
+    pub morph_target: Option<image::DynamicImage>,
+    /// The pixels strings may not cross, from the exclusion mask
+    #[serde(skip)]
+    pub excluded: HashSet<Point>,
+    /// The pixels hidden by the mask
+    #[serde(skip)]
+    pub masked: HashSet<Point>,
+    /// The weight map, resized to match the input image
+    #[serde(skip)]
+    pub weight_map: Option<image::GrayImage>,
+    /// Stops the run early when cancelled
+    #[serde(skip)]
+    pub cancellation: Cancellation,
+    /// Suspends the run while paused
+    #[serde(skip)]
+    pub pause: Pause,
+    /// How far the run has got
+    #[serde(skip)]
+    pub progress: Progress,
+    /// Every frame of an animated input, with how long each is shown. Empty unless the input
+    /// is animated.
+    #[serde(skip)]
+    pub frames: Vec<(image::DynamicImage, Delay)>,
 }
 
+#[cfg(feature = "cli")]
 impl Cli {
-    pub fn image(&self) -> image::DynamicImage {
-        ImageReader::open(&self.input_filepath)
-            .unwrap_or_else(|_| {
-                clap::Command::new("input_filepath")
-                    .error(
-                        ErrorKind::Io,
-                        format!(
-                            "The input filepath '{}' could not be opened",
-                            &self.input_filepath
-                        ),
-                    )
-                    .exit()
+    /// The background color, before any automatic color selection
+    pub fn background_or_default(&self) -> Rgb {
+        self.background_color
+            .unwrap_or_else(|| Rgb::from_str(DEFAULT_BG).unwrap())
+    }
+
+    /// How many foreground colors the run will use, before any automatic color selection
+    pub fn color_count(&self) -> usize {
+        match self.style.inks() {
+            Some(inks) => inks.len(),
+            None if self.gradient_strings.is_some() => 2,
+            None => usize::max(
+                1,
+                self.foreground_color
+                    .as_ref()
+                    .map_or(0, |colors| colors.len())
+                    + self.auto_color.unwrap_or(0),
+            ),
+        }
+    }
+
+    /// Scale the preprocessed images down if a run on them would not fit in --max-memory-mb
+    fn fit_in_memory(
+        &self,
+        images: Vec<image::DynamicImage>,
+    ) -> Result<Vec<image::DynamicImage>, StringArtError> {
+        let (Some(budget_mb), Some(first)) = (self.max_memory_mb, images.first()) else {
+            return Ok(images);
+        };
+        let estimate = Estimate::new(
+            self.pin_count,
+            self.color_count(),
+            self.max_overlap.is_some(),
+            self.float_accumulation,
+        );
+        let (width, height) = (first.width(), first.height());
+        match estimate.fit(width, height, budget_mb * memory::BYTES_PER_MB) {
+            Ok(None) => Ok(images),
+            Ok(Some((fit_width, fit_height))) => {
+                term::warn(&format!(
+                    "A {}x{} image needs about {} MB, so it was scaled down to {}x{} to fit in \
+                     --max-memory-mb {}",
+                    width,
+                    height,
+                    estimate.required_bytes(width, height) / memory::BYTES_PER_MB,
+                    fit_width,
+                    fit_height,
+                    budget_mb
+                ));
+                Ok(images
+                    .into_iter()
+                    .map(|image| {
+                        image.resize_exact(
+                            fit_width,
+                            fit_height,
+                            image::imageops::FilterType::Triangle,
+                        )
+                    })
+                    .collect())
+            }
+            Err(fixed_bytes) => Err(arg_error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "--max-memory-mb {} is too small: {} pins need about {} MB before the image \
+                     is counted. Lower --pin-count or raise --max-memory-mb.",
+                    budget_mb,
+                    self.pin_count,
+                    fixed_bytes / memory::BYTES_PER_MB
+                ),
+            )),
+        }
+    }
+
+    /// The color to pad the image with if it should be padded to a square
+    pub fn pad_color(&self) -> Option<Rgb> {
+        self.pad_to_square
+            .map(|color| color.unwrap_or_else(|| self.background_or_default()))
+    }
+
+    /// The foreground and background colors to dither the image to, if it should be dithered
+    pub fn dither_colors(&self) -> Option<(Rgb, Rgb)> {
+        match (self.dither, self.foreground_color.as_deref()) {
+            (true, Some([foreground])) => Some((*foreground, self.background_or_default())),
+            _ => None,
+        }
+    }
+
+    /// Every frame of the input, which is a single frame unless the input is an animated gif or
+    /// a directory of images
+    pub fn frames(&self) -> Result<Vec<(image::DynamicImage, Delay)>, StringArtError> {
+        let path = std::path::Path::new(&self.input_filepath);
+        let is_gif = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("gif"));
+        let frames = if path.is_dir() {
+            self.directory_frames()?
+        } else if is_gif {
+            self.gif_frames()?
+        } else {
+            vec![(self.image()?, Delay::from_numer_denom_ms(0, 1))]
+        };
+        if frames.is_empty() {
+            return Err(self.input_error("contains no images"));
+        }
+        if frames.iter().any(|(image, _)| {
+            (image.width(), image.height()) != (frames[0].0.width(), frames[0].0.height())
+        }) {
+            return Err(self.input_error("has frames of different sizes"));
+        }
+        Ok(frames)
+    }
+
+    fn directory_frames(&self) -> Result<Vec<(image::DynamicImage, Delay)>, StringArtError> {
+        let mut paths = std::fs::read_dir(&self.input_filepath)
+            .map_err(|source| StringArtError::Read {
+                filepath: self.input_filepath.clone(),
+                source,
+            })?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| {
+                let filepath = path.display().to_string();
+                let image = ImageReader::open(&path)
+                    .and_then(|reader| reader.with_guessed_format())
+                    .map_err(|source| StringArtError::Read {
+                        filepath: filepath.clone(),
+                        source,
+                    })?
+                    .decode()
+                    .map_err(|source| StringArtError::decode(&filepath, source))?;
+                Ok((image, Delay::from_numer_denom_ms(DEFAULT_FRAME_DELAY_MS, 1)))
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "gif")]
+    fn gif_frames(&self) -> Result<Vec<(image::DynamicImage, Delay)>, StringArtError> {
+        let file =
+            std::fs::File::open(&self.input_filepath).map_err(|source| StringArtError::Read {
+                filepath: self.input_filepath.clone(),
+                source,
+            })?;
+        Ok(GifDecoder::new(std::io::BufReader::new(file))
+            .and_then(|decoder| decoder.into_frames().collect_frames())
+            .map_err(|source| StringArtError::decode(&self.input_filepath, source))?
+            .into_iter()
+            .map(|frame| {
+                let delay = frame.delay();
+                (image::DynamicImage::ImageRgba8(frame.into_buffer()), delay)
             })
-            .decode()
-            .unwrap_or_else(|_| {
-                clap::Command::new("input_filepath")
-                    .error(
-                        ErrorKind::Io,
-                        format!(
-                            "The input filepath '{}' could not be decoded",
-                            &self.input_filepath
-                        ),
-                    )
-                    .exit()
+            .collect())
+    }
+
+    #[cfg(not(feature = "gif"))]
+    fn gif_frames(&self) -> Result<Vec<(image::DynamicImage, Delay)>, StringArtError> {
+        Err(self.input_error("is a gif, but string_art was built without the `gif` feature"))
+    }
+
+    fn input_error(&self, problem: &str) -> StringArtError {
+        arg_error(
+            ErrorKind::Io,
+            format!("The input filepath '{}' {}", &self.input_filepath, problem),
+        )
+    }
+
+    /// The input image, for inputs that are a single image
+    pub fn image(&self) -> Result<image::DynamicImage, StringArtError> {
+        open_image(&self.input_filepath)
+    }
+}
+
+#[cfg(feature = "cli")]
+impl TryFrom<Cli> for Args {
+    type Error = StringArtError;
+
+    fn try_from(mut cli: Cli) -> Result<Self, StringArtError> {
+        #[cfg(not(feature = "gif"))]
+        if cli.gif_filepath.is_some()
+            || cli.animation_filepath.is_some()
+            || cli.morph_filepath.is_some()
+        {
+            return Err(arg_error(
+                ErrorKind::InvalidValue,
+                "Writing gifs needs string_art to be built with the `gif` feature",
+            ));
+        }
+        #[cfg(not(feature = "video"))]
+        if cli.video_filepath.is_some() {
+            return Err(arg_error(
+                ErrorKind::InvalidValue,
+                "Writing videos needs string_art to be built with the `video` feature",
+            ));
+        }
+        let mut frames = cli.frames()?;
+        let (width, height) = (frames[0].0.width(), frames[0].0.height());
+        // Record the crop in pixels of the original image
+        cli.crop = match cli.auto_trim {
+            true => Some(auto_trim(&frames[0].0, cli.crop)),
+            false => cli.crop.map(|crop| crop.in_pixels(width, height)),
+        };
+        let morph_target = cli
+            .morph_to
+            .as_ref()
+            .map(|filepath| {
+                let target = open_image(filepath)?.resize_exact(
+                    width,
+                    height,
+                    image::imageops::FilterType::Triangle,
+                );
+                Ok::<_, StringArtError>(preprocess(target, &cli))
             })
+            .transpose()?;
+        let delays: Vec<Delay> = frames.iter().map(|(_, delay)| *delay).collect();
+        let mut images: Vec<image::DynamicImage> = frames
+            .into_iter()
+            .map(|(image, _)| preprocess(image, &cli))
+            .chain(morph_target)
+            .collect();
+        images = cli.fit_in_memory(images)?;
+        let morph_target = match cli.morph_to {
+            Some(_) => images.pop(),
+            None => None,
+        };
+        frames = images.into_iter().zip(delays).collect();
+        let image = frames[0].0.clone();
+        if cli.output_scale.len() > cli.output_filepath.len() {
+            return Err(arg_error(
+                ErrorKind::TooManyValues,
+                "Each --output-scale needs a matching --output-filepath",
+            ));
+        }
+        if image.width() == 0 || image.height() == 0 {
+            return Err(arg_error(
+                ErrorKind::ValueValidation,
+                "The cropped image is empty",
+            ));
+        }
+        let mut args = Self::with_image(cli, image).with_images_from_options()?;
+        args.morph_target = morph_target;
+        if frames.len() > 1 {
+            args.frames = frames;
+        }
+        Ok(args)
     }
 }
 
-impl From<Cli> for Args {
-    fn from(cli: Cli) -> Self {
-        let image = cli.image();
+/// The black pixels of the mask, once it is resized to match the input image
+fn masked_points(mask: &image::DynamicImage, width: u32, height: u32) -> HashSet<Point> {
+    mask.resize_exact(width, height, image::imageops::FilterType::Nearest)
+        .into_luma8()
+        .enumerate_pixels()
+        .filter(|(_, _, luma)| luma[0] <= 127)
+        .map(|(x, y, _)| Point::new(x, y))
+        .collect()
+}
+
+/// The light pixels of the mask, once it is resized to match the input image
+fn excluded_points(mask: &image::DynamicImage, width: u32, height: u32) -> HashSet<Point> {
+    mask.resize_exact(width, height, image::imageops::FilterType::Nearest)
+        .into_luma8()
+        .enumerate_pixels()
+        .filter(|(_, _, luma)| luma[0] > 127)
+        .map(|(x, y, _)| Point::new(x, y))
+        .collect()
+}
+
+impl Args {
+    /// Every file the arguments ask to have written, with what each one is
+    pub fn outputs(&self) -> Vec<(&'static str, &String)> {
+        let optional = [
+            ("svg", &self.svg_filepath),
+            ("pins", &self.pins_filepath),
+            ("data", &self.data_filepath),
+            ("instructions", &self.instructions_filepath),
+            ("gif", &self.gif_filepath),
+            ("video", &self.video_filepath),
+            ("snapshot", &self.snapshot_filepath),
+            ("milestones", &self.milestones_filepath),
+            ("animation", &self.animation_filepath),
+            ("morph", &self.morph_filepath),
+            ("explain", &self.explain_filepath),
+            ("simulate", &self.simulate_filepath),
+            ("event_log", &self.event_log_filepath),
+            ("archive", &self.archive_filepath),
+        ];
+        self.output_filepaths
+            .iter()
+            .map(|filepath| ("output", filepath))
+            .chain(
+                optional
+                    .into_iter()
+                    .filter_map(|(kind, filepath)| filepath.as_ref().map(|f| (kind, f))),
+            )
+            .collect()
+    }
+
+    /// How to draw strings whose colors are relative to the background, like the colors the
+    /// optimizer works with
+    pub fn pen(&self) -> Pen {
+        Pen {
+            step_size: self.step_size,
+            string_alpha: self.string_alpha,
+            gradient: self
+                .gradient_strings
+                .map(|gradient| gradient.minus(self.background_color)),
+            blend: self.blend,
+            background: self.background_color,
+            traces: None,
+        }
+    }
+
+    /// How the color runs along a string whose color is relative to the background, in the
+    /// space strings are blended in
+    pub fn shade(&self, rgb: Rgb) -> Shade {
+        self.pen().shade(rgb)
+    }
+
+    /// The same arguments, but without saving any files or logging, for runs whose results are
+    /// only used to make other results
+    pub fn without_outputs(&self) -> Self {
+        Args {
+            output_filepaths: Vec::new(),
+            output_scales: Vec::new(),
+            svg_filepath: None,
+            pins_filepath: None,
+            data_filepath: None,
+            instructions_filepath: None,
+            gif_filepath: None,
+            video_filepath: None,
+            snapshot_filepath: None,
+            milestones_filepath: None,
+            animation_filepath: None,
+            morph_filepath: None,
+            archive_filepath: None,
+            explain_filepath: None,
+            simulate_filepath: None,
+            event_log_filepath: None,
+            verbosity: 0,
+            ..self.clone()
+        }
+    }
+
+    /// Load the canvas, masks and weight map named by the options, resized to
+    /// match the image
+    pub fn with_images_from_options(mut self) -> Result<Self, StringArtError> {
+        let (width, height) = (self.image.width(), self.image.height());
+        let resized = |filepath: &String| {
+            open_image(filepath).map(|loaded| {
+                loaded.resize_exact(width, height, image::imageops::FilterType::Triangle)
+            })
+        };
+        self.canvas = self.canvas_image.as_ref().map(resized).transpose()?;
+        self.excluded = self
+            .exclusion_mask
+            .as_ref()
+            .map(|filepath| open_image(filepath).map(|mask| excluded_points(&mask, width, height)))
+            .transpose()?
+            .unwrap_or_default();
+        self.masked = self
+            .mask_filepath
+            .as_ref()
+            .map(|filepath| open_image(filepath).map(|mask| masked_points(&mask, width, height)))
+            .transpose()?
+            .unwrap_or_default();
+        self.weight_map = self
+            .weight_filepath
+            .as_ref()
+            .map(|filepath| resized(filepath).map(|weights| weights.into_luma8()))
+            .transpose()?;
+        Ok(self)
+    }
+
+    /// Build the arguments around an image that has already been loaded and preprocessed
+    #[cfg(feature = "cli")]
+    pub fn with_image(cli: Cli, image: image::DynamicImage) -> Self {
+        let pad_to_square = cli.pad_color();
         let auto_color = cli.auto_color.map(|_| AutoColor::from(&cli));
-        let (foreground_colors, background_color) = match &auto_color {
-            Some(ac) => fg_and_bg(ac, &image),
-            None => (
-                cli.foreground_color
+        let gradient_colors = cli.gradient_strings.map(|g| vec![g.from, g.to]);
+        let listed_colors = match cli.style.inks() {
+            Some(inks) => inks.to_vec(),
+            None => gradient_colors
+                .clone()
+                .or(cli.foreground_color.clone())
+                .unwrap_or_default(),
+        };
+        let (foreground_colors, background_color) = match (&auto_color, cli.style.inks()) {
+            (_, Some(inks)) => (inks.iter().copied().collect(), Rgb::WHITE),
+            (Some(ac), None) => fg_and_bg(ac, &image),
+            (None, None) => (
+                gradient_colors
+                    .or(cli.foreground_color)
                     .unwrap_or_else(|| vec![Rgb::from_str(DEFAULT_FG).unwrap()])
                     .into_iter()
                     .collect(),
@@ -161,27 +1290,154 @@ impl From<Cli> for Args {
             ),
         };
 
-        Self {
+        let foreground_colors = ordered_colors(&listed_colors, foreground_colors);
+
+        Args {
             input_filepath: cli.input_filepath,
-            output_filepath: cli.output_filepath,
+            output_scales: (0..cli.output_filepath.len())
+                .map(|i| cli.output_scale.get(i).copied().unwrap_or(1.0))
+                .collect(),
+            output_filepaths: cli.output_filepath,
+            force: cli.force,
+            render_style: cli.render_style,
+            draw_order: cli.draw_order,
+            svg_filepath: cli.svg_filepath,
+            svg_stroke_width: cli.svg_stroke_width,
             pins_filepath: cli.pins_filepath,
-            data_filepath: cli.data_filepath,
+            data_filepath: cli.data_filepath.or(cli.resume.clone()),
+            data_pretty: cli.data_pretty,
+            data_gzip: cli.data_gzip,
+            instructions_filepath: cli.instructions_filepath,
+            no_tie_off_markers: cli.no_tie_off_markers,
+            no_color_change_markers: cli.no_color_change_markers,
+            checkpoint_every: cli.checkpoint_every,
+            archive_filepath: cli.archive_filepath,
+            minimize_travel: cli.minimize_travel,
+            continuous_path: cli.continuous_path,
             gif_filepath: cli.gif_filepath,
+            gif_strings_per_frame: cli.gif_strings_per_frame,
+            gif_frame_delay: cli.gif_frame_delay,
+            gif_palette: cli.gif_palette,
+            video_filepath: cli.video_filepath,
+            video_fps: cli.video_fps,
+            snapshot_filepath: cli.snapshot_filepath,
+            milestones_filepath: cli.milestones_filepath,
+            milestones: cli.milestones,
+            animation_filepath: cli.animation_filepath,
+            morph_to: cli.morph_to,
+            morph_filepath: cli.morph_filepath,
+            explain_filepath: cli.explain_filepath,
+            simulate_filepath: cli.simulate_filepath,
+            simulate_distance: cli.simulate_distance,
+            physical_width_mm: cli.physical_width_mm,
+            seconds_per_string: cli.seconds_per_string,
+            seconds_per_color_change: cli.seconds_per_color_change,
+            edit_from: cli.edit_from,
+            continue_from: cli.continue_from,
+            resume: cli.resume,
             max_strings: cli.max_strings,
+            time_limit: cli.time_limit,
+            min_improvement: cli.min_improvement,
+            max_passes: cli.max_passes.map(|max| max as usize),
+            string_cost: cli.string_cost,
+            max_overlap: cli.max_overlap,
+            min_pin_angle: cli.min_pin_angle,
+            min_string_length: cli.min_string_length,
+            optimizer: cli.optimizer,
+            start_pin: cli.start_pin,
+            annealing_steps: cli.annealing_steps,
+            beam_width: cli.beam_width,
+            luminance_first: cli.luminance_first,
+            swap_passes: cli.swap_passes,
+            perturb_rounds: cli.perturb_rounds,
+            perturb_percent: cli.perturb_percent,
+            perturb_seed: cli.perturb_seed,
+            ensemble: cli.ensemble,
+            compare_arrangements: cli.compare_arrangements,
+            compare_pin_counts: cli.compare_pin_counts,
+            sweep: cli.sweep,
+            preview_strings: cli.preview_strings,
             step_size: cli.step_size,
             string_alpha: cli.string_alpha,
+            float_accumulation: cli.float_accumulation,
             pin_count: cli.pin_count,
             pin_arrangement: cli.pin_arrangement,
+            pin_margin: cli.pin_margin,
+            min_pin_spacing: cli.min_pin_spacing,
+            circle_start_angle: cli.circle_start_angle,
+            circle_direction: cli.circle_direction,
+            ring_count: cli.ring_count,
             auto_color,
+            style: cli.style,
+            gradient_strings: cli.gradient_strings,
             foreground_colors,
             background_color,
+            crop: cli.crop,
+            auto_trim: cli.auto_trim,
+            rotate: cli.rotate,
+            flip_h: cli.flip_h,
+            flip_v: cli.flip_v,
+            pad_to_square,
+            pin_overscan: cli.pin_overscan,
+            posterize: cli.posterize,
+            dither: cli.dither,
+            equalize: cli.equalize,
+            denoise: cli.denoise,
+            denoise_filter: cli.denoise_filter,
+            log_every: cli.log_every,
+            log_interval: cli.log_interval,
+            event_log_filepath: cli.event_log_filepath,
+            max_memory_mb: cli.max_memory_mb,
+            stats: cli.stats,
             verbosity: cli.verbose,
+            frame: None,
+            canvas_image: cli.canvas_image,
+            canvas_target: cli.canvas_target,
+            canvas: None,
+            morph_target: None,
+            exclusion_mask: cli.exclusion_mask,
+            mask_filepath: cli.mask_filepath,
+            contrast_weighting: cli.contrast_weighting,
+            edge_weighting: cli.edge_weighting,
+            score_space: cli.score_space,
+            blend: cli.blend,
+            weight_filepath: cli.weight_filepath,
+            seed: cli.seed,
+            excluded: HashSet::new(),
+            masked: HashSet::new(),
+            weight_map: None,
+            cancellation: Cancellation::default(),
+            pause: Pause::default(),
+            progress: Progress::default(),
             image,
+            frames: Vec::new(),
         }
     }
 }
 
-#[cfg(test)]
+/// The listed colors in order, without repeats, followed by the rest of the colors sorted so
+/// runs are repeatable
+#[cfg(feature = "cli")]
+fn ordered_colors(listed: &[Rgb], colors: HashSet<Rgb>) -> Vec<Rgb> {
+    let mut ordered: Vec<Rgb> = Vec::new();
+    listed
+        .iter()
+        .filter(|rgb| colors.contains(rgb))
+        .for_each(|rgb| {
+            if !ordered.contains(rgb) {
+                ordered.push(*rgb)
+            }
+        });
+    let mut rest: Vec<Rgb> = colors
+        .into_iter()
+        .filter(|rgb| !ordered.contains(rgb))
+        .collect();
+    rest.sort_by_key(|rgb| (rgb.r, rgb.g, rgb.b));
+    ordered.extend(rest);
+    ordered
+}
+
+#[cfg(all(test, feature = "cli"))]
 mod test {
     use super::*;
 
@@ -212,99 +1468,901 @@ mod test {
             "--output-filepath",
             &output_filepath,
         ]);
-        assert_eq!(Some(output_filepath), cli.output_filepath);
+        assert_eq!(vec![output_filepath], cli.output_filepath);
     }
 
     #[test]
-    fn test_pins_filepath() {
-        let pins_filepath = "pins.png".to_owned();
+    fn test_max_memory_mb() {
         let cli = Cli::parse_from(vec![
             "string_art",
             "--input-filepath",
             &input_filepath(),
-            "--pins-filepath",
-            &pins_filepath,
+            "--max-memory-mb",
+            "512",
         ]);
-        assert_eq!(Some(pins_filepath), cli.pins_filepath);
+        assert_eq!(Some(512), cli.max_memory_mb);
     }
 
     #[test]
-    fn test_data_filepath() {
-        let data_filepath = "data.json".to_owned();
+    fn test_max_memory_mb_scales_the_image_down() {
         let cli = Cli::parse_from(vec![
             "string_art",
             "--input-filepath",
             &input_filepath(),
-            "--data-filepath",
-            &data_filepath,
+            "--pin-count",
+            "10",
+            "--max-memory-mb",
+            "17",
         ]);
-        assert_eq!(Some(data_filepath), cli.data_filepath);
+        // 1 MB is left after the fixed 16 MB, which is room for about 13,000 pixels at 80 bytes
+        let images = cli
+            .fit_in_memory(vec![image::DynamicImage::new_rgb8(400, 200)])
+            .unwrap();
+        assert_eq!((161, 80), (images[0].width(), images[0].height()));
+        let images = cli
+            .fit_in_memory(vec![image::DynamicImage::new_rgb8(40, 20)])
+            .unwrap();
+        assert_eq!((40, 20), (images[0].width(), images[0].height()));
     }
 
     #[test]
-    fn test_gif_filepath() {
-        let gif_filepath = "test.gif".to_owned();
+    fn test_max_memory_mb_too_small_is_an_error() {
+        let filepath = std::env::temp_dir().join("string_art_max_memory.png");
+        let filepath = filepath.to_str().unwrap();
+        image::RgbImage::new(4, 4).save(filepath).unwrap();
         let cli = Cli::parse_from(vec![
             "string_art",
             "--input-filepath",
-            &input_filepath(),
-            "--gif-filepath",
-            &gif_filepath,
+            filepath,
+            "--max-memory-mb",
+            "1",
         ]);
-        assert_eq!(Some(gif_filepath), cli.gif_filepath);
+        let args = Args::try_from(cli);
+        std::fs::remove_file(filepath).unwrap();
+        assert!(matches!(args, Err(StringArtError::Args(_))));
     }
 
     #[test]
-    fn test_max_strings() {
-        let max_strings = 10;
+    fn test_missing_input_is_an_error() {
         let cli = Cli::parse_from(vec![
             "string_art",
             "--input-filepath",
-            &input_filepath(),
-            "--max-strings",
-            &max_strings.to_string(),
+            "/nonexistent/string_art.png",
         ]);
-        assert_eq!(max_strings, cli.max_strings);
+        assert!(matches!(
+            Args::try_from(cli),
+            Err(StringArtError::Read { .. })
+        ));
     }
 
     #[test]
-    fn test_step_size() {
-        let step_size = 0.83;
+    fn test_stats() {
         let cli = Cli::parse_from(vec![
             "string_art",
             "--input-filepath",
             &input_filepath(),
-            "--step-size",
-            &step_size.to_string(),
+            "--stats",
         ]);
-        assert_eq!(step_size, cli.step_size);
+        assert!(cli.stats);
     }
 
     #[test]
-    fn test_string_alpha() {
-        let string_alpha = 0.83;
+    fn test_force() {
         let cli = Cli::parse_from(vec![
             "string_art",
             "--input-filepath",
             &input_filepath(),
-            "--string-alpha",
-            &string_alpha.to_string(),
+            "--force",
         ]);
-        assert_eq!(string_alpha, cli.string_alpha);
+        assert!(cli.force);
     }
 
     #[test]
-    fn test_pin_count() {
-        let pin_count = 12;
+    fn test_output_scales() {
         let cli = Cli::parse_from(vec![
             "string_art",
             "--input-filepath",
             &input_filepath(),
-            "--pin-count",
-            &pin_count.to_string(),
+            "--output-filepath",
+            "web.png",
+            "--output-scale",
+            "0.5",
+            "--output-filepath",
+            "print.png",
+            "--output-scale",
+            "4",
+            "--output-filepath",
+            "plain.png",
         ]);
-        assert_eq!(pin_count, cli.pin_count);
-    }
+        assert_eq!(vec![0.5, 4.0], cli.output_scale);
+        let args = Args::with_image(cli, image::DynamicImage::new_rgb8(1, 1));
+        assert_eq!(
+            vec!["web.png", "print.png", "plain.png"],
+            args.output_filepaths
+        );
+        assert_eq!(vec![0.5, 4.0, 1.0], args.output_scales);
+    }
+
+    #[test]
+    fn test_render_style() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--render-style",
+            "realistic",
+        ]);
+        assert_eq!(RenderStyle::Realistic, cli.render_style);
+    }
+
+    #[test]
+    fn test_output_scale_must_be_positive() {
+        let matches: Result<_, _> = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--output-scale",
+            "0",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_pins_filepath() {
+        let pins_filepath = "pins.png".to_owned();
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--pins-filepath",
+            &pins_filepath,
+        ]);
+        assert_eq!(Some(pins_filepath), cli.pins_filepath);
+    }
+
+    #[test]
+    fn test_data_filepath() {
+        let data_filepath = "data.json".to_owned();
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--data-filepath",
+            &data_filepath,
+        ]);
+        assert_eq!(Some(data_filepath), cli.data_filepath);
+    }
+
+    #[test]
+    fn test_snapshot_filepath() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--snapshot-filepath",
+            "snapshot.png",
+        ]);
+        assert_eq!(Some("snapshot.png".to_owned()), cli.snapshot_filepath);
+    }
+
+    #[test]
+    fn test_instructions() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--instructions-filepath",
+            "steps.txt",
+            "--no-tie-off-markers",
+            "--checkpoint-every",
+            "250",
+        ]);
+        assert_eq!(Some("steps.txt".to_owned()), cli.instructions_filepath);
+        assert!(cli.no_tie_off_markers);
+        assert!(!cli.no_color_change_markers);
+        assert_eq!(Some(250), cli.checkpoint_every);
+    }
+
+    #[test]
+    fn test_minimize_travel() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--minimize-travel",
+        ]);
+        assert!(cli.minimize_travel);
+    }
+
+    #[test]
+    fn test_checkpoints_need_instructions() {
+        let matches = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--checkpoint-every",
+            "250",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_continuous_path() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--continuous-path",
+        ]);
+        assert!(cli.continuous_path);
+        let matches = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--continuous-path",
+            "--minimize-travel",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_milestones() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--milestones-filepath",
+            "milestones.png",
+            "--milestones",
+            "100,200",
+        ]);
+        assert_eq!(Some("milestones.png".to_owned()), cli.milestones_filepath);
+        assert_eq!(vec![100, 200], cli.milestones);
+    }
+
+    #[test]
+    fn test_default_milestones() {
+        let cli = Cli::parse_from(vec!["string_art", "--input-filepath", &input_filepath()]);
+        assert_eq!(vec![500, 1000, 2000, 4000], cli.milestones);
+    }
+
+    #[test]
+    fn test_data_format() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--data-pretty",
+            "--data-gzip",
+        ]);
+        assert!(cli.data_pretty);
+        assert!(cli.data_gzip);
+    }
+
+    #[test]
+    fn test_data_pretty_conflicts_with_data_compact() {
+        let matches = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--data-pretty",
+            "--data-compact",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_gif_filepath() {
+        let gif_filepath = "test.gif".to_owned();
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--gif-filepath",
+            &gif_filepath,
+        ]);
+        assert_eq!(Some(gif_filepath), cli.gif_filepath);
+    }
+
+    #[test]
+    fn test_gif_pacing() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--gif-filepath",
+            "test.gif",
+            "--gif-strings-per-frame",
+            "500",
+            "--gif-frame-delay",
+            "40",
+        ]);
+        assert_eq!(Some(500), cli.gif_strings_per_frame);
+        assert_eq!(Some(40), cli.gif_frame_delay);
+        assert!(!cli.gif_palette);
+        assert!(Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--gif-palette",
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--gif-frame-delay",
+            "40",
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--gif-filepath",
+            "test.gif",
+            "--gif-strings-per-frame",
+            "0",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_video() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--video-filepath",
+            "build.mp4",
+        ]);
+        assert_eq!(Some("build.mp4".to_owned()), cli.video_filepath);
+        assert_eq!(30, cli.video_fps);
+        assert!(Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--video-fps",
+            "60",
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--video-filepath",
+            "build.mp4",
+            "--video-fps",
+            "0",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_explain_filepath() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--explain-filepath",
+            "explain.png",
+        ]);
+        assert_eq!(Some("explain.png".to_owned()), cli.explain_filepath);
+    }
+
+    #[test]
+    fn test_svg() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--svg-filepath",
+            "art.svg",
+            "--svg-stroke-width",
+            "0.5",
+        ]);
+        assert_eq!(Some("art.svg".to_owned()), cli.svg_filepath);
+        assert_eq!(0.5, cli.svg_stroke_width);
+        assert!(Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--svg-stroke-width",
+            "0.5",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_simulate_distance() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--simulate-filepath",
+            "far.png",
+            "--simulate-distance",
+            "3.5",
+            "--physical-width-mm",
+            "600",
+        ]);
+        assert_eq!(Some("far.png".to_owned()), cli.simulate_filepath);
+        assert_eq!(Some(3.5), cli.simulate_distance);
+        assert_eq!(Some(600.0), cli.physical_width_mm);
+    }
+
+    #[test]
+    fn test_build_time_rates() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--seconds-per-string",
+            "7.5",
+        ]);
+        assert_eq!(7.5, cli.seconds_per_string);
+        assert_eq!(180.0, cli.seconds_per_color_change);
+    }
+
+    #[test]
+    fn test_simulate_distance_requires_physical_width() {
+        let matches: Result<_, _> = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--simulate-filepath",
+            "far.png",
+            "--simulate-distance",
+            "3.5",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_edit_from() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--edit-from",
+            "data.json",
+        ]);
+        assert_eq!(Some("data.json".to_owned()), cli.edit_from);
+    }
+
+    #[test]
+    fn test_continue_from() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--continue-from",
+            "data.json",
+        ]);
+        assert_eq!(Some("data.json".to_owned()), cli.continue_from);
+
+        let matches = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--continue-from",
+            "data.json",
+            "--edit-from",
+            "data.json",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_resume() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--resume",
+            "data.json",
+        ]);
+        assert_eq!(Some("data.json".to_owned()), cli.resume);
+        let args = Args::with_image(cli, image::DynamicImage::new_rgb8(4, 4));
+        assert_eq!(Some("data.json".to_owned()), args.data_filepath);
+
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--resume",
+            "data.json",
+            "--data-filepath",
+            "resumed.json",
+        ]);
+        let args = Args::with_image(cli, image::DynamicImage::new_rgb8(4, 4));
+        assert_eq!(Some("resumed.json".to_owned()), args.data_filepath);
+
+        let matches = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--resume",
+            "data.json",
+            "--continue-from",
+            "data.json",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_max_strings() {
+        let max_strings = 10;
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--max-strings",
+            &max_strings.to_string(),
+        ]);
+        assert_eq!(max_strings, cli.max_strings);
+    }
+
+    #[test]
+    fn test_string_cost() {
+        let cli = Cli::parse_from(vec!["string_art", "--input-filepath", &input_filepath()]);
+        assert_eq!(0, cli.string_cost);
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--string-cost",
+            "50000",
+        ]);
+        assert_eq!(50000, cli.string_cost);
+        let matches = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--string-cost",
+            "-1",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--time-limit",
+            "3600",
+        ]);
+        assert_eq!(Some(3600.0), cli.time_limit);
+    }
+
+    #[test]
+    fn test_min_improvement() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--min-improvement",
+            "0.01",
+        ]);
+        assert_eq!(Some(0.01), cli.min_improvement);
+    }
+
+    #[test]
+    fn test_max_passes() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--max-passes",
+            "20",
+        ]);
+        assert_eq!(Some(20), cli.max_passes);
+    }
+
+    #[test]
+    fn test_optimizer() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--optimizer",
+            "beam",
+            "--beam-width",
+            "8",
+        ]);
+        assert_eq!(Strategy::Beam, cli.optimizer);
+        assert_eq!(8, cli.beam_width);
+    }
+
+    #[test]
+    fn test_start_pin() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--optimizer",
+            "greedy-walk",
+            "--start-pin",
+            "17",
+        ]);
+        assert_eq!(Some(17), cli.start_pin);
+    }
+
+    #[test]
+    fn test_luminance_first() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--luminance-first",
+        ]);
+        assert!(cli.luminance_first);
+    }
+
+    #[test]
+    fn test_swap_passes() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--swap-passes",
+            "3",
+        ]);
+        assert_eq!(Some(3), cli.swap_passes);
+    }
+
+    #[test]
+    fn test_perturb() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--perturb-rounds",
+            "4",
+            "--perturb-percent",
+            "10",
+            "--perturb-seed",
+            "42",
+        ]);
+        assert_eq!(Some(4), cli.perturb_rounds);
+        assert_eq!(10.0, cli.perturb_percent);
+        assert_eq!(Some(42), cli.perturb_seed);
+    }
+
+    #[test]
+    fn test_default_optimizer() {
+        let cli = Cli::parse_from(vec!["string_art", "--input-filepath", &input_filepath()]);
+        assert_eq!(Strategy::GreedyBatch, cli.optimizer);
+    }
+
+    #[test]
+    fn test_ensemble() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--ensemble",
+            "4",
+        ]);
+        assert_eq!(Some(4), cli.ensemble);
+    }
+
+    #[test]
+    fn test_compare_arrangements() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--compare-arrangements",
+            "--compare-pin-counts",
+            "150,250",
+            "--preview-strings",
+            "100",
+        ]);
+        assert!(cli.compare_arrangements);
+        assert_eq!(vec![150, 250], cli.compare_pin_counts);
+        assert_eq!(100, cli.preview_strings);
+    }
+
+    #[test]
+    fn test_sweep() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--output-filepath",
+            "sheet.png",
+            "--sweep",
+            "string_alpha=0.1,0.2;pin_count=150",
+        ]);
+        assert_eq!(
+            Some(Sweep::from_str("string_alpha=0.1,0.2;pin_count=150").unwrap()),
+            cli.sweep
+        );
+    }
+
+    #[test]
+    fn test_sweep_needs_output_filepath() {
+        let matches = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--sweep",
+            "pin_count=150",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_compare_pin_counts_need_compare_arrangements() {
+        let matches = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--compare-pin-counts",
+            "150,250",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_ensemble_needs_two_members() {
+        let matches = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--ensemble",
+            "1",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_without_outputs() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "-o",
+            "out.png",
+            "-d",
+            "data.json",
+            "-v",
+        ]);
+        let args = Args::with_image(cli, image::DynamicImage::new_rgb8(1, 1));
+        let quiet = args.without_outputs();
+        assert!(quiet.output_filepaths.is_empty());
+        assert_eq!(None, quiet.data_filepath);
+        assert_eq!(0, quiet.verbosity);
+        assert_eq!(args.max_strings, quiet.max_strings);
+    }
+
+    #[test]
+    fn test_max_overlap() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--max-overlap",
+            "6",
+        ]);
+        assert_eq!(Some(6), cli.max_overlap);
+    }
+
+    #[test]
+    fn test_gradient_strings() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--gradient-strings",
+            "#FF8000,#400080",
+        ]);
+        let (orange, purple) = (Rgb::new(255, 128, 0), Rgb::new(64, 0, 128));
+        assert_eq!(
+            Some(Gradient {
+                from: orange,
+                to: purple
+            }),
+            cli.gradient_strings
+        );
+        assert_eq!(2, cli.color_count());
+        let args = Args::with_image(cli, image::DynamicImage::new_rgb8(1, 1));
+        assert_eq!(vec![orange, purple], args.foreground_colors);
+        assert_eq!(Shade::Fade(orange, purple), args.shade(orange));
+        assert_eq!(Shade::Fade(purple, orange), args.shade(purple));
+    }
+
+    #[test]
+    fn test_gradient_strings_conflict_with_foreground_color() {
+        let result = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--gradient-strings",
+            "#FF8000,#400080",
+            "-f",
+            "#FFFFFF",
+        ]);
+        assert!(result.is_err());
+        assert!("#FF8000".parse::<Gradient>().is_err());
+    }
+
+    #[test]
+    fn test_min_pin_angle() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--min-pin-angle",
+            "7.5",
+        ]);
+        assert_eq!(Some(7.5), cli.min_pin_angle);
+    }
+
+    #[test]
+    fn test_min_string_length() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--min-string-length",
+            "40",
+        ]);
+        assert_eq!(Some(40.0), cli.min_string_length);
+    }
+
+    #[test]
+    fn test_step_size() {
+        let step_size = 0.83;
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--step-size",
+            &step_size.to_string(),
+        ]);
+        assert_eq!(step_size, cli.step_size);
+    }
+
+    #[test]
+    fn test_string_alpha() {
+        let string_alpha = 0.83;
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--string-alpha",
+            &string_alpha.to_string(),
+        ]);
+        assert_eq!(string_alpha, cli.string_alpha);
+    }
+
+    #[test]
+    fn test_float_accumulation() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--float-accumulation",
+        ]);
+        assert!(cli.float_accumulation);
+    }
+
+    #[test]
+    fn test_pin_count() {
+        let pin_count = 12;
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--pin-count",
+            &pin_count.to_string(),
+        ]);
+        assert_eq!(pin_count, cli.pin_count);
+    }
 
     #[test]
     fn test_pin_arrangement() {
@@ -315,7 +2373,87 @@ mod test {
             "--pin-arrangement",
             "random",
         ]);
-        assert_eq!(PinArrangement::Random, cli.pin_arrangement);
+        assert_eq!(Arrangement::Random, cli.pin_arrangement);
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--pin-arrangement",
+            "poisson",
+        ]);
+        assert_eq!(Arrangement::Poisson, cli.pin_arrangement);
+    }
+
+    #[test]
+    fn test_pin_margin() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--pin-margin",
+            "12",
+        ]);
+        assert_eq!(Length::Pixels(12), cli.pin_margin);
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--pin-margin",
+            "2.5%",
+        ]);
+        assert_eq!(Length::Percent(2.5), cli.pin_margin);
+    }
+
+    #[test]
+    fn test_min_pin_spacing() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--min-pin-spacing",
+            "4.5",
+        ]);
+        assert_eq!(Some(4.5), cli.min_pin_spacing);
+    }
+
+    #[test]
+    fn test_circle_orientation() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--pin-arrangement",
+            "circle",
+            "--circle-start-angle",
+            "-90",
+            "--circle-direction",
+            "counter-clockwise",
+        ]);
+        assert_eq!(-90.0, cli.circle_start_angle);
+        assert_eq!(Direction::CounterClockwise, cli.circle_direction);
+    }
+
+    #[test]
+    fn test_ring_count() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--pin-arrangement",
+            "rings",
+            "--ring-count",
+            "4",
+        ]);
+        assert_eq!(Arrangement::Rings, cli.pin_arrangement);
+        assert_eq!(4, cli.ring_count);
+        assert!(Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--ring-count",
+            "0",
+        ])
+        .is_err());
     }
 
     #[test]
@@ -341,10 +2479,39 @@ mod test {
             "--foreground-color",
             "#00FF00",
         ]);
-        assert_eq!(
-            Some(vec![Rgb::new(0, 0, 255), Rgb::new(0, 255, 0)]),
-            cli.foreground_color
-        );
+        assert_eq!(
+            Some(vec![Rgb::new(0, 0, 255), Rgb::new(0, 255, 0)]),
+            cli.foreground_color
+        );
+    }
+
+    #[test]
+    fn test_style_cmyk() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--style",
+            "cmyk",
+        ]);
+        assert_eq!(Style::Cmyk, cli.style);
+        let args = Args::with_image(cli, image::DynamicImage::new_rgb8(2, 2));
+        assert_eq!(Rgb::WHITE, args.background_color);
+        assert_eq!(Style::Cmyk.inks().unwrap(), &args.foreground_colors[..]);
+    }
+
+    #[test]
+    fn test_style_conflicts_with_colors() {
+        let result = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--style",
+            "cmy",
+            "-f",
+            "#FF0000",
+        ]);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -366,6 +2533,31 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_draw_order() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--draw-order",
+            "darkest-first",
+        ]);
+        assert_eq!(DrawOrder::DarkestFirst, cli.draw_order);
+    }
+
+    #[test]
+    fn test_ordered_colors() {
+        let red = Rgb::new(255, 0, 0);
+        let blue = Rgb::new(0, 0, 255);
+        assert_eq!(
+            vec![red, Rgb::WHITE, Rgb::BLACK, blue],
+            ordered_colors(
+                &[red, Rgb::WHITE, red],
+                HashSet::from([blue, Rgb::WHITE, red, Rgb::BLACK])
+            )
+        );
+    }
+
     #[test]
     fn test_two_foreground_colors() {
         let cli = Cli::parse_from(vec![
@@ -406,6 +2598,406 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_posterize() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--posterize",
+            "4",
+        ]);
+        assert_eq!(Some(4), cli.posterize);
+    }
+
+    #[test]
+    fn test_dither_colors() {
+        let dither_colors = |args: &[&str]| {
+            Cli::parse_from(
+                ["string_art", "--input-filepath", "in.png"]
+                    .iter()
+                    .chain(args),
+            )
+            .dither_colors()
+        };
+        assert_eq!(None, dither_colors(&[]));
+        assert_eq!(Some((Rgb::WHITE, Rgb::BLACK)), dither_colors(&["--dither"]));
+        assert_eq!(
+            Some((Rgb::BLACK, Rgb::WHITE)),
+            dither_colors(&["--dither", "-f", "#000000", "-b", "#FFFFFF"])
+        );
+        assert_eq!(
+            None,
+            dither_colors(&["--dither", "-f", "#000000", "-f", "#FF0000"])
+        );
+    }
+
+    #[test]
+    fn test_posterize_out_of_range() {
+        let matches: Result<_, _> = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--posterize",
+            "1",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_equalize() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--equalize",
+            "clahe",
+        ]);
+        assert_eq!(Some(Equalize::Clahe), cli.equalize);
+    }
+
+    #[test]
+    fn test_crop() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--crop",
+            "0,0,50%,100",
+        ]);
+        assert_eq!(Some("0,0,50%,100".parse().unwrap()), cli.crop);
+    }
+
+    #[test]
+    fn test_auto_trim() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--auto-trim",
+        ]);
+        assert!(cli.auto_trim);
+    }
+
+    #[test]
+    fn test_rotate() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--rotate",
+            "270",
+        ]);
+        assert_eq!(Some(Rotation::Rotate270), cli.rotate);
+    }
+
+    #[test]
+    fn test_invalid_rotate() {
+        let matches: Result<_, _> = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--rotate",
+            "45",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_flip() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--flip-h",
+            "--flip-v",
+        ]);
+        assert!(cli.flip_h);
+        assert!(cli.flip_v);
+    }
+
+    #[test]
+    fn test_pad_to_square() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--pad-to-square",
+            "#0000FF",
+        ]);
+        assert_eq!(Some(Some(Rgb::new(0, 0, 255))), cli.pad_to_square);
+        assert_eq!(Some(Rgb::new(0, 0, 255)), cli.pad_color());
+    }
+
+    #[test]
+    fn test_pin_overscan() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--pin-overscan",
+            "5%",
+        ]);
+        assert_eq!(Some(Length::Percent(5.0)), cli.pin_overscan);
+    }
+
+    #[test]
+    fn test_pad_to_square_without_color() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--pad-to-square",
+            "--background-color",
+            "#00FF00",
+        ]);
+        assert_eq!(Some(None), cli.pad_to_square);
+        assert_eq!(Some(Rgb::new(0, 255, 0)), cli.pad_color());
+    }
+
+    #[test]
+    fn test_denoise() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--denoise",
+            "2",
+            "--denoise-filter",
+            "bilateral",
+        ]);
+        assert_eq!(Some(2), cli.denoise);
+        assert_eq!(DenoiseFilter::Bilateral, cli.denoise_filter);
+    }
+
+    #[test]
+    fn test_canvas_image() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--canvas-image",
+            "wood.jpg",
+            "--canvas-target",
+        ]);
+        assert_eq!(Some("wood.jpg".to_owned()), cli.canvas_image);
+        assert!(cli.canvas_target);
+    }
+
+    #[test]
+    fn test_archive_filepath() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--archive-filepath",
+            "kit.zip",
+        ]);
+        assert_eq!(Some("kit.zip".to_owned()), cli.archive_filepath);
+    }
+
+    #[test]
+    fn test_morph() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--morph-to",
+            "after.jpg",
+            "--morph-filepath",
+            "morph.gif",
+        ]);
+        assert_eq!(Some("after.jpg".to_owned()), cli.morph_to);
+        assert_eq!(Some("morph.gif".to_owned()), cli.morph_filepath);
+    }
+
+    #[test]
+    fn test_morph_filepath_requires_morph_to() {
+        let result = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--morph-filepath",
+            "morph.gif",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_contrast_weighting() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--contrast-weighting",
+            "2.5",
+        ]);
+        assert_eq!(Some(2.5), cli.contrast_weighting);
+    }
+
+    #[test]
+    fn test_edge_weighting() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--edge-weighting",
+            "4",
+        ]);
+        assert_eq!(Some(4.0), cli.edge_weighting);
+    }
+
+    #[test]
+    fn test_score_space() {
+        let cli = Cli::parse_from(vec!["string_art", "--input-filepath", &input_filepath()]);
+        assert_eq!(ScoreSpace::Rgb, cli.score_space);
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--score-space",
+            "lab",
+        ]);
+        assert_eq!(ScoreSpace::Lab, cli.score_space);
+    }
+
+    #[test]
+    fn test_blend() {
+        let cli = Cli::parse_from(vec!["string_art", "--input-filepath", &input_filepath()]);
+        assert_eq!(Blend::Srgb, cli.blend);
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--blend",
+            "linear",
+        ]);
+        assert_eq!(Blend::Linear, cli.blend);
+    }
+
+    #[test]
+    fn test_weight_filepath() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--weight-filepath",
+            "faces.png",
+        ]);
+        assert_eq!(Some("faces.png".to_owned()), cli.weight_filepath);
+    }
+
+    #[test]
+    fn test_seed() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--seed",
+            "7",
+        ]);
+        assert_eq!(Some(7), cli.seed);
+    }
+
+    #[test]
+    fn test_seeded_rng_repeats() {
+        use rand::RngCore;
+        assert_eq!(
+            seeded_rng(Some(7)).next_u64(),
+            seeded_rng(Some(7)).next_u64()
+        );
+        assert_ne!(
+            seeded_rng(Some(7)).next_u64(),
+            seeded_rng(Some(8)).next_u64()
+        );
+    }
+
+    #[test]
+    fn test_exclusion_mask() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--exclusion-mask",
+            "hole.png",
+        ]);
+        assert_eq!(Some("hole.png".to_owned()), cli.exclusion_mask);
+    }
+
+    #[test]
+    fn test_excluded_points() {
+        let mut mask = image::GrayImage::new(2, 2);
+        mask.put_pixel(1, 0, image::Luma([255]));
+        let excluded = excluded_points(&image::DynamicImage::ImageLuma8(mask), 4, 4);
+        let expected = [(2, 0), (3, 0), (2, 1), (3, 1)]
+            .into_iter()
+            .map(|(x, y)| Point::new(x, y))
+            .collect::<HashSet<_>>();
+        assert_eq!(expected, excluded);
+    }
+
+    #[test]
+    fn test_mask_filepath() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--mask-filepath",
+            "border.png",
+        ]);
+        assert_eq!(Some("border.png".to_owned()), cli.mask_filepath);
+
+        let mut mask = image::GrayImage::from_pixel(2, 2, image::Luma([255]));
+        mask.put_pixel(0, 1, image::Luma([0]));
+        let masked = masked_points(&image::DynamicImage::ImageLuma8(mask), 4, 4);
+        let expected = [(0, 2), (1, 2), (0, 3), (1, 3)]
+            .into_iter()
+            .map(|(x, y)| Point::new(x, y))
+            .collect::<HashSet<_>>();
+        assert_eq!(expected, masked);
+    }
+
+    #[test]
+    fn test_canvas_target_requires_canvas_image() {
+        let matches: Result<_, _> = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--canvas-target",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn test_log_throttling() {
+        let cli = Cli::parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--log-every",
+            "100",
+            "--log-interval",
+            "2.5",
+            "--event-log-filepath",
+            "events.jsonl",
+        ]);
+        assert_eq!(100, cli.log_every);
+        assert_eq!(Some(2.5), cli.log_interval);
+        assert_eq!(Some("events.jsonl".to_owned()), cli.event_log_filepath);
+    }
+
+    #[test]
+    fn test_log_every_must_be_positive() {
+        let matches = Cli::try_parse_from(vec![
+            "string_art",
+            "--input-filepath",
+            &input_filepath(),
+            "--log-every",
+            "0",
+        ]);
+        assert!(matches.is_err());
+    }
+
     #[test]
     fn test_verbosity() {
         let cli = Cli::parse_from(vec![
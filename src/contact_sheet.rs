@@ -0,0 +1,177 @@
+use crate::image::Rgba;
+use crate::image::RgbaImage;
+
+/// How many times larger than the 3x5 font each label is drawn
+const TEXT_SCALE: u32 = 2;
+
+/// Space around each tile and between the lines of its label
+const MARGIN: u32 = 8;
+
+const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const TEXT_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// Arrange images in a grid with `columns` columns, each with the lines of its label beneath it
+pub fn contact_sheet(tiles: &[(Vec<String>, RgbaImage)], columns: usize) -> RgbaImage {
+    let columns = columns.clamp(1, usize::max(1, tiles.len()));
+    let rows = tiles.len().div_ceil(columns);
+    let lines = tiles.iter().map(|(l, _)| l.len()).max().unwrap_or(0) as u32;
+    let tile_width = tiles
+        .iter()
+        .map(|(label, image)| {
+            label
+                .iter()
+                .map(|line| text_width(line))
+                .fold(image.width(), u32::max)
+        })
+        .max()
+        .unwrap_or(0);
+    let image_height = tiles.iter().map(|(_, i)| i.height()).max().unwrap_or(0);
+    let cell_width = tile_width + MARGIN;
+    let cell_height = image_height + lines * (text_height() + MARGIN) + MARGIN;
+
+    let mut sheet = RgbaImage::from_pixel(
+        columns as u32 * cell_width + MARGIN,
+        rows as u32 * cell_height + MARGIN,
+        BACKGROUND,
+    );
+    for (i, (label, image)) in tiles.iter().enumerate() {
+        let x = MARGIN + (i % columns) as u32 * cell_width;
+        let y = MARGIN + (i / columns) as u32 * cell_height;
+        image::imageops::overlay(
+            &mut sheet,
+            image,
+            (x + (tile_width - image.width()) / 2) as i64,
+            y as i64,
+        );
+        for (j, line) in label.iter().enumerate() {
+            let line_y = y + image_height + MARGIN + j as u32 * (text_height() + MARGIN);
+            draw_text(&mut sheet, line, x, line_y);
+        }
+    }
+    sheet
+}
+
+fn text_width(text: &str) -> u32 {
+    (text.chars().count() as u32 * 4).saturating_sub(1) * TEXT_SCALE
+}
+
+fn text_height() -> u32 {
+    5 * TEXT_SCALE
+}
+
+fn draw_text(image: &mut RgbaImage, text: &str, x: u32, y: u32) {
+    for (i, c) in text.chars().enumerate() {
+        let glyph_x = x + i as u32 * 4 * TEXT_SCALE;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                for dy in 0..TEXT_SCALE {
+                    for dx in 0..TEXT_SCALE {
+                        let px = glyph_x + column * TEXT_SCALE + dx;
+                        let py = y + row as u32 * TEXT_SCALE + dy;
+                        if px < image.width() && py < image.height() {
+                            image.put_pixel(px, py, TEXT_COLOR);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The rows of a 3x5 pixel character, most significant bit on the left. Letters are always
+/// drawn in upper case.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tile(label: &str, width: u32, height: u32) -> (Vec<String>, RgbaImage) {
+        (
+            vec![label.to_owned()],
+            RgbaImage::from_pixel(width, height, Rgba([255, 0, 0, 255])),
+        )
+    }
+
+    #[test]
+    fn test_contact_sheet_size() {
+        let tiles = vec![tile("a", 40, 30), tile("b", 40, 30), tile("c", 20, 30)];
+        let sheet = contact_sheet(&tiles, 2);
+        let cell_height = 30 + text_height() + MARGIN * 2;
+        assert_eq!(2 * (40 + MARGIN) + MARGIN, sheet.width());
+        assert_eq!(2 * cell_height + MARGIN, sheet.height());
+    }
+
+    #[test]
+    fn test_contact_sheet_fits_long_labels() {
+        let sheet = contact_sheet(&[tile("string_alpha=0.05", 10, 10)], 3);
+        assert_eq!(text_width("string_alpha=0.05") + MARGIN * 2, sheet.width());
+    }
+
+    #[test]
+    fn test_contact_sheet_draws_tiles_and_labels() {
+        let sheet = contact_sheet(&[tile("1", 10, 10)], 1);
+        assert_eq!(Rgba([255, 0, 0, 255]), *sheet.get_pixel(MARGIN, MARGIN));
+        assert_eq!(BACKGROUND, *sheet.get_pixel(0, 0));
+        assert!(sheet.pixels().any(|p| *p == TEXT_COLOR));
+    }
+
+    #[test]
+    fn test_lowercase_glyphs_are_uppercase() {
+        assert_eq!(glyph('A'), glyph('a'));
+        assert_ne!(glyph('A'), glyph('?'));
+    }
+}
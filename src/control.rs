@@ -0,0 +1,213 @@
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// How often the score of a run is measured for its progress
+const SCORE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The cancellation that Ctrl-C triggers
+static INTERRUPTED: OnceLock<Cancellation> = OnceLock::new();
+
+/// The pause that SIGUSR1 toggles
+static SIGNALED: OnceLock<Pause> = OnceLock::new();
+
+/// Stops a run early. Clone it before starting the run, and call `cancel` from anywhere, such
+/// as another thread or a frame sink. The optimizer stops between steps, and the run finishes
+/// with the strings placed so far.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Cancel on the first Ctrl-C, so the run finishes with the strings placed so far. A second
+/// Ctrl-C stops immediately.
+#[cfg(unix)]
+pub fn cancel_on_interrupt(cancellation: &Cancellation) {
+    extern "C" fn handle(_: libc::c_int) {
+        if let Some(cancellation) = INTERRUPTED.get() {
+            cancellation.cancel();
+        }
+        unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+    }
+    if INTERRUPTED.set(cancellation.clone()).is_ok() {
+        unsafe { libc::signal(libc::SIGINT, handle as *const () as libc::sighandler_t) };
+    }
+}
+
+#[cfg(not(unix))]
+pub fn cancel_on_interrupt(_cancellation: &Cancellation) {}
+
+/// Clones of a cancellation are equal, since cancelling one cancels them all
+impl PartialEq for Cancellation {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Pauses a run between steps. Clone it before starting the run, and `toggle` it from anywhere,
+/// like a `Cancellation`.
+#[derive(Debug, Clone, Default)]
+pub struct Pause(Arc<AtomicBool>);
+
+impl Pause {
+    pub fn toggle(&self) {
+        self.0.fetch_xor(true, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Pause or resume each time the process receives SIGUSR1
+#[cfg(unix)]
+pub fn pause_on_signal(pause: &Pause) {
+    extern "C" fn handle(_: libc::c_int) {
+        if let Some(pause) = SIGNALED.get() {
+            pause.toggle();
+        }
+    }
+    if SIGNALED.set(pause.clone()).is_ok() {
+        unsafe { libc::signal(libc::SIGUSR1, handle as *const () as libc::sighandler_t) };
+    }
+}
+
+#[cfg(not(unix))]
+pub fn pause_on_signal(_pause: &Pause) {}
+
+/// Clones of a pause are equal, since toggling one toggles them all
+impl PartialEq for Pause {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// How far a run has got. Clone it before starting the run, and read it from anywhere, like a
+/// `Cancellation`.
+#[derive(Debug, Clone, Default)]
+pub struct Progress(Arc<Counts>);
+
+#[derive(Debug, Default)]
+struct Counts {
+    strings: AtomicUsize,
+    placed: AtomicU64,
+    score: AtomicI64,
+    scored_at: Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    /// How many strings there are now
+    pub fn strings(&self) -> usize {
+        self.0.strings.load(Ordering::Relaxed)
+    }
+
+    /// How many strings have been added since the run started, counting any that were removed
+    /// again
+    pub fn placed(&self) -> u64 {
+        self.0.placed.load(Ordering::Relaxed)
+    }
+
+    /// The score when it was last measured
+    pub fn score(&self) -> i64 {
+        self.0.score.load(Ordering::Relaxed)
+    }
+
+    /// Start from the strings the run was given, and their score
+    pub fn start(&self, strings: usize, score: i64) {
+        self.0.strings.store(strings, Ordering::Relaxed);
+        self.set_score(score);
+    }
+
+    /// Record the strings after a step. Measuring the score takes a pass over the image, so
+    /// `score` is only called once a second.
+    pub fn record(&self, strings: usize, score: impl FnOnce() -> i64) {
+        let previous = self.0.strings.swap(strings, Ordering::Relaxed);
+        self.0
+            .placed
+            .fetch_add(strings.saturating_sub(previous) as u64, Ordering::Relaxed);
+        let due = self
+            .0
+            .scored_at
+            .lock()
+            .unwrap()
+            .is_none_or(|at| at.elapsed() >= SCORE_INTERVAL);
+        if due {
+            self.set_score(score());
+        }
+    }
+
+    /// Record a score that was measured some other way, such as the final score
+    pub fn set_score(&self, score: i64) {
+        self.0.score.store(score, Ordering::Relaxed);
+        *self.0.scored_at.lock().unwrap() = Some(Instant::now());
+    }
+}
+
+/// Clones of a progress are equal, since they all report the same run
+impl PartialEq for Progress {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cancelling_a_clone_cancels_the_original() {
+        let cancellation = Cancellation::default();
+        let clone = cancellation.clone();
+        assert!(!cancellation.is_cancelled());
+        clone.cancel();
+        assert!(cancellation.is_cancelled());
+        assert_eq!(cancellation, clone);
+    }
+
+    #[test]
+    fn test_separate_cancellations_are_not_equal() {
+        assert_ne!(Cancellation::default(), Cancellation::default());
+    }
+
+    #[test]
+    fn test_toggling_a_clone_toggles_the_original() {
+        let pause = Pause::default();
+        let clone = pause.clone();
+        clone.toggle();
+        assert!(pause.is_paused());
+        clone.toggle();
+        assert!(!pause.is_paused());
+    }
+
+    #[test]
+    fn test_progress_counts_strings_placed() {
+        let progress = Progress::default();
+        progress.start(3, 100);
+        progress.record(5, || 90);
+        progress.record(4, || 80);
+        progress.record(6, || 70);
+        assert_eq!(6, progress.strings());
+        assert_eq!(4, progress.placed());
+    }
+
+    #[test]
+    fn test_progress_measures_the_score_once_a_second() {
+        let progress = Progress::default();
+        progress.start(0, 100);
+        progress.record(1, || panic!("The score was just measured"));
+        assert_eq!(100, progress.score());
+        *progress.0.scored_at.lock().unwrap() = Some(Instant::now() - SCORE_INTERVAL);
+        progress.record(2, || 90);
+        assert_eq!(90, progress.score());
+    }
+}
@@ -0,0 +1,313 @@
+use crate::geometry::Point;
+use crate::imagery::LineSegment;
+use crate::imagery::RefImage;
+use crate::imagery::Rgb;
+use crate::segment;
+use crate::serde::Deserialize;
+use crate::string_art;
+use crate::summary::row;
+use crate::term;
+use clap::Parser;
+use std::collections::HashMap;
+
+/// Compare the strings of two data files, such as runs with different options or from
+/// different versions of string_art.
+#[derive(Parser, Debug)]
+#[command(name = "string_art diff", version, max_term_width(100))]
+pub struct DiffCli {
+    /// The earlier data file, compressed or not.
+    #[arg(value_name("BEFORE"))]
+    pub before_filepath: String,
+
+    /// The later data file, compressed or not.
+    #[arg(value_name("AFTER"))]
+    pub after_filepath: String,
+
+    /// Save an image of the differences: added strings in green, removed strings in red,
+    /// recolored strings in blue, and unchanged strings in faint gray.
+    #[arg(short, long, value_name("FILEPATH"))]
+    pub output_filepath: Option<String>,
+
+    /// List every added, removed, and recolored string.
+    #[arg(short, long)]
+    pub list: bool,
+}
+
+/// The parts of a data file that are compared
+#[derive(Deserialize)]
+pub struct DiffFile {
+    image_width: u32,
+    image_height: u32,
+    final_score: Option<i64>,
+    #[serde(deserialize_with = "segment::deserialize_line_segments")]
+    line_segments: Vec<LineSegment>,
+}
+
+/// How the strings of one run became the strings of another. A string is the same whichever way
+/// it runs between its two ends.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Diff {
+    pub unchanged: Vec<LineSegment>,
+    /// Strings only in the later run, in its order
+    pub added: Vec<LineSegment>,
+    /// Strings only in the earlier run, in its order
+    pub removed: Vec<LineSegment>,
+    /// Strings along the same chord in both runs, with their earlier color and their later one
+    pub recolored: Vec<(LineSegment, Rgb)>,
+}
+
+/// The two ends of a string, in the same order whichever way it runs
+fn chord((a, b, _): &LineSegment) -> (Point, Point) {
+    match (a.x, a.y) <= (b.x, b.y) {
+        true => (*a, *b),
+        false => (*b, *a),
+    }
+}
+
+/// The strings that differ between two runs. Strings wound more than once are matched one
+/// winding at a time.
+pub fn compare(before: &[LineSegment], after: &[LineSegment]) -> Diff {
+    let key = |line_segment: &LineSegment| (chord(line_segment), line_segment.2);
+    let mut before_counts: HashMap<((Point, Point), Rgb), usize> = HashMap::new();
+    before
+        .iter()
+        .for_each(|l| *before_counts.entry(key(l)).or_default() += 1);
+
+    let mut diff = Diff::default();
+    let mut matched: HashMap<((Point, Point), Rgb), usize> = HashMap::new();
+    for line_segment in after {
+        match before_counts.get_mut(&key(line_segment)) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                *matched.entry(key(line_segment)).or_default() += 1;
+                diff.unchanged.push(*line_segment);
+            }
+            _ => diff.added.push(*line_segment),
+        }
+    }
+    for line_segment in before {
+        match matched.get_mut(&key(line_segment)) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => diff.removed.push(*line_segment),
+        }
+    }
+
+    let mut added = Vec::new();
+    for line_segment in std::mem::take(&mut diff.added) {
+        match diff
+            .removed
+            .iter()
+            .position(|removed| chord(removed) == chord(&line_segment))
+        {
+            Some(i) => {
+                let removed = diff.removed.remove(i);
+                diff.recolored.push((removed, line_segment.2));
+            }
+            None => added.push(line_segment),
+        }
+    }
+    diff.added = added;
+    diff
+}
+
+impl Diff {
+    /// A line for every added, removed, and recolored string
+    pub fn list(&self) -> Vec<String> {
+        let describe = |(a, b, _): &LineSegment| format!("{} to {}", a, b);
+        let added = self
+            .added
+            .iter()
+            .map(|l| format!("+ {} with {}", describe(l), l.2));
+        let removed = self
+            .removed
+            .iter()
+            .map(|l| format!("- {} with {}", describe(l), l.2));
+        let recolored = self
+            .recolored
+            .iter()
+            .map(|(l, rgb)| format!("~ {} from {} to {}", describe(l), l.2, rgb));
+        added.chain(removed).chain(recolored).collect()
+    }
+
+    /// The differences drawn over the unchanged strings, on a black background
+    pub fn image(&self, width: u32, height: u32) -> image::RgbaImage {
+        let mut ref_image = RefImage::new(width, height);
+        let faint = Rgb {
+            r: 96,
+            g: 96,
+            b: 96,
+        };
+        for (a, b, _) in &self.unchanged {
+            ref_image += ((*a, *b), faint, 1.0, 0.2);
+        }
+        let changes = [
+            (&self.added, Rgb { r: 0, g: 255, b: 0 }),
+            (&self.removed, Rgb { r: 255, g: 0, b: 0 }),
+        ];
+        for (line_segments, rgb) in changes {
+            for (a, b, _) in line_segments {
+                ref_image += ((*a, *b), rgb, 1.0, 1.0);
+            }
+        }
+        for ((a, b, _), _) in &self.recolored {
+            ref_image += ((*a, *b), Rgb { r: 0, g: 0, b: 255 }, 1.0, 1.0);
+        }
+        ref_image.color()
+    }
+}
+
+/// The change from one score to another, when both are known
+fn score_change(before: Option<i64>, after: Option<i64>) -> String {
+    match (before, after) {
+        (Some(0), Some(after)) => format!("0 -> {}", after),
+        (Some(before), Some(after)) => format!(
+            "{} -> {} ({:+.1}%)",
+            before,
+            after,
+            (after - before) as f64 / before as f64 * 100.0
+        ),
+        _ => "unknown".to_owned(),
+    }
+}
+
+/// A summary of the differences between two data files
+pub fn report(before: &DiffFile, after: &DiffFile, diff: &Diff) -> Vec<String> {
+    let (b, a) = (before.line_segments.len(), after.line_segments.len());
+    vec![
+        row(
+            "Strings",
+            &format!("{} -> {} ({:+})", b, a, a as i64 - b as i64),
+        ),
+        row("Unchanged", &diff.unchanged.len().to_string()),
+        row("Added", &diff.added.len().to_string()),
+        row("Removed", &diff.removed.len().to_string()),
+        row("Recolored", &diff.recolored.len().to_string()),
+        row(
+            "Final score",
+            &score_change(before.final_score, after.final_score),
+        ),
+    ]
+}
+
+/// Run `string_art diff`, printing how the strings changed between two data files
+pub fn diff() {
+    // Skip the program name, so `diff` is treated as the name of the command
+    let cli = DiffCli::parse_from(std::env::args().skip(1));
+    let read = |filepath: &str| -> DiffFile {
+        string_art::read_data(filepath).unwrap_or_else(|error| {
+            term::error(&error.to_string());
+            std::process::exit(1)
+        })
+    };
+    let before = read(&cli.before_filepath);
+    let after = read(&cli.after_filepath);
+    if (before.image_width, before.image_height) != (after.image_width, after.image_height) {
+        term::warn("The data files were made from images of different sizes");
+    }
+
+    let diff = compare(&before.line_segments, &after.line_segments);
+    if cli.list {
+        diff.list().iter().for_each(|line| println!("{}", line));
+    }
+    report(&before, &after, &diff)
+        .iter()
+        .for_each(|line| println!("{}", line));
+
+    if let Some(filepath) = &cli.output_filepath {
+        diff.image(after.image_width, after.image_height)
+            .save(filepath)
+            .unwrap_or_else(|_| panic!("Unable to save image at: '{}'", filepath));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::style::Data;
+
+    const RED: Rgb = Rgb { r: 255, g: 0, b: 0 };
+
+    fn p(x: u32, y: u32) -> Point {
+        Point::new(x, y)
+    }
+
+    #[test]
+    fn test_compare() {
+        let before = vec![
+            (p(0, 0), p(9, 0), Rgb::WHITE),
+            (p(0, 9), p(9, 0), Rgb::WHITE),
+            (p(0, 0), p(0, 9), Rgb::WHITE),
+            (p(0, 0), p(0, 9), Rgb::WHITE),
+        ];
+        let after = vec![
+            (p(9, 0), p(0, 0), Rgb::WHITE),
+            (p(0, 9), p(9, 0), RED),
+            (p(0, 0), p(0, 9), Rgb::WHITE),
+            (p(3, 3), p(9, 9), Rgb::WHITE),
+        ];
+        assert_eq!(
+            Diff {
+                unchanged: vec![
+                    (p(9, 0), p(0, 0), Rgb::WHITE),
+                    (p(0, 0), p(0, 9), Rgb::WHITE),
+                ],
+                added: vec![(p(3, 3), p(9, 9), Rgb::WHITE)],
+                removed: vec![(p(0, 0), p(0, 9), Rgb::WHITE)],
+                recolored: vec![((p(0, 9), p(9, 0), Rgb::WHITE), RED)],
+            },
+            compare(&before, &after)
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let diff = compare(
+            &[(p(0, 0), p(9, 0), Rgb::WHITE)],
+            &[(p(0, 0), p(9, 0), RED), (p(0, 9), p(9, 0), RED)],
+        );
+        assert_eq!(
+            vec![
+                format!("+ {} to {} with #FF0000", p(0, 9), p(9, 0)),
+                format!("~ {} to {} from #FFFFFF to #FF0000", p(0, 0), p(9, 0)),
+            ],
+            diff.list()
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let read = |data: Data| -> DiffFile {
+            serde_json::from_value(serde_json::to_value(data).unwrap()).unwrap()
+        };
+        let before = read(Data::example(&[]));
+        let mut data = Data::example(&[]);
+        data.line_segments
+            .push((data.pin_locations[1], data.pin_locations[2], Rgb::WHITE));
+        data.final_score = 21;
+        let after = read(data);
+        let diff = compare(&before.line_segments, &after.line_segments);
+        assert_eq!(
+            vec![
+                "Strings              : 1 -> 2 (+1)",
+                "Unchanged            : 1",
+                "Added                : 1",
+                "Removed              : 0",
+                "Recolored            : 0",
+                "Final score          : 42 -> 21 (-50.0%)",
+            ],
+            report(&before, &after, &diff)
+        );
+    }
+
+    #[test]
+    fn test_image_colors_the_changes() {
+        let diff = compare(
+            &[(p(0, 0), p(9, 0), Rgb::WHITE)],
+            &[(p(0, 9), p(9, 9), Rgb::WHITE)],
+        );
+        let img = diff.image(10, 10);
+        assert_eq!([255, 0, 0, 255], img.get_pixel(5, 0).0);
+        assert_eq!([0, 255, 0, 255], img.get_pixel(5, 9).0);
+        assert_eq!([0, 0, 0, 255], img.get_pixel(5, 5).0);
+    }
+}
@@ -0,0 +1,403 @@
+use crate::cli_app::{self, Args};
+use crate::error::StringArtError;
+use crate::geometry::Point;
+use crate::imagery::LineSegment;
+use crate::optimum::Constraints;
+use crate::segment;
+use crate::serde::Deserialize;
+use crate::string_art;
+use crate::style;
+use crate::style::Data;
+use crate::term;
+use clap::error::ErrorKind;
+use std::io::{BufRead, Write};
+use std::ops::RangeInclusive;
+
+const HELP: &str = "Commands:
+  list [RANGE]         List strings, optionally only those in RANGE
+  delete RANGE         Delete strings and keep them from being added again
+  delete-pins RANGE    Delete every string attached to the pins in RANGE
+  lock RANGE           Keep strings from being removed
+  lock-pins RANGE      Lock every string attached to the pins in RANGE
+  unlock               Unlock every string
+  run                  Improve the strings around the edits and save the outputs
+  save [FILEPATH]      Save the data file (defaults to --data-filepath)
+  help                 Show this message
+  quit                 Stop editing
+
+A RANGE is a single index like `12`, or an inclusive range like `12-20`.";
+
+/// The parts of a data file needed to keep working on it
+#[derive(Deserialize)]
+pub struct SavedRun {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub pin_locations: Vec<Point>,
+    #[serde(deserialize_with = "segment::deserialize_line_segments")]
+    pub line_segments: Vec<LineSegment>,
+}
+
+impl SavedRun {
+    /// Load a data file made from an image the size of `args.image`, for the command line
+    /// `option` that named it
+    pub fn load_for(filepath: &str, args: &Args, option: &str) -> Result<Self, StringArtError> {
+        let saved = SavedRun::load(filepath)?;
+        if (saved.image_width, saved.image_height) != (args.image.width(), args.image.height()) {
+            return Err(cli_app::arg_error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "The data file given to {} was made from an image with a different size",
+                    option
+                ),
+            ));
+        }
+        Ok(saved)
+    }
+
+    pub fn load(filepath: &str) -> Result<Self, StringArtError> {
+        string_art::read_data(filepath)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Help,
+    List(Option<RangeInclusive<usize>>),
+    Delete(RangeInclusive<usize>),
+    DeletePins(RangeInclusive<usize>),
+    Lock(RangeInclusive<usize>),
+    LockPins(RangeInclusive<usize>),
+    Unlock,
+    Run,
+    Save(Option<String>),
+    Quit,
+}
+
+fn parse_range(word: Option<&str>) -> Result<RangeInclusive<usize>, String> {
+    let word = word.ok_or("Expected a RANGE")?;
+    let index = |s: &str| {
+        s.parse::<usize>()
+            .map_err(|_| format!("Invalid RANGE: \"{}\"", word))
+    };
+    match word.split_once('-') {
+        Some((start, end)) => Ok(index(start)?..=index(end)?),
+        None => index(word).map(|i| i..=i),
+    }
+}
+
+impl core::str::FromStr for Command {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        let mut words = string.split_whitespace();
+        let command = match words.next() {
+            Some("help") => Command::Help,
+            Some("list") => match words.next() {
+                Some(range) => Command::List(Some(parse_range(Some(range))?)),
+                None => Command::List(None),
+            },
+            Some("delete") => Command::Delete(parse_range(words.next())?),
+            Some("delete-pins") => Command::DeletePins(parse_range(words.next())?),
+            Some("lock") => Command::Lock(parse_range(words.next())?),
+            Some("lock-pins") => Command::LockPins(parse_range(words.next())?),
+            Some("unlock") => Command::Unlock,
+            Some("run") => Command::Run,
+            Some("save") => Command::Save(words.next().map(str::to_owned)),
+            Some("quit") | Some("exit") => Command::Quit,
+            Some(other) => return Err(format!("Unknown command: \"{}\"", other)),
+            None => return Err("Type `help` to see the commands".to_owned()),
+        };
+        match words.next() {
+            Some(extra) => Err(format!("Unexpected argument: \"{}\"", extra)),
+            None => Ok(command),
+        }
+    }
+}
+
+struct Session {
+    pin_locations: Vec<Point>,
+    line_segments: Vec<LineSegment>,
+    constraints: Constraints,
+}
+
+impl Session {
+    fn pin_index(&self, point: &Point) -> usize {
+        self.pin_locations
+            .iter()
+            .position(|p| p == point)
+            .unwrap_or(usize::MAX)
+    }
+
+    fn touches_pins(&self, (a, b, _): &LineSegment, pins: &RangeInclusive<usize>) -> bool {
+        pins.contains(&self.pin_index(a)) || pins.contains(&self.pin_index(b))
+    }
+
+    fn describe(&self, i: usize) -> String {
+        let line_segment = self.line_segments[i];
+        let (a, b, rgb) = line_segment;
+        format!(
+            "[{:>6}]: pin {:>4} {} to pin {:>4} {} with {}{}",
+            i,
+            self.pin_index(&a),
+            a,
+            self.pin_index(&b),
+            b,
+            rgb,
+            if self.constraints.locked.contains(&line_segment) {
+                " (locked)"
+            } else {
+                ""
+            }
+        )
+    }
+
+    fn delete(&mut self, keep: impl Fn(usize, &LineSegment) -> bool) -> String {
+        let (kept, deleted): (Vec<_>, Vec<_>) = self
+            .line_segments
+            .iter()
+            .enumerate()
+            .partition(|(i, l)| keep(*i, l));
+        let deleted: Vec<LineSegment> = deleted.into_iter().map(|(_, l)| *l).collect();
+        self.line_segments = kept.into_iter().map(|(_, l)| *l).collect();
+        for line_segment in &deleted {
+            self.constraints.locked.remove(line_segment);
+            self.constraints.banned.insert(*line_segment);
+        }
+        format!("Deleted {} strings", deleted.len())
+    }
+
+    fn lock(&mut self, lock: impl Fn(usize, &LineSegment) -> bool) -> String {
+        let locked: Vec<LineSegment> = self
+            .line_segments
+            .iter()
+            .enumerate()
+            .filter(|(i, l)| lock(*i, l))
+            .map(|(_, l)| *l)
+            .collect();
+        self.constraints.locked.extend(&locked);
+        format!("Locked {} strings", locked.len())
+    }
+
+    /// Apply an editing command, returning a message for the user
+    fn apply(&mut self, command: &Command) -> String {
+        match command {
+            Command::Help => HELP.to_owned(),
+            Command::List(range) => (0..self.line_segments.len())
+                .filter(|i| range.as_ref().is_none_or(|r| r.contains(i)))
+                .map(|i| self.describe(i))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Command::Delete(range) => self.delete(|i, _| !range.contains(&i)),
+            Command::DeletePins(pins) => {
+                let doomed: Vec<bool> = self
+                    .line_segments
+                    .iter()
+                    .map(|l| self.touches_pins(l, pins))
+                    .collect();
+                self.delete(|i, _| !doomed[i])
+            }
+            Command::Lock(range) => self.lock(|i, _| range.contains(&i)),
+            Command::LockPins(pins) => {
+                let chosen: Vec<bool> = self
+                    .line_segments
+                    .iter()
+                    .map(|l| self.touches_pins(l, pins))
+                    .collect();
+                self.lock(|i, _| chosen[i])
+            }
+            Command::Unlock => {
+                let count = self.constraints.locked.len();
+                self.constraints.locked.clear();
+                format!("Unlocked {} strings", count)
+            }
+            Command::Run | Command::Save(_) | Command::Quit => String::new(),
+        }
+    }
+}
+
+/// Interactively edit the strings saved in a data file
+pub fn edit(filepath: &str, args: Args) -> Result<(), StringArtError> {
+    let saved = SavedRun::load_for(filepath, &args, "--edit-from")?;
+
+    let mut session = Session {
+        pin_locations: saved.pin_locations,
+        line_segments: saved.line_segments,
+        constraints: Constraints::default(),
+    };
+    let mut last_run: Option<Data> = None;
+
+    println!(
+        "Loaded {} strings on {} pins. Type `help` to see the commands.",
+        session.line_segments.len(),
+        session.pin_locations.len()
+    );
+    let stdin = std::io::stdin();
+    loop {
+        print!("> ");
+        std::io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let command = match line.parse::<Command>() {
+            Ok(command) => command,
+            Err(message) => {
+                println!("{}", message);
+                continue;
+            }
+        };
+        match command {
+            Command::Run => {
+                let data = match style::color_on_custom(
+                    session.pin_locations.clone(),
+                    args.clone(),
+                    session.line_segments.clone(),
+                    &session.constraints,
+                    Vec::new(),
+                ) {
+                    Ok(data) => data,
+                    Err(error) => {
+                        term::error(&error.to_string());
+                        continue;
+                    }
+                };
+                session.line_segments = data.line_segments.clone();
+                println!(
+                    "Now {} strings with a score of {}",
+                    data.line_segments.len(),
+                    data.final_score
+                );
+                last_run = Some(data);
+            }
+            Command::Save(ref path) => {
+                match (path.as_ref().or(args.data_filepath.as_ref()), &last_run) {
+                    (None, _) => println!("Give a FILEPATH or use --data-filepath"),
+                    (_, None) => println!("Use `run` before saving"),
+                    (Some(path), Some(data)) => match string_art::write_data(data, path) {
+                        Ok(()) => println!("Saved {}", path),
+                        Err(error) => term::error(&error.to_string()),
+                    },
+                }
+            }
+            Command::Quit => break,
+            _ => println!("{}", session.apply(&command)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::imagery::Rgb;
+
+    fn session() -> Session {
+        let pins = vec![Point::new(0, 0), Point::new(9, 0), Point::new(0, 9)];
+        Session {
+            line_segments: vec![
+                (pins[0], pins[1], Rgb::WHITE),
+                (pins[1], pins[2], Rgb::WHITE),
+                (pins[2], pins[0], Rgb::BLACK),
+            ],
+            pin_locations: pins,
+            constraints: Constraints::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(Ok(Command::List(None)), "list".parse());
+        assert_eq!(Ok(Command::List(Some(2..=5))), " list 2-5 ".parse());
+        assert_eq!(Ok(Command::Delete(3..=3)), "delete 3".parse());
+        assert_eq!(Ok(Command::LockPins(0..=10)), "lock-pins 0-10".parse());
+        assert_eq!(
+            Ok(Command::Save(Some("out.json".to_owned()))),
+            "save out.json".parse()
+        );
+        assert!("delete".parse::<Command>().is_err());
+        assert!("delete x".parse::<Command>().is_err());
+        assert!("run now".parse::<Command>().is_err());
+        assert!("dance".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_saved_run_reads_written_data() {
+        let data = Data::example(&[]);
+        let saved: SavedRun = serde_json::from_slice(&serde_json::to_vec(&data).unwrap()).unwrap();
+        assert_eq!(data.line_segments, saved.line_segments);
+        assert_eq!(data.pin_locations, saved.pin_locations);
+    }
+
+    #[test]
+    fn test_load_reports_unreadable_files() {
+        assert!(matches!(
+            SavedRun::load("/nonexistent/string_art.json"),
+            Err(StringArtError::Read { .. })
+        ));
+        let filepath = std::env::temp_dir().join("string_art_not_data.json");
+        let filepath = filepath.to_str().unwrap();
+        std::fs::write(filepath, "not json").unwrap();
+        let loaded = SavedRun::load(filepath);
+        std::fs::remove_file(filepath).unwrap();
+        assert!(matches!(loaded, Err(StringArtError::Decode { .. })));
+    }
+
+    #[test]
+    fn test_load_for_rejects_other_image_sizes() {
+        let filepath = std::env::temp_dir().join("string_art_load_for.json");
+        let filepath = filepath.to_str().unwrap();
+        let mut data = Data::example(&[]);
+        string_art::write_data(&data, filepath).unwrap();
+        let same_size = SavedRun::load_for(filepath, &data.args, "--resume");
+        data.args.image = image::DynamicImage::new_rgb8(20, 10);
+        let other_size = SavedRun::load_for(filepath, &data.args, "--resume");
+        std::fs::remove_file(filepath).unwrap();
+        assert!(same_size.is_ok());
+        assert!(matches!(other_size, Err(StringArtError::Args(_))));
+    }
+
+    #[test]
+    fn test_delete_bans_strings() {
+        let mut session = session();
+        let deleted = session.line_segments[1];
+        session.apply(&Command::Delete(1..=1));
+        assert_eq!(2, session.line_segments.len());
+        assert!(!session.line_segments.contains(&deleted));
+        assert_eq!(
+            vec![&deleted],
+            session.constraints.banned.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_delete_pins() {
+        let mut session = session();
+        session.apply(&Command::DeletePins(2..=2));
+        assert_eq!(1, session.line_segments.len());
+        assert_eq!(2, session.constraints.banned.len());
+    }
+
+    #[test]
+    fn test_lock_and_unlock() {
+        let mut session = session();
+        session.apply(&Command::Lock(0..=1));
+        assert_eq!(2, session.constraints.locked.len());
+        assert!(session.describe(0).ends_with("(locked)"));
+        session.apply(&Command::Unlock);
+        assert!(session.constraints.locked.is_empty());
+    }
+
+    #[test]
+    fn test_lock_pins() {
+        let mut session = session();
+        session.apply(&Command::LockPins(0..=0));
+        assert_eq!(2, session.constraints.locked.len());
+    }
+
+    #[test]
+    fn test_deleting_a_locked_string_unlocks_it() {
+        let mut session = session();
+        session.apply(&Command::Lock(0..=0));
+        session.apply(&Command::Delete(0..=0));
+        assert!(session.constraints.locked.is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::cli_app::{seeded_rng, Args};
+use crate::error::StringArtError;
+use crate::geometry::Point;
+use crate::optimum::Constraints;
+use crate::parallel::*;
+use crate::rand::seq::SliceRandom;
+use crate::style;
+use crate::style::Data;
+use crate::term;
+
+/// The fraction of the pins each ensemble member, besides the first, may use
+const MEMBER_PIN_FRACTION: f64 = 0.75;
+
+/// Run `members` independent optimizations in parallel, each limited to a different random
+/// sample of the pins so they find different strings. Then merge them: start from the best
+/// member's strings and optimize again, adding only strings that some member used.
+pub fn ensemble(
+    pin_locations: Vec<Point>,
+    args: Args,
+    members: usize,
+) -> Result<Data, StringArtError> {
+    let member_args = args.without_outputs();
+    let runs: Vec<Data> = (0..members)
+        .into_par_iter()
+        .map(|i| {
+            let pins = match i {
+                0 => pin_locations.clone(),
+                // Each member samples differently, even from the same seed
+                _ => sample(
+                    &pin_locations,
+                    args.seed.map(|seed| seed.wrapping_add(i as u64)),
+                ),
+            };
+            style::color_on_custom(
+                pins,
+                member_args.clone(),
+                Vec::new(),
+                &Constraints::default(),
+                Vec::new(),
+            )
+        })
+        .collect::<Result<_, _>>()?;
+
+    runs.iter().enumerate().for_each(|(i, run)| {
+        term::info(
+            args.verbosity,
+            &format!(
+                "Ensemble member {}: {} strings with a score of {}",
+                i + 1,
+                run.line_segments.len(),
+                run.final_score
+            ),
+        )
+    });
+
+    let allowed = runs
+        .iter()
+        .flat_map(|run| run.line_segments.iter().copied())
+        .collect();
+    let best = runs
+        .into_iter()
+        .min_by_key(|run| run.final_score)
+        .map(|run| run.line_segments)
+        .unwrap_or_default();
+    style::color_on_custom(
+        pin_locations,
+        args,
+        best,
+        &Constraints {
+            allowed: Some(allowed),
+            ..Constraints::default()
+        },
+        Vec::new(),
+    )
+}
+
+fn sample(pin_locations: &[Point], seed: Option<u64>) -> Vec<Point> {
+    let count = (pin_locations.len() as f64 * MEMBER_PIN_FRACTION).round() as usize;
+    pin_locations
+        .choose_multiple(&mut seeded_rng(seed), count)
+        .copied()
+        .collect()
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::cli_app::Cli;
+    use clap::Parser;
+
+    fn rings() -> image::DynamicImage {
+        let mut i = image::RgbImage::new(30, 30);
+        i.enumerate_pixels_mut().for_each(|(x, y, p)| {
+            let d = ((x as f64 - 15.0).powi(2) + (y as f64 - 15.0).powi(2)).sqrt() as u32;
+            *p = image::Rgb([if d % 6 < 3 { 255 } else { 0 }; 3])
+        });
+        image::DynamicImage::ImageRgb8(i)
+    }
+
+    #[test]
+    fn test_sample() {
+        let pins: Vec<_> = (0..8).map(|i| Point::new(i, 0)).collect();
+        let sampled = sample(&pins, None);
+        assert_eq!(6, sampled.len());
+        assert!(sampled.iter().all(|p| pins.contains(p)));
+        assert_eq!(sample(&pins, Some(7)), sample(&pins, Some(7)));
+    }
+
+    #[test]
+    fn test_ensemble_is_no_worse_than_its_best_member() {
+        let cli = Cli::parse_from(["string_art", "-i", "in.png", "-m", "30"]);
+        let args = Args::with_image(cli, rings());
+        let pins = crate::pins::generate(&args.pin_arrangement, 24, 30, 30);
+        let single = style::color_on_custom(
+            pins.clone(),
+            args.clone(),
+            Vec::new(),
+            &Constraints::default(),
+            Vec::new(),
+        )
+        .unwrap();
+        let merged = ensemble(pins, args, 3).unwrap();
+        assert!(merged.line_segments.len() <= 30);
+        assert!(merged.final_score <= single.final_score);
+    }
+}
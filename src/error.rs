@@ -0,0 +1,167 @@
+use std::fmt;
+
+/// Why string art could not be created, so library consumers and scripts can handle failures
+/// instead of the process exiting
+#[derive(Debug)]
+pub enum StringArtError {
+    /// The options could not be parsed, or don't work together
+    #[cfg(feature = "cli")]
+    Args(clap::Error),
+    /// A file could not be opened or read
+    Read {
+        filepath: String,
+        source: std::io::Error,
+    },
+    /// A file could not be created, written, or renamed
+    Write {
+        filepath: String,
+        source: std::io::Error,
+    },
+    /// An image or data file could not be decoded
+    Decode {
+        filepath: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// An image could not be encoded
+    Encode {
+        filepath: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+impl StringArtError {
+    /// An encoding failure from any of the image encoders
+    pub fn encode(
+        filepath: &str,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        Self::Encode {
+            filepath: filepath.to_owned(),
+            source: source.into(),
+        }
+    }
+
+    /// A decoding failure from an image decoder or a data file parser
+    pub fn decode(
+        filepath: &str,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        Self::Decode {
+            filepath: filepath.to_owned(),
+            source: source.into(),
+        }
+    }
+
+    /// A failure writing to `filepath`
+    pub fn write(filepath: &str, source: std::io::Error) -> Self {
+        Self::Write {
+            filepath: filepath.to_owned(),
+            source,
+        }
+    }
+
+    /// A failure saving an image to `filepath`. The image crate reports failing to create or
+    /// write the file as an `ImageError` too, so those are told apart from encoding failures.
+    pub fn save(filepath: &str, source: image::ImageError) -> Self {
+        match source {
+            image::ImageError::IoError(source) => Self::write(filepath, source),
+            source => Self::encode(filepath, source),
+        }
+    }
+}
+
+impl fmt::Display for StringArtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // clap starts its messages with "error: ", which the caller adds if it wants it
+            #[cfg(feature = "cli")]
+            Self::Args(error) => {
+                let message = error.to_string();
+                write!(f, "{}", message.trim_start_matches("error: ").trim_end())
+            }
+            Self::Read { filepath, source } => {
+                write!(f, "Unable to read '{}': {}", filepath, source)
+            }
+            Self::Write { filepath, source } => {
+                write!(f, "Unable to write '{}': {}", filepath, source)
+            }
+            Self::Decode { filepath, source } => {
+                write!(f, "Unable to decode '{}': {}", filepath, source)
+            }
+            Self::Encode { filepath, source } => {
+                write!(f, "Unable to encode '{}': {}", filepath, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StringArtError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "cli")]
+            Self::Args(error) => Some(error),
+            Self::Read { source, .. } | Self::Write { source, .. } => Some(source),
+            Self::Decode { source, .. } | Self::Encode { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<clap::Error> for StringArtError {
+    fn from(error: clap::Error) -> Self {
+        Self::Args(error)
+    }
+}
+
+/// Open and decode the image at `filepath`
+pub fn open_image(filepath: &str) -> Result<image::DynamicImage, StringArtError> {
+    image::io::Reader::open(filepath)
+        .map_err(|source| StringArtError::Read {
+            filepath: filepath.to_owned(),
+            source,
+        })?
+        .decode()
+        .map_err(|source| StringArtError::decode(filepath, source))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_missing_image() {
+        let error = open_image("/nonexistent/string_art.png").unwrap_err();
+        assert!(matches!(error, StringArtError::Read { .. }));
+        assert!(error
+            .to_string()
+            .starts_with("Unable to read '/nonexistent/string_art.png': "));
+        assert!(error.source().is_some());
+    }
+
+    #[test]
+    fn test_undecodable_image() {
+        let filepath = std::env::temp_dir().join("string_art_undecodable.png");
+        let filepath = filepath.to_str().unwrap();
+        std::fs::write(filepath, "not an image").unwrap();
+        let error = open_image(filepath).unwrap_err();
+        std::fs::remove_file(filepath).unwrap();
+        assert!(matches!(error, StringArtError::Decode { .. }));
+    }
+
+    #[test]
+    fn test_save_tells_writing_from_encoding() {
+        let io = image::ImageError::IoError(std::io::ErrorKind::NotFound.into());
+        assert!(matches!(
+            StringArtError::save("a.png", io),
+            StringArtError::Write { .. }
+        ));
+        let unsupported = image::RgbaImage::new(1, 1)
+            .save("string_art.unknown-extension")
+            .unwrap_err();
+        assert!(matches!(
+            StringArtError::save("a.png", unsupported),
+            StringArtError::Encode { .. }
+        ));
+    }
+}
@@ -0,0 +1,574 @@
+#[cfg(any(feature = "gif", feature = "video"))]
+use crate::error::StringArtError;
+#[cfg(feature = "gif")]
+use crate::image::codecs::gif::GifEncoder;
+#[cfg(feature = "gif")]
+use crate::image::Delay;
+#[cfg(feature = "gif")]
+use crate::image::Frame;
+use crate::image::RgbaImage;
+#[cfg(feature = "gif")]
+use crate::imagery::Rgb;
+#[cfg(feature = "gif")]
+use std::collections::HashMap;
+#[cfg(feature = "gif")]
+use std::fs::File;
+#[cfg(feature = "video")]
+use std::io::Write;
+#[cfg(feature = "video")]
+use std::path::Path;
+#[cfg(feature = "video")]
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::thread::Scope;
+
+/// How many snapshots may wait to be rendered before placing strings waits for the sinks
+const FRAME_QUEUE: usize = 8;
+
+/// The program that encodes videos
+#[cfg(feature = "video")]
+const FFMPEG: &str = "ffmpeg";
+
+/// Receives snapshots of the string art while the strings are being placed. Any
+/// `FnMut(&RgbaImage)` is a sink, so frames can be sent to a GUI, a video encoder, or anywhere
+/// else.
+pub trait FrameSink {
+    fn receive(&mut self, frame: &RgbaImage);
+}
+
+impl<F: FnMut(&RgbaImage)> FrameSink for F {
+    fn receive(&mut self, frame: &RgbaImage) {
+        self(frame)
+    }
+}
+
+/// Render snapshots and send the frames to the sinks on a worker thread of `scope`, so placing
+/// strings isn't held up by rendering and encoding. Once the returned sender is dropped, the
+/// worker finishes the snapshots still queued and stops.
+pub fn spawn_worker<'scope, 'sink: 'scope, T: Send + 'scope>(
+    scope: &'scope Scope<'scope, '_>,
+    sinks: &'scope mut [Box<dyn FrameSink + Send + 'sink>],
+    render: impl Fn(T) -> RgbaImage + Send + 'scope,
+) -> SyncSender<T> {
+    let (sender, receiver) = sync_channel(FRAME_QUEUE);
+    scope.spawn(move || {
+        for snapshot in receiver {
+            let frame = render(snapshot);
+            sinks.iter_mut().for_each(|sink| sink.receive(&frame));
+        }
+    });
+    sender
+}
+
+/// Writes frames to a looping gif. As a sink it can't return an error, so the first frame that
+/// fails to be written is kept and returned by `finish`, and later frames are skipped.
+#[cfg(feature = "gif")]
+pub struct GifSink {
+    encoder: GifWriter,
+    filepath: String,
+    delay: Delay,
+    error: Option<StringArtError>,
+}
+
+/// How the frames of a gif get their colors
+#[cfg(feature = "gif")]
+enum GifWriter {
+    /// Each frame is quantized to a palette of its own
+    FullColor(GifEncoder<File>),
+    /// Every frame is mapped to one palette, written once for the whole gif. Since the colors
+    /// don't change, only the pixels that differ from the `previous` frame are written.
+    Palette {
+        encoder: gif::Encoder<File>,
+        palette: GifPalette,
+        previous: Option<Vec<u8>>,
+    },
+}
+
+#[cfg(feature = "gif")]
+impl GifSink {
+    pub fn create(filepath: &str) -> Result<Self, StringArtError> {
+        let file_out =
+            File::create(filepath).map_err(|error| StringArtError::write(filepath, error))?;
+        let mut encoder = GifEncoder::new_with_speed(file_out, 10);
+        encoder
+            .set_repeat(image::codecs::gif::Repeat::Infinite)
+            .map_err(|error| StringArtError::save(filepath, error))?;
+        Ok(Self::new(GifWriter::FullColor(encoder), filepath))
+    }
+
+    /// A gif whose `width` by `height` frames all share `palette`. Each frame after the first
+    /// only holds the pixels that changed, which is much smaller than a full frame with a
+    /// palette of its own.
+    pub fn create_with_palette(
+        filepath: &str,
+        width: u32,
+        height: u32,
+        palette: GifPalette,
+    ) -> Result<Self, StringArtError> {
+        let encode_error = |error: gif::EncodingError| match error {
+            gif::EncodingError::Io(error) => StringArtError::write(filepath, error),
+            error => StringArtError::encode(filepath, error),
+        };
+        let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => {
+                return Err(StringArtError::encode(
+                    filepath,
+                    "A gif can be at most 65535 pixels on a side",
+                ))
+            }
+        };
+        let file_out =
+            File::create(filepath).map_err(|error| StringArtError::write(filepath, error))?;
+        let mut encoder =
+            gif::Encoder::new(file_out, width, height, &palette.flat()).map_err(encode_error)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(encode_error)?;
+        let writer = GifWriter::Palette {
+            encoder,
+            palette,
+            previous: None,
+        };
+        Ok(Self::new(writer, filepath))
+    }
+
+    fn new(encoder: GifWriter, filepath: &str) -> Self {
+        Self {
+            encoder,
+            filepath: filepath.to_owned(),
+            delay: Delay::from_numer_denom_ms(0, 1),
+            error: None,
+        }
+    }
+
+    /// Show each frame received as a sink for `delay`, instead of no time at all
+    pub fn with_delay(mut self, delay: Delay) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Write a frame that is shown for `delay`
+    pub fn add(&mut self, frame: RgbaImage, delay: Delay) -> Result<(), StringArtError> {
+        match &mut self.encoder {
+            GifWriter::FullColor(encoder) => encoder
+                .encode_frame(Frame::from_parts(frame, 0, 0, delay))
+                .map_err(|error| StringArtError::save(&self.filepath, error)),
+            GifWriter::Palette {
+                encoder,
+                palette,
+                previous,
+            } => {
+                let indices = palette.indices(&frame);
+                let mut gif_frame = match previous {
+                    Some(previous) => {
+                        changed_region(previous, &indices, frame.width(), palette.transparent())
+                    }
+                    None => gif::Frame {
+                        width: frame.width() as u16,
+                        height: frame.height() as u16,
+                        buffer: indices.clone().into(),
+                        ..gif::Frame::default()
+                    },
+                };
+                // Gifs count delays in hundredths of a second
+                let (numer, denom) = delay.numer_denom_ms();
+                gif_frame.delay = (numer / denom / 10) as u16;
+                *previous = Some(indices);
+                encoder
+                    .write_frame(&gif_frame)
+                    .map_err(|error| match error {
+                        gif::EncodingError::Io(error) => {
+                            StringArtError::write(&self.filepath, error)
+                        }
+                        error => StringArtError::encode(&self.filepath, error),
+                    })
+            }
+        }
+    }
+
+    /// Finish the gif, returning the error from the first frame that couldn't be written
+    pub fn finish(self) -> Result<(), StringArtError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "gif")]
+impl FrameSink for GifSink {
+    fn receive(&mut self, frame: &RgbaImage) {
+        if self.error.is_none() {
+            self.error = self.add(frame.clone(), self.delay).err();
+        }
+    }
+}
+
+/// A frame covering the pixels of `current` that differ from `previous`, both `width` pixels
+/// wide, with the unchanged pixels inside it left `transparent` to show the previous frame
+#[cfg(feature = "gif")]
+fn changed_region(
+    previous: &[u8],
+    current: &[u8],
+    width: u32,
+    transparent: u8,
+) -> gif::Frame<'static> {
+    let width = width as usize;
+    let changed = || (0..current.len()).filter(|&i| previous[i] != current[i]);
+    let (Some(left), Some(right)) = (
+        changed().map(|i| i % width).min(),
+        changed().map(|i| i % width).max(),
+    ) else {
+        // Nothing changed, so the frame only shows the previous one for a while longer
+        return gif::Frame {
+            width: 1,
+            height: 1,
+            transparent: Some(transparent),
+            buffer: vec![transparent].into(),
+            ..gif::Frame::default()
+        };
+    };
+    let top = changed().next().unwrap_or(0) / width;
+    let bottom = changed().next_back().unwrap_or(0) / width;
+    let buffer = (top..=bottom)
+        .flat_map(|y| (left..=right).map(move |x| y * width + x))
+        .map(|i| match previous[i] == current[i] {
+            true => transparent,
+            false => current[i],
+        })
+        .collect::<Vec<_>>();
+    gif::Frame {
+        left: left as u16,
+        top: top as u16,
+        width: (right - left + 1) as u16,
+        height: (bottom - top + 1) as u16,
+        transparent: Some(transparent),
+        buffer: buffer.into(),
+        ..gif::Frame::default()
+    }
+}
+
+/// One palette for every frame of a gif: the background, and shades from it to each of the
+/// foreground colors. Strings that blend into other colors are drawn in the nearest shade.
+#[cfg(feature = "gif")]
+pub struct GifPalette {
+    colors: Vec<[u8; 3]>,
+    nearest: HashMap<[u8; 3], u8>,
+}
+
+#[cfg(feature = "gif")]
+impl GifPalette {
+    /// The most colors a gif palette can have
+    const SIZE: usize = 256;
+
+    pub fn new(background: Rgb, foregrounds: &[Rgb]) -> Self {
+        let rgb = |rgb: Rgb| [rgb.r, rgb.g, rgb.b].map(|c| c.clamp(0, 255) as u8);
+        let shades = usize::max(1, (Self::SIZE - 2) / usize::max(1, foregrounds.len()));
+        let mut colors = vec![rgb(background)];
+        for foreground in foregrounds {
+            colors.extend((1..=shades).map(|i| {
+                let blend = |b: i64, f: i64| b + (f - b) * i as i64 / shades as i64;
+                rgb(Rgb::new(
+                    blend(background.r, foreground.r),
+                    blend(background.g, foreground.g),
+                    blend(background.b, foreground.b),
+                ))
+            }));
+        }
+        // The last color of the palette is left for transparency
+        colors.truncate(Self::SIZE - 1);
+        Self {
+            colors,
+            nearest: HashMap::new(),
+        }
+    }
+
+    /// The palette as the gif stores it, three bytes per color
+    fn flat(&self) -> Vec<u8> {
+        [self.colors.concat(), vec![0; 3]].concat()
+    }
+
+    /// The index of the color that shows the frame beneath
+    fn transparent(&self) -> u8 {
+        self.colors.len() as u8
+    }
+
+    /// The index of the color nearest each pixel of `frame`
+    fn indices(&mut self, frame: &RgbaImage) -> Vec<u8> {
+        frame
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b, _] = pixel.0;
+                self.index([r, g, b])
+            })
+            .collect()
+    }
+
+    fn index(&mut self, pixel: [u8; 3]) -> u8 {
+        let colors = &self.colors;
+        *self.nearest.entry(pixel).or_insert_with(|| {
+            let distance = |color: &[u8; 3]| -> u32 {
+                (0..3)
+                    .map(|c| (color[c] as i32 - pixel[c] as i32).pow(2) as u32)
+                    .sum()
+            };
+            (0..colors.len())
+                .min_by_key(|&i| distance(&colors[i]))
+                .unwrap_or(0) as u8
+        })
+    }
+}
+
+/// Writes frames to an H.264 MP4 or VP9 WebM video by piping them to ffmpeg, which keeps long
+/// runs much smaller than a gif. Like `GifSink`, the first frame that fails to be written is
+/// kept and returned by `finish`, and later frames are skipped.
+#[cfg(feature = "video")]
+pub struct VideoSink {
+    encoder: Child,
+    filepath: String,
+    error: Option<StringArtError>,
+}
+
+#[cfg(feature = "video")]
+impl VideoSink {
+    pub fn create(
+        filepath: &str,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> Result<Self, StringArtError> {
+        let encoder = Command::new(FFMPEG)
+            .args(ffmpeg_args(filepath, width, height, fps)?)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|error| {
+                StringArtError::encode(
+                    filepath,
+                    format!("{} could not be started: {}", FFMPEG, error),
+                )
+            })?;
+        Ok(Self {
+            encoder,
+            filepath: filepath.to_owned(),
+            error: None,
+        })
+    }
+
+    /// Finish the video, waiting for ffmpeg to write the last of it
+    pub fn finish(mut self) -> Result<(), StringArtError> {
+        // ffmpeg finishes once it reaches the end of its input
+        drop(self.encoder.stdin.take());
+        let status = self
+            .encoder
+            .wait()
+            .map_err(|error| StringArtError::write(&self.filepath, error))?;
+        match (self.error, status.success()) {
+            (Some(error), _) => Err(error),
+            (None, true) => Ok(()),
+            (None, false) => Err(StringArtError::encode(
+                &self.filepath,
+                format!("{} failed with {}", FFMPEG, status),
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "video")]
+impl FrameSink for VideoSink {
+    fn receive(&mut self, frame: &RgbaImage) {
+        if let (None, Some(stdin)) = (&self.error, self.encoder.stdin.as_mut()) {
+            self.error = stdin
+                .write_all(frame.as_raw())
+                .map_err(|error| StringArtError::write(&self.filepath, error))
+                .err();
+        }
+    }
+}
+
+/// The ffmpeg arguments that encode raw RGBA frames from stdin at `fps`, with the codec chosen
+/// by the extension of `filepath`
+#[cfg(feature = "video")]
+pub fn ffmpeg_args(
+    filepath: &str,
+    width: u32,
+    height: u32,
+    fps: u32,
+) -> Result<Vec<String>, StringArtError> {
+    let extension = Path::new(filepath)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    let codec = match extension.as_deref() {
+        Some("mp4" | "m4v" | "mov") => "libx264",
+        Some("webm") => "libvpx-vp9",
+        _ => {
+            return Err(StringArtError::encode(
+                filepath,
+                "A video must end in .mp4, .m4v, .mov, or .webm",
+            ))
+        }
+    };
+    let size = format!("{}x{}", width, height);
+    let fps = fps.to_string();
+    let args = [
+        "-y",
+        "-loglevel",
+        "error",
+        "-f",
+        "rawvideo",
+        "-pix_fmt",
+        "rgba",
+        "-s",
+        &size,
+        "-r",
+        &fps,
+        "-i",
+        "-",
+        // The chroma of yuv420p is half size, so both sides must be even
+        "-vf",
+        "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+        "-c:v",
+        codec,
+        "-pix_fmt",
+        "yuv420p",
+        filepath,
+    ];
+    Ok(args.iter().map(|arg| arg.to_string()).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_closure_is_a_sink() {
+        let mut widths = Vec::new();
+        let mut sink = |frame: &RgbaImage| widths.push(frame.width());
+        sink.receive(&RgbaImage::new(3, 1));
+        sink.receive(&RgbaImage::new(5, 1));
+        assert_eq!(vec![3, 5], widths);
+    }
+
+    #[test]
+    fn test_worker_sends_every_frame_in_order() {
+        let mut widths = Vec::new();
+        let mut sinks: Vec<Box<dyn FrameSink + Send>> =
+            vec![Box::new(|frame: &RgbaImage| widths.push(frame.width()))];
+        std::thread::scope(|scope| {
+            let sender = spawn_worker(scope, &mut sinks, |width| RgbaImage::new(width, 1));
+            (1..=20).for_each(|width| sender.send(width).unwrap());
+        });
+        drop(sinks);
+        assert_eq!((1..=20).collect::<Vec<_>>(), widths);
+    }
+
+    #[test]
+    #[cfg(feature = "gif")]
+    fn test_gif_sink() {
+        let filepath = std::env::temp_dir().join("string_art_gif_sink.gif");
+        let filepath = filepath.to_str().unwrap();
+        {
+            let mut sink = GifSink::create(filepath).unwrap();
+            sink.receive(&RgbaImage::new(4, 4));
+            sink.add(RgbaImage::new(4, 4), Delay::from_numer_denom_ms(200, 1))
+                .unwrap();
+            sink.finish().unwrap();
+        }
+        let decoded = image::open(filepath).unwrap();
+        std::fs::remove_file(filepath).unwrap();
+        assert_eq!((4, 4), (decoded.width(), decoded.height()));
+    }
+
+    #[test]
+    #[cfg(feature = "gif")]
+    fn test_gif_palette() {
+        let mut palette = GifPalette::new(Rgb::BLACK, &[Rgb::WHITE, Rgb::new(255, 0, 0)]);
+        assert_eq!(1 + 2 * 127, palette.colors.len());
+        let mut nearest = |pixel| {
+            let i = palette.index(pixel) as usize;
+            palette.colors[i]
+        };
+        assert_eq!([0, 0, 0], nearest([0, 0, 0]));
+        assert_eq!([255, 255, 255], nearest([254, 255, 255]));
+        assert_eq!([255, 0, 0], nearest([255, 3, 0]));
+    }
+
+    #[test]
+    #[cfg(feature = "gif")]
+    fn test_gif_sink_with_palette() {
+        use image::AnimationDecoder;
+        let filepath = std::env::temp_dir().join("string_art_gif_palette.gif");
+        let filepath = filepath.to_str().unwrap();
+        let palette = GifPalette::new(Rgb::BLACK, &[Rgb::WHITE]);
+        let mut first = RgbaImage::from_pixel(4, 3, image::Rgba([0, 0, 0, 255]));
+        first.put_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+        let mut second = first.clone();
+        second.put_pixel(2, 2, image::Rgba([255, 255, 255, 255]));
+        {
+            let mut sink = GifSink::create_with_palette(filepath, 4, 3, palette).unwrap();
+            sink.receive(&first);
+            sink.add(second.clone(), Delay::from_numer_denom_ms(200, 1))
+                .unwrap();
+            // Nothing changed
+            sink.receive(&second);
+            sink.finish().unwrap();
+        }
+        let file = std::io::BufReader::new(File::open(filepath).unwrap());
+        let frames = image::codecs::gif::GifDecoder::new(file)
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        std::fs::remove_file(filepath).unwrap();
+        assert_eq!(3, frames.len());
+        assert_eq!(&first, frames[0].buffer());
+        assert_eq!(&second, frames[1].buffer());
+        assert_eq!(&second, frames[2].buffer());
+        assert_eq!((200, 1), frames[1].delay().numer_denom_ms());
+    }
+
+    #[test]
+    #[cfg(feature = "gif")]
+    fn test_changed_region() {
+        let previous = [0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let current = [0, 0, 0, 0, 1, 0, 0, 0, 2];
+        let frame = changed_region(&previous, &current, 3, 9);
+        assert_eq!(
+            (1, 1, 2, 2),
+            (frame.left, frame.top, frame.width, frame.height)
+        );
+        assert_eq!(&[1, 9, 9, 2], frame.buffer.as_ref());
+        let unchanged = changed_region(&current, &current, 3, 9);
+        assert_eq!((1, 1), (unchanged.width, unchanged.height));
+    }
+
+    #[test]
+    #[cfg(feature = "gif")]
+    fn test_gif_sink_in_missing_directory() {
+        assert!(matches!(
+            GifSink::create("/nonexistent/art.gif"),
+            Err(StringArtError::Write { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "video")]
+    fn test_ffmpeg_args() {
+        let args = ffmpeg_args("art.webm", 31, 20, 24).unwrap();
+        let after = |flag: &str| {
+            let i = args.iter().position(|arg| arg == flag).unwrap();
+            args[i + 1].as_str()
+        };
+        assert_eq!("31x20", after("-s"));
+        assert_eq!("24", after("-r"));
+        assert_eq!("libvpx-vp9", after("-c:v"));
+        assert_eq!("art.webm", args.last().unwrap());
+        assert_eq!("libx264", ffmpeg_args("art.MP4", 4, 4, 30).unwrap()[16]);
+        assert!(matches!(
+            ffmpeg_args("art.avi", 4, 4, 30),
+            Err(StringArtError::Encode { .. })
+        ));
+    }
+}
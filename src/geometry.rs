@@ -1,4 +1,4 @@
-use crate::serde::Serialize;
+use crate::serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vector {
@@ -18,6 +18,14 @@ impl Vector {
     fn basis(&self) -> Self {
         *self / self.len()
     }
+
+    fn dot(&self, other: Self) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    fn cross(&self, other: Self) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
 }
 
 impl std::ops::Add for Vector {
@@ -70,6 +78,20 @@ impl Line {
             step_size,
         }
     }
+
+    pub fn length(&self) -> f64 {
+        (self.1 - self.0).len()
+    }
+
+    /// The same line, running the other way
+    pub fn reversed(&self) -> Self {
+        Self(self.1, self.0)
+    }
+
+    /// The points the line runs between
+    pub fn ends(&self) -> (Point, Point) {
+        (self.0.into(), self.1.into())
+    }
 }
 
 impl<T: Into<Vector>> std::convert::From<(T, T)> for Line {
@@ -106,7 +128,7 @@ impl Iterator for LineIter {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Point {
     pub x: u32,
     pub y: u32,
@@ -130,6 +152,55 @@ impl std::convert::From<Vector> for Point {
     }
 }
 
+/// The angle in degrees at `vertex` between the directions to `a` and to `b`: 0 when they point
+/// the same way, and 180 when they point opposite ways. A direction to the vertex itself points
+/// nowhere, so it is treated as opposite to everything.
+pub fn angle_at(vertex: Point, a: Point, b: Point) -> f64 {
+    let to_a = Vector::from(a) - Vector::from(vertex);
+    let to_b = Vector::from(b) - Vector::from(vertex);
+    if a == vertex || b == vertex {
+        return 180.0;
+    }
+    to_a.cross(to_b).abs().atan2(to_a.dot(to_b)).to_degrees()
+}
+
+/// A distance given either in pixels, or as a percentage of some total length
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Length {
+    Pixels(u32),
+    Percent(f64),
+}
+
+impl Length {
+    pub fn in_pixels(&self, total: u32) -> u32 {
+        match self {
+            Length::Pixels(pixels) => *pixels,
+            Length::Percent(percent) => (f64::from(total) * percent / 100.0).round() as u32,
+        }
+    }
+}
+
+impl core::str::FromStr for Length {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        let string = string.trim();
+        match string.strip_suffix('%') {
+            Some(percent) => percent
+                .parse::<f64>()
+                .ok()
+                .filter(|p| *p >= 0.0)
+                .map(Length::Percent),
+            None => string.parse::<u32>().ok().map(Length::Pixels),
+        }
+        .ok_or_else(|| {
+            format!(
+                "Length should be a number of pixels or a percentage, but got: \"{}\"",
+                string
+            )
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -150,6 +221,16 @@ mod test {
         v(6.0, 0.0)
     }
 
+    #[test]
+    fn test_angle_at() {
+        let p = Point::new;
+        assert_eq!(90.0, angle_at(p(5, 5), p(9, 5), p(5, 0)));
+        assert_eq!(180.0, angle_at(p(5, 5), p(9, 5), p(0, 5)));
+        assert_eq!(0.0, angle_at(p(5, 5), p(9, 5), p(7, 5)));
+        assert!((angle_at(p(0, 0), p(10, 0), p(10, 10)) - 45.0).abs() < 1e-9);
+        assert_eq!(180.0, angle_at(p(5, 5), p(5, 5), p(7, 5)));
+    }
+
     #[test]
     fn test_line_iter() {
         let line = Line(origin(), a());
@@ -207,6 +288,20 @@ mod test {
         assert_eq!(v(2.0, 0.0), b() / 3.0);
     }
 
+    #[test]
+    fn test_length_from_str() {
+        assert_eq!(Ok(Length::Pixels(12)), "12".parse());
+        assert_eq!(Ok(Length::Percent(2.5)), "2.5%".parse());
+        assert!("-2%".parse::<Length>().is_err());
+        assert!("ten".parse::<Length>().is_err());
+    }
+
+    #[test]
+    fn test_length_in_pixels() {
+        assert_eq!(12, Length::Pixels(12).in_pixels(1000));
+        assert_eq!(25, Length::Percent(2.5).in_pixels(1000));
+    }
+
     #[test]
     fn test_vector_from_point() {
         assert_eq!(v(2.0, 3.0), Vector::from(Point::new(2, 3)));
@@ -0,0 +1,504 @@
+//! A window for making string art without the command line. Load an image, adjust the main
+//! options, watch the strings being placed, and export the finished work. Every other option can
+//! still be given as it would be on the command line.
+
+use crate::cli_app::Args;
+use crate::cli_app::Cli;
+use crate::cli_app::DEFAULT_BG;
+use crate::cli_app::DEFAULT_FG;
+use crate::control::Cancellation;
+use crate::error::open_image;
+use crate::error::StringArtError;
+use crate::image::RgbaImage;
+use crate::imagery::RefImage;
+use crate::imagery::Rgb;
+use crate::instructions;
+use crate::optimum::Constraints;
+use crate::overwrite;
+use crate::pins;
+use crate::pins::Arrangement;
+use crate::resolution;
+use crate::string_art;
+use crate::style;
+use crate::style::Data;
+use crate::svg;
+use crate::term;
+use clap::Parser;
+use eframe::egui;
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+
+/// How many strings are placed between refreshes of the preview, unless the options say
+/// otherwise with --gif-strings-per-frame
+const PREVIEW_STRINGS_PER_FRAME: u64 = 20;
+
+/// Open the window, and exit once it is closed
+pub fn gui() {
+    let result = eframe::run_native(
+        "String Art",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Ok(Box::<App>::default())),
+    );
+    if let Err(error) = result {
+        term::error(&error.to_string());
+        std::process::exit(1)
+    }
+}
+
+/// The options that have their own controls, along with the rest as they would be given on the
+/// command line
+#[derive(Debug, Clone, PartialEq)]
+struct Settings {
+    input_filepath: String,
+    pin_count: u32,
+    pin_arrangement: Arrangement,
+    max_strings: usize,
+    string_alpha: f64,
+    background_color: [u8; 3],
+    foreground_colors: Vec<[u8; 3]>,
+    /// Any other options, separated by whitespace
+    more_options: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            input_filepath: String::new(),
+            pin_count: 200,
+            pin_arrangement: Arrangement::Perimeter,
+            max_strings: 2000,
+            string_alpha: 0.2,
+            background_color: srgb(Rgb::from_str(DEFAULT_BG).unwrap()),
+            foreground_colors: vec![srgb(Rgb::from_str(DEFAULT_FG).unwrap())],
+            more_options: String::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// The command line these settings stand for
+    fn command_line(&self) -> Vec<String> {
+        let mut command_line: Vec<String> = vec![
+            "string_art".to_owned(),
+            "--input-filepath".to_owned(),
+            self.input_filepath.clone(),
+            "--pin-count".to_owned(),
+            self.pin_count.to_string(),
+            "--pin-arrangement".to_owned(),
+            arrangement_name(&self.pin_arrangement),
+            "--max-strings".to_owned(),
+            self.max_strings.to_string(),
+            "--string-alpha".to_owned(),
+            self.string_alpha.to_string(),
+            "--background-color".to_owned(),
+            hex(self.background_color),
+        ];
+        for color in &self.foreground_colors {
+            command_line.push("--foreground-color".to_owned());
+            command_line.push(hex(*color));
+        }
+        command_line.extend(self.more_options.split_whitespace().map(str::to_owned));
+        command_line
+    }
+
+    /// Parse the settings like the command line, loading and preprocessing the input image
+    fn args(&self) -> Result<Args, StringArtError> {
+        let mut args = Args::try_from(Cli::try_parse_from(self.command_line())?)?;
+        args.gif_strings_per_frame
+            .get_or_insert(PREVIEW_STRINGS_PER_FRAME);
+        Ok(args)
+    }
+}
+
+/// The name of the arrangement on the command line
+fn arrangement_name(arrangement: &Arrangement) -> String {
+    format!("{:?}", arrangement).to_lowercase()
+}
+
+fn srgb(rgb: Rgb) -> [u8; 3] {
+    [rgb.r, rgb.g, rgb.b].map(|level| level.clamp(0, 255) as u8)
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    Rgb {
+        r: r.into(),
+        g: g.into(),
+        b: b.into(),
+    }
+    .to_string()
+}
+
+/// A run that is placing strings on another thread
+struct Run {
+    cancellation: Cancellation,
+    /// The newest frame that hasn't been shown yet
+    frame: Arc<Mutex<Option<RgbaImage>>>,
+    finished: Receiver<Result<Data, StringArtError>>,
+}
+
+impl Run {
+    fn start(args: Args, ctx: &egui::Context) -> Self {
+        let cancellation = args.cancellation.clone();
+        let frame = Arc::new(Mutex::new(None));
+        let (sender, finished) = channel();
+        let (sink_frame, sink_ctx, ctx) = (Arc::clone(&frame), ctx.clone(), ctx.clone());
+        std::thread::spawn(move || {
+            let sink = move |image: &RgbaImage| {
+                *sink_frame.lock().unwrap() = Some(image.clone());
+                sink_ctx.request_repaint();
+            };
+            let pins = pins::from_args(&args, args.image.width(), args.image.height());
+            let result = style::color_on_custom(
+                pins,
+                args,
+                Vec::new(),
+                &Constraints::default(),
+                vec![Box::new(sink)],
+            );
+            // The window may have been closed already, and then nobody is waiting
+            let _ = sender.send(result);
+            ctx.request_repaint();
+        });
+        Self {
+            cancellation,
+            frame,
+            finished,
+        }
+    }
+}
+
+/// The files the finished work can be exported to, each named after the export filepath
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Export {
+    Image,
+    Svg,
+    Data,
+    Instructions,
+}
+
+impl Export {
+    const ALL: [Export; 4] = [
+        Export::Image,
+        Export::Svg,
+        Export::Data,
+        Export::Instructions,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Export::Image => "Save image",
+            Export::Svg => "Save SVG",
+            Export::Data => "Save data",
+            Export::Instructions => "Save instructions",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Export::Image => "png",
+            Export::Svg => "svg",
+            Export::Data => "json",
+            Export::Instructions => "txt",
+        }
+    }
+
+    /// Write the file, returning where it was written
+    fn write(&self, data: &Data, filepath: &str) -> Result<String, StringArtError> {
+        let filepath = format!("{}.{}", filepath, self.extension());
+        match self {
+            Export::Image => resolution::save(
+                &RefImage::from(data).color(),
+                &filepath,
+                data.args.physical_width_mm,
+            )?,
+            Export::Svg => svg::write(data, &filepath)?,
+            Export::Data => string_art::write_data(data, &filepath)?,
+            Export::Instructions => instructions::write(data, &filepath)?,
+        }
+        Ok(filepath)
+    }
+}
+
+struct App {
+    settings: Settings,
+    /// The input image, as it was loaded
+    input: Option<egui::TextureHandle>,
+    /// The strings placed so far, or the finished work
+    preview: Option<egui::TextureHandle>,
+    run: Option<Run>,
+    data: Option<Data>,
+    /// Where to export to, without an extension
+    export_filepath: String,
+    /// What happened last, such as an error
+    status: String,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            settings: Settings::default(),
+            input: None,
+            preview: None,
+            run: None,
+            data: None,
+            export_filepath: "string_art".to_owned(),
+            status: "Choose an image, or drop one on the window".to_owned(),
+        }
+    }
+}
+
+impl App {
+    fn load(&mut self, ctx: &egui::Context, filepath: String) {
+        self.settings.input_filepath = filepath;
+        match open_image(&self.settings.input_filepath) {
+            Ok(image) => {
+                self.input = Some(texture(ctx, "input", &image.to_rgba8()));
+                self.preview = None;
+                self.data = None;
+                self.status = format!("Loaded {}", self.settings.input_filepath);
+            }
+            Err(error) => {
+                self.input = None;
+                self.status = error.to_string();
+            }
+        }
+    }
+
+    fn start(&mut self, ctx: &egui::Context) {
+        let args = self
+            .settings
+            .args()
+            .and_then(|args| overwrite::check(&args).map(|_| args));
+        match args {
+            Ok(args) => {
+                self.data = None;
+                self.run = Some(Run::start(args, ctx));
+                self.status = "Placing strings...".to_owned();
+            }
+            Err(error) => self.status = error.to_string(),
+        }
+    }
+
+    /// Show the newest frame, and take the finished work once the run is done
+    fn poll(&mut self, ctx: &egui::Context) {
+        let Some(run) = &self.run else {
+            return;
+        };
+        if let Some(frame) = run.frame.lock().unwrap().take() {
+            self.preview = Some(texture(ctx, "preview", &frame));
+        }
+        let result = match run.finished.try_recv() {
+            Err(TryRecvError::Empty) => return,
+            result => result,
+        };
+        self.run = None;
+        match result {
+            Ok(Ok(data)) => {
+                self.preview = Some(texture(ctx, "preview", &RefImage::from(&data).color()));
+                self.status = format!(
+                    "Placed {} strings. The score went from {} to {} (lower is better).",
+                    data.line_segments.len(),
+                    data.initial_score,
+                    data.final_score
+                );
+                self.data = Some(data);
+            }
+            Ok(Err(error)) => self.status = error.to_string(),
+            Err(_) => self.status = "The run stopped unexpectedly".to_owned(),
+        }
+    }
+
+    fn settings_panel(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let running = self.run.is_some();
+        ui.heading("Image");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.settings.input_filepath);
+            if ui.button("Load").clicked() {
+                self.load(ctx, self.settings.input_filepath.clone());
+            }
+        });
+
+        ui.separator();
+        ui.heading("Pins");
+        ui.add(
+            egui::Slider::new(&mut self.settings.pin_count, 3..=1000)
+                .text("count")
+                .clamping(egui::SliderClamping::Never),
+        );
+        egui::ComboBox::from_label("arrangement")
+            .selected_text(arrangement_name(&self.settings.pin_arrangement))
+            .show_ui(ui, |ui| {
+                for arrangement in Arrangement::ALL {
+                    let name = arrangement_name(&arrangement);
+                    ui.selectable_value(&mut self.settings.pin_arrangement, arrangement, name);
+                }
+            });
+
+        ui.separator();
+        ui.heading("Strings");
+        ui.add(
+            egui::Slider::new(&mut self.settings.max_strings, 1..=10_000)
+                .text("most strings")
+                .clamping(egui::SliderClamping::Never),
+        );
+        ui.add(egui::Slider::new(&mut self.settings.string_alpha, 0.01..=1.0).text("opacity"));
+
+        ui.separator();
+        ui.heading("Colors");
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgb(&mut self.settings.background_color);
+            ui.label("background");
+        });
+        let mut removed = None;
+        for (i, color) in self.settings.foreground_colors.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.color_edit_button_srgb(color);
+                ui.label("string");
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed.filter(|_| self.settings.foreground_colors.len() > 1) {
+            self.settings.foreground_colors.remove(i);
+        }
+        if ui.button("Add a string color").clicked() {
+            self.settings.foreground_colors.push([255, 255, 255]);
+        }
+
+        ui.separator();
+        ui.heading("More options");
+        ui.label("Any other options, as they would be given on the command line");
+        ui.text_edit_multiline(&mut self.settings.more_options);
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    !running && self.input.is_some(),
+                    egui::Button::new("Generate"),
+                )
+                .clicked()
+            {
+                self.start(ctx);
+            }
+            if ui.add_enabled(running, egui::Button::new("Stop")).clicked() {
+                if let Some(run) = &self.run {
+                    run.cancellation.cancel();
+                }
+            }
+        });
+
+        ui.separator();
+        ui.heading("Export");
+        ui.text_edit_singleline(&mut self.export_filepath);
+        ui.horizontal_wrapped(|ui| {
+            for export in Export::ALL {
+                let button = egui::Button::new(export.label());
+                if ui.add_enabled(self.data.is_some(), button).clicked() {
+                    if let Some(data) = &self.data {
+                        self.status = match export.write(data, &self.export_filepath) {
+                            Ok(filepath) => format!("Saved {}", filepath),
+                            Err(error) => error.to_string(),
+                        };
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let dropped = ctx.input(|input| {
+            input
+                .raw
+                .dropped_files
+                .iter()
+                .find_map(|file| file.path.clone())
+        });
+        if let Some(path) = dropped {
+            self.load(ctx, path.to_string_lossy().into_owned());
+        }
+        self.poll(ctx);
+
+        egui::SidePanel::left("settings").show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| self.settings_panel(ui, ctx));
+        });
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            ui.label(&self.status);
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.columns(2, |columns| {
+                for (column, texture) in columns.iter_mut().zip([&self.input, &self.preview]) {
+                    if let Some(texture) = texture {
+                        column.add(egui::Image::new(texture).shrink_to_fit());
+                    }
+                }
+            });
+        });
+    }
+}
+
+fn texture(ctx: &egui::Context, name: &str, image: &RgbaImage) -> egui::TextureHandle {
+    let size = [image.width() as usize, image.height() as usize];
+    ctx.load_texture(
+        name,
+        egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()),
+        egui::TextureOptions::default(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_settings_match_the_command_line_defaults() {
+        let settings = Settings::default();
+        let cli = Cli::try_parse_from(settings.command_line()).unwrap();
+        let defaults = Cli::try_parse_from(["string_art", "--input-filepath", ""]).unwrap();
+        assert_eq!(defaults.pin_count, cli.pin_count);
+        assert_eq!(defaults.pin_arrangement, cli.pin_arrangement);
+        assert_eq!(defaults.string_alpha, cli.string_alpha);
+        assert_eq!(defaults.background_color, cli.background_color);
+        assert_eq!(defaults.foreground_color, cli.foreground_color);
+    }
+
+    #[test]
+    fn test_more_options_are_added_to_the_command_line() {
+        let settings = Settings {
+            pin_arrangement: Arrangement::Circle,
+            foreground_colors: vec![[255, 0, 0], [0, 0, 255]],
+            more_options: "--step-size 0.5\n--float-accumulation".to_owned(),
+            ..Settings::default()
+        };
+        let cli = Cli::try_parse_from(settings.command_line()).unwrap();
+        assert_eq!(Arrangement::Circle, cli.pin_arrangement);
+        assert_eq!(
+            Some(vec![
+                Rgb::from_str("#FF0000").unwrap(),
+                Rgb::from_str("#0000FF").unwrap()
+            ]),
+            cli.foreground_color
+        );
+        assert_eq!(0.5, cli.step_size);
+        assert!(cli.float_accumulation);
+    }
+
+    #[test]
+    fn test_previews_refresh_every_few_strings() {
+        let input = std::env::temp_dir().join("string_art_gui_input.png");
+        image::RgbImage::new(4, 4).save(&input).unwrap();
+        let settings = Settings {
+            input_filepath: input.to_str().unwrap().to_owned(),
+            ..Settings::default()
+        };
+        assert_eq!(
+            Some(PREVIEW_STRINGS_PER_FRAME),
+            settings.args().unwrap().gif_strings_per_frame
+        );
+        std::fs::remove_file(input).unwrap();
+    }
+}
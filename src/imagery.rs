@@ -1,11 +1,17 @@
-use crate::geometry::{Line, Point};
+use crate::blend::Blend;
+use crate::channel::{Channel, LANES};
+use crate::geometry::{self, Line, Point};
 use crate::image::DynamicImage;
-use crate::serde::Serialize;
+use crate::lab::Lab;
+use crate::parallel::*;
+use crate::serde::{Deserialize, Serialize};
 use crate::style::Data;
 use crate::util;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Rgb {
     pub r: i64,
     pub g: i64,
@@ -15,7 +21,6 @@ pub struct Rgb {
 pub type LineSegment = (Point, Point, Rgb);
 
 impl Rgb {
-    #[cfg(test)]
     pub const WHITE: Self = Rgb {
         r: 255,
         g: 255,
@@ -104,15 +109,17 @@ impl std::ops::Neg for Rgb {
     }
 }
 
-#[derive(Clone, Copy)]
-struct Rgbf {
-    r: f64,
-    g: f64,
-    b: f64,
+/// A color whose channels can hold fractions of a level, such as the color a string adds to a
+/// pixel before it's rounded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgbf {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
 }
 
 impl Rgbf {
-    fn new(r: f64, g: f64, b: f64) -> Self {
+    pub fn new(r: f64, g: f64, b: f64) -> Self {
         Self { r, g, b }
     }
 }
@@ -124,6 +131,13 @@ impl std::ops::Add<Self> for Rgbf {
     }
 }
 
+impl std::ops::Neg for Rgbf {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.r, -self.g, -self.b)
+    }
+}
+
 impl std::ops::Mul<f64> for Rgbf {
     type Output = Self;
     fn mul(self, rhs: f64) -> Self {
@@ -139,14 +153,26 @@ impl std::convert::From<Rgb> for Rgbf {
 
 impl std::convert::From<Rgbf> for Rgb {
     fn from(rgbf: Rgbf) -> Self {
-        Self::new(
-            rgbf.r.round() as i64,
-            rgbf.g.round() as i64,
-            rgbf.b.round() as i64,
-        )
+        Self::new(round(rgbf.r), round(rgbf.g), round(rgbf.b))
     }
 }
 
+/// `n.round() as i64`, without the library call that `round` compiles to on targets lacking a
+/// rounding instruction, so that loops over many channels can be vectorized
+fn round(n: f64) -> i64 {
+    // Adding and taking away 2^52 rounds to the nearest integer, but ties go to the even one
+    const TWO_TO_52: f64 = 4503599627370496.0;
+    let abs = n.abs();
+    let even = (abs + TWO_TO_52) - TWO_TO_52;
+    let away = match even - abs == -0.5 {
+        true => even + 1.0,
+        false => even,
+    };
+    // From 2^52 on, every float is already an integer
+    let rounded = if abs < TWO_TO_52 { away } else { abs };
+    rounded.copysign(n) as i64
+}
+
 impl<T: Into<i64>> std::convert::From<(T, T, T)> for Rgb {
     fn from((r, g, b): (T, T, T)) -> Self {
         Rgb::new(r, g, b)
@@ -159,72 +185,591 @@ impl<T: Into<i64>> std::convert::From<[T; 3]> for Rgb {
     }
 }
 
-/// Line of pixels
-pub struct PixLine(HashMap<Point, Rgb>);
+/// Line of pixels, and the points it runs between. Each pixel appears once, with the color the
+/// line adds to it before rounding.
+pub struct PixLine(Vec<(Point, Rgbf)>, (Point, Point));
 
 impl PixLine {
-    fn into_iter(self) -> std::collections::hash_map::IntoIter<Point, Rgb> {
+    fn into_iter(self) -> std::vec::IntoIter<(Point, Rgbf)> {
         self.0.into_iter()
     }
 
+    fn points(&self) -> impl Iterator<Item = &Point> {
+        self.0.iter().map(|(point, _)| point)
+    }
+
     fn negated(&self) -> Self {
-        Self(self.0.iter().map(|(point, rgb)| (*point, -*rgb)).collect())
+        Self(
+            self.0
+                .iter()
+                .map(|(point, rgbf)| (*point, -*rgbf))
+                .collect(),
+            self.1,
+        )
+    }
+
+    /// Whether the line passes through any of the points
+    pub fn crosses(&self, points: &HashSet<Point>) -> bool {
+        self.points().any(|point| points.contains(point))
+    }
+
+    /// How much of the line, from 0 to 1, lies on the points
+    pub fn fraction_on(&self, points: &HashSet<Point>) -> f64 {
+        match self.0.len() {
+            0 => 0.0,
+            len => self.points().filter(|point| points.contains(point)).count() as f64 / len as f64,
+        }
     }
 }
 
-impl<T: Into<Line>> std::convert::From<(T, Rgb, f64, f64)> for PixLine {
-    fn from((line, rgb, step_size, string_alpha): (T, Rgb, f64, f64)) -> Self {
-        let coloring_val = Rgbf::from(rgb) * step_size * string_alpha;
-        Self(
-            line.into()
-                .iter(step_size)
-                .map(Point::from)
-                .fold(HashMap::new(), |mut hash, point| {
-                    if let Some(old) = hash.insert(point, coloring_val) {
-                        hash.insert(point, old + coloring_val);
-                    }
-                    hash
+impl<T: Into<Line>, S: Into<Shade>> std::convert::From<(T, S, f64, f64)> for PixLine {
+    fn from((line, shade, step_size, string_alpha): (T, S, f64, f64)) -> Self {
+        let line = line.into();
+        Trace::new(line, step_size).pix_line(line.ends(), shade.into(), step_size * string_alpha)
+    }
+}
+
+/// The pixels a string crosses, with how many of its steps land on each pixel and how far along
+/// the string those steps are in total, where 0 is the string's first end and 1 its second. A
+/// string is always traced from the same end, so it shares its trace with its reverse.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace(Vec<(Point, f32, f32)>);
+
+impl Trace {
+    pub fn new(line: Line, step_size: f64) -> Self {
+        let line = match is_reversed(line.ends()) {
+            true => line.reversed(),
+            false => line,
+        };
+        let length = line.length();
+        let mut pixels: Vec<(Point, f32, f32)> = Vec::new();
+        for (i, point) in line.iter(step_size).enumerate() {
+            let along = match length > 0.0 {
+                true => f64::min(1.0, i as f64 * step_size / length),
+                false => 0.0,
+            } as f32;
+            let point = Point::from(point);
+            // Each step moves further from the first end, so a pixel's steps all come together
+            match pixels.last_mut() {
+                Some((last, steps, total)) if *last == point => {
+                    *steps += 1.0;
+                    *total += along;
+                }
+                _ => pixels.push((point, 1.0, along)),
+            }
+        }
+        Self(pixels)
+    }
+
+    /// The line of pixels from `a` to `b` along this trace, where each step adds `strength` of
+    /// the shade
+    fn pix_line(&self, (a, b): (Point, Point), shade: Shade, strength: f64) -> PixLine {
+        let reversed = is_reversed((a, b));
+        PixLine(
+            self.0
+                .iter()
+                .map(|(point, steps, along)| {
+                    let steps = *steps as f64;
+                    let along = match reversed {
+                        true => steps - *along as f64,
+                        false => *along as f64,
+                    };
+                    (*point, shade.total(steps, along) * strength)
                 })
-                .into_iter()
-                .map(|(point, rgbf)| (point, Rgb::from(rgbf)))
-                .collect::<HashMap<_, _>>(),
+                .collect(),
+            (a, b),
         )
     }
+
+    /// Roughly how many bytes the trace of a string between `a` and `b` takes
+    fn bytes((a, b): (Point, Point)) -> u64 {
+        let pixels = u32::max(a.x.abs_diff(b.x), a.y.abs_diff(b.y)) as u64 + 1;
+        pixels * std::mem::size_of::<(Point, f32, f32)>() as u64
+            + std::mem::size_of::<((Point, Point), Trace)>() as u64
+    }
+}
+
+/// Whether a string from `a` to `b` runs the other way from its trace
+fn is_reversed((a, b): (Point, Point)) -> bool {
+    (b.y, b.x) < (a.y, a.x)
 }
 
+/// The traces of the strings between every pair of pins, so strings aren't traced again each
+/// time they're scored
 #[derive(Debug)]
-pub struct RefImage(Vec<Vec<Rgb>>);
+pub struct Traces(HashMap<(Point, Point), Trace>);
+
+impl Traces {
+    /// Trace the strings between every pair of `pins`, unless that would take more than
+    /// `max_bytes`
+    pub fn new(pins: &[Point], step_size: f64, max_bytes: u64) -> Option<Self> {
+        let pairs = || {
+            pins.iter()
+                .enumerate()
+                .flat_map(|(i, a)| pins[i..].iter().map(move |b| (*a, *b)))
+        };
+        if pairs().map(Trace::bytes).sum::<u64>() > max_bytes {
+            return None;
+        }
+        let pairs: Vec<_> = pairs().map(|(a, b)| Self::key(a, b)).collect();
+        Some(Self(
+            pairs
+                .into_par_iter()
+                .map(|(a, b)| ((a, b), Trace::new(Line::from((a, b)), step_size)))
+                .collect(),
+        ))
+    }
+
+    /// The trace of the string between `a` and `b`, in either order
+    pub fn get(&self, a: Point, b: Point) -> Option<&Trace> {
+        self.0.get(&Self::key(a, b))
+    }
+
+    fn key(a: Point, b: Point) -> (Point, Point) {
+        match is_reversed((a, b)) {
+            true => (b, a),
+            false => (a, b),
+        }
+    }
+}
+
+/// Two colors a string can fade between, for --gradient-strings. A gradient string is stored
+/// with the color at its first pin, which is either end of the gradient, and fades to the other
+/// end by its second pin.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Gradient {
+    pub from: Rgb,
+    pub to: Rgb,
+}
+
+impl Gradient {
+    /// Both ends less `rgb`, such as to make them relative to the background
+    pub fn minus(&self, rgb: Rgb) -> Self {
+        Self {
+            from: self.from - rgb,
+            to: self.to - rgb,
+        }
+    }
+
+    /// The color at the second pin of a string with `rgb` at its first, if `rgb` is either end
+    pub fn end_for(&self, rgb: Rgb) -> Option<Rgb> {
+        match rgb {
+            rgb if rgb == self.from => Some(self.to),
+            rgb if rgb == self.to => Some(self.from),
+            _ => None,
+        }
+    }
+}
+
+impl core::str::FromStr for Gradient {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string.split(',').collect::<Vec<_>>()[..] {
+            [from, to] => Ok(Self {
+                from: from.trim().parse()?,
+                to: to.trim().parse()?,
+            }),
+            _ => Err(format!(
+                "Invalid gradient: \"{}\". Expected two colors like #RRGGBB,#RRGGBB",
+                string
+            )),
+        }
+    }
+}
+
+/// How strings are drawn into an image: how far apart the pixels they color are, how strongly
+/// they color them, the gradient they fade along, if any, and how their light adds up over the
+/// background
+#[derive(Debug, Clone)]
+pub struct Pen {
+    pub step_size: f64,
+    pub string_alpha: f64,
+    pub gradient: Option<Gradient>,
+    pub blend: Blend,
+    pub background: Rgb,
+    /// The strings between the pins, if they were traced ahead of time
+    pub traces: Option<Arc<Traces>>,
+}
+
+impl Pen {
+    pub fn pix_line(&self, (a, b, rgb): LineSegment) -> PixLine {
+        match self.traces.as_ref().and_then(|traces| traces.get(a, b)) {
+            Some(trace) => {
+                trace.pix_line((a, b), self.shade(rgb), self.step_size * self.string_alpha)
+            }
+            None => PixLine::from(((a, b), self.shade(rgb), self.step_size, self.string_alpha)),
+        }
+    }
+
+    /// How the color runs along a string whose color is relative to the background, in the
+    /// space strings are blended in
+    pub fn shade(&self, rgb: Rgb) -> Shade {
+        let background = self.blend.decode(self.background);
+        Shade::of(rgb, self.gradient)
+            .map(|rgb| self.blend.decode(rgb + self.background) - background)
+    }
+}
+
+/// How a string's color runs from its first pin to its second
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shade {
+    Solid(Rgb),
+    Fade(Rgb, Rgb),
+}
+
+impl Shade {
+    /// The shade of a string with `rgb` at its first pin, which fades if `rgb` is an end of the
+    /// gradient
+    pub fn of(rgb: Rgb, gradient: Option<Gradient>) -> Self {
+        match gradient.and_then(|gradient| gradient.end_for(rgb)) {
+            Some(end) => Shade::Fade(rgb, end),
+            None => Shade::Solid(rgb),
+        }
+    }
+
+    /// The same shade with `f` applied to its colors
+    pub fn map(self, f: impl Fn(Rgb) -> Rgb) -> Self {
+        match self {
+            Shade::Solid(rgb) => Shade::Solid(f(rgb)),
+            Shade::Fade(start, end) => Shade::Fade(f(start), f(end)),
+        }
+    }
+
+    /// The colors at the first and second pins
+    pub fn ends(&self) -> (Rgb, Rgb) {
+        match self {
+            Shade::Solid(rgb) => (*rgb, *rgb),
+            Shade::Fade(start, end) => (*start, *end),
+        }
+    }
+
+    /// The total color of `steps` points whose distances along the way from the first pin add
+    /// up to `along`, where 0 is the first pin and 1 the second
+    fn total(&self, steps: f64, along: f64) -> Rgbf {
+        match self {
+            Shade::Solid(rgb) => Rgbf::from(*rgb) * steps,
+            Shade::Fade(start, end) => {
+                Rgbf::from(*start) * (steps - along) + Rgbf::from(*end) * along
+            }
+        }
+    }
+}
+
+impl std::convert::From<Rgb> for Shade {
+    fn from(rgb: Rgb) -> Self {
+        Shade::Solid(rgb)
+    }
+}
+
+/// The pixels of an image, and optionally how many strings cross each pixel, where the strings
+/// at each pin lead, how much each pixel's error counts, and the target each pixel is compared
+/// to in CIELAB. Pixels are `Rgb` unless another `Channel` representation is chosen.
+#[derive(Debug, Clone)]
+pub struct RefImage<C: Channel = Rgb> {
+    grid: Grid<C>,
+    coverage: Option<Grid<u32>>,
+    pin_ends: Option<HashMap<Point, Vec<Point>>>,
+    weights: Option<Grid<i64>>,
+    lab: Option<LabTargets>,
+}
+
+/// The target of each pixel, in the space strings are blended in and in CIELAB
+type LabTargets = (Blend, Grid<(Rgb, Lab)>);
+
+/// A value for each pixel of an image, kept row after row in a single buffer
+#[derive(Debug, Clone, PartialEq)]
+struct Grid<T> {
+    width: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    fn new(width: u32, height: u32, value: T) -> Self {
+        Self {
+            width: width as usize,
+            cells: vec![value; width as usize * height as usize],
+        }
+    }
+
+    fn from_rows(rows: Vec<Vec<T>>) -> Self {
+        Self {
+            width: rows.first().map_or(0, |row| row.len()),
+            cells: rows.into_iter().flatten().collect(),
+        }
+    }
+
+    fn height(&self) -> usize {
+        self.cells.len().checked_div(self.width).unwrap_or(0)
+    }
+}
+
+impl<T> std::ops::Index<Point> for Grid<T> {
+    type Output = T;
+    fn index(&self, point: Point) -> &Self::Output {
+        &self.cells[point.y as usize * self.width + point.x as usize]
+    }
+}
+
+impl<T> std::ops::IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, point: Point) -> &mut Self::Output {
+        &mut self.cells[point.y as usize * self.width + point.x as usize]
+    }
+}
 
 impl RefImage {
     pub fn new(width: u32, height: u32) -> Self {
-        Self(vec![vec![Rgb::BLACK; width as usize]; height as usize])
+        Self::blank(width, height)
+    }
+}
+
+impl<C: Channel> RefImage<C> {
+    /// A black image with any channel representation
+    pub fn blank(width: u32, height: u32) -> Self {
+        Self {
+            grid: Grid::new(width, height, C::ZERO),
+            coverage: None,
+            pin_ends: None,
+            weights: None,
+            lab: None,
+        }
+    }
+
+    /// Load an image with any channel representation
+    pub fn from_image(image: &DynamicImage) -> Self {
+        let mut ref_image = Self::blank(image.width(), image.height());
+        image.to_rgb8().enumerate_pixels().for_each(|(x, y, p)| {
+            ref_image[(x, y)] = C::from_rgb(Rgb::from(p.0));
+        });
+        ref_image
+    }
+
+    /// Start counting how many strings cross each pixel
+    pub fn with_coverage(mut self) -> Self {
+        self.coverage = Some(Grid::new(self.width(), self.height(), 0));
+        self
+    }
+
+    /// How many strings cross the pixel, if strings are being counted
+    pub fn coverage(&self, point: Point) -> Option<u32> {
+        self.coverage.as_ref().map(|c| c[point])
+    }
+
+    /// Whether the line can be added without any pixel being crossed by more than `max` strings
+    pub fn has_room_for(&self, line: &PixLine, max: u32) -> bool {
+        line.points()
+            .all(|p| self.coverage(*p).is_none_or(|c| c < max))
+    }
+
+    fn count_coverage(&mut self, line: &PixLine, change: i32) {
+        if let Some(coverage) = &mut self.coverage {
+            line.points().for_each(|p| {
+                let count = &mut coverage[*p];
+                *count = count.saturating_add_signed(change);
+            })
+        }
+    }
+
+    fn accumulate(&mut self, point: Point, rgbf: Rgbf) {
+        self[point] = self[point].plus(C::from_rgbf(rgbf));
+    }
+
+    /// The same image with another channel representation. Fractions of a level are rounded
+    /// away when the new representation can't hold them.
+    pub fn converted<D: Channel>(self) -> RefImage<D> {
+        RefImage {
+            grid: Grid {
+                width: self.grid.width,
+                cells: self
+                    .grid
+                    .cells
+                    .into_iter()
+                    .map(|c| D::from_rgb(c.to_rgb()))
+                    .collect(),
+            },
+            coverage: self.coverage,
+            pin_ends: self.pin_ends,
+            weights: self.weights,
+            lab: self.lab,
+        }
+    }
+
+    /// Start keeping track of where the strings at each pin lead
+    pub fn with_pin_ends(mut self) -> Self {
+        self.pin_ends = Some(HashMap::new());
+        self
+    }
+
+    /// Whether the line leaves both of its pins at least `min` degrees away from every string
+    /// already there, if pin ends are being tracked
+    pub fn has_angle_for(&self, line: &PixLine, min: f64) -> bool {
+        let Some(pin_ends) = &self.pin_ends else {
+            return true;
+        };
+        let (a, b) = line.1;
+        [(a, b), (b, a)].into_iter().all(|(pin, end)| {
+            pin_ends.get(&pin).is_none_or(|ends| {
+                ends.iter()
+                    .all(|other| geometry::angle_at(pin, end, *other) >= min)
+            })
+        })
+    }
+
+    fn track_pin_ends(&mut self, line: &PixLine, add: bool) {
+        if let Some(pin_ends) = &mut self.pin_ends {
+            let (a, b) = line.1;
+            for (pin, end) in [(a, b), (b, a)] {
+                let ends = pin_ends.entry(pin).or_default();
+                match add {
+                    true => ends.push(end),
+                    false => {
+                        if let Some(i) = ends.iter().position(|e| *e == end) {
+                            ends.swap_remove(i);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Multiply the error of each pixel by its weight, so some pixels count more than others
+    pub fn with_weights(mut self, weights: Vec<Vec<i64>>) -> Self {
+        self.weights = Some(Grid::from_rows(weights));
+        self
+    }
+
+    fn weight(&self, point: Point) -> i64 {
+        self.weights.as_ref().map_or(1, |w| w[point])
+    }
+
+    /// Score each pixel by how different the strings look from `target` in CIELAB, instead of
+    /// by the squared difference of the channels. The pixels must hold the strings less the
+    /// target, blended with `blend`, as they do when made from the negated target.
+    pub fn in_lab(mut self, target: &DynamicImage, blend: Blend) -> Self {
+        let targets = Grid {
+            width: target.width() as usize,
+            cells: target
+                .to_rgb8()
+                .pixels()
+                .map(|p| {
+                    let rgb = Rgb::from(p.0);
+                    (blend.decode(rgb), Lab::from(rgb))
+                })
+                .collect(),
+        };
+        self.lab = Some((blend, targets));
+        self
+    }
+
+    /// The weighted error of the pixel at `point` if it held `c`
+    fn pixel_score(&self, point: Point, c: C) -> i64 {
+        let score = match &self.lab {
+            None => c.score(),
+            Some((blend, targets)) => {
+                let (rgb, lab) = targets[point];
+                lab.score(Lab::of(rgb + c.to_rgb(), *blend))
+            }
+        };
+        score * self.weight(point)
+    }
+
+    /// Each pixel converted from sRGB to the space strings are blended in
+    pub fn decoded(self, blend: Blend) -> Self {
+        self.mapped(|rgb| blend.decode(rgb), blend)
+    }
+
+    /// Each pixel converted from the space strings are blended in back to sRGB
+    pub fn encoded(self, blend: Blend) -> Self {
+        self.mapped(|rgb| blend.encode(rgb), blend)
+    }
+
+    fn mapped(mut self, f: impl Fn(Rgb) -> Rgb, blend: Blend) -> Self {
+        if blend != Blend::Srgb {
+            self.grid
+                .cells
+                .iter_mut()
+                .for_each(|c| *c = C::from_rgb(f(c.to_rgb())));
+        }
+        self
     }
 
     pub fn negated(mut self) -> Self {
-        self.0
-            .iter_mut()
-            .for_each(|row| row.iter_mut().for_each(|rgb| *rgb = -*rgb));
+        self.grid.cells.iter_mut().for_each(|c| *c = c.negated());
         self
     }
 
+    /// A copy where every pixel is replaced by the grey with the same mean of its channels
+    pub fn luminance(&self) -> Self {
+        let grey = |c: &C| C::from_rgb(i32::from_rgb(c.to_rgb()).to_rgb());
+        Self {
+            grid: Grid {
+                width: self.grid.width,
+                cells: self.grid.cells.iter().map(grey).collect(),
+            },
+            coverage: self.coverage.clone(),
+            pin_ends: self.pin_ends.clone(),
+            weights: self.weights.clone(),
+            lab: self.lab.clone(),
+        }
+    }
+
     pub fn add_rgb(mut self, other: Rgb) -> Self {
-        self.0
+        let other = C::from_rgb(other);
+        self.grid.cells.iter_mut().for_each(|c| *c = c.plus(other));
+        self
+    }
+
+    pub fn add_ref_image(mut self, other: &RefImage<C>) -> Self {
+        self.grid
+            .cells
             .iter_mut()
-            .for_each(|row| row.iter_mut().for_each(|rgb| *rgb = *rgb + other));
+            .zip(other.grid.cells.iter())
+            .for_each(|(c, other)| *c = c.plus(*other));
         self
     }
 
     pub fn score(&self) -> i64 {
-        self.0.iter().flatten().map(pixel_score).sum()
+        match (&self.weights, &self.lab) {
+            (None, None) => self.grid.cells.iter().map(|c| c.score()).sum(),
+            (Some(weights), None) => self
+                .grid
+                .cells
+                .iter()
+                .zip(weights.cells.iter())
+                .map(|(c, weight)| c.score() * weight)
+                .sum(),
+            _ => (0..self.height())
+                .flat_map(|y| (0..self.width()).map(move |x| Point::new(x, y)))
+                .map(|p| self.pixel_score(p, self[p]))
+                .sum(),
+        }
     }
 
+    /// Pixels compared in CIELAB are scored one at a time; otherwise `LANES` pixels are scored
+    /// at once
     pub fn score_change_on_add<T: Into<PixLine>>(&self, line: T) -> i64 {
-        line.into()
-            .into_iter()
-            .map(|(p, rgb)| {
-                let a = self[p];
-                let b = a + rgb;
-                pixel_score(&b) - pixel_score(&a)
+        let line = line.into();
+        if self.lab.is_some() {
+            return line
+                .into_iter()
+                .map(|(p, rgbf)| {
+                    let a = self[p];
+                    let b = a.plus(C::from_rgbf(rgbf));
+                    self.pixel_score(p, b) - self.pixel_score(p, a)
+                })
+                .sum();
+        }
+        line.0
+            .chunks(LANES)
+            .map(|pixels| {
+                let mut before = [C::ZERO; LANES];
+                let mut after = [C::ZERO; LANES];
+                let mut weights = [0; LANES];
+                for (lane, (p, rgbf)) in pixels.iter().enumerate() {
+                    before[lane] = self[*p];
+                    after[lane] = before[lane].plus(C::from_rgbf(*rgbf));
+                    weights[lane] = self.weight(*p);
+                }
+                C::score_changes(&before, &after, &weights)
             })
             .sum()
     }
@@ -234,35 +779,44 @@ impl RefImage {
     }
 
     pub fn width(&self) -> u32 {
-        self.0[0].len() as u32
+        self.grid.width as u32
     }
 
     pub fn height(&self) -> u32 {
-        self.0.len() as u32
+        self.grid.height() as u32
     }
 
     pub fn color(&self) -> image::RgbaImage {
         let mut img = image::RgbaImage::new(self.width(), self.height());
-        for (y, row) in self.0.iter().enumerate() {
-            for (x, rgb) in row.iter().map(|rgb| rgb.clamped()).enumerate() {
-                let pixel = img.get_pixel_mut(x as u32, y as u32);
-                pixel[0] = rgb.r as u8;
-                pixel[1] = rgb.g as u8;
-                pixel[2] = rgb.b as u8;
-                pixel[3] = u8::MAX; // Alpha channel
-            }
+        for (pixel, c) in img.pixels_mut().zip(self.grid.cells.iter()) {
+            let rgb = c.to_rgb().clamped();
+            pixel[0] = rgb.r as u8;
+            pixel[1] = rgb.g as u8;
+            pixel[2] = rgb.b as u8;
+            pixel[3] = u8::MAX; // Alpha channel
         }
         img
     }
-}
 
-fn pixel_score(Rgb { r, g, b }: &Rgb) -> i64 {
-    r * r + g * g + b * b
+    /// Visualize how well each pixel matches the target, given the state of the image before
+    /// any strings were placed. Red shows the error that remains, and green shows the error
+    /// that the strings removed.
+    pub fn explain(&self, initial: &RefImage<C>) -> image::RgbImage {
+        // Scale the squared error of all three channels back to a single channel's range
+        let brightness = |score: i64| ((score as f64 / 3.0).sqrt().round() as i64).min(255) as u8;
+        let mut img = image::RgbImage::new(self.width(), self.height());
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            let remaining = self[(x, y)].score();
+            let removed = i64::max(0, initial[(x, y)].score() - remaining);
+            *pixel = image::Rgb([brightness(remaining), brightness(removed), 0]);
+        }
+        img
+    }
 }
 
-impl<T: Into<PixLine> + Copy> std::convert::From<(&Vec<T>, u32, u32)> for RefImage {
+impl<C: Channel, T: Into<PixLine> + Copy> std::convert::From<(&Vec<T>, u32, u32)> for RefImage<C> {
     fn from((line_segmentables, width, height): (&Vec<T>, u32, u32)) -> Self {
-        let mut ref_image = Self::new(width, height);
+        let mut ref_image = Self::blank(width, height);
         line_segmentables.iter().fold(&mut ref_image, |i, a| {
             *i += *a;
             i
@@ -273,76 +827,117 @@ impl<T: Into<PixLine> + Copy> std::convert::From<(&Vec<T>, u32, u32)> for RefIma
 
 impl std::convert::From<&DynamicImage> for RefImage {
     fn from(image: &DynamicImage) -> Self {
-        let mut ref_image = Self::new(image.width(), image.height());
-        image.to_rgb8().enumerate_pixels().for_each(|(x, y, p)| {
-            ref_image[(x, y)] = Rgb::from(p.0);
-        });
-        ref_image
+        Self::from_image(image)
     }
 }
 
 impl std::convert::From<&Data> for RefImage {
     fn from(data: &Data) -> Self {
-        Self::from((
-            &data
-                .line_segments
-                .iter()
-                .map(|(a, b, rgb)| (a, b, *rgb - data.args.background_color))
-                .map(|(a, b, rgb)| ((*a, *b), rgb, data.args.step_size, data.args.string_alpha))
-                .collect(),
-            data.image_width,
-            data.image_height,
-        ))
-        .add_rgb(data.args.background_color)
+        Self::from((data, 1.0))
     }
 }
 
-impl<T: Into<PixLine>> std::ops::AddAssign<T> for RefImage {
+/// Render the finished work at `scale` times the size of the input image
+impl std::convert::From<(&Data, f64)> for RefImage {
+    fn from((data, scale): (&Data, f64)) -> Self {
+        let width = u32::max(1, (data.image_width as f64 * scale).round() as u32);
+        let height = u32::max(1, (data.image_height as f64 * scale).round() as u32);
+        let scaled = |p: &Point| {
+            Point::new(
+                u32::min(width - 1, (p.x as f64 * scale).round() as u32),
+                u32::min(height - 1, (p.y as f64 * scale).round() as u32),
+            )
+        };
+        let lines: Vec<_> = data
+            .drawn_line_segments()
+            .iter()
+            .map(|(a, b, rgb)| {
+                (
+                    (scaled(a), scaled(b)),
+                    data.args.shade(*rgb - data.args.background_color),
+                    data.args.step_size,
+                    data.args.string_alpha,
+                )
+            })
+            .collect();
+        let strings = match data.args.float_accumulation {
+            true => RefImage::<[f32; 3]>::from((&lines, width, height)).converted(),
+            false => Self::from((&lines, width, height)),
+        };
+        let blend = data.args.blend;
+        match &data.args.canvas {
+            Some(canvas) => strings.add_ref_image(
+                &Self::from(&canvas.resize_exact(
+                    width,
+                    height,
+                    image::imageops::FilterType::Triangle,
+                ))
+                .decoded(blend),
+            ),
+            None => strings.add_rgb(blend.decode(data.args.background_color)),
+        }
+        .encoded(blend)
+    }
+}
+
+impl<C: Channel, T: Into<PixLine>> std::ops::AddAssign<T> for RefImage<C> {
     fn add_assign(&mut self, pix_line: T) {
-        pix_line.into().into_iter().for_each(|(point, rgb)| {
-            self[point] = self[point] + rgb;
-        })
+        let pix_line = pix_line.into();
+        self.count_coverage(&pix_line, 1);
+        self.track_pin_ends(&pix_line, true);
+        pix_line
+            .into_iter()
+            .for_each(|(point, rgbf)| self.accumulate(point, rgbf))
     }
 }
 
-impl<T: Into<PixLine>> std::ops::SubAssign<T> for RefImage {
+impl<C: Channel, T: Into<PixLine>> std::ops::SubAssign<T> for RefImage<C> {
     fn sub_assign(&mut self, pix_line: T) {
-        pix_line.into().into_iter().for_each(|(point, rgb)| {
-            self[point] = self[point] - rgb;
-        })
+        let pix_line = pix_line.into();
+        self.count_coverage(&pix_line, -1);
+        self.track_pin_ends(&pix_line, false);
+        pix_line
+            .into_iter()
+            .for_each(|(point, rgbf)| self.accumulate(point, -rgbf))
     }
 }
 
-impl std::ops::Index<Point> for RefImage {
-    type Output = Rgb;
+impl<C: Channel> std::ops::Index<Point> for RefImage<C> {
+    type Output = C;
     fn index(&self, point: Point) -> &Self::Output {
-        &self.0[point.y as usize][point.x as usize]
+        &self.grid[point]
     }
 }
 
-impl std::ops::Index<(u32, u32)> for RefImage {
-    type Output = Rgb;
+impl<C: Channel> std::ops::Index<(u32, u32)> for RefImage<C> {
+    type Output = C;
     fn index(&self, (x, y): (u32, u32)) -> &Self::Output {
-        &self.0[y as usize][x as usize]
+        &self.grid[Point::new(x, y)]
     }
 }
 
-impl std::ops::IndexMut<Point> for RefImage {
+impl<C: Channel> std::ops::IndexMut<Point> for RefImage<C> {
     fn index_mut(&mut self, point: Point) -> &mut Self::Output {
-        &mut self.0[point.y as usize][point.x as usize]
+        &mut self.grid[point]
     }
 }
 
-impl std::ops::IndexMut<(u32, u32)> for RefImage {
+impl<C: Channel> std::ops::IndexMut<(u32, u32)> for RefImage<C> {
     fn index_mut(&mut self, (x, y): (u32, u32)) -> &mut Self::Output {
-        &mut self.0[y as usize][x as usize]
+        &mut self.grid[Point::new(x, y)]
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "cli"))]
 mod test {
     use super::*;
 
+    fn rounded(line: PixLine) -> Vec<(Point, Rgb)> {
+        line.into_iter()
+            .map(|(point, rgbf)| (point, Rgb::from(rgbf)))
+            .collect()
+    }
+
     #[test]
     fn test_rgb_to_string() {
         assert_eq!("#000000", Rgb::BLACK.to_string());
@@ -377,31 +972,32 @@ mod test {
                 (Point::new(0, 0), Rgb::new(51, 51, 51)),
                 (Point::new(0, 1), Rgb::new(51, 51, 51)),
                 (Point::new(0, 2), Rgb::new(51, 51, 51))
-            ]
-            .into_iter()
-            .collect::<HashMap<_, _>>(),
-            line.0
+            ],
+            rounded(line)
         );
     }
 
     #[test]
     fn test_new_ref_image_is_black() {
-        assert_eq!(vec![vec![Rgb::BLACK]], RefImage::new(1, 1).0);
+        assert_eq!(
+            Grid::from_rows(vec![vec![Rgb::BLACK]]),
+            RefImage::new(1, 1).grid
+        );
     }
 
     #[test]
     fn test_ref_image_add_rgb() {
         assert_eq!(
-            vec![vec![Rgb::WHITE]],
-            RefImage::new(1, 1).add_rgb(Rgb::WHITE).0
+            Grid::from_rows(vec![vec![Rgb::WHITE]]),
+            RefImage::new(1, 1).add_rgb(Rgb::WHITE).grid
         );
     }
 
     #[test]
     fn test_ref_image_negated() {
         assert_eq!(
-            vec![vec![-Rgb::WHITE]],
-            RefImage::new(1, 1).add_rgb(Rgb::WHITE).negated().0
+            Grid::from_rows(vec![vec![-Rgb::WHITE]]),
+            RefImage::new(1, 1).add_rgb(Rgb::WHITE).negated().grid
         );
     }
 
@@ -444,6 +1040,68 @@ mod test {
         assert_eq!(real_score_change, predicted_score_change);
     }
 
+    #[test]
+    fn test_weighted_score_change_is_accurate() {
+        let pix_line = || {
+            PixLine::from((
+                (Point::new(0, 0), Point::new(101, 67)),
+                Rgb::WHITE,
+                1.0,
+                1.0,
+            ))
+        };
+        let weights = (0..150).map(|y| vec![1 + y % 5; 150]).collect();
+        let mut ref_image = RefImage::new(150, 150)
+            .add_rgb(-Rgb::WHITE)
+            .with_weights(weights);
+        let initial_score = ref_image.score();
+        assert!(initial_score > RefImage::new(150, 150).add_rgb(-Rgb::WHITE).score());
+        let predicted_score_change = ref_image.score_change_on_add(pix_line());
+        ref_image += pix_line();
+        assert_eq!(ref_image.score() - initial_score, predicted_score_change);
+    }
+
+    #[test]
+    fn test_lab_score_change_is_accurate() {
+        let pix_line = || {
+            PixLine::from((
+                (Point::new(0, 0), Point::new(101, 67)),
+                Rgb::new(200, 0, 0),
+                1.0,
+                0.5,
+            ))
+        };
+        let target = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            150,
+            150,
+            image::Rgb([255, 40, 40]),
+        ));
+        let mut ref_image = RefImage::from(&target)
+            .negated()
+            .in_lab(&target, Blend::Srgb);
+        let initial_score = ref_image.score();
+        let predicted_score_change = ref_image.score_change_on_add(pix_line());
+        assert!(predicted_score_change < 0);
+        ref_image += pix_line();
+        assert_eq!(ref_image.score() - initial_score, predicted_score_change);
+        // The target itself scores perfectly
+        assert_eq!(
+            0,
+            RefImage::new(150, 150).in_lab(&target, Blend::Srgb).score()
+        );
+    }
+
+    #[test]
+    fn test_linear_blend_lightens_faint_strings() {
+        let render = |blend: &str| {
+            let mut data = Data::example(&["--blend", blend, "--string-alpha", "0.5"]);
+            data.line_segments = vec![(Point::new(0, 0), Point::new(9, 0), Rgb::WHITE)];
+            RefImage::from(&data).color()[(5, 0)].0[0]
+        };
+        assert_eq!(128, render("srgb"));
+        assert_eq!(188, render("linear"));
+    }
+
     #[test]
     fn test_score_change_on_sub_is_accurate() {
         let pix_line = || {
@@ -462,6 +1120,283 @@ mod test {
         assert_eq!(real_score_change, predicted_score_change);
     }
 
+    #[test]
+    fn test_ref_image_from_data() {
+        let data = Data::example(&["--string-alpha", "1"]);
+        let ref_image = RefImage::from(&data);
+        assert_eq!((10, 10), (ref_image.width(), ref_image.height()));
+        assert_eq!(Rgb::WHITE, ref_image[(9, 0)]);
+        assert_eq!(Rgb::BLACK, ref_image[(9, 1)]);
+    }
+
+    #[test]
+    fn test_scaled_ref_image_from_data() {
+        let data = Data::example(&["--string-alpha", "1"]);
+        let ref_image = RefImage::from((&data, 2.0));
+        assert_eq!((20, 20), (ref_image.width(), ref_image.height()));
+        assert_eq!(Rgb::WHITE, ref_image[(18, 0)]);
+        assert_eq!(Rgb::BLACK, ref_image[(19, 0)]);
+    }
+
+    #[test]
+    fn test_explain() {
+        let initial = RefImage::new(3, 1).add_rgb(-Rgb::WHITE);
+        let mut last = RefImage::new(3, 1).add_rgb(-Rgb::WHITE);
+        last[(0, 0)] = Rgb::BLACK;
+        last[(1, 0)] = Rgb::new(-100, -100, -100);
+        let explained = last.explain(&initial);
+        assert_eq!([0, 255, 0], explained[(0, 0)].0);
+        assert_eq!([100, 235, 0], explained[(1, 0)].0);
+        assert_eq!([255, 0, 0], explained[(2, 0)].0);
+    }
+
+    #[test]
+    fn test_ref_image_add_ref_image() {
+        let a = RefImage::new(2, 1).add_rgb(Rgb::new(1, 2, 3));
+        let b = RefImage::new(2, 1).add_rgb(Rgb::new(10, 20, 30));
+        assert_eq!(
+            Grid::from_rows(vec![vec![Rgb::new(11, 22, 33); 2]]),
+            a.add_ref_image(&b).grid
+        );
+    }
+
+    #[test]
+    fn test_ref_image_from_data_on_canvas() {
+        let mut data = Data::example(&["--string-alpha", "0.5"]);
+        let mut canvas = image::RgbImage::new(10, 10);
+        canvas
+            .pixels_mut()
+            .for_each(|p| *p = image::Rgb([100, 50, 0]));
+        data.args.canvas = Some(DynamicImage::ImageRgb8(canvas));
+        let ref_image = RefImage::from(&data);
+        assert_eq!(Rgb::new(100, 50, 0), ref_image[(5, 5)]);
+        assert_eq!(Rgb::new(228, 178, 128), ref_image[(5, 0)]);
+    }
+
+    #[test]
+    fn test_gradient_from_str() {
+        let gradient: Gradient = "#FF0000, #0000FF".parse().unwrap();
+        assert_eq!(Rgb::new(255, 0, 0), gradient.from);
+        assert_eq!(Rgb::new(0, 0, 255), gradient.to);
+        assert!("#FF0000".parse::<Gradient>().is_err());
+        assert!("#FF0000,blue".parse::<Gradient>().is_err());
+    }
+
+    #[test]
+    fn test_shade_of_gradient_colors() {
+        let gradient = Some(Gradient {
+            from: Rgb::WHITE,
+            to: Rgb::BLACK,
+        });
+        assert_eq!(
+            Shade::Fade(Rgb::WHITE, Rgb::BLACK),
+            Shade::of(Rgb::WHITE, gradient)
+        );
+        assert_eq!(
+            Shade::Fade(Rgb::BLACK, Rgb::WHITE),
+            Shade::of(Rgb::BLACK, gradient)
+        );
+        let red = Rgb::new(255, 0, 0);
+        assert_eq!(Shade::Solid(red), Shade::of(red, gradient));
+        assert_eq!(Shade::Solid(red), Shade::of(red, None));
+    }
+
+    #[test]
+    fn test_pix_line_fades() {
+        let shade = Shade::Fade(Rgb::new(100, 0, 0), Rgb::new(0, 0, 100));
+        let pix_line = PixLine::from(((Point::new(0, 0), Point::new(10, 0)), shade, 1.0, 1.0));
+        let pixels: HashMap<_, _> = rounded(pix_line).into_iter().collect();
+        assert_eq!(Rgb::new(100, 0, 0), pixels[&Point::new(0, 0)]);
+        assert_eq!(Rgb::new(50, 0, 50), pixels[&Point::new(5, 0)]);
+        assert_eq!(Rgb::new(0, 0, 100), pixels[&Point::new(10, 0)]);
+    }
+
+    #[test]
+    fn test_reversed_pix_line_fades_the_other_way() {
+        let shade = Shade::Fade(Rgb::new(100, 0, 0), Rgb::new(0, 0, 100));
+        let pix_line = PixLine::from(((Point::new(10, 0), Point::new(0, 0)), shade, 1.0, 1.0));
+        let pixels: HashMap<_, _> = rounded(pix_line).into_iter().collect();
+        assert_eq!(Rgb::new(100, 0, 0), pixels[&Point::new(10, 0)]);
+        assert_eq!(Rgb::new(0, 0, 100), pixels[&Point::new(0, 0)]);
+    }
+
+    #[test]
+    fn test_traced_pix_lines_match_untraced_ones() {
+        let pins = [Point::new(0, 0), Point::new(7, 3), Point::new(2, 9)];
+        let pen = Pen {
+            step_size: 0.5,
+            string_alpha: 0.2,
+            gradient: Some(Gradient {
+                from: Rgb::new(100, 0, 0),
+                to: Rgb::new(0, 0, 100),
+            }),
+            blend: Blend::Srgb,
+            background: Rgb::BLACK,
+            traces: None,
+        };
+        let traced = Pen {
+            traces: Traces::new(&pins, pen.step_size, u64::MAX).map(Arc::new),
+            ..pen.clone()
+        };
+        for a in pins {
+            for b in pins {
+                let line_segment = (a, b, Rgb::new(100, 0, 0));
+                assert_eq!(
+                    pen.pix_line(line_segment).0,
+                    traced.pix_line(line_segment).0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_traces_stay_within_their_budget() {
+        let pins = [Point::new(0, 0), Point::new(7, 3), Point::new(2, 9)];
+        assert!(Traces::new(&pins, 1.0, u64::MAX).is_some());
+        assert!(Traces::new(&pins, 1.0, 100).is_none());
+    }
+
+    fn faint_line() -> PixLine {
+        PixLine::from((
+            (Point::new(0, 0), Point::new(0, 2)),
+            Rgb::new(4, 4, 4),
+            1.0,
+            0.1,
+        ))
+    }
+
+    #[test]
+    fn test_fractions_add_up_faint_strings() {
+        let mut plain = RefImage::new(1, 3);
+        let mut fractions = RefImage::<[f32; 3]>::blank(1, 3);
+        for _ in 0..10 {
+            plain += faint_line();
+            fractions += faint_line();
+        }
+        assert_eq!(Rgb::BLACK, plain[(0, 1)]);
+        assert_eq!(Rgb::new(4, 4, 4), fractions[(0, 1)].to_rgb());
+    }
+
+    #[test]
+    fn test_fractions_count_toward_the_score() {
+        let mut ref_image = RefImage::<[f32; 3]>::blank(1, 3);
+        assert_eq!(0, ref_image.score_change_on_add(faint_line()));
+        ref_image += faint_line();
+        assert_eq!(6, ref_image.score_change_on_add(faint_line()));
+    }
+
+    #[test]
+    fn test_removing_strings_takes_back_their_fractions() {
+        let mut ref_image = RefImage::<[f32; 3]>::blank(1, 3);
+        for _ in 0..3 {
+            ref_image += faint_line();
+        }
+        for _ in 0..3 {
+            ref_image -= faint_line();
+        }
+        assert_eq!(0, ref_image.score());
+        assert_eq!(0, ref_image.score_change_on_add(faint_line()));
+    }
+
+    #[test]
+    fn test_ref_image_pin_ends() {
+        let p = Point::new;
+        let line = |a: Point, b: Point| PixLine::from(((a, b), Rgb::WHITE, 1.0, 0.2));
+        let mut ref_image = RefImage::new(10, 10).with_pin_ends();
+        ref_image += line(p(0, 0), p(9, 0));
+        // 45 degrees from the first string at (0, 0)
+        assert!(ref_image.has_angle_for(&line(p(0, 0), p(9, 9)), 45.0));
+        assert!(!ref_image.has_angle_for(&line(p(0, 0), p(9, 9)), 46.0));
+        // Close to the first string at (9, 0), which the new string also leaves from
+        assert!(!ref_image.has_angle_for(&line(p(0, 1), p(9, 0)), 10.0));
+        assert!(ref_image.has_angle_for(&line(p(0, 9), p(9, 9)), 90.0));
+        ref_image -= line(p(9, 0), p(0, 0));
+        assert!(ref_image.has_angle_for(&line(p(0, 0), p(9, 9)), 90.0));
+    }
+
+    #[test]
+    fn test_ref_image_without_pin_ends() {
+        let line = PixLine::from(((Point::new(0, 0), Point::new(9, 0)), Rgb::WHITE, 1.0, 0.2));
+        let mut ref_image = RefImage::new(10, 10);
+        ref_image += ((Point::new(0, 0), Point::new(9, 0)), Rgb::WHITE, 1.0, 0.2);
+        assert!(ref_image.has_angle_for(&line, 90.0));
+    }
+
+    #[test]
+    fn test_ref_image_coverage() {
+        let line = || PixLine::from(((Point::new(0, 0), Point::new(0, 2)), Rgb::WHITE, 0.5, 0.2));
+        let mut ref_image = RefImage::new(3, 3).with_coverage();
+        ref_image += line();
+        ref_image += line();
+        assert_eq!(Some(2), ref_image.coverage(Point::new(0, 1)));
+        assert_eq!(Some(0), ref_image.coverage(Point::new(1, 1)));
+        assert!(ref_image.has_room_for(&line(), 3));
+        assert!(!ref_image.has_room_for(&line(), 2));
+        ref_image -= line();
+        assert_eq!(Some(1), ref_image.coverage(Point::new(0, 1)));
+    }
+
+    #[test]
+    fn test_ref_image_without_coverage() {
+        let line = PixLine::from(((Point::new(0, 0), Point::new(0, 2)), Rgb::WHITE, 0.5, 0.2));
+        let ref_image = RefImage::new(3, 3);
+        assert_eq!(None, ref_image.coverage(Point::new(0, 1)));
+        assert!(ref_image.has_room_for(&line, 0));
+    }
+
+    #[test]
+    fn test_round_matches_f64_round() {
+        [
+            0.0,
+            -0.0,
+            0.5,
+            1.5,
+            2.5,
+            -2.5,
+            0.49999999999999994,
+            -7.3,
+            1e16 + 2.0,
+            -1e30,
+            f64::INFINITY,
+            f64::NAN,
+        ]
+        .into_iter()
+        .for_each(|n| assert_eq!(n.round() as i64, round(n), "{n}"));
+    }
+
+    #[test]
+    fn test_score_change_on_add_is_accurate_for_other_channels() {
+        fn check<C: Channel>() {
+            let pix_line = || {
+                PixLine::from((
+                    (Point::new(0, 0), Point::new(101, 67)),
+                    Rgb::WHITE,
+                    1.0,
+                    0.3,
+                ))
+            };
+            let mut ref_image = RefImage::<C>::blank(150, 150).add_rgb(-Rgb::WHITE);
+            let initial_score = ref_image.score();
+            let predicted_score_change = ref_image.score_change_on_add(pix_line());
+            ref_image += pix_line();
+            assert_eq!(ref_image.score() - initial_score, predicted_score_change);
+        }
+        check::<[i16; 3]>();
+        check::<[i32; 3]>();
+        check::<[f32; 3]>();
+        check::<i16>();
+        check::<i32>();
+        check::<f32>();
+    }
+
+    #[test]
+    fn test_ref_image_channels_agree() {
+        let rgb = RefImage::new(2, 2).add_rgb(Rgb::new(-40, 100, 7));
+        let compact = RefImage::<[i16; 3]>::blank(2, 2).add_rgb(Rgb::new(-40, 100, 7));
+        assert_eq!(rgb.score(), compact.score());
+        assert_eq!(rgb.color(), compact.color());
+    }
+
     #[test]
     fn test_ref_image_width() {
         assert_eq!(5, RefImage::new(5, 1).width());
@@ -477,9 +1412,9 @@ mod test {
         // Create a ref image where each pixel is unique
         let mut ref_image = RefImage::new(400, 400);
         ref_image
-            .0
+            .grid
+            .cells
             .iter_mut()
-            .flatten()
             .enumerate()
             .for_each(|(i, rgb)| {
                 *rgb = Rgb::new(
@@ -490,9 +1425,9 @@ mod test {
             });
 
         let ref_pixels: Vec<_> = ref_image
-            .0
+            .grid
+            .cells
             .iter()
-            .flatten()
             .map(|Rgb { r, g, b }| [*r as u8, *g as u8, *b as u8, 255])
             .collect();
 
@@ -0,0 +1,315 @@
+use crate::error::StringArtError;
+use crate::geometry::Point;
+use crate::imagery::Rgb;
+use crate::segment;
+use crate::style::Data;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// One step of winding the finished work. Pins are numbered by their index in the data file's
+/// pin locations.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// Tie a new thread onto a pin
+    Start { pin: usize, rgb: Rgb },
+    /// Wind the thread from one pin to another, and back and forth for `count` strings in all
+    Wind {
+        string: usize,
+        from: usize,
+        to: usize,
+        count: usize,
+    },
+    /// Tie the thread off at a pin and cut it
+    TieOff { pin: usize },
+    /// Switch to a thread of another color
+    ChangeColor { rgb: Rgb },
+    /// Compare the work so far with the preview
+    Checkpoint { strings: usize },
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Step::Start { pin, rgb } => write!(f, "Tie a {} thread onto pin {}", rgb, pin),
+            Step::Wind {
+                string,
+                from,
+                to,
+                count: 1,
+            } => write!(f, "{:>6}: pin {} to pin {}", string + 1, from, to),
+            Step::Wind {
+                string,
+                from,
+                to,
+                count,
+            } => write!(
+                f,
+                "{:>6}: pin {} to pin {}, wrapping the chord {} times",
+                string + 1,
+                from,
+                to,
+                count
+            ),
+            Step::TieOff { pin } => write!(f, "Tie off at pin {} and cut the thread", pin),
+            Step::ChangeColor { rgb } => write!(f, "Change to the {} thread", rgb),
+            Step::Checkpoint { strings } => write!(
+                f,
+                "Checkpoint: {} strings are done. Take a photo and compare it with the preview",
+                strings
+            ),
+        }
+    }
+}
+
+/// Which markers to add between the winding steps
+pub struct Markers {
+    pub tie_offs: bool,
+    pub color_changes: bool,
+    pub checkpoint_every: Option<usize>,
+}
+
+impl Markers {
+    pub fn new(data: &Data) -> Self {
+        Self {
+            tie_offs: !data.args.no_tie_off_markers,
+            color_changes: !data.args.no_color_change_markers,
+            checkpoint_every: data.args.checkpoint_every.map(|n| n as usize),
+        }
+    }
+}
+
+/// Every step of winding the strings in threading order. Consecutive strings that share a pin
+/// are wound with one thread. Otherwise the thread is tied off and a new one is started.
+/// Consecutive strings along the same chord are one step.
+pub fn steps(data: &Data, markers: &Markers) -> Vec<Step> {
+    let pins: HashMap<Point, usize> = data
+        .pin_locations
+        .iter()
+        .enumerate()
+        .map(|(i, pin)| (*pin, i))
+        .collect();
+    let pin = |point: &Point| pins.get(point).copied().unwrap_or(usize::MAX);
+
+    let mut steps = Vec::new();
+    // The pin the thread is at and the thread's color
+    let mut thread: Option<(usize, Rgb)> = None;
+    for (string, (a, b, rgb), count) in segment::runs(data) {
+        let (a, b, rgb) = (pin(&a), pin(&b), &rgb);
+        let (from, to) = match thread {
+            Some((at, color)) if color == *rgb && at == b => (b, a),
+            Some((at, color)) if color == *rgb && at == a => (a, b),
+            _ => {
+                if let Some((at, color)) = thread {
+                    if markers.tie_offs {
+                        steps.push(Step::TieOff { pin: at });
+                    }
+                    if markers.color_changes && color != *rgb {
+                        steps.push(Step::ChangeColor { rgb: *rgb });
+                    }
+                }
+                steps.push(Step::Start { pin: a, rgb: *rgb });
+                (a, b)
+            }
+        };
+        steps.push(Step::Wind {
+            string,
+            from,
+            to,
+            count,
+        });
+        // Each string along the chord turns back at the other pin
+        thread = Some((if count % 2 == 1 { to } else { from }, *rgb));
+
+        let done = string + count;
+        if let Some(every) = markers.checkpoint_every {
+            if done / every > string / every && done < data.line_segments.len() {
+                steps.push(Step::Checkpoint { strings: done });
+            }
+        }
+    }
+    if let (Some((at, _)), true) = (thread, markers.tie_offs) {
+        steps.push(Step::TieOff { pin: at });
+    }
+    steps
+}
+
+/// Write the steps for winding the finished work as a text file
+pub fn write(data: &Data, filepath: &str) -> Result<(), StringArtError> {
+    let write = |error| StringArtError::write(filepath, error);
+    let mut writer = BufWriter::new(File::create(filepath).map_err(write)?);
+    writeln!(
+        writer,
+        "{} strings on {} pins. Pins are numbered as in the data file, starting from 0.",
+        data.line_segments.len(),
+        data.pin_locations.len()
+    )
+    .and_then(|_| match data.args.start_pin {
+        Some(pin) => writeln!(writer, "Start winding at pin {}.", pin),
+        None => Ok(()),
+    })
+    .and_then(|_| writeln!(writer))
+    .and_then(|_| {
+        steps(data, &Markers::new(data))
+            .iter()
+            .try_for_each(|step| writeln!(writer, "{}", step))
+    })
+    .and_then(|_| writer.flush())
+    .map_err(write)
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+
+    const RED: Rgb = Rgb { r: 255, g: 0, b: 0 };
+
+    fn data() -> Data {
+        let mut data = Data::example(&[]);
+        let pins = data.pin_locations.clone();
+        data.line_segments = vec![
+            (pins[0], pins[1], Rgb::WHITE),
+            (pins[2], pins[1], Rgb::WHITE),
+            (pins[0], pins[1], Rgb::WHITE),
+            (pins[1], pins[2], RED),
+        ];
+        data
+    }
+
+    fn markers(checkpoint_every: Option<usize>) -> Markers {
+        Markers {
+            tie_offs: true,
+            color_changes: true,
+            checkpoint_every,
+        }
+    }
+
+    #[test]
+    fn test_steps() {
+        assert_eq!(
+            vec![
+                Step::Start {
+                    pin: 0,
+                    rgb: Rgb::WHITE
+                },
+                Step::Wind {
+                    string: 0,
+                    from: 0,
+                    to: 1,
+                    count: 1
+                },
+                Step::Wind {
+                    string: 1,
+                    from: 1,
+                    to: 2,
+                    count: 1
+                },
+                Step::TieOff { pin: 2 },
+                Step::Start {
+                    pin: 0,
+                    rgb: Rgb::WHITE
+                },
+                Step::Wind {
+                    string: 2,
+                    from: 0,
+                    to: 1,
+                    count: 1
+                },
+                Step::TieOff { pin: 1 },
+                Step::ChangeColor { rgb: RED },
+                Step::Start { pin: 1, rgb: RED },
+                Step::Wind {
+                    string: 3,
+                    from: 1,
+                    to: 2,
+                    count: 1
+                },
+                Step::TieOff { pin: 2 },
+            ],
+            steps(&data(), &markers(None))
+        );
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let checkpoints: Vec<Step> = steps(&data(), &markers(Some(2)))
+            .into_iter()
+            .filter(|step| matches!(step, Step::Checkpoint { .. }))
+            .collect();
+        assert_eq!(vec![Step::Checkpoint { strings: 2 }], checkpoints);
+    }
+
+    #[test]
+    fn test_markers_can_be_left_out() {
+        let markers = Markers {
+            tie_offs: false,
+            color_changes: false,
+            checkpoint_every: None,
+        };
+        assert!(steps(&data(), &markers)
+            .iter()
+            .all(|step| matches!(step, Step::Start { .. } | Step::Wind { .. })));
+    }
+
+    #[test]
+    fn test_repeated_chords_are_one_step() {
+        let mut data = data();
+        let pins = data.pin_locations.clone();
+        data.line_segments = vec![
+            (pins[0], pins[1], Rgb::WHITE),
+            (pins[1], pins[0], Rgb::WHITE),
+            (pins[0], pins[1], Rgb::WHITE),
+            (pins[1], pins[2], Rgb::WHITE),
+        ];
+        let winds: Vec<Step> = steps(&data, &markers(None))
+            .into_iter()
+            .filter(|step| matches!(step, Step::Wind { .. }))
+            .collect();
+        assert_eq!(
+            vec![
+                Step::Wind {
+                    string: 0,
+                    from: 0,
+                    to: 1,
+                    count: 3
+                },
+                Step::Wind {
+                    string: 3,
+                    from: 1,
+                    to: 2,
+                    count: 1
+                },
+            ],
+            winds
+        );
+        assert_eq!(
+            "     1: pin 0 to pin 1, wrapping the chord 3 times",
+            winds[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_write() {
+        let filepath = std::env::temp_dir().join("string_art_instructions.txt");
+        let filepath = filepath.to_str().unwrap();
+        write(&data(), filepath).unwrap();
+        let text = std::fs::read_to_string(filepath).unwrap();
+        std::fs::remove_file(filepath).unwrap();
+        assert!(text.starts_with("4 strings on 3 pins."));
+        assert!(text.contains("\n     1: pin 0 to pin 1\n"));
+        assert!(text.contains("\nChange to the #FF0000 thread\n"));
+        assert!(!text.contains("Start winding"));
+    }
+
+    #[test]
+    fn test_write_start_pin() {
+        let filepath = std::env::temp_dir().join("string_art_instructions_start_pin.txt");
+        let filepath = filepath.to_str().unwrap();
+        let mut data = data();
+        data.args.start_pin = Some(2);
+        write(&data, filepath).unwrap();
+        let text = std::fs::read_to_string(filepath).unwrap();
+        std::fs::remove_file(filepath).unwrap();
+        assert!(text.contains("\nStart winding at pin 2.\n"));
+    }
+}
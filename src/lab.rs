@@ -0,0 +1,125 @@
+use crate::blend::{self, Blend};
+use crate::imagery::Rgb;
+use crate::serde::{Deserialize, Serialize};
+
+/// Scales the squared ΔE between two colors so black and white are as far apart as they are in
+/// RGB, and scores in either space are on about the same scale
+const SCORE_SCALE: f64 = 3.0 * 255.0 * 255.0 / (100.0 * 100.0);
+
+/// The D65 white point, in XYZ
+const WHITE: [f64; 3] = [0.95047, 1.0, 1.08883];
+
+/// The color space the error of each pixel is measured in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScoreSpace {
+    /// The squared difference of the red, green, and blue channels
+    Rgb,
+    /// The squared ΔE of CIELAB, which counts differences as they look
+    Lab,
+}
+
+impl core::str::FromStr for ScoreSpace {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "rgb" => Ok(ScoreSpace::Rgb),
+            "lab" => Ok(ScoreSpace::Lab),
+            _ => Err(format!("Invalid score space: \"{}\"", string)),
+        }
+    }
+}
+
+/// A color in CIELAB, where the distance between two colors is about how different they look
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lab {
+    l: f32,
+    a: f32,
+    b: f32,
+}
+
+impl Lab {
+    /// How far apart the colors look, scaled to score like the squared difference in RGB
+    pub fn score(self, other: Self) -> i64 {
+        let delta_e_squared = [self.l - other.l, self.a - other.a, self.b - other.b]
+            .iter()
+            .map(|d| f64::from(*d) * f64::from(*d))
+            .sum::<f64>();
+        (delta_e_squared * SCORE_SCALE).round() as i64
+    }
+
+    /// The color, in the space strings are blended in, in CIELAB
+    pub fn of(rgb: Rgb, blend: Blend) -> Self {
+        match blend {
+            Blend::Srgb => Self::from(rgb),
+            Blend::Linear => {
+                Self::from_linear([rgb.r, rgb.g, rgb.b].map(|c| c.clamp(0, 255) as f64))
+            }
+        }
+    }
+
+    /// The color in linear light, on a 0 to 255 scale, in CIELAB
+    fn from_linear([r, g, b]: [f64; 3]) -> Self {
+        let [r, g, b] = [r, g, b].map(|c| c / 255.0);
+        let xyz = [
+            0.4124 * r + 0.3576 * g + 0.1805 * b,
+            0.2126 * r + 0.7152 * g + 0.0722 * b,
+            0.0193 * r + 0.1192 * g + 0.9505 * b,
+        ];
+        let [fx, fy, fz] = [0, 1, 2].map(|i| f(xyz[i] / WHITE[i]));
+        Self {
+            l: (116.0 * fy - 16.0) as f32,
+            a: (500.0 * (fx - fy)) as f32,
+            b: (200.0 * (fy - fz)) as f32,
+        }
+    }
+}
+
+impl From<Rgb> for Lab {
+    /// The sRGB color in CIELAB, with channels outside 0 to 255 clamped
+    fn from(rgb: Rgb) -> Self {
+        Self::from_linear([rgb.r, rgb.g, rgb.b].map(blend::linear))
+    }
+}
+
+/// The companding function of CIELAB
+fn f(t: f64) -> f64 {
+    const DELTA: f64 = 6.0 / 29.0;
+    match t > DELTA.powi(3) {
+        true => t.cbrt(),
+        false => t / (3.0 * DELTA * DELTA) + 4.0 / 29.0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::channel::Channel;
+
+    fn close(expected: [f32; 3], lab: Lab) -> bool {
+        [lab.l, lab.a, lab.b]
+            .iter()
+            .zip(expected)
+            .all(|(actual, expected)| (actual - expected).abs() < 0.1)
+    }
+
+    #[test]
+    fn test_known_colors() {
+        assert!(close([0.0, 0.0, 0.0], Lab::from(Rgb::BLACK)));
+        assert!(close([100.0, 0.0, 0.0], Lab::from(Rgb::WHITE)));
+        let red = Lab::from(Rgb::new(255, 0, 0));
+        assert!(close([53.24, 80.09, 67.20], red), "{:?}", red);
+    }
+
+    #[test]
+    fn test_black_and_white_score_like_rgb() {
+        let score = Lab::from(Rgb::BLACK).score(Lab::from(Rgb::WHITE));
+        assert!((score - Rgb::WHITE.score()).abs() < 100, "{}", score);
+    }
+
+    #[test]
+    fn test_green_differences_count_more_than_blue() {
+        let grey = Rgb::new(128, 128, 128);
+        let score = |change: Rgb| Lab::from(grey).score(Lab::from(grey + change));
+        assert!(score(Rgb::new(0, 20, 0)) > score(Rgb::new(0, 0, 20)));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::imagery::Rgb;
+use crate::segment;
+use crate::serde::Serialize;
+use crate::style::Data;
+
+/// How many equal bins the histogram splits lengths into, from 0 up to the image's diagonal
+const HISTOGRAM_BINS: usize = 10;
+
+/// The spread of string lengths of one color, in pixels. Many long strings make a result look
+/// like a web of chords, and limiting them is a matter of tuning the constraints.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LengthStats {
+    pub rgb: Rgb,
+    pub strings: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub p10: f64,
+    pub median: f64,
+    pub p90: f64,
+    /// How wide each bin of the histogram is
+    pub bin_width: f64,
+    /// How many strings fall in each bin, shortest first
+    pub histogram: Vec<usize>,
+}
+
+/// The length stats of each color that has strings, in the order the foreground colors were
+/// given
+pub fn length_stats(data: &Data) -> Vec<LengthStats> {
+    let diagonal = (data.image_width as f64).hypot(data.image_height as f64);
+    let bin_width = diagonal / HISTOGRAM_BINS as f64;
+    let segments: Vec<segment::Segment> = segment::segments(data).collect();
+    let mut colors = data.args.foreground_colors.clone();
+    segments.iter().for_each(|s| {
+        if !colors.contains(&s.rgb) {
+            colors.push(s.rgb);
+        }
+    });
+
+    colors
+        .into_iter()
+        .filter_map(|rgb| {
+            let mut lengths: Vec<f64> = segments
+                .iter()
+                .filter(|s| s.rgb == rgb)
+                .flat_map(|s| std::iter::repeat_n(s.length_px, s.count))
+                .collect();
+            if lengths.is_empty() {
+                return None;
+            }
+            lengths.sort_by(f64::total_cmp);
+            let mut histogram = vec![0; HISTOGRAM_BINS];
+            lengths.iter().for_each(|length| {
+                histogram[usize::min(HISTOGRAM_BINS - 1, (length / bin_width) as usize)] += 1
+            });
+            Some(LengthStats {
+                rgb,
+                strings: lengths.len(),
+                min: lengths[0],
+                max: lengths[lengths.len() - 1],
+                mean: lengths.iter().sum::<f64>() / lengths.len() as f64,
+                p10: percentile(&lengths, 10.0),
+                median: percentile(&lengths, 50.0),
+                p90: percentile(&lengths, 90.0),
+                bin_width,
+                histogram,
+            })
+        })
+        .collect()
+}
+
+/// The nearest-rank percentile of sorted values
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+impl std::fmt::Display for LengthStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} strings, {:.1} to {:.1} px, mean {:.1}, p10 {:.1}, median {:.1}, p90 {:.1}",
+            self.rgb, self.strings, self.min, self.max, self.mean, self.p10, self.median, self.p90
+        )
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::geometry::Point;
+
+    #[test]
+    fn test_percentile() {
+        let sorted: Vec<f64> = (1..=10).map(f64::from).collect();
+        assert_eq!(1.0, percentile(&sorted, 10.0));
+        assert_eq!(5.0, percentile(&sorted, 50.0));
+        assert_eq!(9.0, percentile(&sorted, 90.0));
+        assert_eq!(7.0, percentile(&[7.0], 90.0));
+    }
+
+    #[test]
+    fn test_length_stats() {
+        let mut data = Data::example(&[]);
+        let p = Point::new;
+        data.line_segments = vec![
+            (p(0, 0), p(9, 0), Rgb::WHITE),
+            (p(0, 0), p(0, 3), Rgb::WHITE),
+            (p(0, 9), p(9, 0), Rgb::WHITE),
+        ];
+        let stats = length_stats(&data);
+
+        assert_eq!(1, stats.len());
+        let stats = &stats[0];
+        assert_eq!(
+            (3, 3.0, 9f64.hypot(9.0)),
+            (stats.strings, stats.min, stats.max)
+        );
+        assert_eq!(9.0, stats.median);
+        // The diagonal of the 10x10 image is about 14.1, so the bins are about 1.41 wide
+        assert_eq!(3, stats.histogram.iter().sum::<usize>());
+        assert_eq!(1, stats.histogram[2]);
+        assert_eq!(1, stats.histogram[6]);
+        assert_eq!(1, stats.histogram[9]);
+        assert_eq!(
+            "#FFFFFF: 3 strings, 3.0 to 12.7 px, mean 8.2, p10 3.0, median 9.0, p90 12.7",
+            stats.to_string()
+        );
+    }
+
+    #[test]
+    fn test_colors_without_strings_are_left_out() {
+        let mut data = Data::example(&[]);
+        data.line_segments.clear();
+        assert_eq!(Vec::<LengthStats>::new(), length_stats(&data));
+    }
+}
@@ -0,0 +1,206 @@
+//! Transform an image into string art. This is the library behind the `string_art` command,
+//! for creating string art from another Rust program without going through files.
+//!
+//! `generate` covers the common case: it takes an image and the same options as the command
+//! line, and returns the finished `Data`, or an `error::StringArtError` saying why it couldn't.
+//! The pipeline it runs is also available in parts: `pins` places the pins,
+//! `style::color_on_custom` chooses the strings, `imagery::RefImage` renders and scores them,
+//! and `optimum` finds the best strings to add or remove.
+//!
+//! `generate` and the command itself need the default `cli` feature, which brings in clap.
+//! Without it, `generate_from_args` takes arguments that are already filled in. Scoring the
+//! strings on every core needs rayon, which is behind the `parallel` feature; `cli` turns it
+//! on, and without it the strings are scored on one thread.
+
+#[cfg(feature = "cli")]
+extern crate clap;
+extern crate flate2;
+extern crate image;
+#[cfg(unix)]
+extern crate libc;
+extern crate rand;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+extern crate serde;
+extern crate sha2;
+extern crate zip;
+
+pub mod animation;
+pub mod archive;
+pub mod arrangements;
+pub mod auto_color;
+#[cfg(feature = "cli")]
+pub mod batch;
+pub mod blend;
+pub mod build_time;
+pub mod candidate;
+pub mod channel;
+pub mod cli_app;
+pub mod contact_sheet;
+pub mod control;
+#[cfg(feature = "cli")]
+pub mod diff;
+#[cfg(feature = "cli")]
+pub mod edit;
+pub mod ensemble;
+pub mod error;
+pub mod frame_sink;
+pub mod geometry;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod imagery;
+pub mod instructions;
+pub mod lab;
+pub mod length_stats;
+pub mod log;
+pub mod memory;
+pub mod metrics;
+pub mod milestones;
+pub mod morph;
+#[cfg(feature = "node")]
+pub mod node;
+pub mod optimizer;
+pub mod optimum;
+#[cfg(feature = "cli")]
+pub mod overwrite;
+pub mod parallel;
+pub mod pin_stats;
+pub mod pins;
+pub mod preprocess;
+pub mod provenance;
+pub mod realistic;
+#[cfg(feature = "cli")]
+pub mod replay;
+pub mod resolution;
+pub mod segment;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "cli")]
+pub mod string_art;
+pub mod style;
+pub mod summary;
+pub mod svg;
+pub mod sweep;
+pub mod template;
+pub mod term;
+pub mod travel;
+pub mod util;
+#[cfg(feature = "cli")]
+pub mod validate;
+pub mod warnings;
+
+#[cfg(feature = "cli")]
+use clap::Parser;
+use cli_app::Args;
+#[cfg(feature = "cli")]
+use cli_app::Cli;
+use error::StringArtError;
+#[cfg(feature = "cli")]
+use image::DynamicImage;
+use optimum::Constraints;
+use style::Data;
+
+/// Create string art from `image`, with `options` given as they would be on the command line,
+/// like `["--pin-count", "200", "--max-strings", "2000"]`. The image is used as it is, without
+/// the preprocessing options, and output files are only written if their options are given.
+/// Masks, weight maps, and the canvas image are read from their files.
+#[cfg(feature = "cli")]
+pub fn generate(image: DynamicImage, options: &[&str]) -> Result<Data, StringArtError> {
+    let cli = Cli::try_parse_from(["string_art", "--input-filepath", ""].iter().chain(options))?;
+    generate_from_args(Args::with_image(cli, image))
+}
+
+/// Create string art from arguments that are already filled in, such as the `args` of a saved
+/// data file with `image` set. Masks, weight maps, and the canvas image are read from their
+/// files.
+pub fn generate_from_args(args: Args) -> Result<Data, StringArtError> {
+    let args = args.with_images_from_options()?;
+    let pins = pins::from_args(&args, args.image.width(), args.image.height());
+    style::color_on_custom(pins, args, Vec::new(), &Constraints::default(), Vec::new())
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let mut image = image::RgbImage::new(20, 20);
+        (0..20).for_each(|i| image.put_pixel(i, i, image::Rgb([255; 3])));
+        let data = generate(
+            DynamicImage::ImageRgb8(image),
+            &["--pin-count", "8", "--max-strings", "20"],
+        )
+        .unwrap();
+        assert_eq!(8, data.pin_locations.len());
+        assert!((1..=20).contains(&data.line_segments.len()));
+        assert!(data.final_score < data.initial_score);
+    }
+
+    #[test]
+    fn test_generate_rejects_invalid_options() {
+        assert!(matches!(
+            generate(DynamicImage::new_rgb8(4, 4), &["--pin-count", "many"]),
+            Err(StringArtError::Args(_))
+        ));
+    }
+
+    #[test]
+    fn test_generate_reports_unwritable_event_log() {
+        assert!(matches!(
+            generate(
+                DynamicImage::new_rgb8(4, 4),
+                &[
+                    "--event-log-filepath",
+                    "/nonexistent/string_art_events.jsonl"
+                ]
+            ),
+            Err(StringArtError::Write { .. })
+        ));
+    }
+
+    #[test]
+    fn test_generate_reads_the_mask() {
+        let filepath = std::env::temp_dir().join("string_art_generate_mask.png");
+        let filepath = filepath.to_str().unwrap();
+        let mut image = image::RgbImage::new(20, 20);
+        (0..20).for_each(|i| image.put_pixel(i, i, image::Rgb([255; 3])));
+        // Black everywhere, so every string is masked
+        image::GrayImage::new(20, 20).save(filepath).unwrap();
+        let data = generate(
+            DynamicImage::ImageRgb8(image),
+            &[
+                "--pin-count",
+                "8",
+                "--max-strings",
+                "20",
+                "--mask-filepath",
+                filepath,
+            ],
+        );
+        std::fs::remove_file(filepath).unwrap();
+        assert!(data.unwrap().line_segments.is_empty());
+    }
+
+    #[test]
+    fn test_generate_reports_missing_files() {
+        for option in [
+            "--mask-filepath",
+            "--exclusion-mask",
+            "--weight-filepath",
+            "--canvas-image",
+        ] {
+            assert!(
+                matches!(
+                    generate(
+                        DynamicImage::new_rgb8(4, 4),
+                        &[option, "/nonexistent/string_art.png"]
+                    ),
+                    Err(StringArtError::Read { .. })
+                ),
+                "{}",
+                option
+            );
+        }
+    }
+}
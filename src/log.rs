@@ -0,0 +1,233 @@
+use crate::cli_app::Args;
+use crate::error::StringArtError;
+use crate::geometry::Point;
+use crate::imagery::Rgb;
+use crate::serde::Serialize;
+use crate::term;
+use crate::term::Level;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A string that was added or removed, as written to the event log
+#[derive(Serialize)]
+struct Event {
+    event: &'static str,
+    strings: usize,
+    score_change: i64,
+    a: Point,
+    b: Point,
+    rgb: Rgb,
+}
+
+/// Reports each string that is added or removed. Every change is written to the event log,
+/// while verbose mode only prints as often as --log-every and --log-interval allow.
+pub struct Log {
+    verbosity: u8,
+    background_color: Rgb,
+    every: u64,
+    interval: Option<Duration>,
+    changes: AtomicU64,
+    last_printed: Mutex<Option<Instant>>,
+    events: Option<Mutex<Events>>,
+}
+
+/// The event log, and the first error writing to it. Once writing fails, no more events are
+/// written, and the error is returned by `Log::finish`.
+struct Events {
+    filepath: String,
+    writer: BufWriter<File>,
+    error: Option<std::io::Error>,
+}
+
+impl Log {
+    /// Creates the event log, if --event-log-filepath is given
+    pub fn new(args: &Args) -> Result<Self, StringArtError> {
+        let events = match &args.event_log_filepath {
+            Some(filepath) => Some(Mutex::new(Events {
+                filepath: filepath.clone(),
+                writer: BufWriter::new(
+                    File::create(filepath)
+                        .map_err(|error| StringArtError::write(filepath, error))?,
+                ),
+                error: None,
+            })),
+            None => None,
+        };
+        Ok(Self {
+            events,
+            ..Self::without_events(args)
+        })
+    }
+
+    /// A log that only prints, for strings that don't belong in the event log
+    pub fn without_events(args: &Args) -> Self {
+        Self {
+            verbosity: args.verbosity,
+            background_color: args.background_color,
+            every: u64::max(1, args.log_every),
+            interval: args.log_interval.map(Duration::from_secs_f64),
+            changes: AtomicU64::new(0),
+            last_printed: Mutex::new(None),
+            events: None,
+        }
+    }
+
+    /// Write out the rest of the event log, or say why it couldn't all be written
+    pub fn finish(&self) -> Result<(), StringArtError> {
+        let Some(events) = &self.events else {
+            return Ok(());
+        };
+        let mut events = events.lock().unwrap();
+        let error = match events.error.take() {
+            Some(error) => error,
+            None => match events.writer.flush() {
+                Ok(()) => return Ok(()),
+                Err(error) => error,
+            },
+        };
+        Err(StringArtError::write(&events.filepath, error))
+    }
+
+    pub fn add(&self, strings: usize, score_change: i64, a: Point, b: Point, rgb: Rgb) {
+        self.record("add", strings, score_change, a, b, rgb)
+    }
+
+    pub fn sub(&self, strings: usize, score_change: i64, a: Point, b: Point, rgb: Rgb) {
+        self.record("sub", strings, score_change, a, b, rgb)
+    }
+
+    fn record(
+        &self,
+        event: &'static str,
+        strings: usize,
+        score_change: i64,
+        a: Point,
+        b: Point,
+        rgb: Rgb,
+    ) {
+        let rgb = rgb + self.background_color;
+        if let Some(events) = &self.events {
+            let event = Event {
+                event,
+                strings,
+                score_change,
+                a,
+                b,
+                rgb,
+            };
+            let mut events = events.lock().unwrap();
+            if events.error.is_none() {
+                let written = serde_json::to_writer(&mut events.writer, &event)
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| writeln!(events.writer));
+                events.error = written.err();
+            }
+        }
+        if self.should_print() {
+            let sign = if event == "add" { '+' } else { '-' };
+            term::info(
+                self.verbosity,
+                &format!(
+                    "[{:>6}]:   score change: {:>10}     {}{}  {} to {} with {}",
+                    strings, score_change, sign, event, a, b, rgb
+                ),
+            );
+        }
+    }
+
+    fn should_print(&self) -> bool {
+        if !Level::Info.shown_at(self.verbosity) {
+            return false;
+        }
+        let changes = self.changes.fetch_add(1, Ordering::Relaxed) + 1;
+        if !changes.is_multiple_of(self.every) {
+            return false;
+        }
+        match self.interval {
+            None => true,
+            Some(interval) => {
+                let mut last_printed = self.last_printed.lock().unwrap();
+                let now = Instant::now();
+                let due = last_printed.is_none_or(|last| now - last >= interval);
+                if due {
+                    *last_printed = Some(now);
+                }
+                due
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use clap::Parser;
+
+    fn log(extra_args: &[&str]) -> Log {
+        let cli = crate::cli_app::Cli::parse_from(
+            ["string_art", "--input-filepath", "in.png"]
+                .iter()
+                .chain(extra_args),
+        );
+        Log::new(&Args::with_image(cli, image::DynamicImage::new_rgb8(1, 1))).unwrap()
+    }
+
+    #[test]
+    fn test_quiet_log_never_prints() {
+        let log = log(&[]);
+        assert!(!(0..10).any(|_| log.should_print()));
+    }
+
+    #[test]
+    fn test_log_every() {
+        let log = log(&["-v", "--log-every", "3"]);
+        let printed: Vec<bool> = (0..6).map(|_| log.should_print()).collect();
+        assert_eq!(vec![false, false, true, false, false, true], printed);
+    }
+
+    #[test]
+    fn test_log_interval() {
+        let log = log(&["-v", "--log-interval", "60"]);
+        let printed: Vec<bool> = (0..3).map(|_| log.should_print()).collect();
+        assert_eq!(vec![true, false, false], printed);
+    }
+
+    #[test]
+    fn test_event_log_has_every_change() {
+        let filepath = std::env::temp_dir().join("string_art_events.jsonl");
+        let filepath = filepath.to_str().unwrap();
+        {
+            let log = log(&["--log-every", "100", "--event-log-filepath", filepath]);
+            log.add(1, -50, Point::new(0, 0), Point::new(3, 4), Rgb::WHITE);
+            log.sub(0, -7, Point::new(0, 0), Point::new(3, 4), Rgb::WHITE);
+            log.finish().unwrap();
+        }
+        let events = std::fs::read_to_string(filepath).unwrap();
+        std::fs::remove_file(filepath).unwrap();
+        let lines: Vec<serde_json::Value> = events
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(2, lines.len());
+        assert_eq!("add", lines[0]["event"]);
+        assert_eq!(-7, lines[1]["score_change"]);
+        assert_eq!(3, lines[1]["b"]["x"]);
+        assert_eq!(255, lines[1]["rgb"]["r"]);
+    }
+
+    #[test]
+    fn test_event_log_that_cant_be_created() {
+        let cli = crate::cli_app::Cli::parse_from([
+            "string_art",
+            "--input-filepath",
+            "in.png",
+            "--event-log-filepath",
+            "/nonexistent/string_art_events.jsonl",
+        ]);
+        let args = Args::with_image(cli, image::DynamicImage::new_rgb8(1, 1));
+        assert!(matches!(Log::new(&args), Err(StringArtError::Write { .. })));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::cli_app::Args;
+
+/// Bytes per pixel of each working image, which has three 64-bit channels
+const REF_IMAGE_BYTES_PER_PIXEL: u64 = 24;
+
+/// Bytes per pixel of each working image's coverage count, kept when --max-overlap is given
+const COVERAGE_BYTES_PER_PIXEL: u64 = 4;
+
+/// Bytes per pixel of each working image with --float-accumulation, which has three 32-bit
+/// floating point channels instead
+const FLOAT_REF_IMAGE_BYTES_PER_PIXEL: u64 = 12;
+
+/// Working images kept during a run: the one being scored, the blank it starts from, and one
+/// for rendering the results
+const REF_IMAGES: u64 = 3;
+
+/// Bytes per pixel of the decoded copies of the input image
+const INPUT_BYTES_PER_PIXEL: u64 = 8;
+
+/// Bytes for each candidate string scored while looking for the best ones: the string and its
+/// score
+const CANDIDATE_BYTES: u64 = 48;
+
+/// Memory used no matter the image or pins
+const BASE_BYTES: u64 = 16 * 1024 * 1024;
+
+pub const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// The most memory the strings between every pair of pins may take when traced ahead of time.
+/// With more pins or a larger image, each string is traced as it's scored instead.
+const MAX_TRACE_BYTES: u64 = 1024 * BYTES_PER_MB;
+
+/// Bytes for each pixel a traced string crosses: the pixel, and how many steps of the string
+/// land in it and how far along the string they are
+const TRACE_BYTES_PER_PIXEL: u64 = 16;
+
+/// Bytes for each traced string besides its pixels
+const TRACE_BYTES_PER_STRING: u64 = 48;
+
+/// How much memory may go to tracing the strings ahead of time. With --max-memory-mb, that's
+/// whatever the rest of the run leaves of the budget.
+pub fn trace_budget(args: &Args) -> u64 {
+    match args.max_memory_mb {
+        Some(budget_mb) => {
+            let required = Estimate::new(
+                args.pin_count,
+                args.foreground_colors.len(),
+                args.max_overlap.is_some(),
+                args.float_accumulation,
+            )
+            .required_bytes(args.image.width(), args.image.height());
+            u64::min(
+                MAX_TRACE_BYTES,
+                (budget_mb * BYTES_PER_MB).saturating_sub(required),
+            )
+        }
+        None => MAX_TRACE_BYTES,
+    }
+}
+
+/// What a run needs, split into the part that grows with the image and the part that doesn't,
+/// and what tracing the strings ahead of time adds when there's room for it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub bytes_per_pixel: u64,
+    pub fixed_bytes: u64,
+    /// How many strings there are between pairs of pins, to be traced
+    pub strings: u64,
+}
+
+impl Estimate {
+    /// Every pair of pins is scored in each color at once, so the candidates grow with the
+    /// square of the pin count
+    pub fn new(pin_count: u32, colors: usize, coverage: bool, float_accumulation: bool) -> Self {
+        let pins = pin_count as u64;
+        let coverage = if coverage {
+            COVERAGE_BYTES_PER_PIXEL
+        } else {
+            0
+        };
+        let ref_image = if float_accumulation {
+            FLOAT_REF_IMAGE_BYTES_PER_PIXEL
+        } else {
+            REF_IMAGE_BYTES_PER_PIXEL
+        };
+        let strings = pins * pins.saturating_sub(1) / 2;
+        Self {
+            bytes_per_pixel: REF_IMAGES * (ref_image + coverage) + INPUT_BYTES_PER_PIXEL,
+            fixed_bytes: BASE_BYTES + strings * colors as u64 * CANDIDATE_BYTES,
+            strings,
+        }
+    }
+
+    /// Everything a run takes, including the traces if they're kept
+    pub fn bytes(&self, width: u32, height: u32) -> u64 {
+        self.required_bytes(width, height) + self.trace_bytes(width, height)
+    }
+
+    /// What a run can't do without. The traces only save time, so they're left out.
+    pub fn required_bytes(&self, width: u32, height: u32) -> u64 {
+        self.fixed_bytes + width as u64 * height as u64 * self.bytes_per_pixel
+    }
+
+    /// What tracing every string ahead of time takes, or nothing if that's more than the
+    /// traces may take. No string crosses more pixels than the longer side of the image has.
+    pub fn trace_bytes(&self, width: u32, height: u32) -> u64 {
+        let pixels = u32::max(width, height) as u64 + 1;
+        let bytes = self.strings * (pixels * TRACE_BYTES_PER_PIXEL + TRACE_BYTES_PER_STRING);
+        match bytes <= MAX_TRACE_BYTES {
+            true => bytes,
+            false => 0,
+        }
+    }
+
+    /// The largest size with the same aspect ratio whose required bytes fit in the budget, or
+    /// `None` if the image already fits. Traces are given up before the image is scaled down.
+    /// Fails when even the parts that don't depend on the image are over budget.
+    pub fn fit(&self, width: u32, height: u32, budget: u64) -> Result<Option<(u32, u32)>, u64> {
+        if self.required_bytes(width, height) <= budget {
+            return Ok(None);
+        }
+        if self.fixed_bytes >= budget {
+            return Err(self.fixed_bytes);
+        }
+        let pixels = (budget - self.fixed_bytes) / self.bytes_per_pixel;
+        let scale = (pixels as f64 / (width as f64 * height as f64)).sqrt();
+        let scaled = |side: u32| u32::max(1, (side as f64 * scale).floor() as u32);
+        Ok(Some((scaled(width), scaled(height))))
+    }
+}
+
+/// The most memory the process has used so far, where the platform can report it
+#[cfg(unix)]
+pub fn peak_bytes() -> Option<u64> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // SAFETY: getrusage fills in the struct it is given and touches nothing else
+    let result = unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) };
+    if result != 0 {
+        return None;
+    }
+    // SAFETY: getrusage succeeded, so the struct is filled in
+    let max_rss = unsafe { usage.assume_init() }.ru_maxrss as u64;
+    // macOS reports bytes, and other platforms kilobytes
+    if cfg!(target_os = "macos") {
+        Some(max_rss)
+    } else {
+        Some(max_rss * 1024)
+    }
+}
+
+#[cfg(not(unix))]
+pub fn peak_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let estimate = Estimate::new(200, 2, false, false);
+        assert_eq!(80, estimate.bytes_per_pixel);
+        assert_eq!(BASE_BYTES + 19900 * 2 * 48, estimate.fixed_bytes);
+        assert_eq!(
+            estimate.fixed_bytes + 100 * 80,
+            estimate.required_bytes(10, 10)
+        );
+        assert_eq!(19900 * (11 * 16 + 48), estimate.trace_bytes(10, 10));
+        assert_eq!(
+            estimate.required_bytes(10, 10) + estimate.trace_bytes(10, 10),
+            estimate.bytes(10, 10)
+        );
+        assert_eq!(0, estimate.trace_bytes(100_000, 10));
+        assert_eq!(92, Estimate::new(200, 2, true, false).bytes_per_pixel);
+        assert_eq!(44, Estimate::new(200, 2, false, true).bytes_per_pixel);
+    }
+
+    #[test]
+    fn test_fit() {
+        let estimate = Estimate {
+            bytes_per_pixel: 10,
+            fixed_bytes: 1000,
+            strings: 1,
+        };
+        assert_eq!(Ok(None), estimate.fit(10, 10, 2000));
+        // 4000 bytes leaves room for 300 pixels, which is 20x15 at a ratio of 4:3
+        assert_eq!(Ok(Some((20, 15))), estimate.fit(400, 300, 4000));
+        assert_eq!(Err(1000), estimate.fit(10, 10, 1000));
+    }
+
+    #[test]
+    fn test_peak_bytes() {
+        if cfg!(unix) {
+            assert!(peak_bytes().is_some_and(|bytes| bytes > 0));
+        }
+    }
+
+    #[test]
+    fn test_trace_budget_is_what_the_budget_leaves() {
+        let mut args = crate::style::Data::example(&[]).args;
+        assert_eq!(MAX_TRACE_BYTES, trace_budget(&args));
+        args.max_memory_mb = Some(64_000);
+        assert_eq!(MAX_TRACE_BYTES, trace_budget(&args));
+        args.max_memory_mb = Some(17);
+        assert!((1..BYTES_PER_MB).contains(&trace_budget(&args)));
+        args.max_memory_mb = Some(1);
+        assert_eq!(0, trace_budget(&args));
+    }
+}
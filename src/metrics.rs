@@ -0,0 +1,116 @@
+use crate::image::{DynamicImage, GrayImage};
+use crate::serde::{Deserialize, Serialize};
+
+/// Side length of the windows SSIM compares
+const SSIM_WINDOW: u32 = 8;
+
+/// Distance between neighboring SSIM windows
+const SSIM_STRIDE: u32 = 4;
+
+/// How closely the finished work resembles the target image
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Quality {
+    /// Peak signal-to-noise ratio across all color channels, in decibels. Higher is better.
+    pub psnr: f64,
+    /// Structural similarity of the luminance, from -1 to 1. Higher is better.
+    pub ssim: f64,
+}
+
+impl Quality {
+    pub fn between(rendered: &DynamicImage, target: &DynamicImage) -> Self {
+        Self {
+            psnr: psnr(rendered, target),
+            ssim: ssim(&rendered.to_luma8(), &target.to_luma8()),
+        }
+    }
+}
+
+fn psnr(a: &DynamicImage, b: &DynamicImage) -> f64 {
+    let (a, b) = (a.to_rgb8(), b.to_rgb8());
+    let count = a.as_raw().len() as f64;
+    let squared_error: f64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(a, b)| (f64::from(*a) - f64::from(*b)).powi(2))
+        .sum();
+    10.0 * (255.0 * 255.0 / (squared_error / count)).log10()
+}
+
+fn ssim(a: &GrayImage, b: &GrayImage) -> f64 {
+    let c1 = (0.01 * 255.0_f64).powi(2);
+    let c2 = (0.03 * 255.0_f64).powi(2);
+    let (width, height) = a.dimensions();
+    let window = SSIM_WINDOW.min(width).min(height);
+    let starts = |length: u32| (0..=length - window).step_by(SSIM_STRIDE as usize);
+
+    let scores: Vec<f64> = starts(height)
+        .flat_map(|y| starts(width).map(move |x| (x, y)))
+        .map(|(x0, y0)| {
+            let pixels = (y0..y0 + window).flat_map(|y| (x0..x0 + window).map(move |x| (x, y)));
+            let pairs: Vec<(f64, f64)> = pixels
+                .map(|(x, y)| {
+                    (
+                        f64::from(a.get_pixel(x, y).0[0]),
+                        f64::from(b.get_pixel(x, y).0[0]),
+                    )
+                })
+                .collect();
+            let n = pairs.len() as f64;
+            let mean_a = pairs.iter().map(|(a, _)| a).sum::<f64>() / n;
+            let mean_b = pairs.iter().map(|(_, b)| b).sum::<f64>() / n;
+            let (var_a, var_b, covariance) =
+                pairs.iter().fold((0.0, 0.0, 0.0), |(va, vb, cov), (a, b)| {
+                    let (da, db) = (a - mean_a, b - mean_b);
+                    (va + da * da / n, vb + db * db / n, cov + da * db / n)
+                });
+            ((2.0 * mean_a * mean_b + c1) * (2.0 * covariance + c2))
+                / ((mean_a * mean_a + mean_b * mean_b + c1) * (var_a + var_b + c2))
+        })
+        .collect();
+    scores.iter().sum::<f64>() / scores.len() as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gradient() -> DynamicImage {
+        let mut i = DynamicImage::new_rgb8(16, 16).to_rgb8();
+        i.enumerate_pixels_mut()
+            .for_each(|(x, y, p)| *p = image::Rgb([(x * 16) as u8, (y * 16) as u8, 0]));
+        DynamicImage::ImageRgb8(i)
+    }
+
+    #[test]
+    fn test_identical_images() {
+        let quality = Quality::between(&gradient(), &gradient());
+        assert_eq!(f64::INFINITY, quality.psnr);
+        assert!((quality.ssim - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_psnr_of_uniform_difference() {
+        let black = DynamicImage::new_rgb8(4, 4);
+        let mut gray = black.to_rgb8();
+        gray.pixels_mut().for_each(|p| *p = image::Rgb([255; 3]));
+        assert_eq!(0.0, psnr(&black, &DynamicImage::ImageRgb8(gray)));
+    }
+
+    #[test]
+    fn test_ssim_is_lower_for_unrelated_images() {
+        let inverted = {
+            let mut i = gradient();
+            i.invert();
+            i
+        };
+        let quality = Quality::between(&gradient(), &inverted);
+        assert!(quality.ssim < 0.0, "ssim was {}", quality.ssim);
+    }
+
+    #[test]
+    fn test_ssim_of_tiny_images() {
+        let image = DynamicImage::new_rgb8(2, 3);
+        assert!((Quality::between(&image, &image).ssim - 1.0).abs() < 1e-9);
+    }
+}
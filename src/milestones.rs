@@ -0,0 +1,151 @@
+use crate::cli_app::Args;
+use crate::contact_sheet;
+use crate::image::RgbaImage;
+use crate::imagery::LineSegment;
+use crate::imagery::RefImage;
+use crate::imagery::Rgb;
+
+/// Snapshots of the strings taken the first time the run reaches each string-count milestone.
+/// Strings are added in batches, so a snapshot may have a few more strings than its milestone.
+pub struct Milestones {
+    remaining: Vec<usize>,
+    tiles: Vec<(Vec<String>, RgbaImage)>,
+}
+
+impl Milestones {
+    pub fn new(milestones: &[usize]) -> Self {
+        let mut remaining = milestones.to_vec();
+        remaining.sort_unstable();
+        remaining.dedup();
+        Self {
+            remaining,
+            tiles: Vec::new(),
+        }
+    }
+
+    /// Take a snapshot if the strings have reached the next milestone
+    pub fn observe(&mut self, args: &Args, line_segments: &[LineSegment], width: u32, height: u32) {
+        let reached = self
+            .remaining
+            .iter()
+            .take_while(|milestone| **milestone <= line_segments.len())
+            .count();
+        if reached == 0 {
+            return;
+        }
+        self.remaining.drain(..reached);
+        self.tiles.push((
+            vec![format!("{} strings", line_segments.len())],
+            snapshot(args, line_segments, width, height),
+        ));
+    }
+
+    /// Arrange the snapshots in a grid, ending with the finished strings unless the last
+    /// milestone already shows them
+    pub fn contact_sheet(
+        mut self,
+        args: &Args,
+        line_segments: &[LineSegment],
+        width: u32,
+        height: u32,
+    ) -> RgbaImage {
+        let finished = format!("{} strings", line_segments.len());
+        if self
+            .tiles
+            .last()
+            .is_none_or(|(label, _)| label[0] != finished)
+        {
+            self.tiles.push((
+                vec![finished, "finished".to_owned()],
+                snapshot(args, line_segments, width, height),
+            ));
+        }
+        let columns = (self.tiles.len() as f64).sqrt().ceil() as usize;
+        contact_sheet::contact_sheet(&self.tiles, columns)
+    }
+}
+
+/// Render the strings on the background. The strings' colors are relative to the background.
+pub fn snapshot(args: &Args, line_segments: &[LineSegment], width: u32, height: u32) -> RgbaImage {
+    let lines = line_segments
+        .iter()
+        .map(|(a, b, rgb)| {
+            (
+                (*a, *b),
+                args.shade(*rgb),
+                args.step_size,
+                args.string_alpha,
+            )
+        })
+        .collect();
+    RefImage::<Rgb>::from((&lines, width, height))
+        .add_rgb(args.blend.decode(args.background_color))
+        .encoded(args.blend)
+        .color()
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::cli_app::Cli;
+    use crate::geometry::Point;
+    use crate::imagery::Rgb;
+    use clap::Parser;
+
+    fn args() -> Args {
+        let cli = Cli::parse_from([
+            "string_art",
+            "-i",
+            "in.png",
+            "--milestones",
+            "4,2,8",
+            "--milestones-filepath",
+            "sheet.png",
+        ]);
+        Args::with_image(cli, image::DynamicImage::new_rgb8(10, 10))
+    }
+
+    fn strings(count: usize) -> Vec<LineSegment> {
+        (0..count)
+            .map(|i| (Point::new(0, i as u32 % 10), Point::new(9, 0), Rgb::WHITE))
+            .collect()
+    }
+
+    fn labels(milestones: &Milestones) -> Vec<String> {
+        milestones.tiles.iter().map(|(l, _)| l[0].clone()).collect()
+    }
+
+    #[test]
+    fn test_observe_each_milestone_once() {
+        let args = args();
+        let mut milestones = Milestones::new(&args.milestones);
+        [1, 2, 3, 3, 5, 9, 10]
+            .iter()
+            .for_each(|count| milestones.observe(&args, &strings(*count), 10, 10));
+        assert_eq!(
+            vec!["2 strings", "5 strings", "9 strings"],
+            labels(&milestones)
+        );
+    }
+
+    #[test]
+    fn test_skipped_milestones_share_a_snapshot() {
+        let args = args();
+        let mut milestones = Milestones::new(&args.milestones);
+        milestones.observe(&args, &strings(5), 10, 10);
+        assert_eq!(vec!["5 strings"], labels(&milestones));
+    }
+
+    #[test]
+    fn test_contact_sheet_ends_with_the_finished_strings() {
+        let args = args();
+        let mut milestones = Milestones::new(&args.milestones);
+        milestones.observe(&args, &strings(2), 10, 10);
+        let with_finished = milestones.contact_sheet(&args, &strings(3), 10, 10);
+
+        let mut milestones = Milestones::new(&args.milestones);
+        milestones.observe(&args, &strings(2), 10, 10);
+        let without_finished = milestones.contact_sheet(&args, &strings(2), 10, 10);
+        assert!(with_finished.width() > without_finished.width());
+    }
+}
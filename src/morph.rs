@@ -0,0 +1,218 @@
+use crate::animation;
+use crate::cli_app::Args;
+use crate::error::StringArtError;
+#[cfg(feature = "gif")]
+use crate::frame_sink::GifSink;
+use crate::geometry::Point;
+use crate::imagery::LineSegment;
+#[cfg(feature = "gif")]
+use crate::imagery::RefImage;
+use crate::optimum::Constraints;
+use crate::style;
+use crate::style::Data;
+#[cfg(feature = "gif")]
+use crate::template;
+use crate::term;
+use crate::term::Level;
+#[cfg(feature = "gif")]
+use image::Delay;
+use image::DynamicImage;
+
+/// About how many frames the morph gif has between the two images
+#[cfg(feature = "gif")]
+const MORPH_FRAMES: usize = 50;
+
+/// One string taken down or put up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Remove(LineSegment),
+    Add(LineSegment),
+}
+
+/// Create string art for the input image and for the --morph-to image, then save a gif of one
+/// turning into the other if it was asked for
+pub fn morph(
+    pin_locations: Vec<Point>,
+    args: Args,
+    target: DynamicImage,
+) -> Result<Vec<Data>, StringArtError> {
+    let datas: Vec<Data> = [args.image.clone(), target]
+        .into_iter()
+        .enumerate()
+        .map(|(i, image)| {
+            term::info(args.verbosity, &format!("Morph image {} of 2", i + 1));
+            style::color_on_custom(
+                pin_locations.clone(),
+                animation::frame_args(&args, i, image),
+                Vec::new(),
+                &Constraints::default(),
+                Vec::new(),
+            )
+        })
+        .collect::<Result<_, _>>()?;
+
+    if Level::Info.shown_at(args.verbosity) {
+        let changes = changes(&datas[0].line_segments, &datas[1].line_segments);
+        let removed = changes
+            .iter()
+            .filter(|change| matches!(change, Change::Remove(_)))
+            .count();
+        term::info(
+            args.verbosity,
+            &format!(
+                "Morphing takes down {} strings and puts up {}",
+                removed,
+                changes.len() - removed
+            ),
+        );
+    }
+
+    #[cfg(feature = "gif")]
+    if let Some(filepath) = &args.morph_filepath {
+        write_gif(&datas[0], &datas[1], &template::expand(filepath, &datas[1]))?;
+    }
+
+    Ok(datas)
+}
+
+/// The changes that turn the strings of `from` into the strings of `to`. Strings in both stay
+/// up, whichever way round they were placed. The rest are swapped one for one, in the order
+/// they were placed, and any left over are taken down or put up at the end.
+pub fn changes(from: &[LineSegment], to: &[LineSegment]) -> Vec<Change> {
+    let mut removed: Vec<LineSegment> = from.to_vec();
+    let mut added: Vec<LineSegment> = Vec::new();
+    for segment in to {
+        match removed.iter().position(|s| same_string(s, segment)) {
+            Some(i) => {
+                removed.remove(i);
+            }
+            None => added.push(*segment),
+        }
+    }
+
+    let mut changes = Vec::with_capacity(removed.len() + added.len());
+    for i in 0..usize::max(removed.len(), added.len()) {
+        if let Some(segment) = removed.get(i) {
+            changes.push(Change::Remove(*segment));
+        }
+        if let Some(segment) = added.get(i) {
+            changes.push(Change::Add(*segment));
+        }
+    }
+    changes
+}
+
+/// Take down or put up the string of the change
+#[cfg(any(feature = "gif", test))]
+pub fn apply(line_segments: &mut Vec<LineSegment>, change: &Change) {
+    match change {
+        Change::Remove(segment) => {
+            if let Some(i) = line_segments.iter().position(|s| same_string(s, segment)) {
+                line_segments.remove(i);
+            }
+        }
+        Change::Add(segment) => line_segments.push(*segment),
+    }
+}
+
+fn same_string((a1, b1, rgb1): &LineSegment, (a2, b2, rgb2): &LineSegment) -> bool {
+    rgb1 == rgb2 && ((a1, b1) == (a2, b2) || (a1, b1) == (b2, a2))
+}
+
+/// Save a gif that starts on `from`, swaps strings a few at a time, and ends on `to`
+#[cfg(feature = "gif")]
+fn write_gif(from: &Data, to: &Data, filepath: &str) -> Result<(), StringArtError> {
+    let changes = changes(&from.line_segments, &to.line_segments);
+    let per_frame = usize::max(1, changes.len().div_ceil(MORPH_FRAMES));
+    let pause = Delay::from_numer_denom_ms(1000, 1);
+    let step = Delay::from_numer_denom_ms(100, 1);
+
+    let mut frame = from.clone();
+    let mut sink = GifSink::create(filepath)?;
+    sink.add(RefImage::from(from).color(), pause)?;
+    for chunk in changes.chunks(per_frame) {
+        chunk
+            .iter()
+            .for_each(|change| apply(&mut frame.line_segments, change));
+        sink.add(RefImage::from(&frame).color(), step)?;
+    }
+    sink.add(RefImage::from(to).color(), pause)
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::cli_app::Cli;
+    use crate::imagery::Rgb;
+    use clap::Parser;
+
+    fn string(a: (u32, u32), b: (u32, u32)) -> LineSegment {
+        (Point::new(a.0, a.1), Point::new(b.0, b.1), Rgb::WHITE)
+    }
+
+    #[test]
+    fn test_changes_keep_shared_strings() {
+        let from = vec![string((0, 0), (9, 0)), string((0, 0), (0, 9))];
+        let to = vec![
+            string((0, 9), (0, 0)),
+            string((9, 0), (9, 9)),
+            string((0, 9), (9, 9)),
+        ];
+        assert_eq!(
+            vec![
+                Change::Remove(string((0, 0), (9, 0))),
+                Change::Add(string((9, 0), (9, 9))),
+                Change::Add(string((0, 9), (9, 9))),
+            ],
+            changes(&from, &to)
+        );
+    }
+
+    #[test]
+    fn test_applying_the_changes_gives_the_target_strings() {
+        let from: Vec<LineSegment> = (0..6).map(|i| string((i, 0), (9 - i, 9))).collect();
+        let to: Vec<LineSegment> = (3..8).map(|i| string((i, 0), (9 - i, 9))).collect();
+        let mut line_segments = from.clone();
+        changes(&from, &to)
+            .iter()
+            .for_each(|change| apply(&mut line_segments, change));
+        assert_eq!(to, line_segments);
+    }
+
+    #[test]
+    fn test_morph_creates_both_images() {
+        let cli = Cli::parse_from(["string_art", "-i", "in.png", "-c", "8", "-m", "20"]);
+        let diagonal = |flip: bool| {
+            let mut i = image::RgbImage::new(10, 10);
+            i.enumerate_pixels_mut()
+                .filter(|(x, y, _)| (*x == *y) != flip)
+                .for_each(|(_, _, p)| *p = image::Rgb([255; 3]));
+            DynamicImage::ImageRgb8(i)
+        };
+        let args = Args::with_image(cli, diagonal(false));
+        let pins = crate::pins::from_args(&args, 10, 10);
+
+        let datas = morph(pins, args, diagonal(true)).unwrap();
+
+        assert_eq!(
+            vec![Some(0), Some(1)],
+            datas.iter().map(|d| d.args.frame).collect::<Vec<_>>()
+        );
+        assert_ne!(datas[0].line_segments, datas[1].line_segments);
+    }
+
+    #[test]
+    #[cfg(feature = "gif")]
+    fn test_morph_gif() {
+        let filepath = std::env::temp_dir().join("string_art_morph.gif");
+        let filepath = filepath.to_str().unwrap();
+        let from = Data::example(&[]);
+        let mut to = from.clone();
+        to.line_segments = vec![string((0, 0), (0, 9))];
+
+        write_gif(&from, &to, filepath).unwrap();
+        let decoded = image::open(filepath).unwrap();
+        std::fs::remove_file(filepath).unwrap();
+        assert_eq!((10, 10), (decoded.width(), decoded.height()));
+    }
+}
@@ -0,0 +1,284 @@
+//! Node.js bindings, so a Node program can create string art without running the command and
+//! reading its files. Build the library with the `node` feature as a cdylib and load it as a
+//! native addon:
+//!
+//! ```bash
+//! cargo rustc --lib --release --features node --crate-type cdylib
+//! cp target/release/libstring_art.so string_art.node
+//! ```
+//!
+//! ```js
+//! const stringArt = require("./string_art.node");
+//! const data = await stringArt.generate(fs.readFileSync("elephant.jpg"), ["--pin-count", "300"],
+//!   (progress) => console.log(progress.strings, progress.score));
+//! fs.writeFileSync("elephant_strings.png", stringArt.render(data));
+//! ```
+//!
+//! Runs happen on Node's worker threads and resolve a promise once they are done, so they don't
+//! block the event loop. Progress is reported to the callbacks on the event loop.
+
+use crate::animation;
+use crate::cli_app::{Args, Cli};
+use crate::control::Progress;
+use crate::error::StringArtError;
+use crate::frame_sink::FrameSink;
+use crate::imagery::RefImage;
+use crate::optimum::Constraints;
+use crate::pins;
+use crate::style;
+use crate::style::Data;
+use clap::Parser;
+use image::codecs::png::PngEncoder;
+use image::{ColorType, Delay, DynamicImage, ImageEncoder, RgbaImage};
+use napi::bindgen_prelude::{AsyncTask, Buffer};
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, JsUnknown, Task};
+use napi_derive::napi;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::Duration;
+
+/// How often the progress callbacks are called while strings are placed
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How many strings apart the frames sent to `onFrame` are, unless `--gif-strings-per-frame`
+/// says otherwise
+const FRAME_STRINGS_PER_FRAME: u64 = 20;
+
+/// How long each frame of `animate` is shown in the `--animation-filepath` gif
+const FRAME_DELAY_MS: u32 = 100;
+
+/// A JavaScript callback, called on the event loop
+type Callback<T> = ThreadsafeFunction<T, ErrorStrategy::Fatal>;
+
+/// How far along a run is
+#[napi(object)]
+pub struct RunProgress {
+    /// How many strings there are now
+    pub strings: u32,
+    /// How many strings have been added, counting any that were removed again
+    pub placed: i64,
+    /// The score, measured about once a second. Lower is better.
+    pub score: i64,
+}
+
+impl From<&Progress> for RunProgress {
+    fn from(progress: &Progress) -> Self {
+        Self {
+            strings: progress.strings() as u32,
+            placed: progress.placed() as i64,
+            score: progress.score(),
+        }
+    }
+}
+
+impl From<StringArtError> for napi::Error {
+    fn from(error: StringArtError) -> Self {
+        napi::Error::from_reason(error.to_string())
+    }
+}
+
+/// Create string art from an encoded image, with `options` given as they would be on the
+/// command line. Resolves to the contents of the data file. `onProgress` is called a few times
+/// a second with how far along the run is, and `onFrame` with snapshots of the strings as PNGs.
+#[napi(ts_return_type = "Promise<object>")]
+pub fn generate(
+    image: Buffer,
+    options: Vec<String>,
+    on_progress: Option<Callback<RunProgress>>,
+    on_frame: Option<Callback<Buffer>>,
+) -> AsyncTask<Generate> {
+    AsyncTask::new(Generate {
+        image: image.to_vec(),
+        options,
+        on_progress,
+        on_frame,
+    })
+}
+
+/// Render the contents of a data file, like `generate` resolves to, as a PNG
+#[napi]
+pub fn render(data: serde_json::Value) -> napi::Result<Buffer> {
+    let data: Data =
+        serde_json::from_value(data).map_err(|error| StringArtError::decode("data", error))?;
+    Ok(png(&RefImage::from(&data).color())?.into())
+}
+
+/// Create string art for each of the encoded `frames`, which must all be the same size. Each
+/// frame starts from the strings of the frame before, so consecutive frames stay similar.
+/// Resolves to the contents of the data file of each frame. `onProgress` is called a few times a
+/// second with how far along the current frame is.
+#[napi(ts_return_type = "Promise<object[]>")]
+pub fn animate(
+    frames: Vec<Buffer>,
+    options: Vec<String>,
+    on_progress: Option<Callback<RunProgress>>,
+) -> AsyncTask<Animate> {
+    AsyncTask::new(Animate {
+        frames: frames.iter().map(|frame| frame.to_vec()).collect(),
+        options,
+        on_progress,
+    })
+}
+
+pub struct Generate {
+    image: Vec<u8>,
+    options: Vec<String>,
+    on_progress: Option<Callback<RunProgress>>,
+    on_frame: Option<Callback<Buffer>>,
+}
+
+impl Task for Generate {
+    type Output = Data;
+    type JsValue = JsUnknown;
+
+    fn compute(&mut self) -> napi::Result<Data> {
+        let mut args = args(decode("image", &self.image)?, &self.options)?;
+        let mut sinks: Vec<Box<dyn FrameSink + Send>> = Vec::new();
+        if let Some(on_frame) = self.on_frame.clone() {
+            args.gif_strings_per_frame
+                .get_or_insert(FRAME_STRINGS_PER_FRAME);
+            sinks.push(Box::new(move |frame: &RgbaImage| {
+                if let Ok(png) = png(frame) {
+                    on_frame.call(png.into(), ThreadsafeFunctionCallMode::NonBlocking);
+                }
+            }));
+        }
+        let progress = args.progress.clone();
+        let pins = pins::from_args(&args, args.image.width(), args.image.height());
+        Ok(reporting(&progress, self.on_progress.as_ref(), || {
+            style::color_on_custom(pins, args, Vec::new(), &Constraints::default(), sinks)
+        })?)
+    }
+
+    fn resolve(&mut self, env: Env, data: Data) -> napi::Result<JsUnknown> {
+        env.to_js_value(&data)
+    }
+}
+
+pub struct Animate {
+    frames: Vec<Vec<u8>>,
+    options: Vec<String>,
+    on_progress: Option<Callback<RunProgress>>,
+}
+
+impl Task for Animate {
+    type Output = Vec<Data>;
+    type JsValue = JsUnknown;
+
+    fn compute(&mut self) -> napi::Result<Vec<Data>> {
+        let frames = self
+            .frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| decode(&format!("frame {}", i + 1), frame))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(first) = frames.first() else {
+            return Err(napi::Error::from_reason("There are no frames to animate"));
+        };
+        let size = (first.width(), first.height());
+        if let Some(i) = frames.iter().position(|f| (f.width(), f.height()) != size) {
+            return Err(napi::Error::from_reason(format!(
+                "Frame {} is {}x{}, but the first frame is {}x{}",
+                i + 1,
+                frames[i].width(),
+                frames[i].height(),
+                size.0,
+                size.1
+            )));
+        }
+        let mut args = args(first.clone(), &self.options)?;
+        let delay = Delay::from_numer_denom_ms(FRAME_DELAY_MS, 1);
+        args.frames = frames.into_iter().map(|frame| (frame, delay)).collect();
+        let progress = args.progress.clone();
+        let pins = pins::from_args(&args, size.0, size.1);
+        Ok(reporting(&progress, self.on_progress.as_ref(), || {
+            animation::animate(pins, args)
+        })?)
+    }
+
+    fn resolve(&mut self, env: Env, datas: Vec<Data>) -> napi::Result<JsUnknown> {
+        env.to_js_value(&datas)
+    }
+}
+
+/// Parse `options` like the command line, for `image`, and load the images they name
+fn args(image: DynamicImage, options: &[String]) -> Result<Args, StringArtError> {
+    let command_line = ["string_art", "--input-filepath", ""]
+        .into_iter()
+        .map(str::to_owned)
+        .chain(options.iter().cloned());
+    Args::with_image(Cli::try_parse_from(command_line)?, image).with_images_from_options()
+}
+
+/// Decode an image, which is called `name` in the error if it can't be
+fn decode(name: &str, bytes: &[u8]) -> Result<DynamicImage, StringArtError> {
+    image::load_from_memory(bytes).map_err(|error| StringArtError::decode(name, error))
+}
+
+fn png(image: &RgbaImage) -> Result<Vec<u8>, StringArtError> {
+    let mut bytes = Vec::new();
+    PngEncoder::new(&mut bytes)
+        .write_image(image, image.width(), image.height(), ColorType::Rgba8)
+        .map_err(|error| StringArtError::encode("PNG", error))?;
+    Ok(bytes)
+}
+
+/// Run `work`, calling `on_progress` with its progress every `PROGRESS_INTERVAL`, and once more
+/// when it is done
+fn reporting<T>(
+    progress: &Progress,
+    on_progress: Option<&Callback<RunProgress>>,
+    work: impl FnOnce() -> T,
+) -> T {
+    let Some(on_progress) = on_progress else {
+        return work();
+    };
+    let (done, finished) = channel::<()>();
+    std::thread::scope(|scope| {
+        scope.spawn(move || loop {
+            let stopped =
+                finished.recv_timeout(PROGRESS_INTERVAL) != Err(RecvTimeoutError::Timeout);
+            on_progress.call(
+                RunProgress::from(progress),
+                ThreadsafeFunctionCallMode::NonBlocking,
+            );
+            if stopped {
+                return;
+            }
+        });
+        let result = work();
+        drop(done);
+        result
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_args_parse_the_options() {
+        let args = args(
+            DynamicImage::new_rgb8(20, 10),
+            &["--pin-count".to_owned(), "12".to_owned()],
+        )
+        .unwrap();
+        assert_eq!(12, args.pin_count);
+        assert_eq!((20, 10), (args.image.width(), args.image.height()));
+        assert!(matches!(
+            super::args(DynamicImage::new_rgb8(4, 4), &["--pin-count".to_owned()]),
+            Err(StringArtError::Args(_))
+        ));
+    }
+
+    #[test]
+    fn test_png_decodes_to_the_image() {
+        let image = RgbaImage::from_fn(3, 2, |x, y| image::Rgba([x as u8, y as u8, 7, 255]));
+        let bytes = png(&image).unwrap();
+        assert_eq!(image, decode("image", &bytes).unwrap().to_rgba8());
+        assert_eq!(
+            "Unable to decode 'frame 2': The image format could not be determined",
+            decode("frame 2", b"not an image").unwrap_err().to_string()
+        );
+    }
+}
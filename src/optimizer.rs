@@ -0,0 +1,1164 @@
+use crate::channel::Channel;
+use crate::cli_app::seeded_rng;
+use crate::cli_app::Args;
+use crate::error::StringArtError;
+use crate::geometry::Point;
+use crate::imagery::LineSegment;
+use crate::imagery::Pen;
+use crate::imagery::RefImage;
+use crate::imagery::Rgb;
+use crate::imagery::Traces;
+use crate::log::Log;
+use crate::memory;
+use crate::optimum;
+use crate::optimum::Constraints;
+use crate::parallel::*;
+use crate::rand::Rng;
+use crate::serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The optimizers that can be chosen by name from the CLI
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Strategy {
+    GreedyBatch,
+    GreedyWalk,
+    Annealing,
+    Beam,
+}
+
+impl core::str::FromStr for Strategy {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "greedy-batch" => Ok(Strategy::GreedyBatch),
+            "greedy-walk" => Ok(Strategy::GreedyWalk),
+            "annealing" => Ok(Strategy::Annealing),
+            "beam" => Ok(Strategy::Beam),
+            _ => Err(format!("Invalid optimizer: \"{}\"", string)),
+        }
+    }
+}
+
+impl Strategy {
+    pub fn optimizer<C: Channel>(&self, args: &Args) -> Box<dyn Optimizer<C>> {
+        match self {
+            Strategy::GreedyBatch => Box::new(GreedyBatch),
+            Strategy::GreedyWalk => Box::new(GreedyWalk),
+            Strategy::Annealing => Box::new(Annealing {
+                steps: args.annealing_steps,
+                seed: args.seed,
+            }),
+            Strategy::Beam => Box::new(Beam {
+                width: args.beam_width,
+            }),
+        }
+    }
+}
+
+/// Everything an optimizer needs to know besides the strings and the image they are improving.
+/// Colors are relative to the background color.
+pub struct Problem<'a> {
+    pub args: &'a Args,
+    pub pin_locations: &'a [Point],
+    pub rgbs: &'a [Rgb],
+    pub constraints: &'a Constraints,
+    /// How strings are drawn, with the strings between the pins traced ahead of time if there's
+    /// room for them
+    pen: Pen,
+    log: Arc<Log>,
+    /// When the time limit runs out
+    deadline: Option<Instant>,
+    /// How many passes of adding and then removing strings have been started
+    passes: Arc<AtomicUsize>,
+}
+
+impl<'a> Problem<'a> {
+    pub fn new(
+        args: &'a Args,
+        pin_locations: &'a [Point],
+        rgbs: &'a [Rgb],
+        constraints: &'a Constraints,
+    ) -> Result<Self, StringArtError> {
+        Ok(Self {
+            args,
+            pin_locations,
+            rgbs,
+            constraints,
+            pen: Pen {
+                traces: Traces::new(pin_locations, args.step_size, memory::trace_budget(args))
+                    .map(Arc::new),
+                ..args.pen()
+            },
+            log: Arc::new(Log::new(args)?),
+            deadline: args
+                .time_limit
+                .map(|seconds| Instant::now() + Duration::from_secs_f64(seconds)),
+            passes: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Write out the rest of the event log
+    pub fn finish_log(&self) -> Result<(), StringArtError> {
+        self.log.finish()
+    }
+
+    /// The same problem with other constraints, reporting to the same log
+    fn with_constraints<'b>(&self, constraints: &'b Constraints) -> Problem<'b>
+    where
+        'a: 'b,
+    {
+        Problem {
+            constraints,
+            ..self.with(self.args, self.rgbs)
+        }
+    }
+
+    /// The same problem with other arguments and colors, reporting to the same log
+    pub fn with<'b>(&self, args: &'b Args, rgbs: &'b [Rgb]) -> Problem<'b>
+    where
+        'a: 'b,
+    {
+        let traces = self
+            .pen
+            .traces
+            .as_ref()
+            .filter(|_| args.step_size == self.args.step_size);
+        Problem {
+            args,
+            pin_locations: self.pin_locations,
+            rgbs,
+            constraints: self.constraints,
+            pen: Pen {
+                traces: traces.map(Arc::clone),
+                ..args.pen()
+            },
+            log: Arc::clone(&self.log),
+            deadline: self.deadline,
+            passes: Arc::clone(&self.passes),
+        }
+    }
+
+    fn cancelled(&self) -> bool {
+        self.args.cancellation.is_cancelled() || self.timed_out()
+    }
+
+    /// Whether the time limit has run out
+    pub fn timed_out(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// How many passes of adding and then removing strings the optimizers have started
+    pub fn passes(&self) -> usize {
+        self.passes.load(Ordering::Relaxed)
+    }
+
+    /// Start another pass of adding and then removing strings, unless --max-passes have been
+    /// started already
+    fn start_pass(&self) -> bool {
+        if self.args.max_passes.is_some_and(|max| self.passes() >= max) {
+            return false;
+        }
+        self.passes.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// The score of the image, plus the cost of its strings. This is what the optimizers lower.
+    fn objective<C: Channel>(&self, ref_image: &RefImage<C>, line_segments: &[LineSegment]) -> i64 {
+        ref_image.score() + self.constraints.string_cost * line_segments.len() as i64
+    }
+
+    /// How many more strings may be added
+    fn room(&self, line_segments: &[LineSegment]) -> usize {
+        self.args.max_strings.saturating_sub(line_segments.len())
+    }
+
+    /// How much a string must lower the score to be added, from --min-improvement
+    fn min_improvement<C: Channel>(&self, ref_image: &RefImage<C>) -> i64 {
+        self.args.min_improvement.map_or(0, |percent| {
+            (ref_image.score() as f64 * percent / 100.0).round() as i64
+        })
+    }
+
+    fn best<C: Channel>(&self, ref_image: &RefImage<C>, max: usize) -> Vec<(LineSegment, i64)> {
+        let min_improvement = self.min_improvement(ref_image);
+        let mut best = optimum::find_best_points(
+            self.pin_locations,
+            ref_image,
+            &self.pen,
+            self.rgbs,
+            self.constraints,
+            max,
+        );
+        best.retain(|(_, s)| -s >= min_improvement);
+        best
+    }
+
+    fn worst<C: Channel>(
+        &self,
+        ref_image: &RefImage<C>,
+        line_segments: &[LineSegment],
+        max: usize,
+    ) -> Vec<(usize, i64)> {
+        optimum::find_worst_points(line_segments, ref_image, &self.pen, self.constraints, max)
+    }
+
+    fn score_on_add<C: Channel>(
+        &self,
+        ref_image: &RefImage<C>,
+        line_segment: LineSegment,
+    ) -> Option<i64> {
+        optimum::score_on_add(line_segment, ref_image, &self.pen, self.constraints)
+    }
+
+    fn score_on_sub<C: Channel>(
+        &self,
+        ref_image: &RefImage<C>,
+        line_segment: LineSegment,
+    ) -> Option<i64> {
+        optimum::score_on_sub(line_segment, ref_image, &self.pen, self.constraints)
+    }
+
+    fn add<C: Channel>(
+        &self,
+        ref_image: &mut RefImage<C>,
+        line_segments: &mut Vec<LineSegment>,
+        (a, b, rgb): LineSegment,
+        score_change: i64,
+    ) {
+        *ref_image += self.pen.pix_line((a, b, rgb));
+        line_segments.push((a, b, rgb));
+        self.log.add(line_segments.len(), score_change, a, b, rgb);
+    }
+
+    fn remove<C: Channel>(
+        &self,
+        ref_image: &mut RefImage<C>,
+        line_segments: &mut Vec<LineSegment>,
+        i: usize,
+        score_change: i64,
+    ) {
+        let (a, b, rgb) = line_segments.remove(i);
+        *ref_image -= self.pen.pix_line((a, b, rgb));
+        self.log.sub(line_segments.len(), score_change, a, b, rgb);
+    }
+}
+
+/// A strategy for choosing strings. Optimizers start from the strings already in
+/// `line_segments`, keep `ref_image` in step with them, and call `on_step` with the current
+/// strings and the image they make as they make progress so it can be recorded.
+pub trait Optimizer<C: Channel = Rgb> {
+    fn optimize(
+        &self,
+        problem: &Problem,
+        ref_image: &mut RefImage<C>,
+        line_segments: &mut Vec<LineSegment>,
+        on_step: &mut dyn FnMut(&[LineSegment], &RefImage<C>),
+    );
+}
+
+/// Adds the best strings in batches, then removes the worst strings in smaller batches, until
+/// neither helps or --max-passes passes are done. The batch size grows while whole batches keep
+/// helping. Strings removed in one pass aren't added back in the next, so the same string
+/// doesn't bounce between added and removed.
+pub struct GreedyBatch;
+
+impl<C: Channel> Optimizer<C> for GreedyBatch {
+    fn optimize(
+        &self,
+        problem: &Problem,
+        ref_image: &mut RefImage<C>,
+        line_segments: &mut Vec<LineSegment>,
+        on_step: &mut dyn FnMut(&[LineSegment], &RefImage<C>),
+    ) {
+        let mut keep_adding = true;
+        let mut keep_removing = true;
+
+        let mut cap = 100;
+        let mut max_at_once = usize::min(problem.args.max_strings / 10, cap);
+        let mut removed: HashSet<LineSegment> = HashSet::new();
+
+        while (keep_adding || keep_removing) && problem.start_pass() {
+            max_at_once = usize::min(max_at_once, cap);
+            cap -= 1;
+
+            let constraints = Constraints {
+                banned: problem
+                    .constraints
+                    .banned
+                    .union(&removed)
+                    .copied()
+                    .collect(),
+                ..problem.constraints.clone()
+            };
+            let adding = problem.with_constraints(&constraints);
+            removed.clear();
+
+            while keep_adding {
+                on_step(line_segments, ref_image);
+                if problem.cancelled() {
+                    return;
+                }
+
+                keep_adding = false;
+
+                let points = adding.best(
+                    ref_image,
+                    usize::min(problem.room(line_segments), max_at_once),
+                );
+
+                if !points.is_empty() {
+                    keep_removing = true;
+                    keep_adding = true;
+                }
+
+                if points.len() == max_at_once {
+                    max_at_once = (max_at_once as f64 * 1.1) as usize
+                }
+
+                // Strings found together can crowd each other at a pin or where they cross
+                points.into_iter().for_each(|(line_segment, s)| {
+                    if adding.score_on_add(ref_image, line_segment).is_some() {
+                        adding.add(ref_image, line_segments, line_segment, s);
+                    }
+                });
+
+                if problem.room(line_segments) == 0 {
+                    keep_adding = false
+                }
+            }
+
+            max_at_once = usize::max(1, (max_at_once as f64 * 0.9) as usize);
+
+            while keep_removing {
+                on_step(line_segments, ref_image);
+                if problem.cancelled() {
+                    return;
+                }
+
+                keep_removing = false;
+
+                let mut worst_points = problem.worst(
+                    ref_image,
+                    line_segments,
+                    // Find these more accurately by finding fewer at once. Saves time overall by
+                    // preventing strings from bouncing back and forth between added and removed.
+                    usize::min(line_segments.len(), usize::max(1, max_at_once / 10)),
+                );
+                worst_points.sort_unstable_by_key(|(i, _)| *i);
+                worst_points.reverse();
+
+                if !worst_points.is_empty() {
+                    keep_removing = true;
+                    keep_adding = true;
+                }
+
+                worst_points.into_iter().for_each(|(i, s)| {
+                    removed.insert(line_segments[i]);
+                    problem.remove(ref_image, line_segments, i, s);
+                });
+
+                if line_segments.is_empty() {
+                    keep_removing = false
+                }
+            }
+        }
+    }
+}
+
+/// Winds one continuous thread from --start-pin, or the first pin. Each string starts at the pin
+/// where the last one ended, and the walk stops once no string from the current pin improves
+/// the image.
+pub struct GreedyWalk;
+
+impl<C: Channel> Optimizer<C> for GreedyWalk {
+    fn optimize(
+        &self,
+        problem: &Problem,
+        ref_image: &mut RefImage<C>,
+        line_segments: &mut Vec<LineSegment>,
+        on_step: &mut dyn FnMut(&[LineSegment], &RefImage<C>),
+    ) {
+        let start = problem.args.start_pin.unwrap_or(0);
+        let mut at = match (line_segments.last(), problem.pin_locations.get(start)) {
+            (Some((_, b, _)), _) => *b,
+            (None, Some(pin)) => *pin,
+            (None, None) => return,
+        };
+
+        while problem.room(line_segments) > 0 {
+            on_step(line_segments, ref_image);
+            if problem.cancelled() {
+                break;
+            }
+
+            let image: &RefImage<C> = ref_image;
+            let min_improvement = problem.min_improvement(image);
+            let lines = problem
+                .pin_locations
+                .par_iter()
+                .filter(|b| **b != at)
+                .flat_map(|b| problem.rgbs.par_iter().map(move |rgb| (at, *b, *rgb)))
+                .filter_map(|line_segment| {
+                    problem
+                        .score_on_add(image, line_segment)
+                        .map(|s| (line_segment, s))
+                })
+                .filter(|(_, s)| *s < 0 && -s >= min_improvement)
+                .collect();
+
+            match optimum::best(lines, 1).into_iter().next() {
+                Some((line_segment, s)) => {
+                    problem.add(ref_image, line_segments, line_segment, s);
+                    at = line_segment.1;
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// Simulated annealing. Proposes adding or removing random strings, and sometimes accepts
+/// proposals that make the image worse so it can escape local optima. The temperature cools
+/// over `steps` proposals, and then a greedy batch pass polishes the result. The same `seed`
+/// makes the same proposals.
+pub struct Annealing {
+    pub steps: usize,
+    pub seed: Option<u64>,
+}
+
+impl<C: Channel> Optimizer<C> for Annealing {
+    fn optimize(
+        &self,
+        problem: &Problem,
+        ref_image: &mut RefImage<C>,
+        line_segments: &mut Vec<LineSegment>,
+        on_step: &mut dyn FnMut(&[LineSegment], &RefImage<C>),
+    ) {
+        let pins = problem.pin_locations;
+        let rgbs = problem.rgbs;
+        let mut rng = seeded_rng(self.seed);
+
+        // Start hot enough to sometimes accept a change as large as the best single string
+        let hottest = problem
+            .best(ref_image, 1)
+            .first()
+            .map_or(1.0, |(_, s)| -*s as f64);
+        let cooling = (0.001_f64).powf(1.0 / usize::max(1, self.steps) as f64);
+        let mut temperature = hottest;
+        let report_every = usize::max(1, self.steps / 100);
+
+        for step in 0..self.steps {
+            if pins.len() < 2 || rgbs.is_empty() {
+                break;
+            }
+            if step % report_every == 0 {
+                on_step(line_segments, ref_image);
+            }
+            if problem.cancelled() {
+                break;
+            }
+            temperature *= cooling;
+            let roll = rng.gen::<f64>();
+            let accept = |s: i64| s < 0 || roll < (-(s as f64) / temperature).exp();
+
+            let remove = !line_segments.is_empty()
+                && (problem.room(line_segments) == 0 || rng.gen_bool(0.5));
+            if remove {
+                let i = rng.gen_range(0..line_segments.len());
+                if let Some(s) = problem.score_on_sub(ref_image, line_segments[i]) {
+                    if accept(s) {
+                        problem.remove(ref_image, line_segments, i, s);
+                    }
+                }
+            } else if problem.room(line_segments) > 0 {
+                let a = pins[rng.gen_range(0..pins.len())];
+                let b = pins[rng.gen_range(0..pins.len())];
+                let line_segment = (a, b, rgbs[rng.gen_range(0..rgbs.len())]);
+                if a == b {
+                    continue;
+                }
+                if let Some(s) = problem.score_on_add(ref_image, line_segment) {
+                    if accept(s) {
+                        problem.add(ref_image, line_segments, line_segment, s);
+                    }
+                }
+            }
+        }
+
+        GreedyBatch.optimize(problem, ref_image, line_segments, on_step);
+    }
+}
+
+/// Beam search. Keeps the `width` best sets of strings found so far, and grows each by its
+/// `width` best strings, until none of the sets can be improved. Only ever adds strings.
+pub struct Beam {
+    pub width: usize,
+}
+
+type Candidate<C> = (RefImage<C>, Vec<LineSegment>, i64);
+
+impl<C: Channel> Optimizer<C> for Beam {
+    fn optimize(
+        &self,
+        problem: &Problem,
+        ref_image: &mut RefImage<C>,
+        line_segments: &mut Vec<LineSegment>,
+        on_step: &mut dyn FnMut(&[LineSegment], &RefImage<C>),
+    ) {
+        let width = usize::max(1, self.width);
+        let mut beam: Vec<Candidate<C>> = vec![(
+            ref_image.clone(),
+            line_segments.clone(),
+            problem.objective(ref_image, line_segments),
+        )];
+
+        loop {
+            on_step(&beam[0].1, &beam[0].0);
+            if problem.cancelled() {
+                break;
+            }
+
+            let mut growths = beam
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, segments, _))| problem.room(segments) > 0)
+                .flat_map(|(i, (image, _, score))| {
+                    problem
+                        .best(image, width)
+                        .into_iter()
+                        .map(move |(line_segment, s)| (i, line_segment, s, score + s))
+                })
+                .collect::<Vec<_>>();
+            growths.sort_unstable_by_key(|(_, _, _, score)| *score);
+
+            // The same set of strings can be reached by adding them in a different order
+            let mut seen = HashSet::new();
+            let next = growths
+                .into_iter()
+                .filter(|(i, line_segment, _, _)| {
+                    let mut key = beam[*i].1.clone();
+                    key.push(*line_segment);
+                    key.sort_unstable_by_key(|(a, b, rgb)| {
+                        (a.x, a.y, b.x, b.y, rgb.r, rgb.g, rgb.b)
+                    });
+                    seen.insert(key)
+                })
+                .take(width)
+                .map(|(i, line_segment, s, score)| {
+                    let (mut image, mut segments) = (beam[i].0.clone(), beam[i].1.clone());
+                    problem.add(&mut image, &mut segments, line_segment, s);
+                    (image, segments, score)
+                })
+                .collect::<Vec<_>>();
+
+            if next.is_empty() {
+                break;
+            }
+            beam = next;
+        }
+
+        if let Some((image, segments, _)) = beam.into_iter().min_by_key(|(_, _, score)| *score) {
+            *ref_image = image;
+            *line_segments = segments;
+        }
+    }
+}
+
+/// A refinement phase that swaps strings: removes one string and adds the best replacement in
+/// one move, keeping the swap only if the two together improve the image. Finds improvements
+/// that adding and removing strings separately miss. Each pass tries to replace every string,
+/// and passes stop early once one makes no swaps.
+pub struct Swaps {
+    pub passes: usize,
+}
+
+impl<C: Channel> Optimizer<C> for Swaps {
+    fn optimize(
+        &self,
+        problem: &Problem,
+        ref_image: &mut RefImage<C>,
+        line_segments: &mut Vec<LineSegment>,
+        on_step: &mut dyn FnMut(&[LineSegment], &RefImage<C>),
+    ) {
+        let pen = &problem.pen;
+
+        for _ in 0..self.passes {
+            on_step(line_segments, ref_image);
+            let mut swapped = false;
+            // Swapped strings move to the end, so the next string to try is at `i` again
+            let mut i = 0;
+            for _ in 0..line_segments.len() {
+                if problem.cancelled() {
+                    return;
+                }
+                let removed = line_segments[i];
+                let Some(sub) = problem.score_on_sub(ref_image, removed) else {
+                    i += 1;
+                    continue;
+                };
+
+                *ref_image -= pen.pix_line(removed);
+                let replacement = problem
+                    .best(ref_image, 1)
+                    .into_iter()
+                    .next()
+                    .filter(|(added, add)| *added != removed && sub + add < 0);
+                *ref_image += pen.pix_line(removed);
+
+                match replacement {
+                    Some((added, add)) => {
+                        problem.remove(ref_image, line_segments, i, sub);
+                        problem.add(ref_image, line_segments, added, add);
+                        swapped = true;
+                    }
+                    None => i += 1,
+                }
+            }
+            if !swapped {
+                break;
+            }
+        }
+    }
+}
+
+/// A refinement phase for when adding and removing strings stalls before --max-strings is
+/// reached. Each round removes a random `fraction` of the strings and resumes the greedy batch
+/// search from what is left, keeping the result only if it beats the best image so far. Shaking
+/// the strings loose like this often escapes a local optimum. The strings removed are chosen
+/// from `seed`, so runs can be repeated, or at random without one.
+pub struct Perturb {
+    pub rounds: usize,
+    pub fraction: f64,
+    pub seed: Option<u64>,
+}
+
+impl<C: Channel> Optimizer<C> for Perturb {
+    fn optimize(
+        &self,
+        problem: &Problem,
+        ref_image: &mut RefImage<C>,
+        line_segments: &mut Vec<LineSegment>,
+        on_step: &mut dyn FnMut(&[LineSegment], &RefImage<C>),
+    ) {
+        let mut rng = seeded_rng(self.seed);
+        for _ in 0..self.rounds {
+            if problem.cancelled() || problem.room(line_segments) == 0 {
+                return;
+            }
+            // Locked strings stay where they are
+            let removable: Vec<usize> = (0..line_segments.len())
+                .filter(|i| problem.score_on_sub(ref_image, line_segments[*i]).is_some())
+                .collect();
+            let count = (removable.len() as f64 * self.fraction).ceil() as usize;
+            if count == 0 {
+                return;
+            }
+
+            let before = line_segments.clone();
+            let best_score = problem.objective(ref_image, line_segments);
+            let mut chosen: Vec<usize> = rand::seq::index::sample(&mut rng, removable.len(), count)
+                .into_iter()
+                .map(|i| removable[i])
+                .collect();
+            chosen.sort_unstable();
+            for i in chosen.into_iter().rev() {
+                let s = problem
+                    .score_on_sub(ref_image, line_segments[i])
+                    .unwrap_or(0);
+                problem.remove(ref_image, line_segments, i, s);
+            }
+            GreedyBatch.optimize(problem, ref_image, line_segments, on_step);
+
+            if problem.objective(ref_image, line_segments) >= best_score {
+                restore(problem, ref_image, line_segments, &before);
+            }
+        }
+    }
+}
+
+/// Change the strings back to `target` through the problem, so the log sees every change
+fn restore<C: Channel>(
+    problem: &Problem,
+    ref_image: &mut RefImage<C>,
+    line_segments: &mut Vec<LineSegment>,
+    target: &[LineSegment],
+) {
+    let mut missing = target.to_vec();
+    let mut i = 0;
+    while i < line_segments.len() {
+        match missing.iter().position(|l| *l == line_segments[i]) {
+            Some(j) => {
+                missing.swap_remove(j);
+                i += 1;
+            }
+            None => {
+                let s = problem
+                    .score_on_sub(ref_image, line_segments[i])
+                    .unwrap_or(0);
+                problem.remove(ref_image, line_segments, i, s);
+            }
+        }
+    }
+    for line_segment in missing {
+        let s = problem.score_on_add(ref_image, line_segment).unwrap_or(0);
+        problem.add(ref_image, line_segments, line_segment, s);
+    }
+}
+
+/// Places strings against the luminance of the image alone, using one grey string that
+/// brightens and one that darkens, then gives each string the foreground color that best fits
+/// the pixels it crosses. Much faster than searching every color for every string, since only
+/// the strings that were placed are tried in each color.
+pub struct LuminanceFirst<C: Channel = Rgb> {
+    pub inner: Box<dyn Optimizer<C>>,
+}
+
+impl<C: Channel> Optimizer<C> for LuminanceFirst<C> {
+    fn optimize(
+        &self,
+        problem: &Problem,
+        ref_image: &mut RefImage<C>,
+        line_segments: &mut Vec<LineSegment>,
+        on_step: &mut dyn FnMut(&[LineSegment], &RefImage<C>),
+    ) {
+        let greys = luminance_levels(problem.rgbs);
+        // Only the strings with their final colors belong in the event log
+        let grey_problem = Problem {
+            log: Arc::new(Log::without_events(problem.args)),
+            ..problem.with(problem.args, &greys)
+        };
+
+        // The starting strings are already in the image, and stay as they are
+        let mut grey_image = ref_image.luminance();
+        let mut grey_segments = Vec::new();
+        let starting = line_segments.clone();
+        self.inner.optimize(
+            &grey_problem,
+            &mut grey_image,
+            &mut grey_segments,
+            &mut |placed, grey_image| on_step(&[&starting[..], placed].concat(), grey_image),
+        );
+
+        // Even when cancelled, finish coloring the strings so none are left grey
+        for (a, b, _) in grey_segments {
+            let best = problem
+                .rgbs
+                .iter()
+                .filter_map(|rgb| {
+                    problem
+                        .score_on_add(ref_image, (a, b, *rgb))
+                        .map(|s| ((a, b, *rgb), s))
+                })
+                .min_by_key(|(_, s)| *s);
+            if let Some((line_segment, s)) = best {
+                problem.add(ref_image, line_segments, line_segment, s);
+            }
+        }
+        on_step(line_segments, ref_image);
+    }
+}
+
+/// Places the strings of each color as a separate layer, in the order of the colors. The
+/// strings are shared evenly between the layers, and any a layer doesn't use are left for the
+/// layers after it.
+pub struct Layers<C: Channel = Rgb> {
+    pub inner: Box<dyn Optimizer<C>>,
+}
+
+impl<C: Channel> Optimizer<C> for Layers<C> {
+    fn optimize(
+        &self,
+        problem: &Problem,
+        ref_image: &mut RefImage<C>,
+        line_segments: &mut Vec<LineSegment>,
+        on_step: &mut dyn FnMut(&[LineSegment], &RefImage<C>),
+    ) {
+        for (i, rgb) in problem.rgbs.iter().enumerate() {
+            if problem.cancelled() {
+                return;
+            }
+            let layers_left = problem.rgbs.len() - i;
+            let budget = problem.room(line_segments) / layers_left;
+            let args = Args {
+                max_strings: line_segments.len() + budget,
+                ..problem.args.clone()
+            };
+            let rgbs = [*rgb];
+            let layer = problem.with(&args, &rgbs);
+            self.inner
+                .optimize(&layer, ref_image, line_segments, on_step);
+        }
+    }
+}
+
+/// The mean grey of the colors that brighten the image, and of those that darken it
+fn luminance_levels(rgbs: &[Rgb]) -> Vec<Rgb> {
+    let greys: Vec<i32> = rgbs.iter().map(|rgb| i32::from_rgb(*rgb)).collect();
+    [
+        greys.iter().filter(|g| **g > 0).collect::<Vec<_>>(),
+        greys.iter().filter(|g| **g < 0).collect(),
+    ]
+    .into_iter()
+    .filter(|side| !side.is_empty())
+    .map(|side| (side.iter().copied().sum::<i32>() / side.len() as i32).to_rgb())
+    .collect()
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use clap::Parser;
+
+    fn args(extra_args: &[&str]) -> Args {
+        let cli = crate::cli_app::Cli::parse_from(
+            ["string_art", "--input-filepath", "in.png"]
+                .iter()
+                .chain(extra_args),
+        );
+        Args::with_image(cli, image::DynamicImage::new_rgb8(10, 10))
+    }
+
+    fn pins() -> Vec<Point> {
+        crate::pins::generate(&crate::pins::Arrangement::Perimeter, 12, 10, 10)
+    }
+
+    /// A white target on a black background
+    fn ref_image() -> RefImage {
+        RefImage::new(10, 10).add_rgb(-Rgb::new(255, 255, 255))
+    }
+
+    fn run(strategy: Strategy, extra_args: &[&str]) -> (RefImage, Vec<LineSegment>) {
+        let args = args(extra_args);
+        let pins = pins();
+        let rgbs = [Rgb::new(255, 255, 255)];
+        let constraints = Constraints::default();
+        let problem = Problem::new(&args, &pins, &rgbs, &constraints).unwrap();
+        let mut ref_image = ref_image();
+        let mut line_segments = Vec::new();
+        let mut steps = 0;
+        strategy.optimizer(&args).optimize(
+            &problem,
+            &mut ref_image,
+            &mut line_segments,
+            &mut |_, _| steps += 1,
+        );
+        assert!(steps > 0);
+        (ref_image, line_segments)
+    }
+
+    fn check(strategy: Strategy, extra_args: &[&str]) -> Vec<LineSegment> {
+        let (ref_image, line_segments) = run(strategy, extra_args);
+        assert!(ref_image.score() < self::ref_image().score());
+
+        // The image must match the strings the optimizer ended with
+        let mut expected = self::ref_image();
+        line_segments
+            .iter()
+            .for_each(|(a, b, rgb)| expected += ((*a, *b), *rgb, 1.0, 0.2));
+        assert_eq!(expected.score(), ref_image.score());
+        line_segments
+    }
+
+    #[test]
+    fn test_greedy_batch() {
+        check(Strategy::GreedyBatch, &[]);
+    }
+
+    #[test]
+    fn test_greedy_walk_is_one_thread() {
+        let line_segments = check(Strategy::GreedyWalk, &[]);
+        assert_eq!(pins()[0], line_segments[0].0);
+        line_segments
+            .windows(2)
+            .for_each(|pair| assert_eq!(pair[0].1, pair[1].0));
+    }
+
+    #[test]
+    fn test_greedy_walk_from_start_pin() {
+        let line_segments = check(Strategy::GreedyWalk, &["--start-pin", "5"]);
+        assert_eq!(pins()[5], line_segments[0].0);
+    }
+
+    #[test]
+    fn test_min_improvement_stops_early() {
+        for strategy in [Strategy::GreedyBatch, Strategy::GreedyWalk] {
+            let all = check(strategy, &[]);
+            let improving = check(strategy, &["--min-improvement", "2"]);
+            assert!(improving.len() < all.len(), "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn test_greedy_batch_does_not_add_back_strings_it_just_removed() {
+        let mut image = image::RgbImage::new(30, 30);
+        image.enumerate_pixels_mut().for_each(|(x, y, p)| {
+            *p = image::Rgb([((x * 7 + y * 13) % 256) as u8; 3]);
+        });
+        let cli = crate::cli_app::Cli::parse_from(["string_art", "-i", "in.png"]);
+        let args = Args::with_image(cli, image::DynamicImage::ImageRgb8(image));
+        let pins = crate::pins::generate(&crate::pins::Arrangement::Perimeter, 40, 30, 30);
+        let rgbs = [Rgb::WHITE];
+        let constraints = Constraints::default();
+        let problem = Problem::new(&args, &pins, &rgbs, &constraints).unwrap();
+        let mut steps: Vec<Vec<LineSegment>> = Vec::new();
+        GreedyBatch.optimize(
+            &problem,
+            &mut crate::style::blank(&args),
+            &mut Vec::new(),
+            &mut |line_segments, _| steps.push(line_segments.to_vec()),
+        );
+
+        // Each step either adds strings to the end or removes some
+        let mut removing = false;
+        let mut removed: HashSet<LineSegment> = HashSet::new();
+        let mut just_removed = HashSet::new();
+        for pair in steps.windows(2) {
+            let (before, after) = (&pair[0], &pair[1]);
+            if after.len() < before.len() {
+                if !removing {
+                    removed.clear();
+                    removing = true;
+                }
+                removed.extend(before.iter().filter(|s| !after.contains(s)).copied());
+            } else if after.len() > before.len() {
+                if removing {
+                    just_removed = std::mem::take(&mut removed);
+                    removing = false;
+                }
+                assert!(after[before.len()..]
+                    .iter()
+                    .all(|s| !just_removed.contains(s)));
+            }
+        }
+        assert!(!just_removed.is_empty());
+    }
+
+    #[test]
+    fn test_max_passes() {
+        let args = args(&["--max-passes", "1"]);
+        let pins = pins();
+        let rgbs = [Rgb::WHITE];
+        let constraints = Constraints::default();
+        let problem = Problem::new(&args, &pins, &rgbs, &constraints).unwrap();
+        GreedyBatch.optimize(&problem, &mut ref_image(), &mut Vec::new(), &mut |_, _| ());
+        assert_eq!(1, problem.passes());
+
+        let args = self::args(&[]);
+        let problem = Problem::new(&args, &pins, &rgbs, &constraints).unwrap();
+        GreedyBatch.optimize(&problem, &mut ref_image(), &mut Vec::new(), &mut |_, _| ());
+        assert!(problem.passes() > 1);
+    }
+
+    #[test]
+    fn test_annealing() {
+        check(Strategy::Annealing, &["--annealing-steps", "500"]);
+    }
+
+    #[test]
+    fn test_beam() {
+        check(Strategy::Beam, &["--beam-width", "3", "--max-strings", "8"]);
+    }
+
+    #[test]
+    fn test_swaps_replace_a_poor_string() {
+        let args = args(&["--max-strings", "1", "--string-alpha", "1"]);
+        let pins = pins();
+        let rgbs = [Rgb::WHITE];
+        let constraints = Constraints::default();
+        let problem = Problem::new(&args, &pins, &rgbs, &constraints).unwrap();
+
+        // A white line along the top row, covered by a string along the diagonal instead
+        let mut ref_image = RefImage::new(10, 10);
+        (0..10).for_each(|x| ref_image[(x, 0)] = -Rgb::WHITE);
+        let diagonal = (Point::new(0, 0), Point::new(9, 9), Rgb::WHITE);
+        ref_image += ((diagonal.0, diagonal.1), diagonal.2, 1.0, 1.0);
+        let before = ref_image.score();
+        let mut line_segments = vec![diagonal];
+
+        Swaps { passes: 2 }.optimize(&problem, &mut ref_image, &mut line_segments, &mut |_, _| ());
+        assert_eq!(1, line_segments.len());
+        assert_ne!(diagonal, line_segments[0]);
+        assert!(ref_image.score() < before);
+    }
+
+    #[test]
+    fn test_perturb_never_makes_the_image_worse() {
+        let args = args(&["--max-strings", "1000"]);
+        let pins = pins();
+        let rgbs = [Rgb::WHITE];
+        let constraints = Constraints::default();
+        let problem = Problem::new(&args, &pins, &rgbs, &constraints).unwrap();
+        let mut ref_image = ref_image();
+        let mut line_segments = Vec::new();
+        GreedyBatch.optimize(&problem, &mut ref_image, &mut line_segments, &mut |_, _| ());
+        let stalled = ref_image.score();
+
+        let perturb = Perturb {
+            rounds: 5,
+            fraction: 0.2,
+            seed: Some(7),
+        };
+        perturb.optimize(&problem, &mut ref_image, &mut line_segments, &mut |_, _| ());
+        assert!(ref_image.score() <= stalled);
+
+        // The image must match the strings it ended with
+        let mut expected = self::ref_image();
+        line_segments
+            .iter()
+            .for_each(|(a, b, rgb)| expected += ((*a, *b), *rgb, 1.0, 0.2));
+        assert_eq!(expected.score(), ref_image.score());
+    }
+
+    #[test]
+    fn test_perturb_is_repeatable() {
+        let run = || {
+            let args = args(&["--max-strings", "1000"]);
+            let pins = pins();
+            let rgbs = [Rgb::WHITE];
+            let constraints = Constraints::default();
+            let problem = Problem::new(&args, &pins, &rgbs, &constraints).unwrap();
+            let mut ref_image = ref_image();
+            let mut line_segments = Vec::new();
+            let perturb = Perturb {
+                rounds: 3,
+                fraction: 0.5,
+                seed: Some(1),
+            };
+            GreedyBatch.optimize(&problem, &mut ref_image, &mut line_segments, &mut |_, _| ());
+            perturb.optimize(&problem, &mut ref_image, &mut line_segments, &mut |_, _| ());
+            line_segments
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn test_perturb_stops_at_max_strings() {
+        let args = args(&["--max-strings", "1"]);
+        let pins = pins();
+        let rgbs = [Rgb::WHITE];
+        let constraints = Constraints::default();
+        let problem = Problem::new(&args, &pins, &rgbs, &constraints).unwrap();
+        let line_segment = (pins[0], pins[6], Rgb::WHITE);
+        let mut ref_image = ref_image();
+        ref_image += ((line_segment.0, line_segment.1), line_segment.2, 1.0, 0.2);
+        let mut line_segments = vec![line_segment];
+        let perturb = Perturb {
+            rounds: 3,
+            fraction: 1.0,
+            seed: Some(0),
+        };
+        perturb.optimize(&problem, &mut ref_image, &mut line_segments, &mut |_, _| ());
+        assert_eq!(vec![line_segment], line_segments);
+    }
+
+    #[test]
+    fn test_luminance_first_colors_every_string() {
+        let args = args(&["--max-strings", "20"]);
+        let pins = pins();
+        let (red, blue) = (Rgb::new(255, 0, 0), Rgb::new(0, 0, 255));
+        let rgbs = [red, blue];
+        let constraints = Constraints::default();
+        let problem = Problem::new(&args, &pins, &rgbs, &constraints).unwrap();
+
+        // Red on the top half and blue on the bottom half
+        let mut target = RefImage::new(10, 10);
+        (0..10).for_each(|x| (0..10).for_each(|y| target[(x, y)] = -[red, blue][y as usize / 5]));
+        let mut ref_image = RefImage::new(10, 10).add_ref_image(&target);
+        let mut line_segments = Vec::new();
+        LuminanceFirst {
+            inner: Box::new(GreedyBatch),
+        }
+        .optimize(&problem, &mut ref_image, &mut line_segments, &mut |_, _| ());
+
+        assert!(!line_segments.is_empty());
+        assert!(line_segments.iter().all(|(_, _, rgb)| rgbs.contains(rgb)));
+        assert!(ref_image.score() < target.score());
+        let mut expected = target;
+        line_segments
+            .iter()
+            .for_each(|(a, b, rgb)| expected += ((*a, *b), *rgb, 1.0, 0.2));
+        assert_eq!(expected.score(), ref_image.score());
+    }
+
+    #[test]
+    fn test_layers_place_each_color_in_turn() {
+        let args = args(&["--max-strings", "60"]);
+        let pins = pins();
+        // Cyan, magenta, and yellow on white, relative to the background
+        let rgbs = [
+            Rgb::new(-255, 0, 0),
+            Rgb::new(0, -255, 0),
+            Rgb::new(0, 0, -255),
+        ];
+        let constraints = Constraints::default();
+        let problem = Problem::new(&args, &pins, &rgbs, &constraints).unwrap();
+
+        // A black target on a white background needs every layer
+        let mut ref_image = RefImage::new(10, 10).add_rgb(Rgb::WHITE);
+        let before = ref_image.score();
+        let mut line_segments = Vec::new();
+        Layers {
+            inner: Box::new(GreedyBatch),
+        }
+        .optimize(&problem, &mut ref_image, &mut line_segments, &mut |_, _| ());
+
+        assert!(ref_image.score() < before);
+        assert!(line_segments.len() <= 60);
+        let colors: Vec<Rgb> = line_segments.iter().map(|(_, _, rgb)| *rgb).collect();
+        let mut layers = colors.clone();
+        layers.dedup();
+        assert_eq!(rgbs.to_vec(), layers);
+    }
+
+    #[test]
+    fn test_luminance_levels() {
+        let levels = luminance_levels(&[
+            Rgb::new(30, 30, 30),
+            Rgb::new(90, 0, 0),
+            Rgb::new(-60, -60, -60),
+        ]);
+        assert_eq!(vec![Rgb::new(30, 30, 30), Rgb::new(-60, -60, -60)], levels);
+    }
+
+    #[test]
+    fn test_cancelled_runs_stop() {
+        for strategy in [
+            Strategy::GreedyBatch,
+            Strategy::GreedyWalk,
+            Strategy::Annealing,
+            Strategy::Beam,
+        ] {
+            let args = args(&[]);
+            args.cancellation.cancel();
+            let pins = pins();
+            let rgbs = [Rgb::new(255, 255, 255)];
+            let constraints = Constraints::default();
+            let problem = Problem::new(&args, &pins, &rgbs, &constraints).unwrap();
+            let mut line_segments = Vec::new();
+            strategy.optimizer(&args).optimize(
+                &problem,
+                &mut ref_image(),
+                &mut line_segments,
+                &mut |_, _| (),
+            );
+            assert!(line_segments.is_empty(), "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn test_max_strings_is_respected() {
+        for strategy in [
+            Strategy::GreedyBatch,
+            Strategy::GreedyWalk,
+            Strategy::Annealing,
+            Strategy::Beam,
+        ] {
+            let (_, line_segments) = run(strategy, &["--max-strings", "3"]);
+            assert!(line_segments.len() <= 3, "{:?}", strategy);
+        }
+    }
+}
@@ -1,50 +1,383 @@
+use crate::channel::Channel;
+use crate::geometry::Line;
 use crate::geometry::Point;
 use crate::imagery::LineSegment;
+use crate::imagery::Pen;
 use crate::imagery::RefImage;
 use crate::imagery::Rgb;
-use crate::rayon::iter::IndexedParallelIterator;
-use crate::rayon::iter::IntoParallelRefIterator;
-use crate::rayon::iter::ParallelIterator;
+use crate::parallel::*;
+use std::collections::HashSet;
 
-pub fn find_best_points(
+/// Restrictions on which strings the optimizer may add or remove. Colors are relative to the
+/// background color, like the rest of the optimizer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Constraints {
+    /// Strings that may not be removed
+    pub locked: HashSet<LineSegment>,
+    /// Strings that may not be added
+    pub banned: HashSet<LineSegment>,
+    /// The most strings that may cross any one pixel. The `RefImage` must be counting coverage.
+    pub max_overlap: Option<u32>,
+    /// The smallest angle in degrees between two strings at the same pin. The `RefImage` must be
+    /// tracking pin ends.
+    pub min_pin_angle: Option<f64>,
+    /// The shortest string, in pixels, that may be added
+    pub min_length: Option<f64>,
+    /// Pixels that no string may cross
+    pub excluded: HashSet<Point>,
+    /// Pixels hidden by the mask, which strings may cross but not lie mostly on
+    pub masked: HashSet<Point>,
+    /// When given, the only strings that may be added
+    pub allowed: Option<HashSet<LineSegment>>,
+    /// Added to the score change of every string added, and taken from every string removed, so
+    /// strings that barely improve the image aren't worth placing
+    pub string_cost: i64,
+}
+
+impl Constraints {
+    fn is_locked(&self, (a, b, rgb): &LineSegment) -> bool {
+        self.locked.contains(&(*a, *b, *rgb)) || self.locked.contains(&(*b, *a, *rgb))
+    }
+
+    fn is_banned(&self, (a, b, rgb): &LineSegment) -> bool {
+        self.banned.contains(&(*a, *b, *rgb))
+            || self.banned.contains(&(*b, *a, *rgb))
+            || self.allowed.as_ref().is_some_and(|allowed| {
+                !allowed.contains(&(*a, *b, *rgb)) && !allowed.contains(&(*b, *a, *rgb))
+            })
+    }
+}
+
+/// How much adding the string would change the score, or `None` if the constraints forbid it
+pub fn score_on_add<C: Channel>(
+    line_segment: LineSegment,
+    ref_image: &RefImage<C>,
+    pen: &Pen,
+    constraints: &Constraints,
+) -> Option<i64> {
+    if constraints.is_banned(&line_segment) {
+        return None;
+    }
+    if constraints
+        .min_length
+        .is_some_and(|min| Line::from(line_segment).length() < min)
+    {
+        return None;
+    }
+    let pix_line = pen.pix_line(line_segment);
+    if pix_line.crosses(&constraints.excluded) {
+        return None;
+    }
+    if !constraints.masked.is_empty() && pix_line.fraction_on(&constraints.masked) > 0.5 {
+        return None;
+    }
+    if let Some(max) = constraints.max_overlap {
+        if !ref_image.has_room_for(&pix_line, max) {
+            return None;
+        }
+    }
+    if let Some(min) = constraints.min_pin_angle {
+        if !ref_image.has_angle_for(&pix_line, min) {
+            return None;
+        }
+    }
+    Some(ref_image.score_change_on_add(pix_line) + constraints.string_cost)
+}
+
+/// How much removing the string would change the score, or `None` if the constraints forbid it
+pub fn score_on_sub<C: Channel>(
+    line_segment: LineSegment,
+    ref_image: &RefImage<C>,
+    pen: &Pen,
+    constraints: &Constraints,
+) -> Option<i64> {
+    if constraints.is_locked(&line_segment) {
+        return None;
+    }
+    Some(ref_image.score_change_on_sub(pen.pix_line(line_segment)) - constraints.string_cost)
+}
+
+pub fn find_best_points<C: Channel>(
     pins: &[Point],
-    ref_image: &RefImage,
-    step_size: f64,
-    string_alpha: f64,
+    ref_image: &RefImage<C>,
+    pen: &Pen,
     rgbs: &[Rgb],
+    constraints: &Constraints,
     max: usize,
 ) -> Vec<(LineSegment, i64)> {
-    let mut lines = pins
+    let lines = pins
         .par_iter()
         .enumerate()
         .flat_map(|(i, a)| pins.par_iter().skip(i).map(move |b| (a, b)))
         .flat_map(|(a, b)| rgbs.par_iter().map(move |rgb| (*a, *b, *rgb)))
-        .map(|(a, b, rgb)| {
-            let score = ref_image.score_change_on_add(((a, b), rgb, step_size, string_alpha));
-            ((a, b, rgb), score)
+        .filter_map(|line_segment| {
+            score_on_add(line_segment, ref_image, pen, constraints).map(|s| (line_segment, s))
         })
         .filter(|(_, s)| *s < 0)
         .collect::<Vec<_>>();
+    best(lines, max)
+}
+
+/// The `max` lines that lower the score the most
+pub fn best<T>(mut lines: Vec<(T, i64)>, max: usize) -> Vec<(T, i64)> {
     lines.sort_unstable_by_key(|(_, s)| *s);
     lines.into_iter().take(max).collect()
 }
 
-pub fn find_worst_points(
+pub fn find_worst_points<C: Channel>(
     points: &[LineSegment],
-    ref_image: &RefImage,
-    step_size: f64,
-    string_alpha: f64,
+    ref_image: &RefImage<C>,
+    pen: &Pen,
+    constraints: &Constraints,
     max: usize,
 ) -> Vec<(usize, i64)> {
-    let mut lines = points
+    let lines = points
         .par_iter()
         .enumerate()
-        .map(|(i, (a, b, rgb))| {
-            let score = ref_image.score_change_on_sub(((*a, *b), *rgb, step_size, string_alpha));
-            (i, score)
+        .filter_map(|(i, line_segment)| {
+            score_on_sub(*line_segment, ref_image, pen, constraints).map(|s| (i, s))
         })
         .filter(|(_, s)| *s < 0)
         .collect::<Vec<_>>();
-    lines.sort_unstable_by_key(|(_, s)| *s);
-    lines.into_iter().take(max).collect()
+    best(lines, max)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::blend::Blend;
+
+    fn ref_image() -> RefImage {
+        // A white horizontal line on the top row
+        let mut ref_image = RefImage::new(5, 5);
+        (0..5).for_each(|x| ref_image[(x, 0)] = -Rgb::WHITE);
+        ref_image
+    }
+
+    fn pen() -> Pen {
+        Pen {
+            step_size: 1.0,
+            string_alpha: 1.0,
+            gradient: None,
+            blend: Blend::Srgb,
+            background: Rgb::BLACK,
+            traces: None,
+        }
+    }
+
+    fn pins() -> Vec<Point> {
+        vec![Point::new(0, 0), Point::new(4, 0), Point::new(4, 4)]
+    }
+
+    #[test]
+    fn test_find_best_points() {
+        let best = find_best_points(
+            &pins(),
+            &ref_image(),
+            &pen(),
+            &[Rgb::WHITE],
+            &Constraints::default(),
+            1,
+        );
+        assert_eq!(
+            vec![(Point::new(0, 0), Point::new(4, 0), Rgb::WHITE)],
+            best.into_iter().map(|(l, _)| l).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_string_cost() {
+        let line_segment = (Point::new(0, 0), Point::new(4, 0), Rgb::WHITE);
+        let free = score_on_add(line_segment, &ref_image(), &pen(), &Constraints::default());
+        let constraints = Constraints {
+            string_cost: 1000,
+            ..Constraints::default()
+        };
+        let costly = score_on_add(line_segment, &ref_image(), &pen(), &constraints);
+        assert_eq!(free.map(|s| s + 1000), costly);
+
+        // The top row is worth less than its cost when the cost is high enough
+        let constraints = Constraints {
+            string_cost: -free.unwrap(),
+            ..Constraints::default()
+        };
+        let best = find_best_points(
+            &pins(),
+            &ref_image(),
+            &pen(),
+            &[Rgb::WHITE],
+            &constraints,
+            usize::MAX,
+        );
+        assert!(best.is_empty());
+    }
+
+    #[test]
+    fn test_find_best_points_skips_masked_strings() {
+        // Mask most of the top row, so the string along it lies mostly in the mask
+        let constraints = Constraints {
+            masked: (0..4).map(|x| Point::new(x, 0)).collect(),
+            ..Constraints::default()
+        };
+        let best = find_best_points(
+            &pins(),
+            &ref_image(),
+            &pen(),
+            &[Rgb::WHITE],
+            &constraints,
+            usize::MAX,
+        );
+        assert!(best
+            .iter()
+            .all(|((a, b, _), _)| (a.y, b.y) != (0, 0) || a.x.abs_diff(b.x) < 2));
+    }
+
+    #[test]
+    fn test_find_best_points_skips_banned_strings() {
+        let constraints = Constraints {
+            banned: HashSet::from([(Point::new(4, 0), Point::new(0, 0), Rgb::WHITE)]),
+            ..Constraints::default()
+        };
+        let best = find_best_points(
+            &pins(),
+            &ref_image(),
+            &pen(),
+            &[Rgb::WHITE],
+            &constraints,
+            usize::MAX,
+        );
+        assert!(best
+            .iter()
+            .all(|((a, b, _), _)| (a.x, b.x) != (0, 4) || (a.y, b.y) != (0, 0)));
+    }
+
+    #[test]
+    fn test_find_best_points_only_adds_allowed_strings() {
+        let lines = |allowed: LineSegment| {
+            let constraints = Constraints {
+                allowed: Some(HashSet::from([allowed])),
+                ..Constraints::default()
+            };
+            find_best_points(
+                &pins(),
+                &ref_image(),
+                &pen(),
+                &[Rgb::WHITE],
+                &constraints,
+                usize::MAX,
+            )
+            .into_iter()
+            .map(|(l, _)| l)
+            .collect::<Vec<_>>()
+        };
+        let top_row = (Point::new(4, 0), Point::new(0, 0), Rgb::WHITE);
+        let diagonal = (Point::new(4, 4), Point::new(0, 0), Rgb::WHITE);
+        assert_eq!(
+            vec![(Point::new(0, 0), Point::new(4, 0), Rgb::WHITE)],
+            lines(top_row)
+        );
+        // The diagonal makes the image worse
+        assert!(lines(diagonal).is_empty());
+    }
+
+    #[test]
+    fn test_find_best_points_respects_max_overlap() {
+        let mut ref_image = ref_image().with_coverage();
+        ref_image += ((Point::new(0, 0), Point::new(4, 0)), Rgb::BLACK, 1.0, 1.0);
+        let constraints = Constraints {
+            max_overlap: Some(1),
+            ..Constraints::default()
+        };
+        let best = find_best_points(
+            &pins(),
+            &ref_image,
+            &pen(),
+            &[Rgb::WHITE],
+            &constraints,
+            usize::MAX,
+        );
+        assert!(best
+            .iter()
+            .all(|((a, b, _), _)| (a.x, b.x) != (0, 4) || (a.y, b.y) != (0, 0)));
+    }
+
+    #[test]
+    fn test_find_best_points_respects_min_pin_angle() {
+        let mut ref_image = ref_image().with_pin_ends();
+        ref_image += ((Point::new(0, 0), Point::new(4, 4)), Rgb::BLACK, 1.0, 1.0);
+        let constraints = Constraints {
+            min_pin_angle: Some(60.0),
+            ..Constraints::default()
+        };
+        let best = find_best_points(
+            &pins(),
+            &ref_image,
+            &pen(),
+            &[Rgb::WHITE],
+            &constraints,
+            usize::MAX,
+        );
+        // The top row leaves (0, 0) at 45 degrees from the diagonal
+        assert!(best
+            .iter()
+            .all(|((a, b, _), _)| (a.x, b.x) != (0, 4) || (a.y, b.y) != (0, 0)));
+    }
+
+    #[test]
+    fn test_find_best_points_respects_min_length() {
+        let constraints = Constraints {
+            min_length: Some(5.0),
+            ..Constraints::default()
+        };
+        let best = find_best_points(
+            &pins(),
+            &ref_image(),
+            &pen(),
+            &[Rgb::WHITE],
+            &constraints,
+            usize::MAX,
+        );
+        // The white top row is only 4 pixels long
+        assert!(best.is_empty());
+    }
+
+    #[test]
+    fn test_find_best_points_avoids_excluded_pixels() {
+        let constraints = Constraints {
+            excluded: HashSet::from([Point::new(2, 0)]),
+            ..Constraints::default()
+        };
+        let best = find_best_points(
+            &pins(),
+            &ref_image(),
+            &pen(),
+            &[Rgb::WHITE],
+            &constraints,
+            usize::MAX,
+        );
+        assert!(best
+            .iter()
+            .all(|((a, b, _), _)| (a.x, b.x) != (0, 4) || (a.y, b.y) != (0, 0)));
+    }
+
+    #[test]
+    fn test_find_worst_points_skips_locked_strings() {
+        let line_segments = vec![
+            (Point::new(0, 4), Point::new(4, 4), Rgb::WHITE),
+            (Point::new(0, 3), Point::new(4, 3), Rgb::WHITE),
+        ];
+        let mut ref_image = ref_image();
+        line_segments.iter().for_each(|(a, b, rgb)| {
+            ref_image += ((*a, *b), *rgb, 1.0, 1.0);
+        });
+        let constraints = Constraints {
+            locked: HashSet::from([line_segments[0]]),
+            ..Constraints::default()
+        };
+        let worst = find_worst_points(&line_segments, &ref_image, &pen(), &constraints, 10);
+        assert_eq!(
+            vec![1],
+            worst.into_iter().map(|(i, _)| i).collect::<Vec<_>>()
+        );
+    }
 }
@@ -0,0 +1,132 @@
+use crate::cli_app::{self, Args};
+use crate::error::StringArtError;
+use std::path::Path;
+
+/// Everything that would stop the run from writing its files, found before the run starts so a
+/// long run doesn't fail at the end. Existing files are problems unless `--force` is given.
+/// Filepaths with placeholders, or that are renamed for each frame or comparison, can't be
+/// known until the run finishes, so only their directories are checked.
+pub fn problems(args: &Args) -> Vec<String> {
+    let exact_names =
+        args.frames.is_empty() && args.morph_target.is_none() && !args.compare_arrangements;
+    let mut problems = Vec::new();
+    for (_, filepath) in args.outputs() {
+        let path = Path::new(filepath);
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        if !directory.is_dir() {
+            problems.push(format!("The directory for '{}' does not exist", filepath));
+        } else if !writable(directory) {
+            problems.push(format!("The directory for '{}' is not writable", filepath));
+        } else if exact_names
+            && !args.force
+            && !filepath.contains('{')
+            && path.exists()
+            && args.resume.as_ref() != Some(filepath)
+        {
+            problems.push(format!(
+                "'{}' already exists. Use --force to overwrite it.",
+                filepath
+            ));
+        }
+    }
+    problems
+}
+
+/// Whether a file can be created in the directory, found by creating and removing one
+fn writable(directory: &Path) -> bool {
+    let probe = directory.join(format!(".string_art_write_check_{}", std::process::id()));
+    let created = std::fs::File::create(&probe).is_ok();
+    if created {
+        let _ = std::fs::remove_file(&probe);
+    }
+    created
+}
+
+/// Every problem before the run starts, if there are any
+pub fn check(args: &Args) -> Result<(), StringArtError> {
+    match problems(args).as_slice() {
+        [] => Ok(()),
+        problems => Err(cli_app::arg_error(
+            clap::error::ErrorKind::ValueValidation,
+            problems.join("\n"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::style::Data;
+
+    fn args(output: &str, force: bool) -> Args {
+        let mut args = Data::example(&[]).args;
+        args.output_filepaths = vec![output.to_owned()];
+        args.force = force;
+        args
+    }
+
+    #[test]
+    fn test_existing_file_needs_force() {
+        let filepath = std::env::temp_dir().join("string_art_overwrite.png");
+        let filepath = filepath.to_str().unwrap();
+        std::fs::write(filepath, b"").unwrap();
+        let without_force = problems(&args(filepath, false));
+        let with_force = problems(&args(filepath, true));
+        std::fs::remove_file(filepath).unwrap();
+
+        assert_eq!(
+            vec![format!(
+                "'{}' already exists. Use --force to overwrite it.",
+                filepath
+            )],
+            without_force
+        );
+        assert_eq!(Vec::<String>::new(), with_force);
+    }
+
+    #[test]
+    fn test_resumed_data_file_is_overwritten() {
+        let filepath = std::env::temp_dir().join("string_art_overwrite_resume.json");
+        let filepath = filepath.to_str().unwrap();
+        std::fs::write(filepath, b"").unwrap();
+        let mut args = Data::example(&["--resume", filepath]).args;
+        let resumed = problems(&args);
+        args.resume = None;
+        let not_resumed = problems(&args);
+        std::fs::remove_file(filepath).unwrap();
+        assert_eq!(Vec::<String>::new(), resumed);
+        assert_eq!(1, not_resumed.len());
+    }
+
+    #[test]
+    fn test_new_file_is_fine() {
+        let filepath = std::env::temp_dir().join("string_art_overwrite_new.png");
+        assert_eq!(
+            Vec::<String>::new(),
+            problems(&args(filepath.to_str().unwrap(), false))
+        );
+    }
+
+    #[test]
+    fn test_placeholders_are_not_checked_for_existence() {
+        let filepath = std::env::temp_dir().join("string_art_{pins}.png");
+        let filepath = filepath.to_str().unwrap();
+        std::fs::write(filepath, b"").unwrap();
+        let problems = problems(&args(filepath, false));
+        std::fs::remove_file(filepath).unwrap();
+        assert_eq!(Vec::<String>::new(), problems);
+    }
+
+    #[test]
+    fn test_missing_directory() {
+        let filepath = std::env::temp_dir().join("string_art_no_such_dir/out.png");
+        let filepath = filepath.to_str().unwrap();
+        assert_eq!(
+            vec![format!("The directory for '{}' does not exist", filepath)],
+            problems(&args(filepath, false))
+        );
+    }
+}
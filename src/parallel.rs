@@ -0,0 +1,38 @@
+//! Rayon's parallel iterators with the `parallel` feature. Without it, `par_iter` and
+//! `into_par_iter` give ordinary iterators instead, and the work runs on the calling thread.
+
+#[cfg(feature = "parallel")]
+pub use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
+
+#[cfg(not(feature = "parallel"))]
+pub use sequential::{IntoParallelIterator, IntoParallelRefIterator};
+
+#[cfg(not(feature = "parallel"))]
+mod sequential {
+    pub trait IntoParallelIterator: IntoIterator + Sized {
+        fn into_par_iter(self) -> Self::IntoIter {
+            self.into_iter()
+        }
+    }
+
+    impl<T: IntoIterator> IntoParallelIterator for T {}
+
+    pub trait IntoParallelRefIterator<'a> {
+        type Iter: Iterator;
+
+        fn par_iter(&'a self) -> Self::Iter;
+    }
+
+    impl<'a, T: 'a + ?Sized> IntoParallelRefIterator<'a> for T
+    where
+        &'a T: IntoIterator,
+    {
+        type Iter = <&'a T as IntoIterator>::IntoIter;
+
+        fn par_iter(&'a self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::geometry::Point;
+use crate::imagery::Rgb;
+use crate::serde::Serialize;
+use crate::style::Data;
+use std::collections::HashMap;
+
+/// How many strings of one color are attached to a pin
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColorCount {
+    pub rgb: Rgb,
+    pub strings: usize,
+}
+
+/// The load on one pin of a finished run, for checking that the pins are strong enough and the
+/// thread wrapped around them won't get too thick
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PinStats {
+    pub pin: usize,
+    pub location: Point,
+    /// Strings attached to the pin, of every color
+    pub strings: usize,
+    /// Strings attached to the pin for each foreground color, in the order they were given
+    pub per_color: Vec<ColorCount>,
+}
+
+/// The stats of every pin, in the order of the pin locations. A string counts once at each of
+/// its ends.
+pub fn pin_stats(data: &Data) -> Vec<PinStats> {
+    let mut colors = data.args.foreground_colors.clone();
+    data.line_segments.iter().for_each(|(_, _, rgb)| {
+        if !colors.contains(rgb) {
+            colors.push(*rgb);
+        }
+    });
+    let mut counts: HashMap<(Point, Rgb), usize> = HashMap::new();
+    for (a, b, rgb) in &data.line_segments {
+        *counts.entry((*a, *rgb)).or_default() += 1;
+        *counts.entry((*b, *rgb)).or_default() += 1;
+    }
+
+    data.pin_locations
+        .iter()
+        .enumerate()
+        .map(|(pin, location)| {
+            let per_color: Vec<ColorCount> = colors
+                .iter()
+                .map(|rgb| ColorCount {
+                    rgb: *rgb,
+                    strings: counts.get(&(*location, *rgb)).copied().unwrap_or(0),
+                })
+                .collect();
+            PinStats {
+                pin,
+                location: *location,
+                strings: per_color.iter().map(|count| count.strings).sum(),
+                per_color,
+            }
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+
+    const RED: Rgb = Rgb { r: 255, g: 0, b: 0 };
+
+    #[test]
+    fn test_pin_stats() {
+        let mut data = Data::example(&[
+            "--foreground-color",
+            "#FFFFFF",
+            "--foreground-color",
+            "#FF0000",
+        ]);
+        let pins = data.pin_locations.clone();
+        data.line_segments = vec![
+            (pins[0], pins[1], Rgb::WHITE),
+            (pins[1], pins[2], RED),
+            (pins[2], pins[0], RED),
+        ];
+        let stats = pin_stats(&data);
+
+        assert_eq!(
+            vec![2, 2, 2],
+            stats.iter().map(|s| s.strings).collect::<Vec<_>>()
+        );
+        let per_color =
+            |pin: usize| -> Vec<usize> { stats[pin].per_color.iter().map(|c| c.strings).collect() };
+        assert_eq!(vec![1, 1], per_color(0));
+        assert_eq!(vec![1, 1], per_color(1));
+        assert_eq!(vec![0, 2], per_color(2));
+        assert_eq!(RED, stats[2].per_color[1].rgb);
+    }
+
+    #[test]
+    fn test_pin_stats_are_written_to_the_data_file() {
+        let value = serde_json::to_value(Data::example(&[])).unwrap();
+        assert_eq!(3, value["pin_stats"].as_array().unwrap().len());
+        assert_eq!(1, value["pin_stats"][1]["strings"]);
+        assert_eq!(0, value["pin_stats"][2]["per_color"][0]["strings"]);
+    }
+}
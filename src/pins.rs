@@ -1,46 +1,258 @@
-use crate::geometry::Point;
+use crate::cli_app::{seeded_rng, Args};
+use crate::geometry::{Line, Point};
 use crate::rand::RngCore;
-use crate::serde::Serialize;
+use crate::serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
 const P: fn(u32, u32) -> Point = Point::new;
 
+/// How far apart `poisson` pins start out, as a fraction of the spacing of a square grid of as
+/// many pins. Random placement jams well before reaching the grid's spacing.
+const POISSON_SPACING: f64 = 0.75;
+
+/// How many pins in a row `poisson` can fail to place before it lets pins be closer together
+const POISSON_ATTEMPTS: u32 = 1000;
+
 pub fn generate(
-    pin_arrangement: &PinArrangement,
+    pin_arrangement: &dyn PinArrangement,
     desired_count: u32,
     width: u32,
     height: u32,
 ) -> Vec<Point> {
-    generator(pin_arrangement)(desired_count, width, height)
+    pin_arrangement.generate(desired_count, width, height)
+}
+
+/// Place the pins the arguments ask for, including the orientation of a circle, inset from the
+/// edges of the image by the pin margin (percentages are of the shorter side) and no closer
+/// together than the minimum pin spacing
+pub fn from_args(args: &Args, width: u32, height: u32) -> Vec<Point> {
+    let shorter = u32::min(width, height);
+    // Always leave at least the middle pixel for the pins
+    let margin = u32::min(args.pin_margin.in_pixels(shorter), (shorter - 1) / 2);
+    let (width, height) = (width - 2 * margin, height - 2 * margin);
+    let arrangement = arrangement(args);
+    match args.min_pin_spacing {
+        Some(min_spacing) => {
+            let spaced = Spaced {
+                inner: arrangement.as_ref(),
+                min_spacing,
+            };
+            generate(&spaced, args.pin_count, width, height)
+        }
+        None => generate(arrangement.as_ref(), args.pin_count, width, height),
+    }
+    .into_iter()
+    .map(|point| P(point.x + margin, point.y + margin))
+    .collect()
+}
+
+fn arrangement(args: &Args) -> Box<dyn PinArrangement> {
+    match args.pin_arrangement {
+        Arrangement::Circle => Box::new(Circle {
+            start_angle: args.circle_start_angle,
+            direction: args.circle_direction,
+        }),
+        Arrangement::Rings => Box::new(Rings {
+            count: args.ring_count,
+        }),
+        Arrangement::Random => Box::new(Random { seed: args.seed }),
+        Arrangement::Poisson => Box::new(Poisson { seed: args.seed }),
+        ref arrangement => Box::new(arrangement.clone()),
+    }
+}
+
+/// Places pins on an image. Implement this to arrange pins in a way the CLI does not provide.
+/// Any `Fn(desired_count, width, height) -> Vec<Point>` is an arrangement.
+pub trait PinArrangement {
+    /// Place approximately `desired_count` pins within a `width` by `height` image
+    fn generate(&self, desired_count: u32, width: u32, height: u32) -> Vec<Point>;
+}
+
+impl<F: Fn(u32, u32, u32) -> Vec<Point>> PinArrangement for F {
+    fn generate(&self, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+        self(desired_count, width, height)
+    }
+}
+
+/// Pins evenly spaced around the edge of the image
+pub struct Perimeter;
+
+/// Pins in evenly spaced rows and columns across the entire image
+pub struct Grid;
+
+/// Pins evenly spaced around the largest possible centered circle. Pin 0 is `start_angle`
+/// degrees clockwise from 12 o'clock, and the rest are numbered in `direction` from there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub start_angle: f64,
+    pub direction: Direction,
+}
+
+/// Which way the pins of a circle are numbered, as seen looking at the image
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Direction {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl core::str::FromStr for Direction {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "clockwise" => Ok(Direction::Clockwise),
+            "counter-clockwise" => Ok(Direction::CounterClockwise),
+            _ => Err(format!("Invalid circle direction: \"{}\"", string)),
+        }
+    }
+}
+
+impl Default for Circle {
+    fn default() -> Self {
+        Self {
+            start_angle: 0.0,
+            direction: Direction::Clockwise,
+        }
+    }
+}
+
+impl PinArrangement for Circle {
+    fn generate(&self, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+        circle(self, desired_count, width, height)
+    }
+}
+
+/// Pins spread across `count` concentric circles, the outermost being the largest possible
+/// centered circle. Each ring gets pins in proportion to its size, so they're about as far apart
+/// on every ring, and pin 0 is at 12 o'clock on the outermost ring.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rings {
+    pub count: u32,
+}
+
+impl Default for Rings {
+    fn default() -> Self {
+        Self { count: 3 }
+    }
+}
+
+impl PinArrangement for Rings {
+    fn generate(&self, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+        rings(self, desired_count, width, height)
+    }
+}
+
+/// Pins scattered randomly across the image. The same `seed` always scatters them the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Random {
+    pub seed: Option<u64>,
+}
+
+impl PinArrangement for Random {
+    fn generate(&self, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+        random(self, desired_count, width, height)
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
-pub enum PinArrangement {
+/// Pins scattered randomly across the image, but never closer together than a minimum distance,
+/// so they cover it evenly without the clumps and gaps of `Random`. The distance is set by how
+/// many pins there are, and is shortened only if there's no room left for the rest.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Poisson {
+    pub seed: Option<u64>,
+}
+
+impl PinArrangement for Poisson {
+    fn generate(&self, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+        poisson(self, desired_count, width, height)
+    }
+}
+
+/// The pins of another arrangement, leaving out any closer than `min_spacing` pixels to an
+/// earlier pin, so fewer pins than asked for may be placed
+pub struct Spaced<'a> {
+    pub inner: &'a dyn PinArrangement,
+    pub min_spacing: f64,
+}
+
+impl PinArrangement for Spaced<'_> {
+    fn generate(&self, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+        let pins = self.inner.generate(desired_count, width, height);
+        pins.into_iter().fold(Vec::new(), |mut spaced, pin| {
+            if spaced
+                .iter()
+                .all(|p| Line::from((*p, pin)).length() >= self.min_spacing)
+            {
+                spaced.push(pin)
+            }
+            spaced
+        })
+    }
+}
+
+macro_rules! provided_arrangement {
+    ($arrangement:ty, $generator:expr) => {
+        impl PinArrangement for $arrangement {
+            fn generate(&self, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+                $generator(desired_count, width, height)
+            }
+        }
+    };
+}
+
+provided_arrangement!(Perimeter, perimeter);
+provided_arrangement!(Grid, grid);
+
+/// The arrangements that can be chosen by name from the CLI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Arrangement {
     Perimeter,
     Grid,
     Circle,
     Random,
+    Rings,
+    Poisson,
+}
+
+impl Arrangement {
+    pub const ALL: [Arrangement; 6] = [
+        Arrangement::Perimeter,
+        Arrangement::Grid,
+        Arrangement::Circle,
+        Arrangement::Random,
+        Arrangement::Rings,
+        Arrangement::Poisson,
+    ];
 }
 
-impl core::str::FromStr for PinArrangement {
+impl core::str::FromStr for Arrangement {
     type Err = String;
     fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
         match string {
-            "perimeter" => Ok(PinArrangement::Perimeter),
-            "grid" => Ok(PinArrangement::Grid),
-            "circle" => Ok(PinArrangement::Circle),
-            "random" => Ok(PinArrangement::Random),
+            "perimeter" => Ok(Arrangement::Perimeter),
+            "grid" => Ok(Arrangement::Grid),
+            "circle" => Ok(Arrangement::Circle),
+            "random" => Ok(Arrangement::Random),
+            "rings" => Ok(Arrangement::Rings),
+            "poisson" => Ok(Arrangement::Poisson),
             _ => Err(format!("Invalid pin arrangement: \"{}\"", string)),
         }
     }
 }
 
-fn generator(pin_arrangement: &PinArrangement) -> fn(u32, u32, u32) -> Vec<Point> {
-    match pin_arrangement {
-        PinArrangement::Perimeter => perimeter,
-        PinArrangement::Grid => grid,
-        PinArrangement::Circle => circle,
-        PinArrangement::Random => random,
+impl PinArrangement for Arrangement {
+    fn generate(&self, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+        let arrangement: &dyn PinArrangement = match self {
+            Arrangement::Perimeter => &Perimeter,
+            Arrangement::Grid => &Grid,
+            Arrangement::Circle => &Circle {
+                start_angle: 0.0,
+                direction: Direction::Clockwise,
+            },
+            Arrangement::Random => &Random::default(),
+            Arrangement::Rings => &Rings::default(),
+            Arrangement::Poisson => &Poisson::default(),
+        };
+        arrangement.generate(desired_count, width, height)
     }
 }
 
@@ -81,40 +293,166 @@ fn grid(desired_count: u32, width: u32, height: u32) -> Vec<Point> {
         .collect()
 }
 
-fn random(desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+fn random(random: &Random, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
     let desired_count = u32::min(width * height, desired_count);
-    let mut points = HashSet::new();
-    let mut rng = rand::thread_rng();
-    loop {
-        if points.len() == desired_count as usize {
-            return points.into_iter().collect();
+    let mut seen = HashSet::new();
+    let mut points = Vec::new();
+    let mut rng = seeded_rng(random.seed);
+    // Kept in the order they were placed, since a set's order differs between runs
+    while points.len() < desired_count as usize {
+        let point = P(rng.next_u32() % width, rng.next_u32() % height);
+        if seen.insert(point) {
+            points.push(point);
+        }
+    }
+    points
+}
+
+fn poisson(poisson: &Poisson, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+    let desired_count = u32::min(width * height, desired_count);
+    let mut min_distance =
+        POISSON_SPACING * (width as f64 * height as f64 / u32::max(1, desired_count) as f64).sqrt();
+    let mut points: Vec<Point> = Vec::new();
+    let mut misses = 0;
+    let mut rng = seeded_rng(poisson.seed);
+    while points.len() < desired_count as usize {
+        let point = P(rng.next_u32() % width, rng.next_u32() % height);
+        if points
+            .iter()
+            .all(|p| Line::from((*p, point)).length() >= min_distance)
+        {
+            points.push(point);
+            misses = 0;
+        } else if misses == POISSON_ATTEMPTS {
+            min_distance *= 0.9;
+            misses = 0;
         } else {
-            points.insert(P(rng.next_u32() % width, rng.next_u32() % height));
+            misses += 1;
         }
     }
+    points
 }
 
-fn circle(desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+fn circle(orientation: &Circle, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
     let center_x = (width - 1) as f64 / 2.0;
     let center_y = (height - 1) as f64 / 2.0;
     let radius = f64::min(center_x, center_y);
-    let step_size = std::f64::consts::PI * 2.0 / desired_count as f64;
-    (0..desired_count).fold(Vec::new(), |mut points, step| {
-        let point = P(
-            ((radius * (step as f64 * step_size).cos()).round() + center_x) as u32,
-            ((radius * (step as f64 * step_size).sin()).round() + center_y) as u32,
-        );
-        if points.iter().all(|p| p != &point) {
-            points.push(point)
+    distinct(ring(orientation, desired_count, radius, center_x, center_y))
+}
+
+fn rings(rings: &Rings, desired_count: u32, width: u32, height: u32) -> Vec<Point> {
+    let center_x = (width - 1) as f64 / 2.0;
+    let center_y = (height - 1) as f64 / 2.0;
+    let radius = f64::min(center_x, center_y);
+    let count = u32::max(1, rings.count);
+    // Ring `k`, counting in from the outermost, has a radius in proportion to `count - k`
+    let total: u32 = (1..=count).sum();
+    let pins_within = |k: u32| {
+        let sizes: u32 = (0..k).map(|k| count - k).sum();
+        (desired_count as f64 * sizes as f64 / total as f64).round() as u32
+    };
+    distinct((0..count).flat_map(|k| {
+        let pins = pins_within(k + 1) - pins_within(k);
+        // Turn every other ring by half a step, so pins on neighboring rings don't line up
+        let orientation = Circle {
+            start_angle: (k % 2) as f64 * 180.0 / u32::max(1, pins) as f64,
+            ..Circle::default()
+        };
+        let radius = radius * (count - k) as f64 / count as f64;
+        ring(&orientation, pins, radius, center_x, center_y)
+    }))
+}
+
+/// The points in order, without the ones that round to the same pixel as an earlier point
+fn distinct(points: impl Iterator<Item = Point>) -> Vec<Point> {
+    points.fold(Vec::new(), |mut distinct, point| {
+        if distinct.iter().all(|p| p != &point) {
+            distinct.push(point)
         }
-        points
+        distinct
+    })
+}
+
+/// `desired_count` points evenly spaced around a circle, which may round to the same pixel
+fn ring(
+    orientation: &Circle,
+    desired_count: u32,
+    radius: f64,
+    center_x: f64,
+    center_y: f64,
+) -> impl Iterator<Item = Point> {
+    let step_size = match orientation.direction {
+        Direction::Clockwise => 1.0,
+        Direction::CounterClockwise => -1.0,
+    } * std::f64::consts::PI
+        * 2.0
+        / desired_count as f64;
+    // Angles are measured clockwise from 12 o'clock; the y axis points down the image
+    let start = orientation.start_angle.to_radians();
+    (0..desired_count).map(move |step| {
+        let angle = start + step as f64 * step_size;
+        P(
+            ((radius * angle.sin()).round() + center_x) as u32,
+            ((-radius * angle.cos()).round() + center_y) as u32,
+        )
     })
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "cli"))]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_named_arrangement_matches_provided_arrangement() {
+        assert_eq!(
+            Grid.generate(9, 25, 25),
+            generate(&Arrangement::Grid, 9, 25, 25)
+        );
+    }
+
+    #[test]
+    fn test_pin_margin_insets_the_arrangement() {
+        use crate::cli_app::Cli;
+        use clap::Parser;
+        let args = |margin: &str| {
+            let cli = Cli::parse_from(["string_art", "-i", "in.png", "--pin-margin", margin]);
+            Args::with_image(cli, image::DynamicImage::new_rgb8(25, 25))
+        };
+        let inset: Vec<Point> = from_args(&args("0"), 15, 15)
+            .into_iter()
+            .map(|p| P(p.x + 5, p.y + 5))
+            .collect();
+        assert_eq!(inset, from_args(&args("5"), 25, 25));
+        assert_eq!(inset, from_args(&args("20%"), 25, 25));
+        // A margin too wide for the image leaves the pins in the middle
+        assert!(from_args(&args("100"), 25, 25)
+            .iter()
+            .all(|p| *p == P(12, 12)));
+    }
+
+    #[test]
+    fn test_spaced_leaves_out_close_pins() {
+        let spaced = Spaced {
+            inner: &Perimeter,
+            min_spacing: 10.0,
+        };
+        // Perimeter pins 8 pixels apart, so every other one is left out
+        assert_eq!(
+            vec![P(0, 0), P(16, 0), P(24, 8), P(24, 24), P(8, 24), P(0, 16)],
+            generate(&spaced, 12, 25, 25)
+        );
+    }
+
+    #[test]
+    fn test_custom_arrangement() {
+        let diagonal = |count: u32, width: u32, height: u32| {
+            (0..count)
+                .map(|i| P(i * (width - 1) / count, i * (height - 1) / count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![P(0, 0), P(5, 5)], generate(&diagonal, 2, 11, 11));
+    }
+
     #[test]
     fn test_perimeter_specifying_0_points_works() {
         let pins = perimeter(0, 1234, 1234);
@@ -129,13 +467,13 @@ mod test {
 
     #[test]
     fn test_random_specifying_0_points_works() {
-        let pins = random(0, 1234, 1234);
+        let pins = random(&Random::default(), 0, 1234, 1234);
         assert_eq!(0, pins.len())
     }
 
     #[test]
     fn test_circle_specifying_0_points_works() {
-        let pins = circle(0, 1234, 1234);
+        let pins = circle(&Circle::default(), 0, 1234, 1234);
         assert_eq!(0, pins.len())
     }
 
@@ -153,13 +491,13 @@ mod test {
 
     #[test]
     fn test_random_specifying_too_many_pins_returns_maximum() {
-        let pins = random(600, 10, 10);
+        let pins = random(&Random::default(), 600, 10, 10);
         assert_eq!(100, pins.len())
     }
 
     #[test]
     fn test_circle_specifying_too_many_pins_returns_maximum() {
-        let pins = circle(600, 10, 10);
+        let pins = circle(&Circle::default(), 600, 10, 10);
         assert_eq!(34, pins.len())
     }
 
@@ -214,4 +552,111 @@ mod test {
             grid(9, 25, 25)
         )
     }
+
+    #[test]
+    fn test_circle_generate_pins_locations() {
+        let clockwise = Circle::default();
+        assert_eq!(
+            vec![P(12, 0), P(24, 12), P(12, 24), P(0, 12)],
+            circle(&clockwise, 4, 25, 25)
+        );
+        let counter_clockwise = Circle {
+            direction: Direction::CounterClockwise,
+            ..clockwise
+        };
+        assert_eq!(
+            vec![P(12, 0), P(0, 12), P(12, 24), P(24, 12)],
+            circle(&counter_clockwise, 4, 25, 25)
+        );
+        let from_3_oclock = Circle {
+            start_angle: 90.0,
+            ..clockwise
+        };
+        assert_eq!(
+            vec![P(24, 12), P(12, 24), P(0, 12), P(12, 0)],
+            circle(&from_3_oclock, 4, 25, 25)
+        );
+    }
+
+    #[test]
+    fn test_rings_generate_pins_locations() {
+        let two = Rings { count: 2 };
+        // The outer ring gets twice the pins of the inner one, which is turned by half a step
+        assert_eq!(
+            vec![
+                P(12, 0),
+                P(24, 12),
+                P(12, 24),
+                P(0, 12),
+                P(18, 12),
+                P(6, 12)
+            ],
+            rings(&two, 6, 25, 25)
+        );
+    }
+
+    #[test]
+    fn test_rings_generate_pins_count() {
+        for count in 1..=5 {
+            let pins = rings(&Rings { count }, 300, 1000, 800);
+            assert_eq!(300, pins.len(), "failed on ring count: {}", count);
+        }
+    }
+
+    #[test]
+    fn test_one_ring_is_a_circle() {
+        assert_eq!(
+            circle(&Circle::default(), 40, 100, 100),
+            rings(&Rings { count: 1 }, 40, 100, 100)
+        );
+    }
+
+    #[test]
+    fn test_seeded_random_repeats() {
+        let seeded = Random { seed: Some(7) };
+        assert_eq!(random(&seeded, 50, 100, 100), random(&seeded, 50, 100, 100));
+        assert_ne!(
+            random(&seeded, 50, 100, 100),
+            random(&Random { seed: Some(8) }, 50, 100, 100)
+        );
+    }
+
+    #[test]
+    fn test_poisson_specifying_0_points_works() {
+        let pins = poisson(&Poisson::default(), 0, 1234, 1234);
+        assert_eq!(0, pins.len())
+    }
+
+    #[test]
+    fn test_poisson_specifying_too_many_pins_returns_maximum() {
+        let pins = poisson(&Poisson::default(), 600, 10, 10);
+        assert_eq!(100, pins.len())
+    }
+
+    #[test]
+    fn test_poisson_keeps_pins_apart() {
+        let pins = poisson(&Poisson { seed: Some(7) }, 200, 300, 200);
+        assert_eq!(200, pins.len());
+        // A square grid of 200 pins over the image would space them about 17 pixels apart
+        let closest = pins
+            .iter()
+            .enumerate()
+            .flat_map(|(i, a)| pins[i + 1..].iter().map(|b| Line::from((*a, *b)).length()))
+            .fold(f64::INFINITY, f64::min);
+        assert!(closest >= 0.75 * 17.0, "{}", closest);
+        let random_closest = {
+            let pins = random(&Random { seed: Some(7) }, 200, 300, 200);
+            pins.iter()
+                .enumerate()
+                .flat_map(|(i, a)| pins[i + 1..].iter().map(|b| Line::from((*a, *b)).length()))
+                .fold(f64::INFINITY, f64::min)
+        };
+        assert!(random_closest < closest);
+    }
+
+    #[test]
+    fn test_rings_specifying_0_points_works() {
+        let pins = rings(&Rings::default(), 0, 1234, 1234);
+        assert_eq!(0, pins.len())
+    }
 }
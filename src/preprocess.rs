@@ -0,0 +1,972 @@
+// The image adjustments are only reached through the command line options
+#![cfg_attr(not(feature = "cli"), allow(dead_code))]
+
+#[cfg(feature = "cli")]
+use crate::cli_app::Cli;
+use crate::geometry::{Length, Point};
+use crate::image::DynamicImage;
+use crate::image::GrayImage;
+use crate::imagery::Rgb;
+use crate::serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// How many tiles CLAHE splits the shorter side of the image into
+const CLAHE_TILES: u32 = 8;
+
+/// CLAHE clips each tile's histogram at this multiple of the average bin height
+const CLAHE_CLIP_FACTOR: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Equalize {
+    Global,
+    Clahe,
+}
+
+impl core::str::FromStr for Equalize {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "global" => Ok(Equalize::Global),
+            "clahe" => Ok(Equalize::Clahe),
+            _ => Err(format!("Invalid equalization: \"{}\"", string)),
+        }
+    }
+}
+
+/// The radius of the neighborhood --contrast-weighting measures, as a fraction of the image's
+/// shorter side
+const CONTRAST_RADIUS_FRACTION: f64 = 0.01;
+
+/// How far from an edge --edge-weighting spreads its boost, as a fraction of the image's shorter
+/// side, so the strings beside an edge count and not only the pixels on it
+const EDGE_RADIUS_FRACTION: f64 = 0.005;
+
+/// Weights are whole numbers, so this is the weight of a pixel without any contrast
+pub const WEIGHT_SCALE: f64 = 16.0;
+
+/// How far, in 8-bit channel units, a pixel may be from the border color and still be trimmed
+/// by --auto-trim, so scanner noise and compression artifacts don't stop the trim
+const TRIM_TOLERANCE: i64 = 24;
+
+/// Range sigma of the bilateral filter, in 8-bit channel units
+const BILATERAL_RANGE_SIGMA: f64 = 25.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DenoiseFilter {
+    Median,
+    Bilateral,
+}
+
+impl core::str::FromStr for DenoiseFilter {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "median" => Ok(DenoiseFilter::Median),
+            "bilateral" => Ok(DenoiseFilter::Bilateral),
+            _ => Err(format!("Invalid denoise filter: \"{}\"", string)),
+        }
+    }
+}
+
+/// A rectangle of the input image to keep
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Crop {
+    pub x: Length,
+    pub y: Length,
+    pub width: Length,
+    pub height: Length,
+}
+
+impl Crop {
+    /// Resolve any percentages and clamp the rectangle to fit inside the image
+    pub fn in_pixels(&self, image_width: u32, image_height: u32) -> Self {
+        let (x, y, width, height) = self.rect(image_width, image_height);
+        Self {
+            x: Length::Pixels(x),
+            y: Length::Pixels(y),
+            width: Length::Pixels(width),
+            height: Length::Pixels(height),
+        }
+    }
+
+    fn rect(&self, image_width: u32, image_height: u32) -> (u32, u32, u32, u32) {
+        let x = u32::min(self.x.in_pixels(image_width), image_width);
+        let y = u32::min(self.y.in_pixels(image_height), image_height);
+        let width = u32::min(self.width.in_pixels(image_width), image_width - x);
+        let height = u32::min(self.height.in_pixels(image_height), image_height - y);
+        (x, y, width, height)
+    }
+
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let (x, y, width, height) = self.rect(image.width(), image.height());
+        image.crop_imm(x, y, width, height)
+    }
+}
+
+impl core::str::FromStr for Crop {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<Length>, _>>()?[..]
+        {
+            [x, y, width, height] => Ok(Crop {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(format!(
+                "Crop should be in x,y,width,height format, but got: \"{}\"",
+                string
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Rotation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl core::str::FromStr for Rotation {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "90" => Ok(Rotation::Rotate90),
+            "180" => Ok(Rotation::Rotate180),
+            "270" => Ok(Rotation::Rotate270),
+            _ => Err(format!(
+                "Rotation should be 90, 180, or 270, but got: \"{}\"",
+                string
+            )),
+        }
+    }
+}
+
+/// Apply the requested adjustments to the input image before it is turned into string art
+#[cfg(feature = "cli")]
+pub fn preprocess(image: DynamicImage, cli: &Cli) -> DynamicImage {
+    let mut image = image;
+    if let Some(crop) = cli.crop {
+        image = crop.apply(&image);
+    }
+    match cli.rotate {
+        Some(Rotation::Rotate90) => image = image.rotate90(),
+        Some(Rotation::Rotate180) => image = image.rotate180(),
+        Some(Rotation::Rotate270) => image = image.rotate270(),
+        None => (),
+    }
+    if cli.flip_h {
+        image = image.fliph();
+    }
+    if cli.flip_v {
+        image = image.flipv();
+    }
+    match (cli.denoise, cli.denoise_filter) {
+        (Some(0), _) | (None, _) => (),
+        (Some(strength), DenoiseFilter::Median) => image = median(&image, strength),
+        (Some(strength), DenoiseFilter::Bilateral) => image = bilateral(&image, strength),
+    }
+    match cli.equalize {
+        Some(Equalize::Global) => image = equalize_global(&image),
+        Some(Equalize::Clahe) => image = equalize_clahe(&image),
+        None => (),
+    }
+    if let Some(levels) = cli.posterize {
+        image = posterize(&image, levels);
+    }
+    if let Some((foreground, background)) = cli.dither_colors() {
+        image = dither(&image, foreground, background);
+    }
+    if let Some(color) = cli.pad_color() {
+        image = pad_to_square(&image, color);
+    }
+    if let Some(overscan) = cli.pin_overscan {
+        image = overscan_margin(&image, overscan, cli.background_or_default());
+    }
+    image
+}
+
+/// The crop that also leaves out the plain border of what `crop` keeps of the image, for
+/// --auto-trim. The border is the rows and columns along the edges that are all the color of the
+/// top left pixel, such as scanner margins or letterboxing. An image that is all one color keeps
+/// everything.
+pub fn auto_trim(image: &DynamicImage, crop: Option<Crop>) -> Crop {
+    let (x, y, width, height) = match crop {
+        Some(crop) => crop.rect(image.width(), image.height()),
+        None => (0, 0, image.width(), image.height()),
+    };
+    let img = image.crop_imm(x, y, width, height).to_rgb8();
+    let whole = Crop {
+        x: Length::Pixels(x),
+        y: Length::Pixels(y),
+        width: Length::Pixels(width),
+        height: Length::Pixels(height),
+    };
+    let Some(border) = img.pixels().next().map(|p| p.0) else {
+        return whole;
+    };
+    let plain = |i: u32, j: u32| {
+        img[(i, j)]
+            .0
+            .iter()
+            .zip(border)
+            .all(|(c, b)| (i64::from(*c) - i64::from(b)).abs() <= TRIM_TOLERANCE)
+    };
+    let plain_row = |j: u32| (0..width).all(|i| plain(i, j));
+    let plain_column = |i: u32| (0..height).all(|j| plain(i, j));
+
+    let Some(top) = (0..height).find(|j| !plain_row(*j)) else {
+        return whole;
+    };
+    let bottom = (0..height).rev().find(|j| !plain_row(*j)).unwrap_or(top);
+    let left = (0..width).find(|i| !plain_column(*i)).unwrap_or(0);
+    let right = (0..width).rev().find(|i| !plain_column(*i)).unwrap_or(left);
+    Crop {
+        x: Length::Pixels(x + left),
+        y: Length::Pixels(y + top),
+        width: Length::Pixels(right - left + 1),
+        height: Length::Pixels(bottom - top + 1),
+    }
+}
+
+/// Surround the image with a margin of the given color, sized from its width on the left and
+/// right and from its height on the top and bottom
+fn overscan_margin(image: &DynamicImage, overscan: Length, color: Rgb) -> DynamicImage {
+    let (x, y) = (
+        overscan.in_pixels(image.width()),
+        overscan.in_pixels(image.height()),
+    );
+    let fill = image::Rgb([color.r, color.g, color.b].map(|c| c as u8));
+    let mut img = image::RgbImage::from_pixel(image.width() + 2 * x, image.height() + 2 * y, fill);
+    image::imageops::overlay(&mut img, &image.to_rgb8(), i64::from(x), i64::from(y));
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Center the image on a square of the given color
+fn pad_to_square(image: &DynamicImage, color: Rgb) -> DynamicImage {
+    let side = u32::max(image.width(), image.height());
+    let fill = image::Rgb([color.r, color.g, color.b].map(|c| c as u8));
+    let mut img = image::RgbImage::from_pixel(side, side, fill);
+    image::imageops::overlay(
+        &mut img,
+        &image.to_rgb8(),
+        i64::from((side - image.width()) / 2),
+        i64::from((side - image.height()) / 2),
+    );
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Reduce the image to the two colors with Floyd-Steinberg error diffusion. Each pixel's tone is
+/// how far it is from the background color towards the foreground color.
+fn dither(image: &DynamicImage, foreground: Rgb, background: Rgb) -> DynamicImage {
+    let axis = [
+        foreground.r - background.r,
+        foreground.g - background.g,
+        foreground.b - background.b,
+    ]
+    .map(|c| c as f64);
+    let length_squared: f64 = axis.iter().map(|c| c * c).sum();
+    if length_squared == 0.0 {
+        return image.clone();
+    }
+    let origin = [background.r, background.g, background.b].map(|c| c as f64);
+
+    let mut img = image.to_rgb8();
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let mut tones: Vec<f64> = img
+        .pixels()
+        .map(|p| {
+            (0..3)
+                .map(|i| (p.0[i] as f64 - origin[i]) * axis[i])
+                .sum::<f64>()
+                / length_squared
+        })
+        .collect();
+    let fill = |rgb: Rgb| image::Rgb([rgb.r, rgb.g, rgb.b].map(|c| c as u8));
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let on = tones[i] >= 0.5;
+            let error = tones[i] - if on { 1.0 } else { 0.0 };
+            let mut spread = |dx: isize, dy: usize, share: f64| {
+                let nx = x as isize + dx;
+                if nx >= 0 && (nx as usize) < width && y + dy < height {
+                    tones[(y + dy) * width + nx as usize] += error * share;
+                }
+            };
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+            img.put_pixel(
+                x as u32,
+                y as u32,
+                fill(if on { foreground } else { background }),
+            );
+        }
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Reduce each channel to `levels` evenly spaced tonal levels
+fn posterize(image: &DynamicImage, levels: u16) -> DynamicImage {
+    let steps = f64::from(levels.clamp(2, 256) - 1);
+    let mut img = image.to_rgb8();
+    img.pixels_mut().for_each(|p| {
+        p.0.iter_mut().for_each(|c| {
+            *c = ((f64::from(*c) * steps / 255.0).round() * 255.0 / steps).round() as u8
+        })
+    });
+    DynamicImage::ImageRgb8(img)
+}
+
+/// The pixels within `radius` of (x, y), clamped to the image
+fn window(
+    x: u32,
+    y: u32,
+    radius: u32,
+    width: u32,
+    height: u32,
+) -> impl Iterator<Item = (u32, u32)> {
+    let xs = x.saturating_sub(radius)..=u32::min(width - 1, x.saturating_add(radius));
+    let ys = y.saturating_sub(radius)..=u32::min(height - 1, y.saturating_add(radius));
+    ys.flat_map(move |j| xs.clone().map(move |i| (i, j)))
+}
+
+/// How much the error of each pixel counts for --weight-filepath: its brightness in the map
+pub fn map_weights(weight_map: &GrayImage) -> Vec<Vec<i64>> {
+    weight_map
+        .rows()
+        .map(|row| row.map(|luma| i64::from(luma.0[0])).collect())
+        .collect()
+}
+
+/// How much the error of each pixel counts for --mask-filepath: not at all where it is masked
+pub fn mask_weights(masked: &HashSet<Point>, width: u32, height: u32) -> Vec<Vec<i64>> {
+    (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| match masked.contains(&Point::new(x, y)) {
+                    true => 0,
+                    false => 1,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Multiply the weights of each pixel together, so every weighting applies at once
+pub fn combine_weights(a: Vec<Vec<i64>>, b: Vec<Vec<i64>>) -> Vec<Vec<i64>> {
+    a.into_iter()
+        .zip(b)
+        .map(|(a, b)| a.into_iter().zip(b).map(|(a, b)| a * b).collect())
+        .collect()
+}
+
+/// How much the error of each pixel counts for --contrast-weighting: `WEIGHT_SCALE` where the
+/// image is flat, up to `1 + strength` times that where the brightness of the neighborhood varies
+/// the most
+pub fn contrast_weights(image: &DynamicImage, strength: f64) -> Vec<Vec<i64>> {
+    let luma = image.to_luma8();
+    let (width, height) = luma.dimensions();
+    let shorter = f64::from(u32::min(width, height));
+    let radius = u32::max(1, (shorter * CONTRAST_RADIUS_FRACTION).round() as u32);
+
+    // Summed-area tables of the brightness and its square, with a leading row and column of 0s
+    let stride = width as usize + 1;
+    let mut sums = vec![0.0; stride * (height as usize + 1)];
+    let mut squares = sums.clone();
+    luma.enumerate_pixels().for_each(|(x, y, p)| {
+        let i = (y as usize + 1) * stride + x as usize + 1;
+        let value = f64::from(p.0[0]);
+        sums[i] = value + sums[i - 1] + sums[i - stride] - sums[i - stride - 1];
+        squares[i] = value * value + squares[i - 1] + squares[i - stride] - squares[i - stride - 1];
+    });
+
+    let deviations: Vec<Vec<f64>> = (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    let (x0, y0) = (x.saturating_sub(radius), y.saturating_sub(radius));
+                    let x1 = u32::min(width - 1, x + radius) + 1;
+                    let y1 = u32::min(height - 1, y + radius) + 1;
+                    let at = |x: u32, y: u32| y as usize * stride + x as usize;
+                    let area = |table: &[f64]| {
+                        table[at(x1, y1)] - table[at(x0, y1)] - table[at(x1, y0)]
+                            + table[at(x0, y0)]
+                    };
+                    let count = f64::from((x1 - x0) * (y1 - y0));
+                    let mean = area(&sums) / count;
+                    f64::max(0.0, area(&squares) / count - mean * mean).sqrt()
+                })
+                .collect()
+        })
+        .collect();
+
+    let max = deviations.iter().flatten().copied().fold(0.0, f64::max);
+    deviations
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|deviation| {
+                    let contrast = match max > 0.0 {
+                        true => deviation / max,
+                        false => 0.0,
+                    };
+                    (WEIGHT_SCALE * (1.0 + strength * contrast)).round() as i64
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// How much the error of each pixel counts for --edge-weighting: `WEIGHT_SCALE` far from any
+/// edge, up to `1 + strength` times that on the strongest edge of the image
+pub fn edge_weights(image: &DynamicImage, strength: f64) -> Vec<Vec<i64>> {
+    let luma = image.to_luma8();
+    let (width, height) = luma.dimensions();
+    let at = |x: i64, y: i64| {
+        let x = x.clamp(0, i64::from(width) - 1) as u32;
+        let y = y.clamp(0, i64::from(height) - 1) as u32;
+        f64::from(luma.get_pixel(x, y).0[0])
+    };
+    // The Sobel filter: the brightness gradient, smoothed across it
+    let magnitude = |x: i64, y: i64| {
+        let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+            - at(x - 1, y - 1)
+            - 2.0 * at(x - 1, y)
+            - at(x - 1, y + 1);
+        let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+            - at(x - 1, y - 1)
+            - 2.0 * at(x, y - 1)
+            - at(x + 1, y - 1);
+        f64::hypot(gx, gy)
+    };
+    let magnitudes: Vec<f64> = luma
+        .enumerate_pixels()
+        .map(|(x, y, _)| magnitude(i64::from(x), i64::from(y)))
+        .collect();
+    let max = magnitudes.iter().copied().fold(0.0, f64::max);
+    if max == 0.0 {
+        return vec![vec![WEIGHT_SCALE as i64; width as usize]; height as usize];
+    }
+    let edges = GrayImage::from_fn(width, height, |x, y| {
+        let i = y as usize * width as usize + x as usize;
+        image::Luma([(magnitudes[i] / max * 255.0).round() as u8])
+    });
+
+    let shorter = f64::from(u32::min(width, height));
+    let sigma = f64::max(1.0, shorter * EDGE_RADIUS_FRACTION);
+    let near = image::imageops::blur(&edges, sigma as f32);
+    let max = near.pixels().map(|p| p.0[0]).max().unwrap_or(0);
+    near.rows()
+        .map(|row| {
+            row.map(|p| {
+                let edge = match max > 0 {
+                    true => f64::from(p.0[0]) / f64::from(max),
+                    false => 0.0,
+                };
+                (WEIGHT_SCALE * (1.0 + strength * edge)).round() as i64
+            })
+            .collect()
+        })
+        .collect()
+}
+
+/// Replace each channel of each pixel with the median of its neighborhood
+fn median(image: &DynamicImage, radius: u32) -> DynamicImage {
+    let src = image.to_rgb8();
+    let (width, height) = src.dimensions();
+    let mut img = src.clone();
+    img.enumerate_pixels_mut().for_each(|(x, y, p)| {
+        let mut values: [Vec<u8>; 3] = Default::default();
+        window(x, y, radius, width, height).for_each(|(i, j)| {
+            let n = src.get_pixel(i, j).0;
+            (0..3).for_each(|c| values[c].push(n[c]));
+        });
+        (0..3).for_each(|c| {
+            values[c].sort_unstable();
+            p.0[c] = values[c][values[c].len() / 2];
+        });
+    });
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Average each pixel with its neighbors, weighted by both distance and color similarity, so
+/// that noise is smoothed while edges are kept sharp
+fn bilateral(image: &DynamicImage, spatial_sigma: u32) -> DynamicImage {
+    let src = image.to_rgb8();
+    let (width, height) = src.dimensions();
+    let sigma = f64::from(spatial_sigma);
+    let mut img = src.clone();
+    img.enumerate_pixels_mut().for_each(|(x, y, p)| {
+        let center = src.get_pixel(x, y).0.map(f64::from);
+        let (sum, total_weight) = window(x, y, spatial_sigma * 2, width, height).fold(
+            ([0.0; 3], 0.0),
+            |(mut sum, total_weight), (i, j)| {
+                let n = src.get_pixel(i, j).0.map(f64::from);
+                let distance =
+                    (f64::from(i) - f64::from(x)).powi(2) + (f64::from(j) - f64::from(y)).powi(2);
+                let difference: f64 = (0..3).map(|c| (n[c] - center[c]).powi(2)).sum();
+                let weight = (-distance / (2.0 * sigma * sigma)
+                    - difference / (2.0 * BILATERAL_RANGE_SIGMA * BILATERAL_RANGE_SIGMA))
+                    .exp();
+                (0..3).for_each(|c| sum[c] += n[c] * weight);
+                (sum, total_weight + weight)
+            },
+        );
+        (0..3).for_each(|c| p.0[c] = (sum[c] / total_weight).round() as u8);
+    });
+    DynamicImage::ImageRgb8(img)
+}
+
+type Histogram = [f64; 256];
+type Lut = [u8; 256];
+
+fn histogram(luma: impl Iterator<Item = u8>) -> Histogram {
+    luma.fold([0.0; 256], |mut histogram, l| {
+        histogram[l as usize] += 1.0;
+        histogram
+    })
+}
+
+/// Build a lookup table that flattens the histogram. If a clip limit is given, bins taller than
+/// the limit are clipped and the excess is spread evenly over every bin.
+fn equalization_lut(mut histogram: Histogram, clip_limit: Option<f64>) -> Lut {
+    if let Some(limit) = clip_limit {
+        let excess: f64 = histogram.iter().map(|c| (c - limit).max(0.0)).sum();
+        histogram
+            .iter_mut()
+            .for_each(|c| *c = c.min(limit) + excess / 256.0);
+    }
+
+    let total: f64 = histogram.iter().sum();
+    let cdf_min = histogram.iter().copied().find(|c| *c > 0.0).unwrap_or(0.0);
+    let mut lut = [0; 256];
+    let mut cdf = 0.0;
+    for (v, count) in histogram.iter().enumerate() {
+        cdf += count;
+        lut[v] = if total - cdf_min <= 0.0 {
+            v as u8
+        } else {
+            ((cdf - cdf_min).max(0.0) * 255.0 / (total - cdf_min)).round() as u8
+        };
+    }
+    lut
+}
+
+/// Stretch the image's tones using its luminance histogram. The same tone curve is applied to
+/// each channel so hues are roughly preserved.
+fn equalize_global(image: &DynamicImage) -> DynamicImage {
+    let lut = equalization_lut(histogram(image.to_luma8().pixels().map(|p| p.0[0])), None);
+    let mut img = image.to_rgb8();
+    img.pixels_mut()
+        .for_each(|p| p.0.iter_mut().for_each(|c| *c = lut[*c as usize]));
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Contrast limited adaptive histogram equalization: equalize each tile of the image separately,
+/// limiting how much contrast is added, and blend between neighboring tiles.
+fn equalize_clahe(image: &DynamicImage) -> DynamicImage {
+    let luma = image.to_luma8();
+    let (width, height) = luma.dimensions();
+    let tile_size = u32::max(1, u32::min(width, height) / CLAHE_TILES);
+    let (tiles_x, tiles_y) = (width.div_ceil(tile_size), height.div_ceil(tile_size));
+
+    let luts: Vec<Lut> = (0..tiles_y)
+        .flat_map(|ty| (0..tiles_x).map(move |tx| (tx, ty)))
+        .map(|(tx, ty)| {
+            let (x0, y0) = (tx * tile_size, ty * tile_size);
+            let (x1, y1) = (
+                u32::min(width, x0 + tile_size),
+                u32::min(height, y0 + tile_size),
+            );
+            let pixels = (y0..y1).flat_map(|y| (x0..x1).map(move |x| (x, y)));
+            let limit = CLAHE_CLIP_FACTOR * f64::from((x1 - x0) * (y1 - y0)) / 256.0;
+            equalization_lut(
+                histogram(pixels.map(|(x, y)| luma.get_pixel(x, y).0[0])),
+                Some(limit),
+            )
+        })
+        .collect();
+
+    // The tiles on either side of a pixel, and how far the pixel is towards the second one
+    let neighbors = |p: u32, tiles: u32| {
+        let f = ((f64::from(p) + 0.5) / f64::from(tile_size) - 0.5).max(0.0);
+        let a = u32::min(f as u32, tiles - 1);
+        let b = u32::min(a + 1, tiles - 1);
+        (a, b, (f - f64::from(a)).min(1.0))
+    };
+
+    let mut img = image.to_rgb8();
+    img.enumerate_pixels_mut().for_each(|(x, y, p)| {
+        let (xa, xb, wx) = neighbors(x, tiles_x);
+        let (ya, yb, wy) = neighbors(y, tiles_y);
+        let lut = |tx: u32, ty: u32| &luts[(ty * tiles_x + tx) as usize];
+        p.0.iter_mut().for_each(|c| {
+            let v = *c as usize;
+            let top = f64::from(lut(xa, ya)[v]) * (1.0 - wx) + f64::from(lut(xb, ya)[v]) * wx;
+            let bottom = f64::from(lut(xa, yb)[v]) * (1.0 - wx) + f64::from(lut(xb, yb)[v]) * wx;
+            *c = (top * (1.0 - wy) + bottom * wy).round() as u8;
+        })
+    });
+    DynamicImage::ImageRgb8(img)
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use clap::Parser;
+
+    fn cli(args: &[&str]) -> Cli {
+        Cli::parse_from(
+            ["string_art", "--input-filepath", "in.png"]
+                .iter()
+                .chain(args),
+        )
+    }
+
+    fn gradient() -> DynamicImage {
+        let mut i = DynamicImage::new_rgb8(256, 1).to_rgb8();
+        i.enumerate_pixels_mut()
+            .for_each(|(x, _, p)| *p = image::Rgb([x as u8; 3]));
+        DynamicImage::ImageRgb8(i)
+    }
+
+    fn levels(image: &DynamicImage) -> Vec<u8> {
+        let mut levels: Vec<u8> = image.to_rgb8().pixels().map(|p| p.0[0]).collect();
+        levels.dedup();
+        levels
+    }
+
+    #[test]
+    fn test_contrast_weights() {
+        let flat = DynamicImage::new_rgb8(20, 10);
+        assert!(contrast_weights(&flat, 3.0)
+            .iter()
+            .flatten()
+            .all(|w| *w == WEIGHT_SCALE as i64));
+
+        // Black on the left half and white on the right, so the middle has the most contrast
+        let mut i = image::RgbImage::new(20, 10);
+        i.enumerate_pixels_mut()
+            .for_each(|(x, _, p)| *p = image::Rgb([if x < 10 { 0 } else { 255 }; 3]));
+        let weights = contrast_weights(&DynamicImage::ImageRgb8(i), 3.0);
+        assert_eq!(64, weights[5][10]);
+        assert_eq!(16, weights[5][0]);
+        assert_eq!(16, weights[5][19]);
+    }
+
+    #[test]
+    fn test_edge_weights() {
+        let flat = DynamicImage::new_rgb8(20, 10);
+        assert!(edge_weights(&flat, 3.0)
+            .iter()
+            .flatten()
+            .all(|w| *w == WEIGHT_SCALE as i64));
+
+        // Black on the left half and white on the right, so the only edge is in the middle
+        let mut i = image::RgbImage::new(20, 10);
+        i.enumerate_pixels_mut()
+            .for_each(|(x, _, p)| *p = image::Rgb([if x < 10 { 0 } else { 255 }; 3]));
+        let weights = edge_weights(&DynamicImage::ImageRgb8(i), 3.0);
+        assert_eq!(64, weights[5][10]);
+        assert!(weights[5][8] > weights[5][5]);
+        assert_eq!(16, weights[5][0]);
+        assert_eq!(16, weights[5][19]);
+    }
+
+    #[test]
+    fn test_mask_weights() {
+        let masked = HashSet::from([Point::new(1, 0)]);
+        assert_eq!(
+            vec![vec![1, 0, 1], vec![1, 1, 1]],
+            mask_weights(&masked, 3, 2)
+        );
+    }
+
+    #[test]
+    fn test_map_weights() {
+        let mut weight_map = GrayImage::new(3, 2);
+        weight_map.put_pixel(2, 1, image::Luma([200]));
+        let weights = map_weights(&weight_map);
+        assert_eq!(vec![vec![0, 0, 0], vec![0, 0, 200]], weights);
+        assert_eq!(
+            vec![vec![0, 0, 0], vec![0, 0, 3200]],
+            combine_weights(weights, vec![vec![16; 3]; 2])
+        );
+    }
+
+    fn low_contrast() -> DynamicImage {
+        let mut i = DynamicImage::new_rgb8(64, 64).to_rgb8();
+        i.enumerate_pixels_mut()
+            .for_each(|(x, y, p)| *p = image::Rgb([100 + ((x + y) % 16) as u8; 3]));
+        DynamicImage::ImageRgb8(i)
+    }
+
+    fn range(image: &DynamicImage) -> (u8, u8) {
+        let levels = image.to_rgb8().pixels().map(|p| p.0[0]).collect::<Vec<_>>();
+        (*levels.iter().min().unwrap(), *levels.iter().max().unwrap())
+    }
+
+    #[test]
+    fn test_equalize_global_stretches_range() {
+        assert_eq!((0, 255), range(&equalize_global(&low_contrast())));
+    }
+
+    #[test]
+    fn test_equalize_global_uniform_image_is_unchanged() {
+        let image = DynamicImage::new_rgb8(4, 4);
+        assert_eq!(
+            image.to_rgb8().into_raw(),
+            equalize_global(&image).to_rgb8().into_raw()
+        );
+    }
+
+    #[test]
+    fn test_equalize_clahe_increases_contrast() {
+        let (min, max) = range(&equalize_clahe(&low_contrast()));
+        assert!(max - min > 15 * 2, "range was {}..{}", min, max);
+    }
+
+    #[test]
+    fn test_equalization_lut_with_clip_limit_keeps_order() {
+        let mut histogram = [0.0; 256];
+        histogram[10] = 100.0;
+        histogram[20] = 1.0;
+        let lut = equalization_lut(histogram, Some(4.0));
+        assert!(lut.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    fn speckled() -> DynamicImage {
+        let mut i = DynamicImage::new_rgb8(5, 5).to_rgb8();
+        i.pixels_mut().for_each(|p| *p = image::Rgb([100; 3]));
+        i[(2, 2)] = image::Rgb([255; 3]);
+        DynamicImage::ImageRgb8(i)
+    }
+
+    fn half_and_half() -> DynamicImage {
+        let mut i = DynamicImage::new_rgb8(6, 6).to_rgb8();
+        i.enumerate_pixels_mut()
+            .for_each(|(x, _, p)| *p = image::Rgb([if x < 3 { 0 } else { 255 }; 3]));
+        DynamicImage::ImageRgb8(i)
+    }
+
+    #[test]
+    fn test_median_removes_speckle() {
+        assert_eq!(vec![100], levels(&median(&speckled(), 1)));
+    }
+
+    #[test]
+    fn test_median_keeps_edges() {
+        assert_eq!(
+            half_and_half().to_rgb8().into_raw(),
+            median(&half_and_half(), 1).to_rgb8().into_raw()
+        );
+    }
+
+    #[test]
+    fn test_bilateral_softens_noise() {
+        let mut noisy = speckled().to_rgb8();
+        noisy[(2, 2)] = image::Rgb([120; 3]);
+        let denoised = bilateral(&DynamicImage::ImageRgb8(noisy), 1).to_rgb8();
+        assert!(denoised[(2, 2)].0[0] < 110);
+        assert_eq!(100, denoised[(0, 0)].0[0]);
+    }
+
+    #[test]
+    fn test_bilateral_keeps_edges() {
+        assert_eq!(
+            half_and_half().to_rgb8().into_raw(),
+            bilateral(&half_and_half(), 1).to_rgb8().into_raw()
+        );
+    }
+
+    #[test]
+    fn test_crop_from_str() {
+        assert_eq!(
+            Ok(Crop {
+                x: Length::Pixels(1),
+                y: Length::Percent(10.0),
+                width: Length::Pixels(30),
+                height: Length::Percent(50.0),
+            }),
+            "1,10%,30,50%".parse()
+        );
+        assert!("1,2,3".parse::<Crop>().is_err());
+    }
+
+    #[test]
+    fn test_crop_in_pixels_is_clamped() {
+        let crop: Crop = "10%,20,100%,100".parse().unwrap();
+        assert_eq!(
+            Crop {
+                x: Length::Pixels(5),
+                y: Length::Pixels(20),
+                width: Length::Pixels(45),
+                height: Length::Pixels(10),
+            },
+            crop.in_pixels(50, 30)
+        );
+    }
+
+    #[test]
+    fn test_crop_apply() {
+        let crop: Crop = "10,0,20%,50%".parse().unwrap();
+        let cropped = crop.apply(&low_contrast());
+        assert_eq!((13, 32), (cropped.width(), cropped.height()));
+        assert_eq!(low_contrast().to_rgb8()[(10, 0)], cropped.to_rgb8()[(0, 0)]);
+    }
+
+    #[test]
+    fn test_auto_trim() {
+        let mut img = image::RgbImage::from_pixel(10, 8, image::Rgb([250, 250, 250]));
+        img.put_pixel(0, 7, image::Rgb([255, 240, 255]));
+        (2..7).for_each(|x| (3..5).for_each(|y| img.put_pixel(x, y, image::Rgb([0, 0, 0]))));
+        let image = DynamicImage::ImageRgb8(img);
+        assert_eq!(Ok(auto_trim(&image, None)), "2,3,5,2".parse());
+        // Only the plain border inside the crop is trimmed
+        let crop = "4,0,6,8".parse().ok();
+        assert_eq!(Ok(auto_trim(&image, crop)), "4,3,3,2".parse());
+
+        let plain = DynamicImage::new_rgb8(4, 3);
+        assert_eq!(Ok(auto_trim(&plain, None)), "0,0,4,3".parse());
+    }
+
+    #[test]
+    fn test_preprocess_rotate() {
+        let rotated = preprocess(DynamicImage::new_rgb8(3, 2), &cli(&["--rotate", "90"]));
+        assert_eq!((2, 3), (rotated.width(), rotated.height()));
+    }
+
+    #[test]
+    fn test_preprocess_crops_before_rotating() {
+        let image = preprocess(
+            low_contrast(),
+            &cli(&["--crop", "0,0,10,20", "--rotate", "270"]),
+        );
+        assert_eq!((20, 10), (image.width(), image.height()));
+    }
+
+    #[test]
+    fn test_preprocess_flip_h() {
+        let flipped = preprocess(half_and_half(), &cli(&["--flip-h"])).to_rgb8();
+        assert_eq!([255; 3], flipped[(0, 0)].0);
+        assert_eq!([0; 3], flipped[(5, 0)].0);
+    }
+
+    #[test]
+    fn test_pad_to_square_wide_image() {
+        let padded = pad_to_square(&half_and_half().crop_imm(0, 0, 6, 2), Rgb::new(1, 2, 3));
+        assert_eq!((6, 6), (padded.width(), padded.height()));
+        let padded = padded.to_rgb8();
+        assert_eq!([1, 2, 3], padded[(0, 1)].0);
+        assert_eq!([0; 3], padded[(0, 2)].0);
+        assert_eq!([255; 3], padded[(5, 3)].0);
+        assert_eq!([1, 2, 3], padded[(5, 4)].0);
+    }
+
+    #[test]
+    fn test_pad_to_square_tall_image() {
+        let padded = pad_to_square(&half_and_half().crop_imm(0, 0, 3, 6), Rgb::WHITE);
+        assert_eq!((6, 6), (padded.width(), padded.height()));
+        let padded = padded.to_rgb8();
+        assert_eq!([255; 3], padded[(0, 0)].0);
+        assert_eq!([0; 3], padded[(1, 0)].0);
+        assert_eq!([255; 3], padded[(4, 0)].0);
+    }
+
+    #[test]
+    fn test_preprocess_pad_to_square_defaults_to_background_color() {
+        let image = DynamicImage::new_rgb8(2, 1);
+        let padded = preprocess(image, &cli(&["--pad-to-square", "-b", "#0000FF"])).to_rgb8();
+        assert_eq!([0, 0, 0], padded[(0, 0)].0);
+        assert_eq!([0, 0, 255], padded[(0, 1)].0);
+    }
+
+    #[test]
+    fn test_overscan_margin() {
+        // 20% of 6 pixels wide and 4 pixels tall rounds to a 1 pixel margin on each side
+        let image = half_and_half().crop_imm(0, 0, 6, 4);
+        let extended = overscan_margin(&image, Length::Percent(20.0), Rgb::new(1, 2, 3));
+        assert_eq!((8, 6), (extended.width(), extended.height()));
+        let extended = extended.to_rgb8();
+        assert_eq!([1, 2, 3], extended[(0, 3)].0);
+        assert_eq!([1, 2, 3], extended[(3, 0)].0);
+        assert_eq!([0; 3], extended[(1, 1)].0);
+        assert_eq!([255; 3], extended[(6, 4)].0);
+    }
+
+    #[test]
+    fn test_preprocess_pin_overscan_uses_background_color() {
+        let image = DynamicImage::new_rgb8(2, 2);
+        let extended = preprocess(image, &cli(&["--pin-overscan", "1", "-b", "#0000FF"])).to_rgb8();
+        assert_eq!((4, 4), extended.dimensions());
+        assert_eq!([0, 0, 255], extended[(0, 0)].0);
+        assert_eq!([0, 0, 0], extended[(1, 1)].0);
+    }
+
+    #[test]
+    fn test_dither_uses_only_the_two_colors() {
+        let dithered = dither(&gradient(), Rgb::WHITE, Rgb::BLACK);
+        assert!(dithered
+            .to_rgb8()
+            .pixels()
+            .all(|p| p.0 == [0; 3] || p.0 == [255; 3]));
+    }
+
+    #[test]
+    fn test_dither_keeps_tone_as_density() {
+        let mut grey = DynamicImage::new_rgb8(20, 20).to_rgb8();
+        grey.pixels_mut().for_each(|p| *p = image::Rgb([64; 3]));
+        let dithered = dither(&DynamicImage::ImageRgb8(grey), Rgb::WHITE, Rgb::BLACK);
+        let on = dithered
+            .to_rgb8()
+            .pixels()
+            .filter(|p| p.0[0] == 255)
+            .count();
+        // A quarter of the way to white is about a quarter of the pixels
+        assert!((90..=110).contains(&on), "{} pixels were on", on);
+    }
+
+    #[test]
+    fn test_dither_towards_a_dark_foreground() {
+        let dithered = dither(&gradient(), Rgb::BLACK, Rgb::WHITE).to_rgb8();
+        assert_eq!([255; 3], dithered[(255, 0)].0);
+        assert_eq!([0; 3], dithered[(0, 0)].0);
+    }
+
+    #[test]
+    fn test_preprocess_dither() {
+        let dithered = preprocess(gradient(), &cli(&["--dither"]));
+        assert_eq!(vec![0, 255], {
+            let mut values: Vec<u8> = dithered.to_rgb8().pixels().map(|p| p.0[0]).collect();
+            values.sort();
+            values.dedup();
+            values
+        });
+    }
+
+    #[test]
+    fn test_posterize_2_levels() {
+        assert_eq!(vec![0, 255], levels(&posterize(&gradient(), 2)));
+    }
+
+    #[test]
+    fn test_posterize_3_levels() {
+        assert_eq!(vec![0, 128, 255], levels(&posterize(&gradient(), 3)));
+    }
+
+    #[test]
+    fn test_posterize_256_levels_is_unchanged() {
+        assert_eq!(
+            gradient().to_rgb8().into_raw(),
+            posterize(&gradient(), 256).to_rgb8().into_raw()
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use crate::image::io::Reader as ImageReader;
+use crate::serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Where a finished run came from, so its outputs can be traced back to the photo and the
+/// version of string_art that made them
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Provenance {
+    /// The SHA-256 of the input file, in hex. Missing when the input is a directory of images.
+    pub input_sha256: Option<String>,
+    /// The size of the input image before any preprocessing
+    pub input_width: Option<u32>,
+    pub input_height: Option<u32>,
+    pub version: String,
+}
+
+impl Provenance {
+    pub fn of(input_filepath: &str) -> Self {
+        let (input_width, input_height) = ImageReader::open(input_filepath)
+            .ok()
+            .and_then(|reader| reader.with_guessed_format().ok())
+            .and_then(|reader| reader.into_dimensions().ok())
+            .unzip();
+        Self {
+            input_sha256: std::fs::read(input_filepath)
+                .ok()
+                .map(|bytes| hex(&sha256(&bytes))),
+            input_width,
+            input_height,
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+        }
+    }
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The SHA-256 digest of `bytes`
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            hex(&sha256(b""))
+        );
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            hex(&sha256(b"abc"))
+        );
+        // Long enough to need a second block
+        assert_eq!(
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            hex(&sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_provenance_of_an_image() {
+        let filepath = std::env::temp_dir().join("string_art_provenance.png");
+        let filepath = filepath.to_str().unwrap();
+        image::RgbImage::new(7, 3).save(filepath).unwrap();
+        let provenance = Provenance::of(filepath);
+        let bytes = std::fs::read(filepath).unwrap();
+        std::fs::remove_file(filepath).unwrap();
+
+        assert_eq!(Some(hex(&sha256(&bytes))), provenance.input_sha256);
+        assert_eq!(
+            (Some(7), Some(3)),
+            (provenance.input_width, provenance.input_height)
+        );
+        assert_eq!(env!("CARGO_PKG_VERSION"), provenance.version);
+    }
+
+    #[test]
+    fn test_provenance_of_a_missing_file() {
+        let provenance = Provenance::of("no/such/image.png");
+        assert_eq!(None, provenance.input_sha256);
+        assert_eq!(None, provenance.input_width);
+    }
+}
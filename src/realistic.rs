@@ -0,0 +1,288 @@
+use crate::geometry::Point;
+use crate::image::imageops::FilterType;
+use crate::imagery::{Rgb, Shade};
+use crate::serde::{Deserialize, Serialize};
+use crate::style::Data;
+use std::collections::HashSet;
+
+/// How much the thread's width varies along its length
+const WIDTH_VARIATION: f64 = 0.15;
+
+/// How far the shadow falls from the thread, relative to the thread's width
+const SHADOW_OFFSET: f64 = 0.8;
+
+/// How much wider the shadow is than the thread
+const SHADOW_SPREAD: f64 = 1.5;
+
+/// How much the shadow darkens whatever is beneath it
+const SHADOW_DARKNESS: f64 = 0.35;
+
+/// How much the highlight brightens the thread
+const HIGHLIGHT_STRENGTH: f64 = 0.35;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RenderStyle {
+    Flat,
+    Realistic,
+}
+
+impl core::str::FromStr for RenderStyle {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "flat" => Ok(RenderStyle::Flat),
+            "realistic" => Ok(RenderStyle::Realistic),
+            _ => Err(format!("Invalid render style: \"{}\"", string)),
+        }
+    }
+}
+
+type Pixels = Vec<[f64; 3]>;
+
+/// A straight thread between two points, in the coordinates of the rendered image
+struct Thread {
+    start: (f64, f64),
+    direction: (f64, f64),
+    normal: (f64, f64),
+    length: f64,
+    width: f64,
+    phase: f64,
+}
+
+impl Thread {
+    fn new(a: Point, b: Point, scale: f64, width: f64) -> Self {
+        let start = (a.x as f64 * scale, a.y as f64 * scale);
+        let end = (b.x as f64 * scale, b.y as f64 * scale);
+        let length = ((end.0 - start.0).powi(2) + (end.1 - start.1).powi(2)).sqrt();
+        let direction = if length > 0.0 {
+            ((end.0 - start.0) / length, (end.1 - start.1) / length)
+        } else {
+            (1.0, 0.0)
+        };
+        Self {
+            start,
+            direction,
+            normal: (-direction.1, direction.0),
+            length,
+            width,
+            // Give each thread its own, repeatable, pattern of width variation
+            phase: (a.x * 31 + a.y * 17 + b.x * 13 + b.y * 7) as f64,
+        }
+    }
+
+    fn half_width_at(&self, along: f64) -> f64 {
+        let variation = 1.0 + WIDTH_VARIATION * (self.phase + along * 0.05).sin();
+        self.width * variation / 2.0
+    }
+
+    /// Call `f` once for every pixel within `reach` of the thread, shifted by `shift`, with the
+    /// distance along and across the thread
+    fn visit(
+        &self,
+        reach: f64,
+        shift: (f64, f64),
+        size: (u32, u32),
+        mut f: impl FnMut(usize, f64, f64),
+    ) {
+        let (width, height) = size;
+        let start = (self.start.0 + shift.0, self.start.1 + shift.1);
+        let mut seen = HashSet::new();
+        // Half pixel steps so diagonal threads don't skip any pixels
+        let steps = (self.length * 2.0).ceil() as i64;
+        let reach_steps = (reach * 2.0).ceil() as i64;
+        for step in 0..=steps {
+            for offset in -reach_steps..=reach_steps {
+                let (step, offset) = (step as f64 / 2.0, offset as f64 / 2.0);
+                let x = start.0 + self.direction.0 * step + self.normal.0 * offset;
+                let y = start.1 + self.direction.1 * step + self.normal.1 * offset;
+                let (x, y) = (x.round(), y.round());
+                if x < 0.0 || y < 0.0 || x >= width as f64 || y >= height as f64 {
+                    continue;
+                }
+                let index = y as usize * width as usize + x as usize;
+                if seen.insert(index) {
+                    let (dx, dy) = (x - start.0, y - start.1);
+                    let along = dx * self.direction.0 + dy * self.direction.1;
+                    let across = dx * self.normal.0 + dy * self.normal.1;
+                    f(index, along, across);
+                }
+            }
+        }
+    }
+
+    /// How much of a pixel the thread covers, given the pixel's position relative to the thread
+    fn coverage(&self, along: f64, across: f64, spread: f64) -> f64 {
+        let half_width = self.half_width_at(along) * spread;
+        let beyond_ends = f64::max(-along, along - self.length).max(0.0);
+        ((half_width + 0.5 - across.abs()).clamp(0.0, 1.0) * (1.0 - beyond_ends).clamp(0.0, 1.0))
+            .min(half_width * 2.0)
+    }
+
+    fn draw_shadow(&self, pixels: &mut Pixels, size: (u32, u32)) {
+        let offset = self.width.max(1.0) * SHADOW_OFFSET;
+        let reach = self.width * SHADOW_SPREAD + 1.0;
+        self.visit(reach, (offset, offset), size, |index, along, across| {
+            let darkness = SHADOW_DARKNESS * self.coverage(along, across, SHADOW_SPREAD);
+            pixels[index].iter_mut().for_each(|c| *c *= 1.0 - darkness);
+        });
+    }
+
+    /// Draw the thread over the pixels, fading from the `start` color to the `end` color.
+    /// Subtractive threads are translucent, so they filter whatever is beneath them instead of
+    /// covering it.
+    fn draw_body(
+        &self,
+        pixels: &mut Pixels,
+        size: (u32, u32),
+        (start, end): ([f64; 3], [f64; 3]),
+        subtractive: bool,
+    ) {
+        let reach = self.width + 1.0;
+        self.visit(reach, (0.0, 0.0), size, |index, along, across| {
+            let coverage = self.coverage(along, across, 1.0);
+            if coverage <= 0.0 {
+                return;
+            }
+            // Shade the thread like a cylinder lit from the upper left
+            let u = (across / self.half_width_at(along).max(0.5)).clamp(-1.0, 1.0);
+            let shading = 0.75 + 0.25 * (1.0 - u * u).sqrt();
+            let highlight = HIGHLIGHT_STRENGTH * (-((u + 0.4) / 0.3).powi(2)).exp();
+            let t = match self.length > 0.0 {
+                true => (along / self.length).clamp(0.0, 1.0),
+                false => 0.0,
+            };
+            let color = [0, 1, 2].map(|i| start[i] * (1.0 - t) + end[i] * t);
+            for (c, thread) in pixels[index].iter_mut().zip(color) {
+                let lit = thread * shading + (255.0 - thread * shading) * highlight;
+                let mixed = if subtractive { *c * lit / 255.0 } else { lit };
+                *c = *c * (1.0 - coverage) + mixed * coverage;
+            }
+        });
+    }
+}
+
+/// Render the finished work as it might look in real life, with each thread drawn with some
+/// width variation, shading, a highlight, and a shadow. Each thread is `string_alpha` pixels
+/// wide at the size of the input image.
+pub fn render(data: &Data, scale: f64) -> image::RgbaImage {
+    let width = u32::max(1, (data.image_width as f64 * scale).round() as u32);
+    let height = u32::max(1, (data.image_height as f64 * scale).round() as u32);
+    let size = (width, height);
+
+    let mut pixels: Pixels = match &data.args.canvas {
+        Some(canvas) => canvas
+            .resize_exact(width, height, FilterType::Triangle)
+            .to_rgb8()
+            .pixels()
+            .map(|p| p.0.map(f64::from))
+            .collect(),
+        None => {
+            let bg = data.args.background_color;
+            vec![[bg.r as f64, bg.g as f64, bg.b as f64]; (width * height) as usize]
+        }
+    };
+
+    let thread_width = data.args.string_alpha * scale;
+    let subtractive = data.args.style.inks().is_some();
+    for (a, b, rgb) in &data.drawn_line_segments() {
+        let thread = Thread::new(*a, *b, scale, thread_width);
+        thread.draw_shadow(&mut pixels, size);
+        let (start, end) = Shade::of(*rgb, data.args.gradient_strings).ends();
+        let color = |rgb: Rgb| [rgb.r, rgb.g, rgb.b].map(|c| c as f64);
+        thread.draw_body(&mut pixels, size, (color(start), color(end)), subtractive);
+    }
+
+    let mut img = image::RgbaImage::new(width, height);
+    img.pixels_mut().zip(pixels).for_each(|(pixel, rgb)| {
+        let [r, g, b] = rgb.map(|c| c.round().clamp(0.0, 255.0) as u8);
+        *pixel = image::Rgba([r, g, b, u8::MAX]);
+    });
+    img
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+
+    fn data() -> Data {
+        Data::example(&["--string-alpha", "1", "-b", "#808080"])
+    }
+
+    #[test]
+    fn test_render_size() {
+        let img = render(&data(), 3.0);
+        assert_eq!((30, 30), img.dimensions());
+    }
+
+    #[test]
+    fn test_render_draws_thread() {
+        let img = render(&data(), 4.0);
+        // The thread runs along the top edge, so the top row is brightened
+        assert!(img[(18, 0)].0[0] > 200, "was {:?}", img[(18, 0)]);
+    }
+
+    #[test]
+    fn test_render_leaves_background() {
+        let img = render(&data(), 4.0);
+        assert_eq!([128, 128, 128, 255], img[(18, 30)].0);
+    }
+
+    #[test]
+    fn test_render_follows_draw_order() {
+        let crossing = |draw_order: &str| {
+            let mut data = Data::example(&["--string-alpha", "1", "--draw-order", draw_order]);
+            data.line_segments = vec![
+                (Point::new(0, 5), Point::new(9, 5), Rgb::new(255, 0, 0)),
+                (Point::new(5, 0), Point::new(5, 9), Rgb::new(0, 0, 255)),
+            ];
+            render(&data, 4.0)[(20, 20)].0
+        };
+        // Blue is darker, so it ends up on top when the lightest strings are drawn first
+        let lightest_first = crossing("lightest-first");
+        let darkest_first = crossing("darkest-first");
+        assert!(
+            lightest_first[2] > lightest_first[0],
+            "was {:?}",
+            lightest_first
+        );
+        assert!(
+            darkest_first[0] > darkest_first[2],
+            "was {:?}",
+            darkest_first
+        );
+    }
+
+    #[test]
+    fn test_render_mixes_subtractive_threads() {
+        let mut data = Data::example(&["--string-alpha", "1", "--style", "cmy"]);
+        data.line_segments = vec![
+            (Point::new(0, 5), Point::new(9, 5), Rgb::new(0, 255, 255)),
+            (Point::new(5, 0), Point::new(5, 9), Rgb::new(255, 255, 0)),
+        ];
+        // Cyan over yellow filters out all but green
+        let [r, g, b, _] = render(&data, 4.0)[(20, 20)].0;
+        assert!(g > 150 && r < 60 && b < 60, "was {:?}", (r, g, b));
+    }
+
+    #[test]
+    fn test_render_fades_gradient_strings() {
+        let mut data = Data::example(&[
+            "--string-alpha",
+            "1",
+            "--gradient-strings",
+            "#FF0000,#0000FF",
+        ]);
+        data.line_segments = vec![(Point::new(0, 5), Point::new(9, 5), Rgb::new(255, 0, 0))];
+        let img = render(&data, 4.0);
+        let [r, _, b, _] = img[(2, 20)].0;
+        assert!(r > b, "was {:?}", img[(2, 20)]);
+        let [r, _, b, _] = img[(34, 20)].0;
+        assert!(b > r, "was {:?}", img[(34, 20)]);
+    }
+
+    #[test]
+    fn test_render_draws_shadow() {
+        let img = render(&data(), 4.0);
+        assert!(img[(18, 4)].0[0] < 128, "was {:?}", img[(18, 4)]);
+    }
+}
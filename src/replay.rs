@@ -0,0 +1,237 @@
+use crate::cli_app::positive_f64;
+use crate::cli_app::Args;
+use crate::error::open_image;
+use crate::error::StringArtError;
+#[cfg(feature = "gif")]
+use crate::frame_sink::GifSink;
+use crate::overwrite;
+use crate::realistic::RenderStyle;
+use crate::string_art;
+use crate::style;
+use crate::style::Data;
+use crate::template;
+use crate::term;
+use clap::Parser;
+#[cfg(feature = "gif")]
+use image::Delay;
+
+/// About how many frames a replayed gif shows the strings being placed in
+#[cfg(feature = "gif")]
+const REPLAY_FRAMES: usize = 100;
+
+/// Render the images of a finished run again from its data file, without running the
+/// optimization again. With no output options, the files the run wrote are written again.
+#[derive(Parser, Debug)]
+#[command(name = "string_art replay", version, max_term_width(100))]
+pub struct ReplayCli {
+    /// The data file of the run, compressed or not.
+    #[arg(value_name("DATA"))]
+    pub data_filepath: String,
+
+    /// Location to save a string image. Can be specified multiple times, each at the size
+    /// given by the matching --output-scale. Placeholders are filled in as in the run.
+    #[arg(short = 'o', long)]
+    pub output_filepath: Vec<String>,
+
+    /// How much to scale each string image relative to the input image.
+    #[arg(long, value_parser = positive_f64)]
+    pub output_scale: Vec<f64>,
+
+    /// Location to save the image of pin locations.
+    #[arg(short = 'p', long)]
+    pub pins_filepath: Option<String>,
+
+    /// Location to save a gif of the strings being placed.
+    #[arg(short = 'g', long)]
+    pub gif_filepath: Option<String>,
+
+    /// Location to save the strings as an SVG.
+    #[arg(long, value_name("FILEPATH"))]
+    pub svg_filepath: Option<String>,
+
+    /// How to draw the string images, instead of how the run drew them.
+    #[arg(long)]
+    pub render_style: Option<RenderStyle>,
+
+    /// Overwrite files that already exist.
+    #[arg(long)]
+    pub force: bool,
+}
+
+impl ReplayCli {
+    /// The arguments of the run, writing only the files this replay asks for
+    pub fn args(&self, saved: &Args) -> Args {
+        let replayed = Args {
+            force: self.force,
+            render_style: self.render_style.unwrap_or(saved.render_style),
+            ..saved.without_outputs()
+        };
+        if self.output_filepath.is_empty()
+            && self.pins_filepath.is_none()
+            && self.gif_filepath.is_none()
+            && self.svg_filepath.is_none()
+        {
+            return Args {
+                output_filepaths: saved.output_filepaths.clone(),
+                output_scales: saved.output_scales.clone(),
+                pins_filepath: saved.pins_filepath.clone(),
+                gif_filepath: saved.gif_filepath.clone(),
+                svg_filepath: saved.svg_filepath.clone(),
+                ..replayed
+            };
+        }
+        Args {
+            output_scales: (0..self.output_filepath.len())
+                .map(|i| self.output_scale.get(i).copied().unwrap_or(1.0))
+                .collect(),
+            output_filepaths: self.output_filepath.clone(),
+            pins_filepath: self.pins_filepath.clone(),
+            gif_filepath: self.gif_filepath.clone(),
+            svg_filepath: self.svg_filepath.clone(),
+            ..replayed
+        }
+    }
+}
+
+/// Write the images of a finished run. The canvas image is loaded again if the run used one.
+pub fn render(mut data: Data) -> Result<(), StringArtError> {
+    if let Some(filepath) = &data.args.canvas_image {
+        data.args.canvas = Some(open_image(filepath)?.resize_exact(
+            data.image_width,
+            data.image_height,
+            image::imageops::FilterType::Triangle,
+        ));
+    }
+
+    style::save_string_images(&data)?;
+
+    if let Some(filepath) = &data.args.pins_filepath {
+        string_art::draw_pin_crosshairs(
+            data.image_width,
+            data.image_height,
+            &data.pin_locations,
+            &template::expand(filepath, &data),
+        )?;
+    }
+
+    #[cfg(feature = "gif")]
+    if let Some(filepath) = &data.args.gif_filepath {
+        write_gif(&data, &template::expand(filepath, &data))?;
+    }
+    Ok(())
+}
+
+/// Save a gif of the strings being placed in the order they were placed, a few at a time,
+/// pausing on the finished strings like the gif of a run. The run's gif pacing is kept if it
+/// had any.
+#[cfg(feature = "gif")]
+fn write_gif(data: &Data, filepath: &str) -> Result<(), StringArtError> {
+    let relative: Vec<_> = data
+        .line_segments
+        .iter()
+        .map(|(a, b, rgb)| (*a, *b, *rgb - data.args.background_color))
+        .collect();
+    let per_frame = match data.args.gif_strings_per_frame {
+        Some(n) => n as usize,
+        None => usize::max(1, relative.len().div_ceil(REPLAY_FRAMES)),
+    };
+    let frame = |strings: usize| {
+        style::render_frame(
+            &relative[..strings],
+            &data.args,
+            data.image_width,
+            data.image_height,
+        )
+    };
+    let step = Delay::from_numer_denom_ms(data.args.gif_frame_delay.unwrap_or(100), 1);
+
+    let mut sink = GifSink::create(filepath)?;
+    for strings in (0..relative.len()).step_by(per_frame) {
+        sink.add(frame(strings), step)?;
+    }
+    sink.add(frame(relative.len()), Delay::from_numer_denom_ms(1000, 1))
+}
+
+/// Run `string_art replay`, writing the images of a finished run from its data file
+pub fn replay() {
+    // Skip the program name, so `replay` is treated as the name of the command
+    let cli = ReplayCli::parse_from(std::env::args().skip(1));
+    let mut data: Data = string_art::read_data(&cli.data_filepath).unwrap_or_else(|error| {
+        term::error(&error.to_string());
+        std::process::exit(1)
+    });
+    data.args = cli.args(&data.args);
+
+    let problems = overwrite::problems(&data.args);
+    if !problems.is_empty() {
+        problems.iter().for_each(|problem| term::error(problem));
+        std::process::exit(1)
+    }
+    if let Err(error) = render(data) {
+        term::error(&error.to_string());
+        std::process::exit(1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::geometry::Point;
+    use crate::imagery::Rgb;
+
+    fn replay_cli(extra_args: &[&str]) -> ReplayCli {
+        ReplayCli::parse_from(["string_art replay", "data.json"].iter().chain(extra_args))
+    }
+
+    #[test]
+    fn test_replays_the_saved_outputs_by_default() {
+        let saved = Data::example(&["-o", "art.png", "-p", "pins.png", "-d", "data.json"]).args;
+        let args = replay_cli(&[]).args(&saved);
+        assert_eq!(vec!["art.png".to_owned()], args.output_filepaths);
+        assert_eq!(Some("pins.png".to_owned()), args.pins_filepath);
+        assert_eq!(None, args.data_filepath);
+    }
+
+    #[test]
+    fn test_given_outputs_replace_the_saved_ones() {
+        let saved = Data::example(&["-o", "art.png", "-p", "pins.png"]).args;
+        let args = replay_cli(&["-o", "big.png", "--output-scale", "2", "--force"]).args(&saved);
+        assert_eq!(vec!["big.png".to_owned()], args.output_filepaths);
+        assert_eq!(vec![2.0], args.output_scales);
+        assert_eq!(None, args.pins_filepath);
+        assert!(args.force);
+    }
+
+    #[test]
+    fn test_render_matches_the_run() {
+        let output = std::env::temp_dir().join("string_art_replay.png");
+        let output = output.to_str().unwrap();
+        let mut data = Data::example(&["-o", output]);
+        data.line_segments
+            .push((Point::new(0, 9), Point::new(9, 0), Rgb::WHITE));
+        let expected = crate::imagery::RefImage::from(&data).color();
+        render(data).unwrap();
+        let replayed = image::open(output).unwrap().to_rgba8();
+        std::fs::remove_file(output).unwrap();
+        assert_eq!(expected, replayed);
+    }
+
+    #[test]
+    #[cfg(feature = "gif")]
+    fn test_gif() {
+        use image::AnimationDecoder;
+        let filepath = std::env::temp_dir().join("string_art_replay.gif");
+        let filepath = filepath.to_str().unwrap();
+        let data = Data::example(&[]);
+        write_gif(&data, filepath).unwrap();
+        let file = std::io::BufReader::new(std::fs::File::open(filepath).unwrap());
+        let frames = image::codecs::gif::GifDecoder::new(file)
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        std::fs::remove_file(filepath).unwrap();
+        // One frame before the only string, and one pausing on it
+        assert_eq!(2, frames.len());
+    }
+}
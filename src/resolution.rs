@@ -0,0 +1,127 @@
+use crate::error::StringArtError;
+use crate::image::RgbaImage;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use tiff::encoder::{colortype, Rational, TiffEncoder};
+use tiff::tags::ResolutionUnit;
+
+const MM_PER_METER: f64 = 1000.0;
+
+/// Save an image, recording its physical size in PNG and TIFF files so it prints at
+/// `width_mm` wide. Other formats, or images without a physical size, are saved as usual.
+pub fn save(img: &RgbaImage, filepath: &str, width_mm: Option<f64>) -> Result<(), StringArtError> {
+    let extension = Path::new(filepath)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    match (width_mm, extension.as_deref()) {
+        (Some(width_mm), Some("png")) => save_png(img, filepath, pixels_per_meter(img, width_mm)),
+        (Some(width_mm), Some("tif" | "tiff")) => {
+            save_tiff(img, filepath, pixels_per_meter(img, width_mm))
+        }
+        _ => img
+            .save(filepath)
+            .map_err(|error| StringArtError::save(filepath, error)),
+    }
+}
+
+fn pixels_per_meter(img: &RgbaImage, width_mm: f64) -> f64 {
+    f64::from(img.width()) / width_mm * MM_PER_METER
+}
+
+fn save_png(img: &RgbaImage, filepath: &str, pixels_per_meter: f64) -> Result<(), StringArtError> {
+    let file = create(filepath)?;
+    let mut encoder = png::Encoder::new(file, img.width(), img.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let ppm = pixels_per_meter.round() as u32;
+    encoder.set_pixel_dims(Some(png::PixelDimensions {
+        xppu: ppm,
+        yppu: ppm,
+        unit: png::Unit::Meter,
+    }));
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(img.as_raw()))
+        .map_err(|error| StringArtError::encode(filepath, error))
+}
+
+fn save_tiff(img: &RgbaImage, filepath: &str, pixels_per_meter: f64) -> Result<(), StringArtError> {
+    let encode = |error| StringArtError::encode(filepath, error);
+    let mut encoder = TiffEncoder::new(create(filepath)?).map_err(encode)?;
+    let mut image = encoder
+        .new_image::<colortype::RGBA8>(img.width(), img.height())
+        .map_err(encode)?;
+    // Pixels per centimeter, to the nearest thousandth
+    image.resolution(
+        ResolutionUnit::Centimeter,
+        Rational {
+            n: (pixels_per_meter * 10.0).round() as u32,
+            d: 1000,
+        },
+    );
+    image.write_data(img.as_raw()).map_err(encode)
+}
+
+fn create(filepath: &str) -> Result<BufWriter<File>, StringArtError> {
+    File::create(filepath)
+        .map(BufWriter::new)
+        .map_err(|error| StringArtError::write(filepath, error))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn saved(filename: &str, width_mm: Option<f64>) -> Vec<u8> {
+        let filepath = std::env::temp_dir().join(filename);
+        let filepath = filepath.to_str().unwrap();
+        save(&RgbaImage::new(254, 10), filepath, width_mm).unwrap();
+        let bytes = std::fs::read(filepath).unwrap();
+        std::fs::remove_file(filepath).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_png_records_pixels_per_meter() {
+        // 254 pixels across 25.4mm is 10,000 pixels per meter
+        let bytes = saved("string_art_resolution.png", Some(25.4));
+        let decoder = png::Decoder::new(&bytes[..]).read_info().unwrap();
+        let dims = decoder.info().pixel_dims.unwrap();
+        assert_eq!((10_000, 10_000), (dims.xppu, dims.yppu));
+        assert_eq!(png::Unit::Meter, dims.unit);
+    }
+
+    #[test]
+    fn test_png_without_physical_size() {
+        let bytes = saved("string_art_no_resolution.png", None);
+        let decoder = png::Decoder::new(&bytes[..]).read_info().unwrap();
+        assert!(decoder.info().pixel_dims.is_none());
+    }
+
+    #[test]
+    fn test_tiff_records_resolution() {
+        use tiff::decoder::{ifd::Value, Decoder};
+        use tiff::tags::Tag;
+
+        let bytes = saved("string_art_resolution.tif", Some(25.4));
+        let mut decoder = Decoder::new(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(
+            Some(Value::Rational(100_000, 1000)),
+            decoder.find_tag(Tag::XResolution).unwrap()
+        );
+        assert_eq!(
+            Some(u32::from(ResolutionUnit::Centimeter.to_u16())),
+            decoder.find_tag_unsigned(Tag::ResolutionUnit).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_save_into_missing_directory() {
+        for width_mm in [None, Some(25.4)] {
+            let error = save(&RgbaImage::new(1, 1), "/nonexistent/art.png", width_mm).unwrap_err();
+            assert!(matches!(error, StringArtError::Write { .. }));
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use crate::geometry::Point;
+use crate::imagery::Gradient;
+use crate::imagery::LineSegment;
+use crate::imagery::Rgb;
+use crate::serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::style::Data;
+use std::collections::HashMap;
+
+/// A string as written to the data file, with the geometry build tools need so they don't have
+/// to recompute it. Strings wound along the same chord one after another are written once, with
+/// how many times to wind it. Lengths in millimeters are only known when --physical-width-mm is
+/// given.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Segment {
+    /// Where the first of the strings comes in the threading order
+    pub index: usize,
+    pub a: Point,
+    pub b: Point,
+    pub rgb: Rgb,
+    /// The color at `b` of a gradient string, which fades to it from `rgb` at `a`
+    pub rgb_end: Option<Rgb>,
+    /// How many times the chord is wound, back and forth
+    pub count: usize,
+    /// Which of the pin locations the string starts and ends at
+    pub pin_a: Option<usize>,
+    pub pin_b: Option<usize>,
+    /// The length of one string along the chord
+    pub length_px: f64,
+    pub length_mm: Option<f64>,
+    /// The length of these strings and every string before them
+    pub cumulative_length_px: f64,
+    pub cumulative_length_mm: Option<f64>,
+}
+
+/// The strings of a finished run, in threading order
+pub fn segments(data: &Data) -> impl Iterator<Item = Segment> + '_ {
+    let pins: HashMap<Point, usize> = data
+        .pin_locations
+        .iter()
+        .enumerate()
+        .map(|(i, pin)| (*pin, i))
+        .collect();
+    let mm_per_px = data
+        .args
+        .physical_width_mm
+        .map(|width_mm| width_mm / data.image_width as f64);
+    let mut cumulative_length_px = 0.0;
+    runs(data).map(move |(index, (a, b, rgb), count)| {
+        let (a, b, rgb) = (&a, &b, &rgb);
+        let length_px = distance(*a, *b);
+        cumulative_length_px += length_px * count as f64;
+        Segment {
+            index,
+            a: *a,
+            b: *b,
+            rgb: *rgb,
+            rgb_end: data
+                .args
+                .gradient_strings
+                .and_then(|gradient| gradient.end_for(*rgb)),
+            count,
+            pin_a: pins.get(a).copied(),
+            pin_b: pins.get(b).copied(),
+            length_px,
+            length_mm: mm_per_px.map(|mm| length_px * mm),
+            cumulative_length_px,
+            cumulative_length_mm: mm_per_px.map(|mm| cumulative_length_px * mm),
+        }
+    })
+}
+
+/// Whether two strings lie along the same chord in the same color. A gradient string looks
+/// different wound the other way, so it must also run the same way.
+pub fn same_chord(x: &LineSegment, y: &LineSegment, gradient: Option<Gradient>) -> bool {
+    let fades = gradient.is_some_and(|gradient| gradient.end_for(x.2).is_some());
+    x.2 == y.2 && ((x.0, x.1) == (y.0, y.1) || (!fades && (x.0, x.1) == (y.1, y.0)))
+}
+
+/// Runs of strings wound one after another along the same chord, as the index of the first
+/// string, the first string, and how many strings there are
+pub fn runs(data: &Data) -> impl Iterator<Item = (usize, LineSegment, usize)> + '_ {
+    let gradient = data.args.gradient_strings;
+    let mut index = 0;
+    data.line_segments
+        .chunk_by(move |x, y| same_chord(x, y, gradient))
+        .map(move |run| {
+            index += run.len();
+            (index - run.len(), run[0], run.len())
+        })
+}
+
+/// Move every string that repeats an earlier string's chord to just after it, so each chord is
+/// wound all at once. The chords keep the order they were first chosen in.
+pub fn gather(line_segments: &mut Vec<LineSegment>, gradient: Option<Gradient>) {
+    let mut gathered: Vec<Vec<LineSegment>> = Vec::new();
+    for line_segment in line_segments.drain(..) {
+        match gathered
+            .iter_mut()
+            .find(|run| same_chord(&run[0], &line_segment, gradient))
+        {
+            Some(run) => run.push(run[0]),
+            None => gathered.push(vec![line_segment]),
+        }
+    }
+    *line_segments = gathered.into_iter().flatten().collect();
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    let dx = a.x as f64 - b.x as f64;
+    let dy = a.y as f64 - b.y as f64;
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// Serialize the strings of a finished run one at a time, so large runs aren't copied
+pub fn serialize<S: Serializer>(data: &Data, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(segments(data))
+}
+
+/// A string read back from a data file. Older data files list each string as
+/// `[a, b, rgb]`, while newer ones use the fields of `Segment`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum SavedSegment {
+    Tuple(LineSegment),
+    Detailed {
+        a: Point,
+        b: Point,
+        rgb: Rgb,
+        #[serde(default)]
+        pin_a: Option<usize>,
+        #[serde(default)]
+        pin_b: Option<usize>,
+        #[serde(default = "one")]
+        count: usize,
+    },
+}
+
+fn one() -> usize {
+    1
+}
+
+impl SavedSegment {
+    /// The strings it stands for, one for each time its chord is wound
+    pub fn line_segments(self) -> impl Iterator<Item = LineSegment> {
+        match self {
+            SavedSegment::Tuple(line_segment) => std::iter::repeat_n(line_segment, 1),
+            SavedSegment::Detailed {
+                a, b, rgb, count, ..
+            } => std::iter::repeat_n((a, b, rgb), count),
+        }
+    }
+}
+
+/// Read the strings of a data file, expanding chords wound more than once
+pub fn deserialize_line_segments<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<LineSegment>, D::Error> {
+    let saved = Vec::<SavedSegment>::deserialize(deserializer)?;
+    Ok(saved
+        .into_iter()
+        .flat_map(SavedSegment::line_segments)
+        .collect())
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+
+    fn data(extra_args: &[&str]) -> Data {
+        let mut data = Data::example(extra_args);
+        data.line_segments = vec![
+            (Point::new(0, 0), Point::new(9, 0), Rgb::WHITE),
+            (Point::new(9, 0), Point::new(0, 9), Rgb::WHITE),
+            (Point::new(3, 4), Point::new(0, 0), Rgb::BLACK),
+        ];
+        data
+    }
+
+    #[test]
+    fn test_segments() {
+        let segments: Vec<Segment> = segments(&data(&[])).collect();
+        assert_eq!(3, segments.len());
+        assert_eq!(2, segments[2].index);
+        assert_eq!((Some(1), Some(2)), (segments[1].pin_a, segments[1].pin_b));
+        assert_eq!((None, Some(0)), (segments[2].pin_a, segments[2].pin_b));
+        assert_eq!(9.0, segments[0].length_px);
+        assert_eq!(5.0, segments[2].length_px);
+        assert_eq!(9.0 + 162f64.sqrt() + 5.0, segments[2].cumulative_length_px);
+        assert_eq!(None, segments[2].length_mm);
+    }
+
+    #[test]
+    fn test_segments_of_gradient_strings() {
+        let gradient: Vec<Segment> =
+            segments(&data(&["--gradient-strings", "#FFFFFF,#000000"])).collect();
+        assert_eq!(Some(Rgb::BLACK), gradient[0].rgb_end);
+        assert_eq!(Some(Rgb::WHITE), gradient[2].rgb_end);
+        assert_eq!(None, segments(&data(&[])).next().unwrap().rgb_end);
+    }
+
+    #[test]
+    fn test_segments_in_millimeters() {
+        // The example image is 10 pixels wide
+        let segments: Vec<Segment> = segments(&data(&["--physical-width-mm", "100"])).collect();
+        assert_eq!(Some(90.0), segments[0].length_mm);
+        assert_eq!(Some(50.0), segments[2].length_mm);
+    }
+
+    #[test]
+    fn test_saved_segments_read_either_format() {
+        let json = r#"[
+            [{"x": 0, "y": 0}, {"x": 9, "y": 0}, {"r": 255, "g": 255, "b": 255}],
+            {"index": 1, "a": {"x": 3, "y": 4}, "b": {"x": 0, "y": 0},
+             "rgb": {"r": 0, "g": 0, "b": 0}, "pin_a": null, "pin_b": 0}
+        ]"#;
+        let saved: Vec<SavedSegment> = serde_json::from_str(json).unwrap();
+        let line_segments: Vec<LineSegment> = saved
+            .into_iter()
+            .flat_map(SavedSegment::line_segments)
+            .collect();
+        assert_eq!(
+            vec![
+                (Point::new(0, 0), Point::new(9, 0), Rgb::WHITE),
+                (Point::new(3, 4), Point::new(0, 0), Rgb::BLACK),
+            ],
+            line_segments
+        );
+    }
+
+    #[test]
+    fn test_saved_segments_expand_their_count() {
+        let json = r#"[{"index": 0, "a": {"x": 0, "y": 0}, "b": {"x": 9, "y": 0},
+             "rgb": {"r": 255, "g": 255, "b": 255}, "count": 3}]"#;
+        let saved: Vec<SavedSegment> = serde_json::from_str(json).unwrap();
+        let line_segments: Vec<LineSegment> = saved
+            .into_iter()
+            .flat_map(SavedSegment::line_segments)
+            .collect();
+        assert_eq!(
+            vec![(Point::new(0, 0), Point::new(9, 0), Rgb::WHITE); 3],
+            line_segments
+        );
+    }
+
+    #[test]
+    fn test_segments_collapse_repeated_chords() {
+        let mut data = data(&[]);
+        let (a, b) = (Point::new(0, 0), Point::new(9, 0));
+        data.line_segments = vec![
+            (a, b, Rgb::WHITE),
+            (b, a, Rgb::WHITE),
+            (a, b, Rgb::WHITE),
+            (a, b, Rgb::BLACK),
+        ];
+        let segments: Vec<Segment> = segments(&data).collect();
+        assert_eq!(2, segments.len());
+        assert_eq!((0, 3), (segments[0].index, segments[0].count));
+        assert_eq!((3, 1), (segments[1].index, segments[1].count));
+        assert_eq!(27.0, segments[0].cumulative_length_px);
+        assert_eq!(9.0, segments[0].length_px);
+    }
+
+    #[test]
+    fn test_gather() {
+        let p = Point::new;
+        let mut line_segments = vec![
+            (p(0, 0), p(9, 0), Rgb::WHITE),
+            (p(0, 9), p(9, 0), Rgb::WHITE),
+            (p(9, 0), p(0, 0), Rgb::WHITE),
+            (p(0, 0), p(9, 0), Rgb::BLACK),
+        ];
+        gather(&mut line_segments, None);
+        assert_eq!(
+            vec![
+                (p(0, 0), p(9, 0), Rgb::WHITE),
+                (p(0, 0), p(9, 0), Rgb::WHITE),
+                (p(0, 9), p(9, 0), Rgb::WHITE),
+                (p(0, 0), p(9, 0), Rgb::BLACK),
+            ],
+            line_segments
+        );
+    }
+
+    #[test]
+    fn test_gradient_strings_only_match_the_same_way() {
+        let p = Point::new;
+        let gradient = Some(Gradient {
+            from: Rgb::WHITE,
+            to: Rgb::BLACK,
+        });
+        let x = (p(0, 0), p(9, 0), Rgb::WHITE);
+        assert!(same_chord(&x, &x, gradient));
+        assert!(!same_chord(&x, &(x.1, x.0, x.2), gradient));
+        assert!(same_chord(&x, &(x.1, x.0, x.2), None));
+    }
+}
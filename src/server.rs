@@ -0,0 +1,1045 @@
+//! `string_art serve`, which makes string art for other programs over HTTP.
+//!
+//! `POST /jobs` with an image as the body queues a job to make string art from it, and answers
+//! with the job's id. The options go in the query string, named like the long options of
+//! `string_art` without the leading `--`, like `/jobs?pin-count=300&max-strings=4000`. Flags are
+//! given without a value. Options that read or write files on the server can't be given.
+//!
+//! - `GET /jobs/{id}` reports the job's state and progress.
+//! - `POST /jobs/{id}/cancel` cancels it. A running job stops with the strings placed so far.
+//! - `GET /jobs/{id}/image.png` and `GET /jobs/{id}/data.json` download the finished work.
+//! - `GET /metrics` reports on the jobs in the Prometheus text format.
+//!
+//! Each job is kept in its own directory, so the queue survives a restart. Jobs that were
+//! running when the server stopped are started over. Finished jobs are removed once the
+//! retention period has passed.
+
+use crate::cli_app;
+use crate::cli_app::{Args, Cli};
+use crate::control::{Cancellation, Progress};
+use crate::error::StringArtError;
+use crate::imagery::RefImage;
+use crate::resolution;
+use crate::serde::{Deserialize, Serialize};
+use crate::string_art;
+use crate::style::Data;
+use crate::term;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tiny_http::{Header, Method, Request, Response};
+
+/// The options that read or write files, which jobs may not be given
+const FILE_OPTIONS: [&str; 23] = [
+    "output_filepath",
+    "svg_filepath",
+    "pins_filepath",
+    "data_filepath",
+    "instructions_filepath",
+    "archive_filepath",
+    "snapshot_filepath",
+    "gif_filepath",
+    "video_filepath",
+    "milestones_filepath",
+    "animation_filepath",
+    "morph_to",
+    "morph_filepath",
+    "explain_filepath",
+    "simulate_filepath",
+    "edit_from",
+    "continue_from",
+    "resume",
+    "canvas_image",
+    "exclusion_mask",
+    "mask_filepath",
+    "weight_filepath",
+    "event_log_filepath",
+];
+
+/// The files a finished job can be downloaded as
+const ARTIFACTS: [&str; 2] = ["image.png", "data.json"];
+
+/// The file in each job's directory that records it
+const RECORD: &str = "job.json";
+
+/// How often finished jobs are checked for being past the retention period
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+const SECONDS_PER_HOUR: f64 = 3600.0;
+
+#[derive(Parser, Debug)]
+#[command(name = "string_art serve", version, max_term_width(100))]
+pub struct ServeCli {
+    /// The address to listen on.
+    #[arg(short, long, default_value("127.0.0.1:8080"))]
+    pub address: String,
+
+    /// How many jobs to work on at once. The threads are split evenly between them, and the
+    /// other jobs wait in a queue.
+    #[arg(short, long, default_value("1"), value_parser = clap::value_parser!(u64).range(1..))]
+    pub jobs: u64,
+
+    /// Where to keep the jobs, their images, and their finished work. Jobs already in it are
+    /// picked up again.
+    #[arg(long, value_name("DIRECTORY"), default_value("string_art_jobs"))]
+    pub jobs_directory: String,
+
+    /// How many hours a finished job can be downloaded for before it is removed.
+    #[arg(long, value_name("HOURS"), default_value("24"), value_parser = cli_app::positive_f64)]
+    pub retention_hours: f64,
+}
+
+/// Run `string_art serve` until the process is stopped
+pub fn serve() {
+    // Skip the program name, so `serve` is treated as the name of the command
+    let cli = ServeCli::parse_from(std::env::args().skip(1));
+    let retention = Duration::from_secs_f64(cli.retention_hours * SECONDS_PER_HOUR);
+    let jobs = Jobs::open(PathBuf::from(&cli.jobs_directory), retention).unwrap_or_else(|error| {
+        term::error(&error.to_string());
+        std::process::exit(1)
+    });
+    let server = tiny_http::Server::http(&cli.address).unwrap_or_else(|error| {
+        term::error(&format!("Unable to listen on {}: {}", cli.address, error));
+        std::process::exit(1)
+    });
+    println!("Listening on http://{}", cli.address);
+    run(&server, &jobs, cli.jobs as usize);
+}
+
+/// Answer requests until the server stops, working on `workers` jobs at once
+fn run(server: &tiny_http::Server, jobs: &Jobs, workers: usize) {
+    let (queue, receiver) = channel();
+    let receiver = Mutex::new(receiver);
+    for job in jobs.unfinished() {
+        queue.send(job).expect("The queue was closed");
+    }
+    let threads = usize::max(1, rayon::current_num_threads() / workers);
+    let (stop, stopped) = channel::<()>();
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| work(jobs, &receiver, threads));
+        }
+        scope.spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(EXPIRY_INTERVAL) {
+                jobs.expire();
+            }
+        });
+        for request in server.incoming_requests() {
+            let queue = queue.clone();
+            scope.spawn(move || respond(jobs, &queue, request));
+        }
+        // The workers stop once the queue is closed, and the expiry once `stop` is dropped
+        drop(queue);
+        drop(stop);
+    });
+}
+
+/// What a job is doing, or how it ended
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum State {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl State {
+    fn is_finished(&self) -> bool {
+        !matches!(self, State::Queued | State::Running)
+    }
+}
+
+/// A job as it is saved in its directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Record {
+    id: u64,
+    /// The name of the image in the job's directory
+    input: String,
+    /// The options, as they would be given on the command line
+    options: Vec<String>,
+    state: State,
+    /// Seconds since the Unix epoch
+    submitted_at: u64,
+    finished_at: Option<u64>,
+    /// Why the job failed
+    error: Option<String>,
+    /// How many strings the finished work has
+    strings: Option<usize>,
+    /// The score of the finished work
+    score: Option<i64>,
+}
+
+struct Job {
+    record: Mutex<Record>,
+    directory: PathBuf,
+    progress: Progress,
+    cancellation: Cancellation,
+    /// When the job started running, since the server started
+    started_at: Mutex<Option<Instant>>,
+}
+
+impl Job {
+    fn new(record: Record, directory: PathBuf) -> Self {
+        Self {
+            record: Mutex::new(record),
+            directory,
+            progress: Progress::default(),
+            cancellation: Cancellation::default(),
+            started_at: Mutex::new(None),
+        }
+    }
+
+    fn id(&self) -> u64 {
+        self.record.lock().unwrap().id
+    }
+
+    fn state(&self) -> State {
+        self.record.lock().unwrap().state
+    }
+
+    /// Change the record, and save it so the change outlasts the server
+    fn update(&self, change: impl FnOnce(&mut Record)) {
+        let mut record = self.record.lock().unwrap();
+        change(&mut record);
+        if let Err(error) = save_record(&self.directory, &record) {
+            term::warn(&error.to_string());
+        }
+    }
+
+    /// Mark the job as running, unless it was cancelled while it was queued
+    fn start(&self) -> bool {
+        let mut started = false;
+        self.update(|record| {
+            if record.state == State::Queued {
+                record.state = State::Running;
+                started = true;
+            }
+        });
+        if started {
+            *self.started_at.lock().unwrap() = Some(Instant::now());
+        }
+        started
+    }
+
+    /// Whether the job made its files. Jobs cancelled before they started didn't.
+    fn has_artifacts(&self, state: State) -> bool {
+        matches!(state, State::Succeeded | State::Cancelled)
+            && self.directory.join(ARTIFACTS[0]).is_file()
+    }
+
+    fn cancel(&self) {
+        self.cancellation.cancel();
+        self.update(|record| {
+            if record.state == State::Queued {
+                record.state = State::Cancelled;
+                record.finished_at = Some(now());
+            }
+        });
+    }
+
+    /// Place the strings, and save the finished work
+    fn run(&self) -> Result<Data, StringArtError> {
+        let (input, options) = {
+            let record = self.record.lock().unwrap();
+            (self.directory.join(&record.input), record.options.clone())
+        };
+        let mut args = job_args(&input.display().to_string(), &options)?;
+        args.progress = self.progress.clone();
+        args.cancellation = self.cancellation.clone();
+        let data = crate::generate_from_args(args)?;
+        let [image, data_file] =
+            ARTIFACTS.map(|name| self.directory.join(name).display().to_string());
+        resolution::save(
+            &RefImage::from(&data).color(),
+            &image,
+            data.args.physical_width_mm,
+        )?;
+        string_art::write_data(&data, &data_file)?;
+        Ok(data)
+    }
+
+    fn finish(&self, result: &Result<Data, StringArtError>) {
+        let cancelled = self.cancellation.is_cancelled();
+        self.update(|record| {
+            record.finished_at = Some(now());
+            match result {
+                Ok(data) => {
+                    record.state = match cancelled {
+                        true => State::Cancelled,
+                        false => State::Succeeded,
+                    };
+                    record.strings = Some(data.line_segments.len());
+                    record.score = Some(data.final_score);
+                }
+                Err(error) => {
+                    record.state = State::Failed;
+                    record.error = Some(error.to_string());
+                }
+            }
+        });
+    }
+
+    /// The job's state and progress, as JSON
+    fn status(&self) -> serde_json::Value {
+        let record = self.record.lock().unwrap().clone();
+        let mut status = serde_json::json!({
+            "id": record.id,
+            "state": record.state,
+            "submitted_at": record.submitted_at,
+            "finished_at": record.finished_at,
+            "error": record.error,
+            "strings": record.strings,
+            "score": record.score,
+            "artifacts": match self.has_artifacts(record.state) {
+                true => ARTIFACTS.to_vec(),
+                false => Vec::new(),
+            },
+        });
+        if let (State::Running, Some(started_at)) = (record.state, *self.started_at.lock().unwrap())
+        {
+            status["strings"] = self.progress.strings().into();
+            status["score"] = self.progress.score().into();
+            status["strings_per_second"] =
+                (self.progress.placed() as f64 / started_at.elapsed().as_secs_f64()).into();
+        }
+        status
+    }
+}
+
+fn save_record(directory: &Path, record: &Record) -> Result<(), StringArtError> {
+    let filepath = directory.join(RECORD);
+    let partial = directory.join(format!("{}.partial", RECORD));
+    let json = serde_json::to_vec_pretty(record).expect("Unable to serialize the job");
+    // Write the whole record before replacing the old one, so a crash can't leave half of it
+    std::fs::write(&partial, json)
+        .and_then(|_| std::fs::rename(&partial, &filepath))
+        .map_err(|error| StringArtError::write(&filepath.display().to_string(), error))
+}
+
+/// Seconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// The jobs the server knows of, and counts of the ones it has finished since it started
+struct Jobs {
+    directory: PathBuf,
+    retention: Duration,
+    next_id: AtomicU64,
+    all: Mutex<BTreeMap<u64, Arc<Job>>>,
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+    cancelled: AtomicU64,
+    /// The strings placed by the jobs that have finished
+    placed: AtomicU64,
+}
+
+impl Jobs {
+    /// Pick up the jobs already in `directory`, creating it if it doesn't exist
+    fn open(directory: PathBuf, retention: Duration) -> Result<Self, StringArtError> {
+        let read_error = |source| StringArtError::Read {
+            filepath: directory.display().to_string(),
+            source,
+        };
+        std::fs::create_dir_all(&directory)
+            .map_err(|error| StringArtError::write(&directory.display().to_string(), error))?;
+        let mut all = BTreeMap::new();
+        for entry in std::fs::read_dir(&directory).map_err(read_error)? {
+            let job_directory = entry.map_err(read_error)?.path();
+            let filepath = job_directory.join(RECORD);
+            if !filepath.is_file() {
+                continue;
+            }
+            match string_art::read_data::<Record>(&filepath.display().to_string()) {
+                Ok(record) => {
+                    all.insert(record.id, Arc::new(Job::new(record, job_directory)));
+                }
+                // One broken job shouldn't keep the others from being served
+                Err(error) => term::warn(&error.to_string()),
+            }
+        }
+        let jobs = Self {
+            directory,
+            retention,
+            next_id: AtomicU64::new(all.keys().max().map_or(1, |id| id + 1)),
+            all: Mutex::new(all),
+            succeeded: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            cancelled: AtomicU64::new(0),
+            placed: AtomicU64::new(0),
+        };
+        jobs.expire();
+        Ok(jobs)
+    }
+
+    fn get(&self, id: u64) -> Option<Arc<Job>> {
+        self.all.lock().unwrap().get(&id).cloned()
+    }
+
+    /// The jobs that haven't finished, oldest first. Jobs that were running when the server
+    /// stopped are queued to start over.
+    fn unfinished(&self) -> Vec<Arc<Job>> {
+        let unfinished: Vec<Arc<Job>> = self
+            .all
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|job| !job.state().is_finished())
+            .cloned()
+            .collect();
+        for job in &unfinished {
+            job.update(|record| record.state = State::Queued);
+        }
+        unfinished
+    }
+
+    /// Save a job for `image`, checking its options first
+    fn submit(&self, image: &[u8], options: Vec<String>) -> Result<Arc<Job>, Rejection> {
+        let format = image::guess_format(image).map_err(|_| Rejection::NotAnImage)?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let directory = self.directory.join(id.to_string());
+        let record = Record {
+            id,
+            input: format!(
+                "input.{}",
+                format.extensions_str().first().unwrap_or(&"img")
+            ),
+            options,
+            state: State::Queued,
+            submitted_at: now(),
+            finished_at: None,
+            error: None,
+            strings: None,
+            score: None,
+        };
+        let input = directory.join(&record.input);
+        let saved = std::fs::create_dir_all(&directory)
+            .and_then(|_| std::fs::write(&input, image))
+            .map_err(|error| StringArtError::write(&input.display().to_string(), error))
+            .and_then(|_| job_args(&input.display().to_string(), &record.options))
+            .and_then(|_| save_record(&directory, &record));
+        if let Err(error) = saved {
+            let _ = std::fs::remove_dir_all(&directory);
+            return Err(Rejection::Invalid(error));
+        }
+        let job = Arc::new(Job::new(record, directory));
+        self.all.lock().unwrap().insert(id, Arc::clone(&job));
+        Ok(job)
+    }
+
+    fn finish(&self, job: &Job, result: &Result<Data, StringArtError>) {
+        job.finish(result);
+        self.placed
+            .fetch_add(job.progress.placed(), Ordering::Relaxed);
+        let count = match job.state() {
+            State::Succeeded => &self.succeeded,
+            State::Cancelled => &self.cancelled,
+            _ => &self.failed,
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Remove the finished jobs that are past the retention period
+    fn expire(&self) {
+        let oldest = now().saturating_sub(self.retention.as_secs());
+        let expired: Vec<Arc<Job>> = {
+            let mut all = self.all.lock().unwrap();
+            let ids: Vec<u64> = all
+                .iter()
+                .filter(|(_, job)| {
+                    let record = job.record.lock().unwrap();
+                    record.finished_at.is_some_and(|at| at <= oldest)
+                })
+                .map(|(id, _)| *id)
+                .collect();
+            ids.iter().filter_map(|id| all.remove(id)).collect()
+        };
+        for job in expired {
+            if let Err(error) = std::fs::remove_dir_all(&job.directory) {
+                term::warn(
+                    &StringArtError::write(&job.directory.display().to_string(), error).to_string(),
+                );
+            }
+        }
+    }
+
+    /// The metrics in the Prometheus text format
+    fn metrics(&self) -> String {
+        let active: Vec<(Arc<Job>, State)> = self
+            .all
+            .lock()
+            .unwrap()
+            .values()
+            .map(|job| (Arc::clone(job), job.state()))
+            .filter(|(_, state)| !state.is_finished())
+            .collect();
+        let running: Vec<(&Job, Instant)> = active
+            .iter()
+            .filter(|(_, state)| *state == State::Running)
+            .filter_map(|(job, _)| Some((&**job, (*job.started_at.lock().unwrap())?)))
+            .collect();
+        let placed = self.placed.load(Ordering::Relaxed)
+            + active
+                .iter()
+                .map(|(job, _)| job.progress.placed())
+                .sum::<u64>();
+
+        let mut metrics = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+            let _ = writeln!(metrics, "# HELP {} {}", name, help);
+            let _ = writeln!(metrics, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(metrics, "{}{} {}", name, labels, value);
+            }
+        };
+        metric(
+            "string_art_jobs_queued",
+            "gauge",
+            "Jobs waiting to start.",
+            vec![(String::new(), (active.len() - running.len()).to_string())],
+        );
+        metric(
+            "string_art_jobs_running",
+            "gauge",
+            "Jobs placing strings.",
+            vec![(String::new(), running.len().to_string())],
+        );
+        metric(
+            "string_art_jobs_finished_total",
+            "counter",
+            "Jobs that have finished since the server started, by how they ended.",
+            [
+                ("succeeded", &self.succeeded),
+                ("failed", &self.failed),
+                ("cancelled", &self.cancelled),
+            ]
+            .into_iter()
+            .map(|(outcome, count)| {
+                (
+                    format!("{{outcome=\"{}\"}}", outcome),
+                    count.load(Ordering::Relaxed).to_string(),
+                )
+            })
+            .collect(),
+        );
+        metric(
+            "string_art_strings_placed_total",
+            "counter",
+            "Strings added by every job, counting any that were removed again.",
+            vec![(String::new(), placed.to_string())],
+        );
+        let per_job = |value: &dyn Fn(&Job, Instant) -> String| {
+            running
+                .iter()
+                .map(|(job, started_at)| {
+                    (format!("{{job=\"{}\"}}", job.id()), value(job, *started_at))
+                })
+                .collect()
+        };
+        metric(
+            "string_art_job_strings_per_second",
+            "gauge",
+            "Strings each running job has added per second.",
+            per_job(&|job, started_at| {
+                let seconds = started_at.elapsed().as_secs_f64();
+                (job.progress.placed() as f64 / seconds).to_string()
+            }),
+        );
+        metric(
+            "string_art_job_strings",
+            "gauge",
+            "Strings each running job has now.",
+            per_job(&|job, _| job.progress.strings().to_string()),
+        );
+        metric(
+            "string_art_job_score",
+            "gauge",
+            "The score of each running job, measured about once a second. Lower is better.",
+            per_job(&|job, _| job.progress.score().to_string()),
+        );
+        metrics
+    }
+}
+
+/// Why a job wasn't queued
+enum Rejection {
+    NotAnImage,
+    Invalid(StringArtError),
+}
+
+/// Take jobs from the queue one at a time, each on its own `threads` threads
+fn work(jobs: &Jobs, queue: &Mutex<Receiver<Arc<Job>>>, threads: usize) {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("Unable to start the threads");
+    loop {
+        let next = queue.lock().unwrap().recv();
+        let Ok(job) = next else {
+            return;
+        };
+        if !job.start() {
+            continue;
+        }
+        let result = pool.install(|| job.run());
+        jobs.finish(&job, &result);
+    }
+}
+
+type Answer = Response<Cursor<Vec<u8>>>;
+
+fn respond(jobs: &Jobs, queue: &Sender<Arc<Job>>, mut request: Request) {
+    let url = request.url().to_owned();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let job = match segments.get(..2) {
+        Some(["jobs", id]) => match id.parse().ok().and_then(|id| jobs.get(id)) {
+            Some(job) => Some(job),
+            None => {
+                let _ = request.respond(text(404, "There is no such job"));
+                return;
+            }
+        },
+        _ => None,
+    };
+    let response = match (request.method(), &segments[..], job) {
+        (Method::Get, ["metrics"], _) => answer(
+            200,
+            "text/plain; version=0.0.4",
+            jobs.metrics().into_bytes(),
+        ),
+        (Method::Post, ["jobs"], _) => submit(jobs, queue, &mut request, query),
+        (Method::Get, ["jobs", _], Some(job)) => json(200, &job.status()),
+        (Method::Post, ["jobs", _, "cancel"], Some(job)) => {
+            job.cancel();
+            json(200, &job.status())
+        }
+        (Method::Get, ["jobs", _, artifact], Some(job)) if ARTIFACTS.contains(artifact) => {
+            download(&job, artifact)
+        }
+        (_, ["metrics"] | ["jobs"] | ["jobs", _] | ["jobs", _, "cancel"], _) => {
+            text(405, "Method not allowed")
+        }
+        _ => text(404, "Not found"),
+    };
+    // The client may have gone away
+    let _ = request.respond(response);
+}
+
+/// Queue a job for the image in the body of the request
+fn submit(jobs: &Jobs, queue: &Sender<Arc<Job>>, request: &mut Request, query: &str) -> Answer {
+    let mut image = Vec::new();
+    if let Err(error) = request.as_reader().read_to_end(&mut image) {
+        return text(400, &format!("Unable to read the image: {}", error));
+    }
+    let job = match options(query)
+        .map_err(Rejection::Invalid)
+        .and_then(|options| jobs.submit(&image, options))
+    {
+        Ok(job) => job,
+        Err(Rejection::NotAnImage) => {
+            return text(415, "The body must be an image in a supported format")
+        }
+        Err(Rejection::Invalid(error)) => return failure(&error),
+    };
+    if queue.send(Arc::clone(&job)).is_err() {
+        return text(503, "The server is shutting down");
+    }
+    let location = format!("/jobs/{}", job.id());
+    json(202, &job.status())
+        .with_header(Header::from_bytes("Location", location).expect("Invalid location header"))
+}
+
+/// Send one of the files a finished job made
+fn download(job: &Job, artifact: &str) -> Answer {
+    if !job.has_artifacts(job.state()) {
+        return text(409, "The job hasn't made its files");
+    }
+    let content_type = match artifact.ends_with(".png") {
+        true => "image/png",
+        false => "application/json",
+    };
+    match std::fs::read(job.directory.join(artifact)) {
+        Ok(bytes) => answer(200, content_type, bytes),
+        Err(error) => text(500, &format!("Unable to read {}: {}", artifact, error)),
+    }
+}
+
+/// The options in the query string, as they would be given on the command line
+fn options(query: &str) -> Result<Vec<String>, StringArtError> {
+    let mut options = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let decoded = |component| {
+            percent_decode(component).ok_or_else(|| {
+                cli_app::arg_error(
+                    ErrorKind::InvalidUtf8,
+                    format!("The query string has a badly encoded part: {}", component),
+                )
+            })
+        };
+        options.push(format!("--{}", decoded(name)?));
+        if !value.is_empty() {
+            options.push(decoded(value)?);
+        }
+    }
+    Ok(options)
+}
+
+/// Parse the options like the command line, for the image at `filepath`
+fn job_args(filepath: &str, options: &[String]) -> Result<Args, StringArtError> {
+    let command_line = ["string_art", "--input-filepath", filepath]
+        .into_iter()
+        .map(str::to_owned)
+        .chain(options.iter().cloned());
+    let matches = Cli::command().try_get_matches_from(command_line)?;
+    if let Some(id) = FILE_OPTIONS
+        .iter()
+        .find(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+    {
+        return Err(cli_app::arg_error(
+            ErrorKind::ArgumentConflict,
+            format!(
+                "--{} reads or writes files on the server, so jobs can't be given it",
+                id.replace('_', "-")
+            ),
+        ));
+    }
+    Args::try_from(Cli::from_arg_matches(&matches)?)
+}
+
+/// Decode a part of a query string, where `+` is a space and `%XX` is a byte
+fn percent_decode(component: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = component.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = std::str::from_utf8(rest.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &rest[2..];
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// The answer to a job that couldn't be queued: a bad request if its options were wrong, or an
+/// error of the server otherwise
+fn failure(error: &StringArtError) -> Answer {
+    match error {
+        StringArtError::Args(_) => text(400, &error.to_string()),
+        _ => text(500, &error.to_string()),
+    }
+}
+
+fn json(status: u16, value: &serde_json::Value) -> Answer {
+    answer(status, "application/json", value.to_string().into_bytes())
+}
+
+fn text(status: u16, message: &str) -> Answer {
+    answer(status, "text/plain", format!("{}\n", message).into_bytes())
+}
+
+fn answer(status: u16, content_type: &str, body: Vec<u8>) -> Answer {
+    Response::from_data(body)
+        .with_status_code(status)
+        .with_header(
+            Header::from_bytes("Content-Type", content_type).expect("Invalid content type header"),
+        )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+
+    /// An empty directory for the jobs of one test
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("string_art_server_{}", name));
+        let _ = std::fs::remove_dir_all(&directory);
+        directory
+    }
+
+    /// Start a server on a free port, working on one job at a time
+    fn start(directory: &Path) -> SocketAddr {
+        let jobs = Jobs::open(directory.to_owned(), Duration::from_secs(3600)).unwrap();
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        std::thread::spawn(move || run(&server, &jobs, 1));
+        address
+    }
+
+    /// Send a request, and return the status code, the head, and the body of the response
+    fn send(
+        address: SocketAddr,
+        method: &str,
+        target: &str,
+        body: &[u8],
+    ) -> (u16, String, Vec<u8>) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            target,
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8(response[..split].to_vec()).unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, head, response[split + 4..].to_vec())
+    }
+
+    /// Send a request, and return the status code and the body of the response
+    fn request(address: SocketAddr, method: &str, target: &str, body: &[u8]) -> (u16, String) {
+        let (status, _, body) = send(address, method, target, body);
+        (status, String::from_utf8(body).unwrap())
+    }
+
+    fn status(address: SocketAddr, id: u64) -> serde_json::Value {
+        let (code, body) = request(address, "GET", &format!("/jobs/{}", id), &[]);
+        assert_eq!(200, code, "{}", body);
+        serde_json::from_str(&body).unwrap()
+    }
+
+    /// Queue a job, and return its id
+    fn submit(address: SocketAddr, query: &str) -> u64 {
+        let (code, head, body) = send(address, "POST", &format!("/jobs?{}", query), &png());
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(202, code, "{}", body);
+        let id = body["id"].as_u64().unwrap();
+        assert!(
+            head.contains(&format!("Location: /jobs/{}\r\n", id)),
+            "{}",
+            head
+        );
+        id
+    }
+
+    /// Poll the job until it has finished, and return its status
+    fn wait(address: SocketAddr, id: u64) -> serde_json::Value {
+        for _ in 0..600 {
+            let status = status(address, id);
+            if !["queued", "running"].contains(&status["state"].as_str().unwrap()) {
+                return status;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("Job {} didn't finish", id)
+    }
+
+    /// A white diagonal line on black
+    fn png() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(20, 20, |x, y| {
+            image::Rgb([if x == y { 255 } else { 0 }; 3])
+        }))
+        .write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+        .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_jobs_can_be_downloaded_when_done() {
+        let address = start(&directory("done"));
+        let id = submit(
+            address,
+            "pin-count=8&max-strings=20&foreground-color=%23FFFF00&float-accumulation",
+        );
+        let status = wait(address, id);
+        assert_eq!("succeeded", status["state"]);
+        assert_eq!(
+            serde_json::json!(["image.png", "data.json"]),
+            status["artifacts"]
+        );
+        let strings = status["strings"].as_u64().unwrap();
+        assert!((1..=20).contains(&strings));
+
+        let (code, body) = request(address, "GET", &format!("/jobs/{}/data.json", id), &[]);
+        assert_eq!(200, code);
+        let data: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(20, data["image_width"]);
+        // Chords wound more than once are saved once
+        let chords = data["line_segments"].as_array().unwrap().len() as u64;
+        assert!((1..=strings).contains(&chords));
+        assert_eq!(
+            serde_json::json!({"r": 255, "g": 255, "b": 0}),
+            data["args"]["foreground_colors"][0]
+        );
+        assert_eq!(true, data["args"]["float_accumulation"]);
+
+        let (code, head, image) = send(address, "GET", &format!("/jobs/{}/image.png", id), &[]);
+        assert_eq!(200, code);
+        assert!(head.contains("Content-Type: image/png"), "{}", head);
+        assert_eq!(
+            (20, 20),
+            image::load_from_memory(&image)
+                .unwrap()
+                .to_rgba8()
+                .dimensions()
+        );
+    }
+
+    #[test]
+    fn test_jobs_are_counted_in_the_metrics() {
+        let address = start(&directory("metrics"));
+        wait(address, submit(address, "pin-count=8&max-strings=20"));
+        let (code, metrics) = request(address, "GET", "/metrics", &[]);
+        assert_eq!(200, code);
+        assert!(metrics.contains("# TYPE string_art_jobs_queued gauge\nstring_art_jobs_queued 0\n"));
+        assert!(metrics.contains("string_art_jobs_running 0\n"));
+        assert!(metrics.contains("string_art_jobs_finished_total{outcome=\"succeeded\"} 1\n"));
+        assert!(metrics.contains("string_art_jobs_finished_total{outcome=\"failed\"} 0\n"));
+        assert!(metrics.contains("string_art_jobs_finished_total{outcome=\"cancelled\"} 0\n"));
+        assert!(!metrics.contains("string_art_strings_placed_total 0\n"));
+        assert!(metrics.contains("# TYPE string_art_job_score gauge\n"));
+    }
+
+    #[test]
+    fn test_jobs_can_be_cancelled() {
+        let address = start(&directory("cancel"));
+        // A long job keeps the only worker busy, so the next one stays queued
+        let running = submit(address, "pin-count=200&max-strings=1000000&step-size=1");
+        let queued = submit(address, "pin-count=8&max-strings=20");
+        while status(address, running)["state"] != "running" {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let (code, body) = request(address, "POST", &format!("/jobs/{}/cancel", queued), &[]);
+        assert_eq!(200, code);
+        assert!(body.contains("\"state\":\"cancelled\""), "{}", body);
+        let (code, _) = request(address, "GET", &format!("/jobs/{}/image.png", queued), &[]);
+        assert_eq!(409, code);
+
+        request(address, "POST", &format!("/jobs/{}/cancel", running), &[]);
+        let status = wait(address, running);
+        assert_eq!("cancelled", status["state"]);
+        let (code, _) = request(address, "GET", &format!("/jobs/{}/data.json", running), &[]);
+        assert_eq!(200, code);
+        assert_eq!("cancelled", self::status(address, queued)["state"]);
+    }
+
+    #[test]
+    fn test_jobs_outlast_the_server() {
+        let directory = directory("restart");
+        let first = start(&directory);
+        let id = submit(first, "pin-count=8&max-strings=20");
+        wait(first, id);
+        // A job that was queued when the server stopped
+        std::fs::create_dir_all(directory.join("7")).unwrap();
+        std::fs::write(directory.join("7").join("input.png"), png()).unwrap();
+        let record = Record {
+            id: 7,
+            input: "input.png".to_owned(),
+            options: vec!["--pin-count".to_owned(), "8".to_owned()],
+            state: State::Running,
+            submitted_at: now(),
+            finished_at: None,
+            error: None,
+            strings: None,
+            score: None,
+        };
+        save_record(&directory.join("7"), &record).unwrap();
+
+        let second = start(&directory);
+        assert_eq!("succeeded", status(second, id)["state"]);
+        assert_eq!(
+            200,
+            request(second, "GET", &format!("/jobs/{}/data.json", id), &[]).0
+        );
+        assert_eq!("succeeded", wait(second, 7)["state"]);
+        assert_eq!(8, submit(second, "pin-count=8&max-strings=20"));
+    }
+
+    #[test]
+    fn test_finished_jobs_expire() {
+        let directory = directory("expire");
+        let jobs = Jobs::open(directory.clone(), Duration::ZERO).unwrap();
+        let job = jobs.submit(&png(), Vec::new()).ok().unwrap();
+        jobs.expire();
+        assert!(jobs.get(job.id()).is_some());
+        job.cancel();
+        jobs.expire();
+        assert!(jobs.get(job.id()).is_none());
+        assert!(!job.directory.exists());
+    }
+
+    #[test]
+    fn test_jobs_may_not_touch_files() {
+        let directory = directory("files");
+        let address = start(&directory);
+        let (code, body) = request(
+            address,
+            "POST",
+            "/jobs?output-filepath=%2Ftmp%2Fstring_art_server.png",
+            &png(),
+        );
+        assert_eq!(400, code);
+        assert!(body.contains("--output-filepath reads or writes files on the server"));
+        let (code, _) = request(address, "POST", "/jobs?output-filepath%3Dout.png", &png());
+        assert_eq!(400, code);
+        assert_eq!(0, std::fs::read_dir(&directory).unwrap().count());
+    }
+
+    #[test]
+    fn test_bad_requests() {
+        let address = start(&directory("bad"));
+        assert_eq!(415, request(address, "POST", "/jobs", b"not an image").0);
+        assert_eq!(
+            400,
+            request(address, "POST", "/jobs?pin-count=many", &png()).0
+        );
+        assert_eq!(405, request(address, "GET", "/jobs", &[]).0);
+        assert_eq!(404, request(address, "GET", "/jobs/12", &[]).0);
+        assert_eq!(404, request(address, "GET", "/jobs/first", &[]).0);
+        assert_eq!(404, request(address, "GET", "/", &[]).0);
+    }
+
+    #[test]
+    fn test_file_options_are_options() {
+        let command = Cli::command();
+        for id in FILE_OPTIONS {
+            assert!(
+                command.get_arguments().any(|arg| arg.get_id() == id),
+                "{} is not an option",
+                id
+            );
+        }
+        for arg in command.get_arguments() {
+            let id = arg.get_id().as_str();
+            if id.ends_with("filepath") && id != "input_filepath" {
+                assert!(FILE_OPTIONS.contains(&id), "{} is missing", id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(Some("#FF0000".to_owned()), percent_decode("%23FF0000"));
+        assert_eq!(Some("a b c".to_owned()), percent_decode("a+b%20c"));
+        assert_eq!(None, percent_decode("%2"));
+        assert_eq!(None, percent_decode("%zz"));
+        assert_eq!(None, percent_decode("%FF"));
+    }
+}
@@ -1,37 +1,264 @@
+use crate::animation;
+use crate::archive;
+use crate::arrangements;
+use crate::batch;
+use crate::candidate;
 use crate::cli_app;
+use crate::cli_app::Args;
+use crate::control;
+use crate::diff;
+use crate::edit;
+use crate::ensemble;
+use crate::error::StringArtError;
 use crate::geometry::Point;
+use crate::instructions;
+use crate::memory;
+use crate::morph;
+use crate::optimum::Constraints;
+use crate::overwrite;
 use crate::pins;
+use crate::replay;
+use crate::serde::de::DeserializeOwned;
+#[cfg(feature = "server")]
+use crate::server;
 use crate::style;
+use crate::style::Data;
+use crate::summary;
+use crate::sweep;
+use crate::template;
+use crate::term;
+use crate::validate;
+use crate::warnings;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::time::Instant;
+
+/// The first bytes of every gzip file
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
 // Create an image of the string art and output the knob positions and sequence
 pub fn create_string() {
-    let args = cli_app::parse_args();
+    match std::env::args().nth(1).as_deref() {
+        Some("validate") => validate::validate(),
+        Some("batch") => batch::batch(),
+        Some("diff") => diff::diff(),
+        Some("replay") => replay::replay(),
+        Some("compare") => candidate::compare(),
+        #[cfg(feature = "server")]
+        Some("serve") => server::serve(),
+        _ => {
+            let started_at = Instant::now();
+            if let Err(error) = cli_app::parse_args().and_then(|args| run(args, started_at)) {
+                term::error(&error.to_string());
+                std::process::exit(1)
+            }
+        }
+    }
+}
+
+/// Create string art from arguments that have already been parsed. `started_at` is when the
+/// parsing started, so the time spent preprocessing can be reported.
+pub fn run(args: Args, started_at: Instant) -> Result<(), StringArtError> {
+    overwrite::check(&args)?;
+    let preprocess_seconds = started_at.elapsed().as_secs_f64();
+
+    if let Some(filepath) = args.edit_from.clone() {
+        return edit::edit(&filepath, args);
+    }
+
+    control::cancel_on_interrupt(&args.cancellation);
+    control::pause_on_signal(&args.pause);
+
+    if args.compare_arrangements {
+        println!("{}", arrangements::table(&arrangements::benchmark(&args)?));
+        return Ok(());
+    }
+
+    if let Some(ref parameters) = args.sweep {
+        let sheet = sweep::sweep(&args, parameters)?;
+        for filepath in &args.output_filepaths {
+            sheet
+                .save(filepath)
+                .map_err(|error| StringArtError::save(filepath, error))?;
+        }
+        return Ok(());
+    }
 
     let height = args.image.height();
     let width = args.image.width();
 
-    if args.verbosity > 1 {
-        println!(
+    term::debug(
+        args.verbosity,
+        &format!(
             "Running with arguments: {}",
             serde_json::to_string(&args).unwrap()
-        );
+        ),
+    );
+
+    let pins = pins::from_args(&args, width, height);
+    if let Some(warning) = warnings::check_pins(&args, &pins) {
+        term::warn(&warning);
+    }
+    if args
+        .start_pin
+        .is_some_and(|start_pin| start_pin >= pins.len())
+    {
+        return Err(cli_app::arg_error(
+            clap::error::ErrorKind::ValueValidation,
+            format!("--start-pin must be less than the {} pins", pins.len()),
+        ));
+    }
+
+    let run_started_at = Instant::now();
+    let datas = if !args.frames.is_empty() {
+        animation::animate(pins, args)?
+    } else if let Some(target) = args.morph_target.clone() {
+        morph::morph(pins, args, target)?
+    } else if let Some(members) = args.ensemble {
+        vec![ensemble::ensemble(pins, args, members as usize)?]
+    } else {
+        let (pins, starting_segments) = match args.continue_from.as_ref().or(args.resume.as_ref()) {
+            Some(filepath) => {
+                let option = match args.resume {
+                    Some(_) => "--resume",
+                    None => "--continue-from",
+                };
+                let saved = edit::SavedRun::load_for(filepath, &args, option)?;
+                if args.resume.is_some() && saved.pin_locations != pins {
+                    term::warn(
+                        "These options place the pins differently than the interrupted run did, \
+                         so the run is resumed on its pins",
+                    );
+                }
+                term::debug(
+                    args.verbosity,
+                    &format!(
+                        "Continuing from {} strings on {} pins",
+                        saved.line_segments.len(),
+                        saved.pin_locations.len()
+                    ),
+                );
+                (saved.pin_locations, saved.line_segments)
+            }
+            None => (pins, Vec::new()),
+        };
+        vec![style::color_on_custom(
+            pins,
+            args,
+            starting_segments,
+            &Constraints::default(),
+            Vec::new(),
+        )?]
+    };
+
+    let run_seconds = run_started_at.elapsed().as_secs_f64();
+
+    let write_started_at = Instant::now();
+    if let Some(data) = datas.first() {
+        if let Some(ref pins_filepath) = data.args.pins_filepath {
+            let pins_filepath = template::expand(pins_filepath, data);
+            draw_pin_crosshairs(width, height, &data.pin_locations, &pins_filepath)?;
+        }
     }
 
-    let pins = pins::generate(&args.pin_arrangement, args.pin_count, width, height);
+    for data in &datas {
+        if let Some(data_filepath) = &data.args.data_filepath {
+            write_data(data, data_filepath)?;
+        }
+        if let Some(instructions_filepath) = &data.args.instructions_filepath {
+            instructions::write(data, &template::expand(instructions_filepath, data))?;
+        }
+    }
 
-    if let Some(ref pins_filepath) = args.pins_filepath {
-        draw_pin_crosshairs(width, height, &pins, pins_filepath);
+    if let (Some(filepath), Some(last)) = (
+        datas
+            .last()
+            .and_then(|data| data.args.archive_filepath.as_ref()),
+        datas.last(),
+    ) {
+        archive::write(&datas, &template::expand(filepath, last))?;
     }
 
-    let data = style::color_on_custom(pins, args);
+    if datas.first().is_some_and(|data| data.args.stats) {
+        let optimize = datas.iter().map(|data| data.elapsed_seconds).sum();
+        let phases = summary::Phases {
+            preprocess: preprocess_seconds,
+            optimize,
+            render: f64::max(0.0, run_seconds - optimize),
+            write: write_started_at.elapsed().as_secs_f64(),
+        };
+        println!("{}", summary::summary(&datas, &phases));
+    }
 
-    if let Some(data_filepath) = &data.args.data_filepath {
-        std::fs::write(data_filepath, serde_json::to_string(&data).unwrap())
-            .expect("Unable to write file");
+    if let Some(args) = datas
+        .first()
+        .map(|data| &data.args)
+        .filter(|args| args.stats || args.max_memory_mb.is_some())
+    {
+        if let Some(peak) = memory::peak_bytes() {
+            let budget = args
+                .max_memory_mb
+                .map(|mb| format!(" of the {} MB budget", mb))
+                .unwrap_or_default();
+            println!(
+                "Peak memory          : {} MB{}",
+                peak / memory::BYTES_PER_MB,
+                budget
+            );
+        }
     }
+    Ok(())
 }
 
-fn draw_pin_crosshairs(width: u32, height: u32, pins: &[Point], pins_filepath: &str) {
+/// Stream the data to a JSON file, compressing it if requested
+pub fn write_data(data: &Data, data_filepath: &str) -> Result<(), StringArtError> {
+    let filepath = template::expand(data_filepath, data);
+    let write = |error| StringArtError::write(&filepath, error);
+    let mut writer = BufWriter::new(File::create(&filepath).map_err(write)?);
+    if data.args.data_gzip {
+        let mut encoder = GzEncoder::new(&mut writer, Compression::default());
+        serialize_data(data, &mut encoder).map_err(write)?;
+        encoder.finish().map_err(write)?;
+    } else {
+        serialize_data(data, &mut writer).map_err(write)?;
+    }
+    writer.flush().map_err(write)
+}
+
+/// Read a data file written by `write_data`, whether or not it is compressed
+pub fn read_data<T: DeserializeOwned>(data_filepath: &str) -> Result<T, StringArtError> {
+    let bytes = std::fs::read(data_filepath).map_err(|source| StringArtError::Read {
+        filepath: data_filepath.to_owned(),
+        source,
+    })?;
+    match bytes.starts_with(&GZIP_MAGIC) {
+        true => serde_json::from_reader(GzDecoder::new(&bytes[..])),
+        false => serde_json::from_slice(&bytes),
+    }
+    .map_err(|error| StringArtError::decode(data_filepath, error))
+}
+
+/// Serialize the data piece by piece, so the whole document is never held in memory at once
+fn serialize_data<W: Write>(data: &Data, writer: W) -> std::io::Result<()> {
+    if data.args.data_pretty {
+        serde_json::to_writer_pretty(writer, data)
+    } else {
+        serde_json::to_writer(writer, data)
+    }
+    .map_err(std::io::Error::from)
+}
+
+/// Save an image marking each pin with a cross
+pub fn draw_pin_crosshairs(
+    width: u32,
+    height: u32,
+    pins: &[Point],
+    pins_filepath: &str,
+) -> Result<(), StringArtError> {
     let mut img = image::GrayImage::from_pixel(width, height, image::Luma([255]));
     for pin in pins {
         let side_length = 3;
@@ -47,5 +274,75 @@ fn draw_pin_crosshairs(width: u32, height: u32, pins: &[Point], pins_filepath: &
         }
     }
     img.save(pins_filepath)
-        .unwrap_or_else(|_| panic!("Unable to create pin file at: '{}'", pins_filepath))
+        .map_err(|error| StringArtError::save(pins_filepath, error))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    fn write_and_read(extra_args: &[&str], filename: &str) -> Vec<u8> {
+        let filepath = std::env::temp_dir().join(filename);
+        let filepath = filepath.to_str().unwrap();
+        write_data(&Data::example(extra_args), filepath).unwrap();
+        let bytes = std::fs::read(filepath).unwrap();
+        std::fs::remove_file(filepath).unwrap();
+        bytes
+    }
+
+    /// Remembers how much was written, and the most written at once
+    #[derive(Default)]
+    struct WriteSizes {
+        total: usize,
+        largest: usize,
+    }
+
+    impl Write for WriteSizes {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.total += buf.len();
+            self.largest = self.largest.max(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_serialize_data_streams_segments() {
+        let mut data = Data::example(&[]);
+        let segment = data.line_segments[0];
+        data.line_segments = vec![segment; 10_000];
+        let mut sizes = WriteSizes::default();
+        serialize_data(&data, &mut sizes).unwrap();
+        assert_eq!(serde_json::to_vec(&data).unwrap().len(), sizes.total);
+        assert!(sizes.largest < 100);
+    }
+
+    #[test]
+    fn test_write_data_compact() {
+        let json = String::from_utf8(write_and_read(&[], "string_art_compact.json")).unwrap();
+        assert!(!json.contains('\n'));
+        assert_eq!(serde_json::to_string(&Data::example(&[])).unwrap(), json);
+    }
+
+    #[test]
+    fn test_write_data_pretty() {
+        let json = String::from_utf8(write_and_read(&["--data-pretty"], "string_art_pretty.json"))
+            .unwrap();
+        assert!(json.contains("\n  \"image_height\": 10"));
+    }
+
+    #[test]
+    fn test_write_data_gzip() {
+        let bytes = write_and_read(&["--data-gzip"], "string_art_gzip.json.gz");
+        let mut json = String::new();
+        GzDecoder::new(&bytes[..])
+            .read_to_string(&mut json)
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(10, value["image_width"]);
+    }
 }
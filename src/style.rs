@@ -1,18 +1,138 @@
+use crate::build_time::BuildTime;
+use crate::channel::Channel;
 use crate::cli_app::Args;
+use crate::error::StringArtError;
+use crate::frame_sink;
+use crate::frame_sink::FrameSink;
+#[cfg(feature = "gif")]
+use crate::frame_sink::GifPalette;
+#[cfg(feature = "gif")]
+use crate::frame_sink::GifSink;
+#[cfg(feature = "video")]
+use crate::frame_sink::VideoSink;
 use crate::geometry::Point;
-use crate::image::codecs::gif::GifEncoder;
-use crate::image::Frame;
+use crate::image::DynamicImage;
 use crate::imagery::LineSegment;
 use crate::imagery::RefImage;
 use crate::imagery::Rgb;
-use crate::optimum;
-use crate::serde::Serialize;
-use std::fs::File;
+use crate::lab::ScoreSpace;
+use crate::length_stats;
+use crate::metrics::Quality;
+use crate::milestones;
+use crate::milestones::Milestones;
+use crate::optimizer::Layers;
+use crate::optimizer::LuminanceFirst;
+use crate::optimizer::Optimizer;
+use crate::optimizer::Perturb;
+use crate::optimizer::Problem;
+use crate::optimizer::Strategy;
+use crate::optimizer::Swaps;
+use crate::optimum::Constraints;
+use crate::pin_stats;
+use crate::preprocess;
+use crate::provenance::Provenance;
+use crate::realistic;
+use crate::realistic::RenderStyle;
+use crate::resolution;
+use crate::segment;
+use crate::serde::ser::SerializeStruct;
+use crate::serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::summary;
+use crate::svg;
+use crate::template;
+use crate::term;
+use crate::term::Level;
+use crate::travel;
+use crate::warnings;
+use std::sync::mpsc::SyncSender;
 use std::time::Instant;
 
-#[derive(Serialize)]
+/// The smallest detail the eye can resolve is about one arcminute wide
+const VISUAL_ACUITY_RADIANS: f64 = std::f64::consts::PI / 180.0 / 60.0;
+
+/// The order strings are drawn in when rendering the finished work. Where strings cross, the
+/// ones drawn later end up on top.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DrawOrder {
+    /// In the order the foreground colors were given
+    Colors,
+    DarkestFirst,
+    LightestFirst,
+    /// In the order the strings were placed
+    Threading,
+}
+
+impl core::str::FromStr for DrawOrder {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "colors" => Ok(DrawOrder::Colors),
+            "darkest-first" => Ok(DrawOrder::DarkestFirst),
+            "lightest-first" => Ok(DrawOrder::LightestFirst),
+            "threading" => Ok(DrawOrder::Threading),
+            _ => Err(format!("Invalid draw order: \"{}\"", string)),
+        }
+    }
+}
+
+/// How the foreground colors are chosen and combined
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Style {
+    /// The foreground colors are chosen together, string by string
+    Standard,
+    /// Cyan, magenta, and yellow strings on a white background, each placed as its own layer
+    Cmy,
+    /// Like `Cmy`, with a layer of black strings placed first
+    Cmyk,
+}
+
+impl Style {
+    const CYAN: Rgb = Rgb {
+        r: 0,
+        g: 255,
+        b: 255,
+    };
+    const MAGENTA: Rgb = Rgb {
+        r: 255,
+        g: 0,
+        b: 255,
+    };
+    const YELLOW: Rgb = Rgb {
+        r: 255,
+        g: 255,
+        b: 0,
+    };
+
+    /// The colors of the layers, in the order they are placed. The black layer comes first so
+    /// dark areas are covered with black strings rather than three colored ones.
+    pub fn inks(&self) -> Option<&'static [Rgb]> {
+        match self {
+            Style::Standard => None,
+            Style::Cmy => Some(&[Self::CYAN, Self::MAGENTA, Self::YELLOW]),
+            Style::Cmyk => Some(&[Rgb::BLACK, Self::CYAN, Self::MAGENTA, Self::YELLOW]),
+        }
+    }
+}
+
+impl core::str::FromStr for Style {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "standard" => Ok(Style::Standard),
+            "cmy" => Ok(Style::Cmy),
+            "cmyk" => Ok(Style::Cmyk),
+            _ => Err(format!("Invalid style: \"{}\"", string)),
+        }
+    }
+}
+
+/// Incremented whenever the layout of the data file changes in a way readers must know about
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone)]
 pub struct Data {
     pub args: Args,
+    pub provenance: Provenance,
     pub image_height: u32,
     pub image_width: u32,
     pub initial_score: i64,
@@ -20,24 +140,366 @@ pub struct Data {
     pub elapsed_seconds: f64,
     pub pin_locations: Vec<Point>,
     pub line_segments: Vec<LineSegment>,
+    pub quality: Quality,
+    pub build_time: BuildTime,
+    pub warnings: Vec<String>,
+    /// Whether the run was stopped by `--time-limit` before it finished
+    pub timed_out: bool,
+    /// How many passes of adding and then removing strings were made
+    pub passes: usize,
+    /// How many pixels shorter `--minimize-travel` made the dead travel, if it was given
+    pub dead_travel_saved_px: Option<f64>,
+}
+
+/// Written like a derived `Serialize`, except each string also gets the details in `Segment`,
+/// the strings of each color are counted in `strings_per_color`, the strings attached to each
+/// pin are counted in `pin_stats`, and the spread of string lengths of each color is summarized
+/// in `length_stats`
+impl Serialize for Data {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Segments<'a>(&'a Data);
+        impl Serialize for Segments<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                segment::serialize(self.0, serializer)
+            }
+        }
+
+        let mut state = serializer.serialize_struct("Data", 19)?;
+        state.serialize_field("schema_version", &SCHEMA_VERSION)?;
+        state.serialize_field("args", &self.args)?;
+        state.serialize_field("provenance", &self.provenance)?;
+        state.serialize_field("image_height", &self.image_height)?;
+        state.serialize_field("image_width", &self.image_width)?;
+        state.serialize_field("initial_score", &self.initial_score)?;
+        state.serialize_field("final_score", &self.final_score)?;
+        state.serialize_field("elapsed_seconds", &self.elapsed_seconds)?;
+        state.serialize_field("pin_locations", &self.pin_locations)?;
+        state.serialize_field("line_segments", &Segments(self))?;
+        state.serialize_field("strings_per_color", &summary::strings_per_color(self))?;
+        state.serialize_field("pin_stats", &pin_stats::pin_stats(self))?;
+        state.serialize_field("length_stats", &length_stats::length_stats(self))?;
+        state.serialize_field("quality", &self.quality)?;
+        state.serialize_field("build_time", &self.build_time)?;
+        state.serialize_field("warnings", &self.warnings)?;
+        state.serialize_field("timed_out", &self.timed_out)?;
+        state.serialize_field("passes", &self.passes)?;
+        state.serialize_field("dead_travel_saved_px", &self.dead_travel_saved_px)?;
+        state.end()
+    }
+}
+
+/// Read like a derived `Deserialize`, expanding chords wound more than once back into their
+/// strings. The fields worked out from the others, like `pin_stats`, are ignored, and the images
+/// the arguments were loaded with are left blank.
+impl<'de> Deserialize<'de> for Data {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Saved {
+            args: Args,
+            #[serde(default)]
+            provenance: Provenance,
+            image_height: u32,
+            image_width: u32,
+            initial_score: i64,
+            final_score: i64,
+            elapsed_seconds: f64,
+            pin_locations: Vec<Point>,
+            #[serde(deserialize_with = "segment::deserialize_line_segments")]
+            line_segments: Vec<LineSegment>,
+            #[serde(default)]
+            quality: Quality,
+            #[serde(default)]
+            build_time: BuildTime,
+            #[serde(default)]
+            warnings: Vec<String>,
+            #[serde(default)]
+            timed_out: bool,
+            #[serde(default)]
+            passes: usize,
+            #[serde(default)]
+            dead_travel_saved_px: Option<f64>,
+        }
+
+        let saved = Saved::deserialize(deserializer)?;
+        Ok(Data {
+            args: saved.args,
+            provenance: saved.provenance,
+            image_height: saved.image_height,
+            image_width: saved.image_width,
+            initial_score: saved.initial_score,
+            final_score: saved.final_score,
+            elapsed_seconds: saved.elapsed_seconds,
+            pin_locations: saved.pin_locations,
+            line_segments: saved.line_segments,
+            quality: saved.quality,
+            build_time: saved.build_time,
+            warnings: saved.warnings,
+            timed_out: saved.timed_out,
+            passes: saved.passes,
+            dead_travel_saved_px: saved.dead_travel_saved_px,
+        })
+    }
+}
+
+impl Data {
+    /// The strings in the order they are drawn
+    pub fn drawn_line_segments(&self) -> Vec<LineSegment> {
+        let mut line_segments = self.line_segments.clone();
+        let luminance = |(_, _, rgb): &LineSegment| 299 * rgb.r + 587 * rgb.g + 114 * rgb.b;
+        match self.args.draw_order {
+            DrawOrder::Threading => {}
+            DrawOrder::Colors => line_segments.sort_by_key(|(_, _, rgb)| {
+                let position = self.args.foreground_colors.iter().position(|c| c == rgb);
+                position.unwrap_or(usize::MAX)
+            }),
+            DrawOrder::DarkestFirst => line_segments.sort_by_key(luminance),
+            DrawOrder::LightestFirst => {
+                line_segments.sort_by_key(|line_segment| -luminance(line_segment))
+            }
+        }
+        line_segments
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+impl Data {
+    /// A small finished run on a 10x10 image, with extra command line arguments
+    pub fn example(extra_args: &[&str]) -> Self {
+        use clap::Parser;
+        let cli = crate::cli_app::Cli::parse_from(
+            ["string_art", "--input-filepath", "in.png"]
+                .iter()
+                .chain(extra_args),
+        );
+        Data {
+            args: Args::with_image(cli, image::DynamicImage::new_rgb8(10, 10)),
+            provenance: Provenance::default(),
+            image_height: 10,
+            image_width: 10,
+            initial_score: 100,
+            final_score: 42,
+            elapsed_seconds: 1.6,
+            pin_locations: vec![Point::new(0, 0), Point::new(9, 0), Point::new(0, 9)],
+            line_segments: vec![(
+                Point::new(0, 0),
+                Point::new(9, 0),
+                crate::imagery::Rgb::WHITE,
+            )],
+            quality: Quality::default(),
+            build_time: BuildTime::default(),
+            warnings: Vec::new(),
+            timed_out: false,
+            passes: 0,
+            dead_travel_saved_px: None,
+        }
+    }
 }
 
-pub fn color_on_custom(pin_locations: Vec<Point>, args: Args) -> Data {
+/// The difference between the target and the empty board the strings are wound on
+pub fn blank(args: &Args) -> RefImage {
+    let target = RefImage::from(&args.image).decoded(args.blend).negated();
+    let blank = match (&args.canvas, args.canvas_target) {
+        (Some(canvas), true) => target.add_ref_image(&RefImage::from(canvas).decoded(args.blend)),
+        _ => target.add_rgb(args.blend.decode(args.background_color)),
+    };
+    match args.score_space {
+        ScoreSpace::Rgb => blank,
+        ScoreSpace::Lab => blank.in_lab(&args.image, args.blend),
+    }
+}
+
+/// How much the error of each pixel counts, from the weighting options and the mask multiplied
+/// together. `None` when every pixel counts the same.
+pub fn weights(args: &Args) -> Option<Vec<Vec<i64>>> {
+    [
+        args.contrast_weighting
+            .map(|strength| preprocess::contrast_weights(&args.image, strength)),
+        args.edge_weighting
+            .map(|strength| preprocess::edge_weights(&args.image, strength)),
+        args.weight_map.as_ref().map(preprocess::map_weights),
+        (!args.masked.is_empty()).then(|| {
+            preprocess::mask_weights(&args.masked, args.image.width(), args.image.height())
+        }),
+    ]
+    .into_iter()
+    .flatten()
+    .reduce(preprocess::combine_weights)
+}
+
+/// The empty board, scored the way a run scores it
+pub fn weighted_blank(args: &Args) -> RefImage {
+    match weights(args) {
+        Some(weights) => blank(args).with_weights(weights),
+        None => blank(args),
+    }
+}
+
+/// Create string art from `args.image`. The optimization starts from `starting_segments`, which
+/// is empty when starting with a blank canvas, and must obey the `constraints`. Each of the
+/// `sinks` receives snapshots of the strings as they are placed, along with the gif if one is
+/// requested. The snapshots are rendered and sent on a worker thread, so slow sinks don't hold
+/// up the optimization. Fails if any of the output files can't be written.
+pub fn color_on_custom<'a>(
+    pin_locations: Vec<Point>,
+    args: Args,
+    starting_segments: Vec<LineSegment>,
+    constraints: &Constraints,
+    sinks: Vec<Box<dyn FrameSink + Send + 'a>>,
+) -> Result<Data, StringArtError> {
+    let mut ref_image = weighted_blank(&args);
+    if args.max_overlap.is_some() {
+        ref_image = ref_image.with_coverage();
+    }
+    if args.min_pin_angle.is_some() {
+        ref_image = ref_image.with_pin_ends();
+    }
+    // Pixels that hold fractions of a level let strings too faint to change a pixel by
+    // themselves still add up
+    match args.float_accumulation {
+        true => color_on::<[f32; 3]>(
+            pin_locations,
+            args,
+            ref_image.converted(),
+            starting_segments,
+            constraints,
+            sinks,
+        ),
+        false => color_on(
+            pin_locations,
+            args,
+            ref_image,
+            starting_segments,
+            constraints,
+            sinks,
+        ),
+    }
+}
+
+fn color_on<'a, C: Channel>(
+    pin_locations: Vec<Point>,
+    args: Args,
+    mut ref_image: RefImage<C>,
+    starting_segments: Vec<LineSegment>,
+    constraints: &Constraints,
+    sinks: Vec<Box<dyn FrameSink + Send + 'a>>,
+) -> Result<Data, StringArtError> {
     let background_color = args.background_color;
-    let mut ref_image = RefImage::from(&args.image)
-        .negated()
-        .add_rgb(background_color);
+    let initial_ref_image = args
+        .explain_filepath
+        .as_ref()
+        .map(|_| blank(&args).converted::<C>());
     let colors = args
         .foreground_colors
         .iter()
         .map(|rgb| *rgb - background_color)
         .collect::<Vec<_>>();
 
+    let mut warnings: Vec<String> = warnings::check_args(&args)
+        .into_iter()
+        .map(warnings::emit)
+        .collect();
+
+    let relative = |(a, b, rgb): LineSegment| (a, b, rgb - background_color);
+    let starting_segments = starting_segments.into_iter().map(relative).collect();
+    let constraints = Constraints {
+        locked: constraints.locked.iter().copied().map(relative).collect(),
+        banned: constraints.banned.iter().copied().map(relative).collect(),
+        allowed: constraints
+            .allowed
+            .as_ref()
+            .map(|allowed| allowed.iter().copied().map(relative).collect()),
+        max_overlap: args.max_overlap,
+        min_pin_angle: args.min_pin_angle,
+        min_length: args.min_string_length,
+        string_cost: args.string_cost,
+        excluded: constraints
+            .excluded
+            .union(&args.excluded)
+            .copied()
+            .collect(),
+        masked: constraints.masked.union(&args.masked).copied().collect(),
+    };
+
+    #[cfg(feature = "gif")]
+    let mut gif = args
+        .gif_filepath
+        .as_deref()
+        .map(|filepath| match args.gif_palette {
+            true => GifSink::create_with_palette(
+                filepath,
+                ref_image.width(),
+                ref_image.height(),
+                GifPalette::new(background_color, &args.foreground_colors),
+            ),
+            false => GifSink::create(filepath),
+        })
+        .transpose()?
+        .map(|gif| match args.gif_frame_delay {
+            Some(ms) => gif.with_delay(image::Delay::from_numer_denom_ms(ms, 1)),
+            None => gif,
+        });
+    #[cfg(feature = "video")]
+    let mut video = args
+        .video_filepath
+        .as_deref()
+        .map(|filepath| {
+            VideoSink::create(
+                filepath,
+                ref_image.width(),
+                ref_image.height(),
+                args.video_fps,
+            )
+        })
+        .transpose()?;
+    let mut sinks: Vec<Box<dyn FrameSink + Send + '_>> = sinks;
+    #[cfg(feature = "gif")]
+    if let Some(gif) = gif.as_mut() {
+        sinks.push(Box::new(|frame: &image::RgbaImage| gif.receive(frame)));
+    }
+    #[cfg(feature = "video")]
+    if let Some(video) = video.as_mut() {
+        sinks.push(Box::new(|frame: &image::RgbaImage| video.receive(frame)));
+    }
+
+    let mut milestones = args
+        .milestones_filepath
+        .as_ref()
+        .map(|_| Milestones::new(&args.milestones));
+
     let start_at = Instant::now();
-    let (line_segments, initial_score, final_score) =
-        implementation(&args, &mut ref_image, &pin_locations, &colors);
+    let problem = Problem::new(&args, &pin_locations, &colors, &constraints)?;
+    let (width, height) = (ref_image.width(), ref_image.height());
+    let (line_segments, initial_score, final_score) = std::thread::scope(|scope| {
+        let frames = (!sinks.is_empty()).then(|| {
+            frame_sink::spawn_worker(scope, &mut sinks, |line_segments: Vec<LineSegment>| {
+                render_frame(&line_segments, &args, width, height)
+            })
+        });
+        // The worker finishes the queued frames once `frames` is dropped at the end
+        implementation(
+            &problem,
+            &mut ref_image,
+            starting_segments,
+            frames,
+            &mut milestones,
+        )
+    });
+    let timed_out = problem.timed_out();
+    let passes = problem.passes();
+    // Finish writing the event log, the gif, and the video before they are moved
+    problem.finish_log()?;
+    drop(problem);
+    drop(sinks);
+    #[cfg(feature = "gif")]
+    gif.map(GifSink::finish).transpose()?;
+    #[cfg(feature = "video")]
+    video.map(VideoSink::finish).transpose()?;
+    let milestones_sheet = milestones.map(|milestones| {
+        milestones.contact_sheet(&args, &line_segments, ref_image.width(), ref_image.height())
+    });
 
-    let data = Data {
+    let mut data = Data {
+        provenance: Provenance::of(&args.input_filepath),
         args,
         image_height: ref_image.height(),
         image_width: ref_image.width(),
@@ -49,166 +511,628 @@ pub fn color_on_custom(pin_locations: Vec<Point>, args: Args) -> Data {
             .into_iter()
             .map(|(a, b, rgb)| (a, b, rgb + background_color))
             .collect(),
+        quality: Quality::default(),
+        build_time: BuildTime::default(),
+        warnings: Vec::new(),
+        timed_out,
+        passes,
+        dead_travel_saved_px: None,
     };
 
-    if let Some(ref filepath) = data.args.output_filepath {
-        RefImage::from(&data).color().save(filepath).unwrap();
+    segment::gather(&mut data.line_segments, data.args.gradient_strings);
+    if data.args.minimize_travel {
+        let saved_px = travel::minimize(&mut data.line_segments);
+        data.dead_travel_saved_px = Some(saved_px);
+        let message = match data.args.physical_width_mm {
+            Some(width_mm) => format!(
+                "Dead travel saved    : {:.0} px ({:.0} mm)",
+                saved_px,
+                saved_px * width_mm / data.image_width as f64
+            ),
+            None => format!("Dead travel saved    : {:.0} px", saved_px),
+        };
+        term::debug(data.args.verbosity, &message);
+    }
+    if data.args.continuous_path {
+        let bridges = travel::continuous(&mut data.line_segments);
+        // The bridges are strings like any other, so they change the score
+        for (a, b, rgb) in &bridges {
+            ref_image += (
+                (*a, *b),
+                data.args.shade(*rgb - background_color),
+                data.args.step_size,
+                data.args.string_alpha,
+            );
+        }
+        data.final_score = ref_image.score();
+        term::debug(
+            data.args.verbosity,
+            &format!("Bridging strings     : {}", bridges.len()),
+        );
     }
 
-    data
-}
+    data.build_time = BuildTime::estimate(&data);
 
-fn log_on_add(args: &Args, pin_len: usize, score_change: i64, a: Point, b: Point, rgb: Rgb) {
-    if args.verbosity > 0 {
-        let rgb = rgb + args.background_color;
-        println!(
-            "[{:>6}]:   score change: {:>10}     +add  {} to {} with {}",
-            pin_len, score_change, a, b, rgb
+    if data.args.cancellation.is_cancelled() {
+        warnings.push(warnings::emit(
+            "The run was cancelled, so the strings are unfinished".to_owned(),
+        ));
+    } else if data.timed_out {
+        warnings.push(warnings::emit(
+            "The run reached its --time-limit, so the strings are unfinished".to_owned(),
+        ));
+    }
+    warnings.extend(
+        warnings::check_colors(&data)
+            .into_iter()
+            .map(warnings::emit),
+    );
+    data.warnings = warnings;
+
+    let rendered = DynamicImage::ImageRgba8(RefImage::from(&data).color());
+    data.quality = Quality::between(&rendered, &data.args.image);
+    let verbosity = data.args.verbosity;
+    // The string lengths take a while to measure, so only when they'll be printed
+    if Level::Debug.shown_at(verbosity) {
+        term::debug(
+            verbosity,
+            &format!("PSNR                 : {:.2} dB", data.quality.psnr),
+        );
+        term::debug(
+            verbosity,
+            &format!("SSIM                 : {:.4}", data.quality.ssim),
         );
+        term::debug(
+            verbosity,
+            &format!(
+                "Build time estimate  : {} ({} strings, {} color changes)",
+                data.build_time, data.build_time.strings, data.build_time.color_changes
+            ),
+        );
+        term::debug(
+            verbosity,
+            &format!("Strings per color    : {}", summary::shares(&data)),
+        );
+        for stats in length_stats::length_stats(&data) {
+            term::debug(verbosity, &format!("String lengths       : {}", stats));
+        }
+    }
+
+    save_string_images(&data)?;
+
+    if let (Some(filepath), Some(distance), Some(width_mm)) = (
+        &data.args.simulate_filepath,
+        data.args.simulate_distance,
+        data.args.physical_width_mm,
+    ) {
+        let filepath = template::expand(filepath, &data);
+        simulate_distance(&rendered, width_mm, distance)
+            .save(&filepath)
+            .map_err(|error| StringArtError::save(&filepath, error))?;
+    }
+
+    if let (Some(filepath), Some(initial)) = (&data.args.explain_filepath, &initial_ref_image) {
+        let filepath = template::expand(filepath, &data);
+        ref_image
+            .explain(initial)
+            .save(&filepath)
+            .map_err(|error| StringArtError::save(&filepath, error))?;
+    }
+
+    if let (Some(filepath), Some(sheet)) = (&data.args.milestones_filepath, milestones_sheet) {
+        let filepath = template::expand(filepath, &data);
+        sheet
+            .save(&filepath)
+            .map_err(|error| StringArtError::save(&filepath, error))?;
     }
+
+    // The gif and the video are written while the strings are placed, so move them once the
+    // run is finished.
+    for filepath in [&data.args.gif_filepath, &data.args.video_filepath]
+        .into_iter()
+        .flatten()
+    {
+        let expanded = template::expand(filepath, &data);
+        if &expanded != filepath {
+            std::fs::rename(filepath, &expanded)
+                .map_err(|error| StringArtError::write(&expanded, error))?;
+        }
+    }
+
+    Ok(data)
 }
 
-fn log_on_sub(args: &Args, pin_len: usize, score_change: i64, a: Point, b: Point, rgb: Rgb) {
-    if args.verbosity > 0 {
-        let rgb = rgb + args.background_color;
-        println!(
-            "[{:>6}]:   score change: {:>10}     -sub  {} to {} with {}",
-            pin_len, score_change, a, b, rgb
-        );
+/// Save the string images at each of their scales, and the SVG if one is requested
+pub fn save_string_images(data: &Data) -> Result<(), StringArtError> {
+    for (filepath, scale) in data
+        .args
+        .output_filepaths
+        .iter()
+        .zip(&data.args.output_scales)
+    {
+        let img = match data.args.render_style {
+            RenderStyle::Flat => RefImage::from((data, *scale)).color(),
+            RenderStyle::Realistic => realistic::render(data, *scale),
+        };
+        resolution::save(
+            &img,
+            &template::expand(filepath, data),
+            data.args.physical_width_mm,
+        )?;
+    }
+
+    if let Some(filepath) = &data.args.svg_filepath {
+        svg::write(data, &template::expand(filepath, data))?;
+    }
+    Ok(())
+}
+
+/// Blur the rendered image to approximate what it looks like from `distance` meters away
+fn simulate_distance(rendered: &DynamicImage, width_mm: f64, distance: f64) -> DynamicImage {
+    let resolvable_mm = distance * 1000.0 * VISUAL_ACUITY_RADIANS.tan();
+    let pixels_per_mm = rendered.width() as f64 / width_mm;
+    rendered.blur((resolvable_mm * pixels_per_mm / 2.0) as f32)
+}
+
+/// Queue a snapshot of the strings to be rendered and sent to the sinks, if there are any
+fn capture_frame(frames: &Option<SyncSender<Vec<LineSegment>>>, line_segments: &[LineSegment]) {
+    if let Some(frames) = frames {
+        frames
+            .send(line_segments.to_vec())
+            .expect("Unable to send the frame to be rendered");
     }
 }
 
-fn capture_frame(
-    possible_encoder: &mut Option<GifEncoder<File>>,
+/// A frame of the gif, showing strings whose colors are relative to the background
+pub fn render_frame(
     line_segments: &[LineSegment],
     args: &Args,
     width: u32,
     height: u32,
-) {
-    if let Some(encoder) = possible_encoder {
-        let lines = line_segments
-            .iter()
-            .map(|(a, b, rgb)| ((*a, *b), *rgb, args.step_size, args.string_alpha))
-            .collect();
-        let img = RefImage::from((&lines, width, height)).color();
-        encoder.encode_frame(Frame::new(img)).unwrap();
+) -> image::RgbaImage {
+    let lines = line_segments
+        .iter()
+        .map(|(a, b, rgb)| {
+            (
+                (*a, *b),
+                args.shade(*rgb),
+                args.step_size,
+                args.string_alpha,
+            )
+        })
+        .collect();
+    RefImage::<Rgb>::from((&lines, width, height))
+        .encoded(args.blend)
+        .color()
+}
+
+/// Block while the run is paused, after saving a snapshot of the strings placed so far
+fn wait_while_paused(args: &Args, line_segments: &[LineSegment], width: u32, height: u32) {
+    if !args.pause.is_paused() {
+        return;
+    }
+    if let Some(filepath) = &args.snapshot_filepath {
+        // The run can go on without its snapshot, so don't stop it
+        if let Err(error) = milestones::snapshot(args, line_segments, width, height).save(filepath)
+        {
+            term::warn(&StringArtError::save(filepath, error).to_string());
+        }
     }
+    println!(
+        "Paused with {} strings. Send SIGUSR1 again to resume.",
+        line_segments.len()
+    );
+    while args.pause.is_paused() && !args.cancellation.is_cancelled() {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    println!("Resumed");
 }
 
-fn implementation(
-    args: &Args,
-    ref_image: &mut RefImage,
-    pin_locations: &[Point],
-    rgbs: &[Rgb],
+fn implementation<C: Channel>(
+    problem: &Problem,
+    ref_image: &mut RefImage<C>,
+    starting_segments: Vec<LineSegment>,
+    frames: Option<SyncSender<Vec<LineSegment>>>,
+    milestones: &mut Option<Milestones>,
 ) -> (Vec<LineSegment>, i64, i64) {
-    let mut line_segments: Vec<LineSegment> = Vec::new();
-    let mut keep_adding = true;
-    let mut keep_removing = true;
-
+    let args = problem.args;
     let initial_score = ref_image.score();
 
-    if args.verbosity > 1 {
-        println!("Initial score: {} (lower is better)", initial_score);
+    let mut line_segments = starting_segments;
+    for (a, b, rgb) in &line_segments {
+        *ref_image += (
+            (*a, *b),
+            args.shade(*rgb),
+            args.step_size,
+            args.string_alpha,
+        );
     }
 
-    let mut cap = 100;
-    let mut max_at_once = usize::min(args.max_strings / 10, cap);
-
-    let mut possible_encoder: Option<GifEncoder<File>> =
-        args.gif_filepath.as_ref().map(|gif_filepath| {
-            let file_out = File::create(gif_filepath).unwrap();
-            let mut encoder = GifEncoder::new_with_speed(file_out, 10);
-            encoder
-                .set_repeat(image::codecs::gif::Repeat::Infinite)
-                .unwrap();
-            encoder
-        });
+    term::debug(
+        args.verbosity,
+        &format!("Initial score: {} (lower is better)", initial_score),
+    );
 
     let width = ref_image.width();
     let height = ref_image.height();
+    args.progress.start(line_segments.len(), ref_image.score());
 
-    while keep_adding || keep_removing {
-        max_at_once = usize::min(max_at_once, cap);
-        cap -= 1;
+    // Without a pace, every step is a frame
+    let mut last_frame = line_segments.len();
+    let mut on_step = |line_segments: &[LineSegment], ref_image: &RefImage<C>| {
+        args.progress
+            .record(line_segments.len(), || ref_image.score());
+        if args
+            .gif_strings_per_frame
+            .is_none_or(|n| line_segments.len().abs_diff(last_frame) as u64 >= n)
+        {
+            last_frame = line_segments.len();
+            capture_frame(&frames, line_segments);
+        }
+        if let Some(milestones) = milestones.as_mut() {
+            milestones.observe(args, line_segments, width, height);
+        }
+        wait_while_paused(args, line_segments, width, height);
+    };
+    let optimizer = args.optimizer.optimizer(args);
+    let optimizer: Box<dyn Optimizer<C>> = match args.luminance_first {
+        true => Box::new(LuminanceFirst { inner: optimizer }),
+        false => optimizer,
+    };
+    let optimizer = match args.style.inks() {
+        Some(_) => Box::new(Layers { inner: optimizer }),
+        None => optimizer,
+    };
+    optimizer.optimize(problem, ref_image, &mut line_segments, &mut on_step);
+    // Removing strings from the middle of greedy-walk's thread would break it in two
+    match args.perturb_rounds {
+        Some(rounds) if args.optimizer != Strategy::GreedyWalk => Perturb {
+            rounds,
+            fraction: args.perturb_percent / 100.0,
+            seed: args.perturb_seed.or(args.seed),
+        }
+        .optimize(problem, ref_image, &mut line_segments, &mut on_step),
+        _ => (),
+    }
+    if let Some(passes) = args.swap_passes {
+        Swaps { passes }.optimize(problem, ref_image, &mut line_segments, &mut on_step);
+    }
 
-        while keep_adding {
-            capture_frame(&mut possible_encoder, &line_segments, args, width, height);
+    // Pause on the last frame
+    (0..10).for_each(|_| capture_frame(&frames, &line_segments));
 
-            keep_adding = false;
+    let final_score = ref_image.score();
+    args.progress.set_score(final_score);
+    term::debug(
+        args.verbosity,
+        &format!("(Recap) Initial score: {} (lower is better)", initial_score),
+    );
+    term::debug(
+        args.verbosity,
+        &format!("Final score          : {}", final_score),
+    );
 
-            let points = optimum::find_best_points(
-                pin_locations,
-                ref_image,
-                args.step_size,
-                args.string_alpha,
-                rgbs,
-                usize::min(args.max_strings - line_segments.len(), max_at_once),
-            );
+    (line_segments, initial_score, final_score)
+}
 
-            if !points.is_empty() {
-                keep_removing = true;
-                keep_adding = true;
-            }
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::imagery::Rgb;
 
-            if points.len() == max_at_once {
-                max_at_once = (max_at_once as f64 * 1.1) as usize
-            }
+    fn stripes() -> DynamicImage {
+        let mut i = image::RgbImage::new(40, 40);
+        i.enumerate_pixels_mut()
+            .for_each(|(x, _, p)| *p = image::Rgb([if x % 2 == 0 { 255 } else { 0 }; 3]));
+        DynamicImage::ImageRgb8(i)
+    }
 
-            points.into_iter().for_each(|((a, b, rgb), s)| {
-                *ref_image += ((a, b), rgb, args.step_size, args.string_alpha);
-                line_segments.push((a, b, rgb));
-                log_on_add(args, line_segments.len(), s, a, b, rgb);
-            });
+    fn contrast(image: &DynamicImage) -> i32 {
+        let row: Vec<i32> = (10..30)
+            .map(|x| image.to_luma8()[(x, 20)].0[0] as i32)
+            .collect();
+        row.iter().max().unwrap() - row.iter().min().unwrap()
+    }
 
-            if line_segments.len() >= args.max_strings {
-                keep_adding = false
-            }
-        }
+    #[test]
+    fn test_data_reads_back() {
+        let mut data = Data::example(&["--pin-count", "12", "-o", "art_{strings}.png"]);
+        // A chord wound twice is saved once, with a count
+        data.line_segments.push(data.line_segments[0]);
+        let read: Data = serde_json::from_value(serde_json::to_value(&data).unwrap()).unwrap();
+        assert_eq!(data.args.output_filepaths, read.args.output_filepaths);
+        assert_eq!(12, read.args.pin_count);
+        assert_eq!(data.pin_locations, read.pin_locations);
+        assert_eq!(data.line_segments, read.line_segments);
+        assert_eq!(
+            (data.final_score, data.quality, data.build_time),
+            (read.final_score, read.quality, read.build_time)
+        );
+        assert_eq!(None, read.dead_travel_saved_px);
+    }
 
-        max_at_once = usize::max(1, (max_at_once as f64 * 0.9) as usize);
+    #[test]
+    fn test_simulate_distance_blurs_fine_detail() {
+        // 40 pixels across 40mm is 1mm per pixel, which blurs together at 10 meters
+        let far = simulate_distance(&stripes(), 40.0, 10.0);
+        assert!(contrast(&far) < 20, "contrast was {}", contrast(&far));
+    }
 
-        while keep_removing {
-            capture_frame(&mut possible_encoder, &line_segments, args, width, height);
+    #[test]
+    fn test_color_on_custom_sends_frames_to_every_sink() {
+        use clap::Parser;
+        let cli = crate::cli_app::Cli::parse_from(["string_art", "-i", "in.png", "-m", "20"]);
+        let args = Args::with_image(cli, stripes());
+        let pins = crate::pins::generate(&args.pin_arrangement, 8, 40, 40);
+        let (mut first, mut second) = (0, 0);
+        let data = color_on_custom(
+            pins,
+            args,
+            Vec::new(),
+            &Constraints::default(),
+            vec![
+                Box::new(|_: &image::RgbaImage| first += 1),
+                Box::new(|frame: &image::RgbaImage| {
+                    assert_eq!((40, 40), frame.dimensions());
+                    second += 1
+                }),
+            ],
+        )
+        .unwrap();
+        assert!(!data.line_segments.is_empty());
+        assert!(first > 0);
+        assert_eq!(first, second);
+    }
 
-            keep_removing = false;
+    #[test]
+    #[cfg(feature = "gif")]
+    fn test_gif_strings_per_frame() {
+        use clap::Parser;
+        let gif = std::env::temp_dir().join("string_art_paced.gif");
+        let gif = gif.to_str().unwrap();
+        let count_frames = |extra_args: &[&str]| {
+            let cli = crate::cli_app::Cli::parse_from(
+                ["string_art", "-i", "in.png", "-m", "20", "-g", gif]
+                    .iter()
+                    .chain(extra_args),
+            );
+            let args = Args::with_image(cli, stripes());
+            let pins = crate::pins::generate(&args.pin_arrangement, 8, 40, 40);
+            let mut frames = 0;
+            color_on_custom(
+                pins,
+                args,
+                Vec::new(),
+                &Constraints::default(),
+                vec![Box::new(|_: &image::RgbaImage| frames += 1)],
+            )
+            .unwrap();
+            frames
+        };
+        let every_step = count_frames(&[]);
+        let paced = count_frames(&["--gif-strings-per-frame", "10"]);
+        std::fs::remove_file(gif).unwrap();
+        // A frame for each 10 strings, and the pause on the last frame
+        assert!(paced <= 2 + 10, "{} frames", paced);
+        assert!(paced < every_step);
+    }
 
-            let mut worst_points = optimum::find_worst_points(
-                &line_segments,
-                ref_image,
-                args.step_size,
-                args.string_alpha,
-                // Find these more accurately by finding fewer at once. Saves time overall by
-                // preventing strings from bouncing back and forth between added and removed.
-                usize::min(line_segments.len(), usize::max(1, max_at_once / 10)),
+    #[test]
+    fn test_float_accumulation_run() {
+        use clap::Parser;
+        let cli = crate::cli_app::Cli::parse_from([
+            "string_art",
+            "-i",
+            "in.png",
+            "-m",
+            "20",
+            "--float-accumulation",
+        ]);
+        let args = Args::with_image(cli, stripes());
+        let pins = crate::pins::generate(&args.pin_arrangement, 8, 40, 40);
+        let data =
+            color_on_custom(pins, args, Vec::new(), &Constraints::default(), Vec::new()).unwrap();
+        assert!(!data.line_segments.is_empty());
+        assert!(data.final_score < data.initial_score);
+    }
+
+    #[test]
+    fn test_progress_follows_the_run() {
+        use clap::Parser;
+        let cli = crate::cli_app::Cli::parse_from(["string_art", "-i", "in.png", "-m", "20"]);
+        let args = Args::with_image(cli, stripes());
+        let progress = args.progress.clone();
+        let pins = crate::pins::generate(&args.pin_arrangement, 8, 40, 40);
+        let data =
+            color_on_custom(pins, args, Vec::new(), &Constraints::default(), Vec::new()).unwrap();
+        assert_eq!(data.line_segments.len(), progress.strings());
+        assert!(progress.placed() >= data.line_segments.len() as u64);
+        assert_eq!(data.final_score, progress.score());
+    }
+
+    #[test]
+    fn test_dead_travel_saved_is_kept() {
+        use clap::Parser;
+        let run = |extra_args: &[&str]| {
+            let cli = crate::cli_app::Cli::parse_from(
+                ["string_art", "-i", "in.png", "-m", "20"]
+                    .iter()
+                    .chain(extra_args),
             );
-            worst_points.sort_unstable_by_key(|(i, _)| *i);
-            worst_points.reverse();
+            let args = Args::with_image(cli, stripes());
+            let pins = crate::pins::generate(&args.pin_arrangement, 8, 40, 40);
+            color_on_custom(pins, args, Vec::new(), &Constraints::default(), Vec::new()).unwrap()
+        };
+        assert_eq!(None, run(&[]).dead_travel_saved_px);
+        let data = run(&["--minimize-travel"]);
+        assert!(data.dead_travel_saved_px.is_some_and(|px| px >= 0.0));
+        let read: Data = serde_json::from_value(serde_json::to_value(&data).unwrap()).unwrap();
+        assert_eq!(data.dead_travel_saved_px, read.dead_travel_saved_px);
+    }
 
-            if !worst_points.is_empty() {
-                keep_removing = true;
-                keep_adding = true;
-            }
+    #[test]
+    fn test_cancelled_run_returns_partial_data() {
+        use clap::Parser;
+        let cli = crate::cli_app::Cli::parse_from(["string_art", "-i", "in.png"]);
+        let args = Args::with_image(cli, stripes());
+        let pins = crate::pins::generate(&args.pin_arrangement, 8, 40, 40);
+        let cancellation = args.cancellation.clone();
+        let mut frames = 0;
+        let data = color_on_custom(
+            pins,
+            args,
+            Vec::new(),
+            &Constraints::default(),
+            vec![Box::new(|_: &image::RgbaImage| {
+                frames += 1;
+                if frames == 2 {
+                    cancellation.cancel()
+                }
+            })],
+        )
+        .unwrap();
+        assert!(!data.line_segments.is_empty());
+        assert!(data.final_score < data.initial_score);
+        assert!(data.warnings.iter().any(|w| w.contains("cancelled")));
+    }
 
-            worst_points.into_iter().for_each(|(i, s)| {
-                let (a, b, rgb) = line_segments.remove(i);
-                *ref_image -= ((a, b), rgb, args.step_size, args.string_alpha);
-                log_on_sub(args, line_segments.len(), s, a, b, rgb);
-            });
+    #[test]
+    fn test_timed_out_run_returns_data() {
+        use clap::Parser;
+        let cli = crate::cli_app::Cli::parse_from(["string_art", "-i", "in.png"]);
+        let args = Args::with_image(cli, stripes());
+        let pins = crate::pins::generate(&args.pin_arrangement, 8, 40, 40);
+        let untimed = color_on_custom(
+            pins.clone(),
+            args.clone(),
+            Vec::new(),
+            &Constraints::default(),
+            Vec::new(),
+        )
+        .unwrap();
+        assert!(!untimed.timed_out);
+        let args = Args {
+            time_limit: Some(1e-9),
+            ..args
+        };
+        let data =
+            color_on_custom(pins, args, Vec::new(), &Constraints::default(), Vec::new()).unwrap();
+        assert!(data.timed_out);
+        assert!(data.line_segments.is_empty());
+        assert!(data.warnings.iter().any(|w| w.contains("--time-limit")));
+    }
 
-            if line_segments.is_empty() {
-                keep_removing = false
-            }
-        }
+    #[test]
+    fn test_paused_run_saves_a_snapshot_and_resumes() {
+        use clap::Parser;
+        let snapshot = std::env::temp_dir().join("string_art_snapshot.png");
+        let snapshot = snapshot.to_str().unwrap();
+        let cli = crate::cli_app::Cli::parse_from([
+            "string_art",
+            "-i",
+            "in.png",
+            "--snapshot-filepath",
+            snapshot,
+        ]);
+        let args = Args::with_image(cli, stripes());
+        let pins = crate::pins::generate(&args.pin_arrangement, 8, 40, 40);
+        let pause = args.pause.clone();
+        pause.toggle();
+        let resume = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            pause.toggle();
+        });
+        let data =
+            color_on_custom(pins, args, Vec::new(), &Constraints::default(), Vec::new()).unwrap();
+        resume.join().unwrap();
+        let saved = image::open(snapshot).unwrap();
+        std::fs::remove_file(snapshot).unwrap();
+        assert_eq!((40, 40), (saved.width(), saved.height()));
+        assert!(!data.line_segments.is_empty());
     }
 
-    // Pause on the last frame
-    (0..10).for_each(|_| capture_frame(&mut possible_encoder, &line_segments, args, width, height));
+    #[test]
+    fn test_milestones_sheet_is_saved() {
+        use clap::Parser;
+        let sheet = std::env::temp_dir().join("string_art_milestones_{strings}.png");
+        let sheet = sheet.to_str().unwrap();
+        let cli = crate::cli_app::Cli::parse_from([
+            "string_art",
+            "-i",
+            "in.png",
+            "-m",
+            "20",
+            "--milestones",
+            "5,10",
+            "--milestones-filepath",
+            sheet,
+        ]);
+        let args = Args::with_image(cli, stripes());
+        let pins = crate::pins::generate(&args.pin_arrangement, 8, 40, 40);
+        let data =
+            color_on_custom(pins, args, Vec::new(), &Constraints::default(), Vec::new()).unwrap();
+        let sheet = template::expand(sheet, &data);
+        let saved = image::open(&sheet).unwrap();
+        std::fs::remove_file(&sheet).unwrap();
+        assert!(saved.width() > 40);
+    }
 
-    let final_score = ref_image.score();
-    if args.verbosity > 1 {
-        println!("(Recap) Initial score: {} (lower is better)", initial_score);
-        println!("Final score          : {}", final_score);
+    #[test]
+    fn test_unwritable_output_is_an_error() {
+        use clap::Parser;
+        let cli = crate::cli_app::Cli::parse_from([
+            "string_art",
+            "-i",
+            "in.png",
+            "-o",
+            "/nonexistent/art.png",
+            "-m",
+            "20",
+        ]);
+        let args = Args::with_image(cli, stripes());
+        let pins = crate::pins::generate(&args.pin_arrangement, 8, 40, 40);
+        let result = color_on_custom(pins, args, Vec::new(), &Constraints::default(), Vec::new());
+        assert!(matches!(result, Err(StringArtError::Write { .. })));
     }
 
-    (line_segments, initial_score, final_score)
+    fn draw_order(extra_args: &[&str]) -> Vec<Rgb> {
+        let red = Rgb::new(255, 0, 0);
+        let mut data = Data::example(extra_args);
+        data.line_segments = [Rgb::WHITE, red, Rgb::BLACK, red]
+            .iter()
+            .map(|rgb| (Point::new(0, 0), Point::new(9, 0), *rgb))
+            .collect();
+        data.drawn_line_segments()
+            .into_iter()
+            .map(|(_, _, rgb)| rgb)
+            .collect()
+    }
+
+    #[test]
+    fn test_draw_order() {
+        let red = Rgb::new(255, 0, 0);
+        assert_eq!(
+            vec![Rgb::WHITE, red, Rgb::BLACK, red],
+            draw_order(&["--draw-order", "threading"])
+        );
+        assert_eq!(
+            vec![Rgb::BLACK, red, red, Rgb::WHITE],
+            draw_order(&["--draw-order", "darkest-first"])
+        );
+        assert_eq!(
+            vec![Rgb::WHITE, red, red, Rgb::BLACK],
+            draw_order(&["--draw-order", "lightest-first"])
+        );
+        assert_eq!(
+            vec![red, red, Rgb::BLACK, Rgb::WHITE],
+            draw_order(&["--draw-order", "colors", "-f", "#FF0000", "-f", "#000000"])
+        );
+    }
+
+    #[test]
+    fn test_simulate_distance_keeps_detail_up_close() {
+        // 40 pixels across 4 meters is 10cm per pixel, which is easily seen from 1 meter
+        let near = simulate_distance(&stripes(), 4000.0, 1.0);
+        assert_eq!(255, contrast(&near));
+    }
 }
@@ -0,0 +1,203 @@
+use crate::archive;
+use crate::pin_stats::ColorCount;
+use crate::segment;
+use crate::style::Data;
+
+/// How long each part of a run took, in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Phases {
+    /// Loading and preprocessing the input image
+    pub preprocess: f64,
+    /// Placing the strings
+    pub optimize: f64,
+    /// Rendering and saving the images
+    pub render: f64,
+    /// Writing the pins image, data files, instructions, and archive
+    pub write: f64,
+}
+
+/// A compact summary of the finished runs, for `--stats`
+pub fn summary(datas: &[Data], phases: &Phases) -> String {
+    let mut lines = Vec::new();
+    for (i, data) in datas.iter().enumerate() {
+        if datas.len() > 1 {
+            lines.push(format!("Run {} of {}", i + 1, datas.len()));
+        }
+        lines.push(row("Strings", &strings(data)));
+        lines.push(row("Score", &score_improvement(data)));
+        lines.push(row("Thread length", &thread_length(data)));
+    }
+    lines.push(row(
+        "Time",
+        &format!(
+            "{:.1}s preprocess, {:.1}s optimize, {:.1}s render, {:.1}s write",
+            phases.preprocess, phases.optimize, phases.render, phases.write
+        ),
+    ));
+    for (_, filepath) in archive::artifacts(datas) {
+        let bytes = std::fs::metadata(&filepath).map_or(0, |m| m.len());
+        lines.push(row("File", &format!("{} ({})", filepath, file_size(bytes))));
+    }
+    lines.join("\n")
+}
+
+/// A labeled line, with the values lined up
+pub fn row(label: &str, value: &str) -> String {
+    format!("{:<20} : {}", label, value)
+}
+
+/// How many strings each color got, with every foreground color in the order it was given,
+/// even when it got none
+pub fn strings_per_color(data: &Data) -> Vec<ColorCount> {
+    let mut colors: Vec<ColorCount> = data
+        .args
+        .foreground_colors
+        .iter()
+        .map(|rgb| ColorCount {
+            rgb: *rgb,
+            strings: 0,
+        })
+        .collect();
+    for (_, _, rgb) in &data.line_segments {
+        match colors.iter_mut().find(|color| color.rgb == *rgb) {
+            Some(color) => color.strings += 1,
+            None => colors.push(ColorCount {
+                rgb: *rgb,
+                strings: 1,
+            }),
+        }
+    }
+    colors
+}
+
+/// The strings of each color, with their share of all the strings
+pub fn shares(data: &Data) -> String {
+    let total = usize::max(1, data.line_segments.len()) as f64;
+    let per_color: Vec<String> = strings_per_color(data)
+        .iter()
+        .map(|color| {
+            format!(
+                "{} {} ({:.1}%)",
+                color.rgb,
+                color.strings,
+                color.strings as f64 / total * 100.0
+            )
+        })
+        .collect();
+    per_color.join(", ")
+}
+
+fn strings(data: &Data) -> String {
+    let per_color: Vec<String> = strings_per_color(data)
+        .iter()
+        .map(|color| format!("{} {}", color.rgb, color.strings))
+        .collect();
+    format!("{} ({})", data.line_segments.len(), per_color.join(", "))
+}
+
+fn score_improvement(data: &Data) -> String {
+    let improvement = match data.initial_score {
+        0 => 0.0,
+        initial => (initial - data.final_score) as f64 / initial as f64 * 100.0,
+    };
+    format!(
+        "{} -> {} ({:.1}% better)",
+        data.initial_score, data.final_score, improvement
+    )
+}
+
+fn thread_length(data: &Data) -> String {
+    let last = segment::segments(data).last();
+    let px = last.as_ref().map_or(0.0, |s| s.cumulative_length_px);
+    match last.and_then(|s| s.cumulative_length_mm) {
+        Some(mm) => format!("{:.0} px ({:.2} m)", px, mm / 1000.0),
+        None => format!("{:.0} px", px),
+    }
+}
+
+/// Bytes in the largest unit that keeps the number at least 1
+fn file_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, units[unit]),
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::imagery::Rgb;
+
+    #[test]
+    fn test_strings_per_color() {
+        let mut data = Data::example(&[
+            "--foreground-color",
+            "#FFFFFF",
+            "--foreground-color",
+            "#000000",
+        ]);
+        let pins = data.pin_locations.clone();
+        data.line_segments = vec![
+            (pins[0], pins[1], Rgb::WHITE),
+            (pins[1], pins[2], Rgb::WHITE),
+            (pins[2], pins[0], Rgb::WHITE),
+        ];
+        assert_eq!(
+            vec![
+                ColorCount {
+                    rgb: Rgb::WHITE,
+                    strings: 3
+                },
+                ColorCount {
+                    rgb: Rgb::BLACK,
+                    strings: 0
+                },
+            ],
+            strings_per_color(&data)
+        );
+        assert_eq!("#FFFFFF 3 (100.0%), #000000 0 (0.0%)", shares(&data));
+        let value = serde_json::to_value(&data).unwrap();
+        assert_eq!(3, value["strings_per_color"][0]["strings"]);
+        assert_eq!(0, value["strings_per_color"][1]["strings"]);
+    }
+
+    #[test]
+    fn test_file_size() {
+        assert_eq!("0 B", file_size(0));
+        assert_eq!("1023 B", file_size(1023));
+        assert_eq!("1.5 KB", file_size(1536));
+        assert_eq!("3.0 MB", file_size(3 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut data = Data::example(&["--physical-width-mm", "1000"]);
+        data.line_segments.push((
+            data.pin_locations[1],
+            data.pin_locations[2],
+            Rgb { r: 255, g: 0, b: 0 },
+        ));
+        let phases = Phases {
+            preprocess: 0.3,
+            optimize: 12.0,
+            render: 1.04,
+            write: 0.0,
+        };
+        assert_eq!(
+            vec![
+                "Strings              : 2 (#FFFFFF 1, #FF0000 1)",
+                "Score                : 100 -> 42 (58.0% better)",
+                "Thread length        : 22 px (2.17 m)",
+                "Time                 : 0.3s preprocess, 12.0s optimize, 1.0s render, 0.0s write",
+            ],
+            summary(&[data], &phases).lines().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use crate::error::StringArtError;
+use crate::style::Data;
+
+/// The strings as an SVG, drawn in pixels of the image over a rectangle of the background color.
+/// Each string is a `<line>` in its own color, in the order they are drawn, so later strings
+/// cover earlier ones where they cross. With a physical width, the SVG is sized to print at it.
+pub fn svg(data: &Data) -> String {
+    let (width, height) = (data.image_width, data.image_height);
+    let size = match data.args.physical_width_mm {
+        Some(width_mm) => format!(
+            "width=\"{}mm\" height=\"{}mm\"",
+            width_mm,
+            width_mm * height as f64 / width as f64
+        ),
+        None => format!("width=\"{}\" height=\"{}\"", width, height),
+    };
+    let mut lines = vec![
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" {} viewBox=\"0 0 {} {}\">",
+            size, width, height
+        ),
+        format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            width, height, data.args.background_color
+        ),
+        format!(
+            "<g stroke-width=\"{}\" stroke-opacity=\"{}\" stroke-linecap=\"round\">",
+            data.args.svg_stroke_width, data.args.string_alpha
+        ),
+    ];
+    lines.extend(data.drawn_line_segments().iter().map(|(a, b, rgb)| {
+        format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\"/>",
+            a.x, a.y, b.x, b.y, rgb
+        )
+    }));
+    lines.push("</g>".to_owned());
+    lines.push("</svg>".to_owned());
+    lines.join("\n") + "\n"
+}
+
+/// Save the strings as an SVG
+pub fn write(data: &Data, filepath: &str) -> Result<(), StringArtError> {
+    std::fs::write(filepath, svg(data)).map_err(|error| StringArtError::write(filepath, error))
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::geometry::Point;
+    use crate::imagery::Rgb;
+
+    #[test]
+    fn test_one_line_per_string() {
+        let mut data = Data::example(&["--svg-filepath", "art.svg", "--svg-stroke-width", "2"]);
+        data.line_segments = vec![
+            (Point::new(0, 0), Point::new(9, 0), Rgb::WHITE),
+            (Point::new(0, 9), Point::new(9, 0), Rgb::new(255, 0, 0)),
+        ];
+        let svg = svg(&data);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"10\""));
+        assert!(svg.contains("<rect width=\"10\" height=\"10\" fill=\"#000000\"/>"));
+        assert!(svg.contains("stroke-width=\"2\""));
+        assert_eq!(
+            vec![
+                "<line x1=\"0\" y1=\"0\" x2=\"9\" y2=\"0\" stroke=\"#FFFFFF\"/>",
+                "<line x1=\"0\" y1=\"9\" x2=\"9\" y2=\"0\" stroke=\"#FF0000\"/>",
+            ],
+            svg.lines()
+                .filter(|line| line.starts_with("<line"))
+                .collect::<Vec<_>>()
+        );
+        assert!(svg.ends_with("</g>\n</svg>\n"));
+    }
+
+    #[test]
+    fn test_physical_size() {
+        let data = Data::example(&["--physical-width-mm", "500"]);
+        assert!(svg(&data).contains("width=\"500mm\" height=\"500mm\" viewBox=\"0 0 10 10\""));
+    }
+}
@@ -0,0 +1,223 @@
+use crate::cli_app::Args;
+use crate::contact_sheet;
+use crate::error::StringArtError;
+use crate::imagery::RefImage;
+use crate::optimum::Constraints;
+use crate::pins;
+use crate::pins::Arrangement;
+use crate::serde::{Deserialize, Serialize};
+use crate::style;
+use crate::term;
+
+/// One value of a parameter that can be swept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Setting {
+    StringAlpha(f64),
+    StepSize(f64),
+    PinCount(u32),
+    PinArrangement(Arrangement),
+    MaxStrings(usize),
+    MaxOverlap(u32),
+}
+
+impl Setting {
+    fn parse(name: &str, value: &str) -> Result<Self, String> {
+        let setting = match name {
+            "string_alpha" => value.parse().ok().map(Setting::StringAlpha),
+            "step_size" => value.parse().ok().map(Setting::StepSize),
+            "pin_count" => value.parse().ok().map(Setting::PinCount),
+            "pin_arrangement" => value.parse().ok().map(Setting::PinArrangement),
+            "max_strings" => value.parse().ok().map(Setting::MaxStrings),
+            "max_overlap" => value.parse().ok().map(Setting::MaxOverlap),
+            _ => {
+                return Err(format!(
+                    "Cannot sweep \"{}\". Choose from string_alpha, step_size, pin_count, \
+                     pin_arrangement, max_strings, and max_overlap",
+                    name
+                ))
+            }
+        };
+        setting.ok_or_else(|| format!("Invalid value for {}: \"{}\"", name, value))
+    }
+
+    fn apply(&self, args: &mut Args) {
+        match self {
+            Setting::StringAlpha(alpha) => args.string_alpha = *alpha,
+            Setting::StepSize(step) => args.step_size = *step,
+            Setting::PinCount(count) => args.pin_count = *count,
+            Setting::PinArrangement(arrangement) => args.pin_arrangement = arrangement.clone(),
+            Setting::MaxStrings(max) => args.max_strings = *max,
+            Setting::MaxOverlap(max) => args.max_overlap = Some(*max),
+        }
+    }
+}
+
+impl std::fmt::Display for Setting {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Setting::StringAlpha(alpha) => write!(f, "string_alpha={}", alpha),
+            Setting::StepSize(step) => write!(f, "step_size={}", step),
+            Setting::PinCount(count) => write!(f, "pin_count={}", count),
+            Setting::PinArrangement(arrangement) => write!(
+                f,
+                "pin_arrangement={}",
+                format!("{:?}", arrangement).to_lowercase()
+            ),
+            Setting::MaxStrings(max) => write!(f, "max_strings={}", max),
+            Setting::MaxOverlap(max) => write!(f, "max_overlap={}", max),
+        }
+    }
+}
+
+/// The values to try for each swept parameter, like
+/// `string_alpha=0.05,0.1,0.2;pin_count=150,250`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sweep(Vec<Vec<Setting>>);
+
+impl core::str::FromStr for Sweep {
+    type Err = String;
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        string
+            .split(';')
+            .filter(|parameter| !parameter.trim().is_empty())
+            .map(|parameter| {
+                let (name, values) = parameter
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected name=values but got \"{}\"", parameter))?;
+                values
+                    .split(',')
+                    .map(|value| Setting::parse(name.trim(), value.trim()))
+                    .collect()
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|parameters| match parameters.is_empty() {
+                true => Err("Expected at least one parameter to sweep".to_owned()),
+                false => Ok(Sweep(parameters)),
+            })
+    }
+}
+
+impl Sweep {
+    /// Every combination of the swept values, varying the last parameter fastest
+    pub fn configurations(&self) -> Vec<Vec<Setting>> {
+        self.0
+            .iter()
+            .fold(vec![Vec::new()], |configurations, values| {
+                configurations
+                    .iter()
+                    .flat_map(|configuration| {
+                        values.iter().map(move |value| {
+                            let mut configuration = configuration.clone();
+                            configuration.push(value.clone());
+                            configuration
+                        })
+                    })
+                    .collect()
+            })
+    }
+
+    /// Lay out the contact sheet so each row holds every value of the last parameter
+    fn columns(&self) -> usize {
+        self.0.last().map_or(1, |values| values.len())
+    }
+}
+
+/// Run a short optimization for every combination of the swept parameters and assemble their
+/// images into a labeled contact sheet
+pub fn sweep(args: &Args, sweep: &Sweep) -> Result<image::RgbaImage, StringArtError> {
+    let width = args.image.width();
+    let height = args.image.height();
+
+    let mut tiles = Vec::new();
+    for configuration in sweep.configurations() {
+        if args.cancellation.is_cancelled() {
+            break;
+        }
+        let mut run_args = Args {
+            max_strings: usize::min(args.max_strings, args.preview_strings),
+            ..args.without_outputs()
+        };
+        configuration
+            .iter()
+            .for_each(|setting| setting.apply(&mut run_args));
+        let label: Vec<String> = configuration.iter().map(|s| s.to_string()).collect();
+        term::info(args.verbosity, &format!("Sweeping {}", label.join(" ")));
+        let pin_locations = pins::from_args(&run_args, width, height);
+        let data = style::color_on_custom(
+            pin_locations,
+            run_args,
+            Vec::new(),
+            &Constraints::default(),
+            Vec::new(),
+        )?;
+        tiles.push((label, RefImage::from(&data).color()));
+    }
+    Ok(contact_sheet::contact_sheet(&tiles, sweep.columns()))
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::cli_app::Cli;
+    use clap::Parser;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parse_sweep() {
+        let sweep = Sweep::from_str("string_alpha=0.05,0.1; pin_count=150").unwrap();
+        assert_eq!(
+            Sweep(vec![
+                vec![Setting::StringAlpha(0.05), Setting::StringAlpha(0.1)],
+                vec![Setting::PinCount(150)],
+            ]),
+            sweep
+        );
+    }
+
+    #[test]
+    fn test_parse_sweep_errors() {
+        assert!(Sweep::from_str("").is_err());
+        assert!(Sweep::from_str("string_alpha").is_err());
+        assert!(Sweep::from_str("string_alpha=x").is_err());
+        assert!(Sweep::from_str("input_filepath=a.png").is_err());
+        assert!(Sweep::from_str("pin_arrangement=hexagon").is_err());
+    }
+
+    #[test]
+    fn test_configurations() {
+        let sweep = Sweep::from_str("string_alpha=0.1,0.2;pin_count=150,250,350").unwrap();
+        let configurations = sweep.configurations();
+        assert_eq!(6, configurations.len());
+        assert_eq!(
+            vec![Setting::StringAlpha(0.1), Setting::PinCount(250)],
+            configurations[1]
+        );
+        assert_eq!(3, sweep.columns());
+    }
+
+    #[test]
+    fn test_setting_label() {
+        assert_eq!("string_alpha=0.05", Setting::StringAlpha(0.05).to_string());
+        assert_eq!(
+            "pin_arrangement=circle",
+            Setting::PinArrangement(Arrangement::Circle).to_string()
+        );
+    }
+
+    #[test]
+    fn test_sweep() {
+        let cli = Cli::parse_from(["string_art", "-i", "in.png", "--preview-strings", "10"]);
+        let args = Args::with_image(cli, image::DynamicImage::new_rgb8(12, 12));
+        let sweep = Sweep::from_str("string_alpha=0.1,0.5;pin_count=8,12").unwrap();
+        let sheet = super::sweep(&args, &sweep).unwrap();
+        let single = contact_sheet::contact_sheet(
+            &[(
+                vec!["string_alpha=0.1".to_owned()],
+                image::RgbaImage::new(12, 12),
+            )],
+            1,
+        );
+        assert!(sheet.width() > single.width());
+        assert!(sheet.height() > single.height());
+    }
+}
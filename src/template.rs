@@ -0,0 +1,137 @@
+use crate::style::Data;
+
+/// Expand the `{placeholder}` names in an output filepath using the results of a finished run.
+///
+/// Supported placeholders:
+///
+/// - `{pins}`: number of pins
+/// - `{strings}`: number of strings in the finished work
+/// - `{score}`: final score
+/// - `{initial_score}`: initial score
+/// - `{colors}`: number of foreground colors
+/// - `{arrangement}`: pin arrangement
+/// - `{alpha}`: string alpha
+/// - `{step}`: step size
+/// - `{seconds}`: elapsed seconds, rounded
+/// - `{frame}`: frame number, when the input is animated
+pub fn expand(template: &str, data: &Data) -> String {
+    [
+        ("{pins}", data.pin_locations.len().to_string()),
+        ("{strings}", data.line_segments.len().to_string()),
+        ("{score}", data.final_score.to_string()),
+        ("{initial_score}", data.initial_score.to_string()),
+        ("{colors}", data.args.foreground_colors.len().to_string()),
+        (
+            "{arrangement}",
+            format!("{:?}", data.args.pin_arrangement).to_lowercase(),
+        ),
+        ("{alpha}", data.args.string_alpha.to_string()),
+        ("{step}", data.args.step_size.to_string()),
+        ("{seconds}", data.elapsed_seconds.round().to_string()),
+        ("{frame}", data.args.frame.unwrap_or(0).to_string()),
+    ]
+    .iter()
+    .fold(template.to_owned(), |path, (placeholder, value)| {
+        path.replace(placeholder, value)
+    })
+}
+
+/// Make sure each frame of an animation gets its own file by adding the `{frame}` placeholder
+/// before the file extension, unless the filepath already has it.
+pub fn per_frame(template: &str) -> String {
+    with_placeholder(template, "{frame}")
+}
+
+/// Make sure each run of an arrangement comparison gets its own file by adding the
+/// `{arrangement}` and `{pins}` placeholders, unless the filepath already has them.
+pub fn per_comparison(template: &str) -> String {
+    with_placeholder(&with_placeholder(template, "{arrangement}"), "{pins}")
+}
+
+/// Add the placeholder before the file extension, unless the filepath already has it
+fn with_placeholder(template: &str, placeholder: &str) -> String {
+    if template.contains(placeholder) {
+        return template.to_owned();
+    }
+    let path = std::path::Path::new(template);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => path
+            .with_file_name(format!(
+                "{}_{}.{}",
+                stem.to_string_lossy(),
+                placeholder,
+                extension.to_string_lossy()
+            ))
+            .to_string_lossy()
+            .into_owned(),
+        _ => format!("{}_{}", template, placeholder),
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+
+    fn data() -> Data {
+        Data::example(&[
+            "--pin-arrangement",
+            "circle",
+            "--string-alpha",
+            "0.1",
+            "--step-size",
+            "0.5",
+        ])
+    }
+
+    #[test]
+    fn test_expand_without_placeholders() {
+        assert_eq!("art.png", expand("art.png", &data()));
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        assert_eq!(
+            "art_3_1_42.png",
+            expand("art_{pins}_{strings}_{score}.png", &data())
+        );
+    }
+
+    #[test]
+    fn test_expand_all_placeholders() {
+        assert_eq!(
+            "100-1-circle-0.1-0.5-2",
+            expand(
+                "{initial_score}-{colors}-{arrangement}-{alpha}-{step}-{seconds}",
+                &data()
+            )
+        );
+    }
+
+    #[test]
+    fn test_expand_frame() {
+        let mut data = data();
+        data.args.frame = Some(7);
+        assert_eq!("art_7.png", expand("art_{frame}.png", &data));
+    }
+
+    #[test]
+    fn test_per_frame() {
+        assert_eq!("out/art_{frame}.png", per_frame("out/art.png"));
+        assert_eq!("art_{frame}", per_frame("art"));
+        assert_eq!("{frame}-art.png", per_frame("{frame}-art.png"));
+    }
+
+    #[test]
+    fn test_per_comparison() {
+        assert_eq!(
+            "out/art_{arrangement}_{pins}.png",
+            per_comparison("out/art.png")
+        );
+        assert_eq!("{pins}_art_{arrangement}", per_comparison("{pins}_art"));
+    }
+
+    #[test]
+    fn test_expand_unknown_placeholder_is_untouched() {
+        assert_eq!("art_{nope}.png", expand("art_{nope}.png", &data()));
+    }
+}
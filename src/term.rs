@@ -0,0 +1,115 @@
+use std::io::IsTerminal;
+
+/// How important a line of output is. Progress is printed to stdout as is, so it stands apart
+/// from the prefixed warnings and errors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    /// Details of the run, printed with -vv
+    Debug,
+    /// Progress through the run, printed with -v
+    Info,
+    /// Something the user should know about, which doesn't stop the run
+    Warn,
+    /// Something that stops the run, or makes its results unusable
+    Error,
+}
+
+impl Level {
+    /// Whether lines of this level are printed at the `verbosity` given by --verbose
+    pub fn shown_at(&self, verbosity: u8) -> bool {
+        match self {
+            Level::Debug => verbosity > 1,
+            Level::Info => verbosity > 0,
+            Level::Warn | Level::Error => true,
+        }
+    }
+
+    /// The prefix, and the ANSI escape code that colors it
+    fn prefix(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Level::Debug | Level::Info => None,
+            Level::Warn => Some(("warning:", "\x1b[1;33m")),
+            Level::Error => Some(("error:", "\x1b[1;31m")),
+        }
+    }
+}
+
+/// The message with its level's prefix, colored when `color` is true
+pub fn line(level: Level, message: &str, color: bool) -> String {
+    match (level.prefix(), color) {
+        (None, _) => message.to_owned(),
+        (Some((prefix, _)), false) => format!("{} {}", prefix, message),
+        (Some((prefix, ansi)), true) => format!("{}{}\x1b[0m {}", ansi, prefix, message),
+    }
+}
+
+/// Whether to color output sent to the stream: only for terminals, and never when the
+/// `NO_COLOR` environment variable is set to something other than an empty string
+pub fn color_for(stream: &impl IsTerminal) -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && stream.is_terminal()
+}
+
+/// Print a line of progress to stdout with -v
+pub fn info(verbosity: u8, message: &str) {
+    if Level::Info.shown_at(verbosity) {
+        println!("{}", line(Level::Info, message, false));
+    }
+}
+
+/// Print a detail of the run to stdout with -vv
+pub fn debug(verbosity: u8, message: &str) {
+    if Level::Debug.shown_at(verbosity) {
+        println!("{}", line(Level::Debug, message, false));
+    }
+}
+
+/// Print a warning to stderr
+pub fn warn(message: &str) {
+    eprintln!(
+        "{}",
+        line(Level::Warn, message, color_for(&std::io::stderr()))
+    );
+}
+
+/// Print an error to stderr
+pub fn error(message: &str) {
+    eprintln!(
+        "{}",
+        line(Level::Error, message, color_for(&std::io::stderr()))
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_plain_lines() {
+        assert_eq!("warning: Too thick", line(Level::Warn, "Too thick", false));
+        assert_eq!("error: No pins", line(Level::Error, "No pins", false));
+        assert_eq!("Frame 1 of 2", line(Level::Info, "Frame 1 of 2", true));
+        assert_eq!("Final score: 3", line(Level::Debug, "Final score: 3", true));
+    }
+
+    #[test]
+    fn test_levels_shown_at_verbosity() {
+        let shown = |level: Level| (0..3).filter(|v| level.shown_at(*v)).collect::<Vec<_>>();
+        assert_eq!(vec![2], shown(Level::Debug));
+        assert_eq!(vec![1, 2], shown(Level::Info));
+        assert_eq!(vec![0, 1, 2], shown(Level::Warn));
+        assert_eq!(vec![0, 1, 2], shown(Level::Error));
+    }
+
+    #[test]
+    fn test_colored_lines() {
+        assert_eq!(
+            "\x1b[1;33mwarning:\x1b[0m Too thick",
+            line(Level::Warn, "Too thick", true)
+        );
+        assert_eq!(
+            "\x1b[1;31merror:\x1b[0m No pins",
+            line(Level::Error, "No pins", true)
+        );
+    }
+}
@@ -0,0 +1,392 @@
+use crate::geometry::Point;
+use crate::imagery::LineSegment;
+use crate::imagery::Rgb;
+use std::collections::HashMap;
+
+/// The distance from where each string ends to where the next string of the same color starts,
+/// summed over every string. This is the thread that has to be carried between pins without
+/// being wound.
+pub fn dead_travel(line_segments: &[LineSegment]) -> f64 {
+    let mut ends: HashMap<Rgb, Point> = HashMap::new();
+    line_segments
+        .iter()
+        .map(|(a, b, rgb)| ends.insert(*rgb, *b).map_or(0.0, |end| distance(end, *a)))
+        .sum()
+}
+
+/// Reorder the strings to shorten the dead travel, and return how much shorter it got. The
+/// strings of each color are kept together, in the order the colors first appear, then are
+/// ordered by nearest neighbor and improved with 2-opt. Strings may be flipped end for end.
+pub fn minimize(line_segments: &mut Vec<LineSegment>) -> f64 {
+    let before = dead_travel(line_segments);
+    *line_segments = by_color(line_segments)
+        .into_iter()
+        .flat_map(|same_color| two_opt(nearest_neighbor(same_color)))
+        .collect();
+    before - dead_travel(line_segments)
+}
+
+/// Reorder the strings so each color can be wound with one continuous thread, where every
+/// string starts at the pin the last one ended at. The strings of each color are kept
+/// together, in the order the colors first appear. Where the strings can't all be wound in one
+/// go, bridging strings are added between the pieces, as few as possible, and are returned.
+pub fn continuous(line_segments: &mut Vec<LineSegment>) -> Vec<LineSegment> {
+    let mut bridges = Vec::new();
+    *line_segments = by_color(line_segments)
+        .into_iter()
+        .flat_map(|same_color| {
+            let mut path: Vec<LineSegment> = Vec::with_capacity(same_color.len());
+            for trail in chain(trails(&same_color)) {
+                if let (Some(&(_, end, rgb)), Some(&(start, _, _))) = (path.last(), trail.first()) {
+                    if end != start {
+                        bridges.push((end, start, rgb));
+                        path.push((end, start, rgb));
+                    }
+                }
+                path.extend(trail);
+            }
+            path
+        })
+        .collect();
+    bridges
+}
+
+/// The strings of each color, in the order the colors first appear
+fn by_color(line_segments: &[LineSegment]) -> Vec<Vec<LineSegment>> {
+    let mut colors: Vec<Rgb> = Vec::new();
+    line_segments.iter().for_each(|(_, _, rgb)| {
+        if !colors.contains(rgb) {
+            colors.push(*rgb);
+        }
+    });
+    colors
+        .into_iter()
+        .map(|rgb| {
+            line_segments
+                .iter()
+                .filter(|(_, _, c)| *c == rgb)
+                .copied()
+                .collect()
+        })
+        .collect()
+}
+
+/// Split strings of one color into as few trails as possible, each of which can be wound
+/// without lifting the thread. A trail has to start or end at each pin with an odd number of
+/// strings, so those pins are paired up by extra strings that make every pin even. Walking the
+/// resulting Euler circuits and cutting out the extra strings leaves the trails.
+fn trails(line_segments: &[LineSegment]) -> Vec<Vec<LineSegment>> {
+    let mut pins: Vec<Point> = Vec::new();
+    let mut index = HashMap::new();
+    let mut edges: Vec<(usize, usize, Option<Rgb>)> = line_segments
+        .iter()
+        .map(|(a, b, rgb)| {
+            let mut id = |p: Point| {
+                *index.entry(p).or_insert_with(|| {
+                    pins.push(p);
+                    pins.len() - 1
+                })
+            };
+            (id(*a), id(*b), Some(*rgb))
+        })
+        .collect();
+
+    let mut degrees = vec![0; pins.len()];
+    edges.iter().for_each(|(a, b, _)| {
+        degrees[*a] += 1;
+        degrees[*b] += 1;
+    });
+    let odd: Vec<usize> = (0..pins.len()).filter(|&p| degrees[p] % 2 == 1).collect();
+    odd.chunks(2)
+        .for_each(|pair| edges.push((pair[0], pair[1], None)));
+
+    let mut adjacent: Vec<Vec<(usize, usize)>> = vec![Vec::new(); pins.len()];
+    edges.iter().enumerate().for_each(|(e, (a, b, _))| {
+        adjacent[*a].push((e, *b));
+        adjacent[*b].push((e, *a));
+    });
+    let mut used = vec![false; edges.len()];
+    let mut next = vec![0; pins.len()];
+
+    let mut trails = Vec::new();
+    for start in 0..pins.len() {
+        // Hierholzer's algorithm, recording each edge as the walk backs out of it
+        let mut circuit: Vec<(usize, usize, usize)> = Vec::new();
+        let mut stack: Vec<(usize, Option<(usize, usize)>)> = vec![(start, None)];
+        while let Some(&(pin, arrived_by)) = stack.last() {
+            while next[pin] < adjacent[pin].len() && used[adjacent[pin][next[pin]].0] {
+                next[pin] += 1;
+            }
+            match adjacent[pin].get(next[pin]) {
+                Some(&(e, other)) => {
+                    used[e] = true;
+                    stack.push((other, Some((e, pin))));
+                }
+                None => {
+                    stack.pop();
+                    if let Some((e, from)) = arrived_by {
+                        circuit.push((e, from, pin));
+                    }
+                }
+            }
+        }
+        circuit.reverse();
+
+        // Start just after an extra string, so cutting them out leaves whole trails
+        if let Some(i) = circuit.iter().position(|(e, _, _)| edges[*e].2.is_none()) {
+            circuit.rotate_left(i + 1);
+        }
+        let mut trail = Vec::new();
+        for (e, from, to) in circuit {
+            match edges[e].2 {
+                Some(rgb) => trail.push((pins[from], pins[to], rgb)),
+                None => trails.push(std::mem::take(&mut trail)),
+            }
+        }
+        if !trail.is_empty() {
+            trails.push(trail);
+        }
+    }
+    trails.retain(|trail| !trail.is_empty());
+    trails
+}
+
+/// Order the trails, starting from the first, by going to the closest place the next one can
+/// start: either end of a trail, or anywhere along one that ends where it starts.
+fn chain(mut remaining: Vec<Vec<LineSegment>>) -> Vec<Vec<LineSegment>> {
+    let mut chained: Vec<Vec<LineSegment>> = Vec::with_capacity(remaining.len());
+    if remaining.is_empty() {
+        return chained;
+    }
+    chained.push(remaining.remove(0));
+    while !remaining.is_empty() {
+        let at = chained[chained.len() - 1].last().unwrap().1;
+        let (i, entry, _) = remaining
+            .iter()
+            .enumerate()
+            .flat_map(|(i, trail)| {
+                let closed = trail[0].0 == trail[trail.len() - 1].1;
+                let entries: Vec<(Entry, Point)> = match closed {
+                    true => trail
+                        .iter()
+                        .enumerate()
+                        .map(|(j, (a, _, _))| (Entry::Rotated(j), *a))
+                        .collect(),
+                    false => vec![
+                        (Entry::Forward, trail[0].0),
+                        (Entry::Reversed, trail[trail.len() - 1].1),
+                    ],
+                };
+                entries
+                    .into_iter()
+                    .map(move |(entry, p)| (i, entry, distance(at, p)))
+            })
+            .min_by(|x, y| x.2.total_cmp(&y.2))
+            .unwrap();
+        let mut trail = remaining.swap_remove(i);
+        match entry {
+            Entry::Forward => {}
+            Entry::Reversed => {
+                trail.reverse();
+                trail
+                    .iter_mut()
+                    .for_each(|s| std::mem::swap(&mut s.0, &mut s.1));
+            }
+            Entry::Rotated(j) => trail.rotate_left(j),
+        }
+        chained.push(trail);
+    }
+    chained
+}
+
+/// How a trail is wound when it is chained
+#[derive(Clone, Copy)]
+enum Entry {
+    Forward,
+    Reversed,
+    /// Starting from the start of its `n`th string, for a trail that ends where it starts
+    Rotated(usize),
+}
+
+/// Starting from the first string, repeatedly go to the closest end of the closest string left
+fn nearest_neighbor(mut remaining: Vec<LineSegment>) -> Vec<LineSegment> {
+    let mut path = Vec::with_capacity(remaining.len());
+    if remaining.is_empty() {
+        return path;
+    }
+    path.push(remaining.remove(0));
+    while !remaining.is_empty() {
+        let at = path[path.len() - 1].1;
+        let (i, flip) = remaining
+            .iter()
+            .enumerate()
+            .flat_map(|(i, (a, b, _))| [(i, false, distance(at, *a)), (i, true, distance(at, *b))])
+            .min_by(|x, y| x.2.total_cmp(&y.2))
+            .map(|(i, flip, _)| (i, flip))
+            .unwrap();
+        let (a, b, rgb) = remaining.swap_remove(i);
+        path.push(if flip { (b, a, rgb) } else { (a, b, rgb) });
+    }
+    path
+}
+
+/// Reverse runs of strings (flipping each one) while doing so shortens the path. Reversing
+/// strings `i..=j` only changes the travel into string `i` and out of string `j`.
+fn two_opt(mut path: Vec<LineSegment>) -> Vec<LineSegment> {
+    let n = path.len();
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 0..n {
+            for j in i + 1..n {
+                let before_in = if i > 0 { Some(path[i - 1].1) } else { None };
+                let after_out = path.get(j + 1).map(|s| s.0);
+                let current = before_in.map_or(0.0, |p| distance(p, path[i].0))
+                    + after_out.map_or(0.0, |p| distance(path[j].1, p));
+                let reversed = before_in.map_or(0.0, |p| distance(p, path[j].1))
+                    + after_out.map_or(0.0, |p| distance(path[i].0, p));
+                if reversed < current - 1e-9 {
+                    path[i..=j].reverse();
+                    path[i..=j]
+                        .iter_mut()
+                        .for_each(|s| std::mem::swap(&mut s.0, &mut s.1));
+                    improved = true;
+                }
+            }
+        }
+    }
+    path
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    let dx = a.x as f64 - b.x as f64;
+    let dy = a.y as f64 - b.y as f64;
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RED: Rgb = Rgb { r: 255, g: 0, b: 0 };
+
+    fn string(a: (u32, u32), b: (u32, u32), rgb: Rgb) -> LineSegment {
+        (Point::new(a.0, a.1), Point::new(b.0, b.1), rgb)
+    }
+
+    #[test]
+    fn test_dead_travel_follows_each_color() {
+        let line_segments = vec![
+            string((0, 0), (9, 0), Rgb::WHITE),
+            string((9, 3), (0, 0), Rgb::WHITE),
+            string((9, 9), (0, 9), RED),
+            string((0, 4), (9, 4), Rgb::WHITE),
+            string((0, 5), (9, 5), RED),
+        ];
+        assert_eq!(3.0 + 4.0 + 4.0, dead_travel(&line_segments));
+    }
+
+    #[test]
+    fn test_minimize() {
+        let mut line_segments = vec![
+            string((0, 0), (9, 0), Rgb::WHITE),
+            string((0, 9), (9, 9), RED),
+            string((0, 0), (0, 9), Rgb::WHITE),
+            string((9, 9), (9, 0), Rgb::WHITE),
+            string((9, 0), (0, 0), RED),
+        ];
+        assert_eq!(27.0, dead_travel(&line_segments));
+        assert_eq!(9.0, minimize(&mut line_segments));
+        assert_eq!(18.0, dead_travel(&line_segments));
+        // The colors stay together, in the order they first appeared
+        let colors: Vec<Rgb> = line_segments.iter().map(|s| s.2).collect();
+        assert_eq!(vec![Rgb::WHITE, Rgb::WHITE, Rgb::WHITE, RED, RED], colors);
+    }
+
+    #[test]
+    fn test_minimize_keeps_every_string() {
+        let mut line_segments: Vec<LineSegment> = (0..20)
+            .map(|i| string((i * 7 % 10, 0), (9 - i * 3 % 10, 9), Rgb::WHITE))
+            .collect();
+        let original = line_segments.clone();
+        assert!(minimize(&mut line_segments) >= 0.0);
+
+        let unordered = |segments: &[LineSegment]| {
+            let mut pairs: Vec<_> = segments
+                .iter()
+                .map(|(a, b, _)| {
+                    if (a.x, a.y) < (b.x, b.y) {
+                        (*a, *b)
+                    } else {
+                        (*b, *a)
+                    }
+                })
+                .map(|(a, b)| (a.x, a.y, b.x, b.y))
+                .collect();
+            pairs.sort();
+            pairs
+        };
+        assert_eq!(unordered(&original), unordered(&line_segments));
+    }
+
+    fn assert_continuous(line_segments: &[LineSegment]) {
+        for pair in line_segments.windows(2) {
+            if pair[0].2 == pair[1].2 {
+                assert_eq!(pair[0].1, pair[1].0, "{:?}", line_segments);
+            }
+        }
+    }
+
+    #[test]
+    fn test_continuous_without_bridges() {
+        // A square with a diagonal has two odd pins, so it can be wound in one go between them
+        let mut line_segments = vec![
+            string((0, 0), (9, 0), Rgb::WHITE),
+            string((0, 9), (9, 9), Rgb::WHITE),
+            string((9, 9), (0, 0), Rgb::WHITE),
+            string((9, 0), (9, 9), Rgb::WHITE),
+            string((0, 0), (0, 9), Rgb::WHITE),
+        ];
+        assert!(continuous(&mut line_segments).is_empty());
+        assert_eq!(5, line_segments.len());
+        assert_continuous(&line_segments);
+    }
+
+    #[test]
+    fn test_continuous_bridges_each_color() {
+        // Two strings with no shared pin need one bridge; each color is its own thread
+        let mut line_segments = vec![
+            string((0, 0), (9, 0), Rgb::WHITE),
+            string((0, 5), (0, 9), RED),
+            string((0, 9), (9, 9), Rgb::WHITE),
+            string((9, 5), (9, 9), RED),
+        ];
+        let bridges = continuous(&mut line_segments);
+        assert_eq!(2, bridges.len());
+        assert_eq!(6, line_segments.len());
+        assert_continuous(&line_segments);
+        let colors: Vec<Rgb> = line_segments.iter().map(|s| s.2).collect();
+        assert_eq!(
+            vec![Rgb::WHITE, Rgb::WHITE, Rgb::WHITE, RED, RED, RED],
+            colors
+        );
+        // Each bridge is as short as it can be
+        assert!(bridges.iter().all(|(a, b, _)| distance(*a, *b) == 9.0));
+    }
+
+    #[test]
+    fn test_continuous_uses_the_fewest_bridges() {
+        // A star's four odd tips make two trails, and the separate loop a third, so two bridges
+        let mut line_segments = vec![
+            string((5, 5), (0, 5), Rgb::WHITE),
+            string((5, 5), (9, 5), Rgb::WHITE),
+            string((5, 5), (5, 0), Rgb::WHITE),
+            string((5, 5), (5, 9), Rgb::WHITE),
+            string((0, 0), (9, 0), Rgb::WHITE),
+            string((9, 0), (0, 0), Rgb::WHITE),
+        ];
+        assert_eq!(2, continuous(&mut line_segments).len());
+        assert_eq!(8, line_segments.len());
+        assert_continuous(&line_segments);
+    }
+}
@@ -0,0 +1,316 @@
+use crate::geometry::Point;
+use crate::imagery::Rgb;
+use crate::segment::SavedSegment;
+use crate::serde::Deserialize;
+use crate::string_art;
+use crate::style::SCHEMA_VERSION;
+use crate::term;
+use crate::term::Level;
+use clap::Parser;
+use std::collections::HashMap;
+
+/// Check a data file for problems before it is used to wind the strings, such as by a winding
+/// machine. Exits with a failure status if any errors are found.
+#[derive(Parser, Debug)]
+#[command(name = "string_art validate", version, max_term_width(100))]
+pub struct ValidateCli {
+    /// The data file to check, compressed or not.
+    #[arg(short, long, value_name("FILEPATH"))]
+    pub data_filepath: String,
+}
+
+/// The parts of the arguments that are checked
+#[derive(Deserialize)]
+struct SavedArgs {
+    foreground_colors: Vec<Rgb>,
+}
+
+/// The parts of a data file that are checked
+#[derive(Deserialize)]
+pub struct DataFile {
+    schema_version: Option<u32>,
+    args: SavedArgs,
+    image_width: u32,
+    image_height: u32,
+    pin_locations: Vec<Point>,
+    line_segments: Vec<SavedSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// The file can't be wound as written
+    Error(String),
+    /// The file can be wound, but something is unusual
+    Warning(String),
+}
+
+impl Issue {
+    /// The issue with its level's prefix, colored when `color` is true
+    fn line(&self, color: bool) -> String {
+        match self {
+            Issue::Error(message) => term::line(Level::Error, message, color),
+            Issue::Warning(message) => term::line(Level::Warn, message, color),
+        }
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.line(false))
+    }
+}
+
+/// Points are written without padding, unlike their `Display`
+fn at(point: &Point) -> String {
+    format!("({}, {})", point.x, point.y)
+}
+
+/// Every problem found in the data file, in the order of the file
+pub fn check(file: &DataFile) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    match file.schema_version {
+        None => issues.push(Issue::Warning(
+            "There is no schema version, so the file was written by an older string_art".to_owned(),
+        )),
+        Some(version) if version > SCHEMA_VERSION => issues.push(Issue::Error(format!(
+            "Schema version {} is newer than this string_art understands ({})",
+            version, SCHEMA_VERSION
+        ))),
+        Some(_) => {}
+    }
+
+    let inside = |point: &Point| point.x < file.image_width && point.y < file.image_height;
+    let mut pins: HashMap<Point, usize> = HashMap::new();
+    for (i, pin) in file.pin_locations.iter().enumerate() {
+        if !inside(pin) {
+            issues.push(Issue::Error(format!(
+                "Pin {} at {} is outside the {}x{} image",
+                i,
+                at(pin),
+                file.image_width,
+                file.image_height
+            )));
+        }
+        if let Some(first) = pins.insert(*pin, i) {
+            issues.push(Issue::Warning(format!(
+                "Pins {} and {} are both at {}",
+                first,
+                i,
+                at(pin)
+            )));
+        }
+    }
+
+    let mut seen: HashMap<(Point, Point, Rgb), usize> = HashMap::new();
+    for (i, saved) in file.line_segments.iter().enumerate() {
+        let (a, b, rgb, pin_a, pin_b, count) = match saved {
+            SavedSegment::Tuple((a, b, rgb)) => (*a, *b, *rgb, None, None, 1),
+            SavedSegment::Detailed {
+                a,
+                b,
+                rgb,
+                pin_a,
+                pin_b,
+                count,
+            } => (*a, *b, *rgb, *pin_a, *pin_b, *count),
+        };
+        if count == 0 {
+            issues.push(Issue::Error(format!("String {} is wound 0 times", i)));
+        }
+        for (point, pin) in [(a, pin_a), (b, pin_b)] {
+            match pin {
+                Some(pin) if pin >= file.pin_locations.len() => issues.push(Issue::Error(format!(
+                    "String {} uses pin {}, but there are only {} pins",
+                    i,
+                    pin,
+                    file.pin_locations.len()
+                ))),
+                Some(pin) if file.pin_locations[pin] != point => {
+                    issues.push(Issue::Error(format!(
+                        "String {} uses pin {} at {}, but the pin is at {}",
+                        i,
+                        pin,
+                        at(&point),
+                        at(&file.pin_locations[pin])
+                    )))
+                }
+                _ if !inside(&point) => issues.push(Issue::Error(format!(
+                    "String {} ends at {}, outside the image",
+                    i,
+                    at(&point)
+                ))),
+                _ if !pins.contains_key(&point) => issues.push(Issue::Warning(format!(
+                    "String {} ends at {}, which is not a pin",
+                    i,
+                    at(&point)
+                ))),
+                _ => {}
+            }
+        }
+        if a == b {
+            issues.push(Issue::Error(format!(
+                "String {} starts and ends at {}",
+                i,
+                at(&a)
+            )));
+        }
+        if !file.args.foreground_colors.contains(&rgb) {
+            issues.push(Issue::Error(format!(
+                "String {} is {}, which is not a foreground color",
+                i, rgb
+            )));
+        }
+        let key = if (a.x, a.y) <= (b.x, b.y) {
+            (a, b, rgb)
+        } else {
+            (b, a, rgb)
+        };
+        if let Some(first) = seen.insert(key, i) {
+            issues.push(Issue::Warning(format!(
+                "String {} is the same as string {}",
+                i, first
+            )));
+        }
+    }
+    issues
+}
+
+/// Run `string_art validate`, printing a report of the data file
+pub fn validate() {
+    // Skip the program name, so `validate` is treated as the name of the command
+    let cli = ValidateCli::parse_from(std::env::args().skip(1));
+    let file: DataFile = string_art::read_data(&cli.data_filepath).unwrap_or_else(|error| {
+        term::error(&error.to_string());
+        std::process::exit(1)
+    });
+
+    let issues = check(&file);
+    let color = term::color_for(&std::io::stdout());
+    issues
+        .iter()
+        .for_each(|issue| println!("{}", issue.line(color)));
+    let errors = issues
+        .iter()
+        .filter(|issue| matches!(issue, Issue::Error(_)))
+        .count();
+    println!(
+        "{} strings on {} pins: {} errors, {} warnings",
+        file.line_segments.len(),
+        file.pin_locations.len(),
+        errors,
+        issues.len() - errors
+    );
+    if errors > 0 {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::style::Data;
+
+    /// The example data, written and read back
+    fn file(edit: impl Fn(&mut serde_json::Value)) -> DataFile {
+        let mut value = serde_json::to_value(Data::example(&[])).unwrap();
+        edit(&mut value);
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn messages(file: &DataFile) -> Vec<String> {
+        check(file).iter().map(|issue| issue.to_string()).collect()
+    }
+
+    #[test]
+    fn test_written_data_is_valid() {
+        assert_eq!(Vec::<Issue>::new(), check(&file(|_| {})));
+    }
+
+    #[test]
+    fn test_count_of_zero() {
+        let file = file(|value| value["line_segments"][0]["count"] = 0.into());
+        assert_eq!(vec!["error: String 0 is wound 0 times"], messages(&file));
+    }
+
+    #[test]
+    fn test_schema_version() {
+        let newer = file(|value| value["schema_version"] = (SCHEMA_VERSION + 1).into());
+        assert!(matches!(check(&newer)[..], [Issue::Error(_)]));
+        let missing = file(|value| {
+            value.as_object_mut().unwrap().remove("schema_version");
+        });
+        assert!(matches!(check(&missing)[..], [Issue::Warning(_)]));
+    }
+
+    #[test]
+    fn test_pin_index_out_of_bounds() {
+        let file = file(|value| value["line_segments"][0]["pin_b"] = 7.into());
+        assert_eq!(
+            vec!["error: String 0 uses pin 7, but there are only 3 pins"],
+            messages(&file)
+        );
+    }
+
+    #[test]
+    fn test_pin_index_at_the_wrong_point() {
+        let file = file(|value| value["line_segments"][0]["pin_b"] = 2.into());
+        assert_eq!(
+            vec!["error: String 0 uses pin 2 at (9, 0), but the pin is at (0, 9)"],
+            messages(&file)
+        );
+    }
+
+    #[test]
+    fn test_color_membership() {
+        let file = file(|value| value["line_segments"][0]["rgb"]["g"] = 0.into());
+        assert_eq!(
+            vec!["error: String 0 is #FF00FF, which is not a foreground color"],
+            messages(&file)
+        );
+    }
+
+    #[test]
+    fn test_duplicate_segments() {
+        let file = file(|value| {
+            let segments = value["line_segments"].as_array_mut().unwrap();
+            let mut flipped = segments[0].clone();
+            flipped["a"] = segments[0]["b"].clone();
+            flipped["b"] = segments[0]["a"].clone();
+            flipped["pin_a"] = segments[0]["pin_b"].clone();
+            flipped["pin_b"] = segments[0]["pin_a"].clone();
+            segments.push(flipped);
+        });
+        assert_eq!(
+            vec!["warning: String 1 is the same as string 0"],
+            messages(&file)
+        );
+    }
+
+    #[test]
+    fn test_geometry() {
+        let file = file(|value| {
+            value["pin_locations"][2]["y"] = 10.into();
+            value["line_segments"][0]["b"] = serde_json::json!({"x": 0, "y": 0});
+            value["line_segments"][0]["pin_b"] = serde_json::Value::Null;
+        });
+        assert_eq!(
+            vec![
+                "error: Pin 2 at (0, 10) is outside the 10x10 image",
+                "error: String 0 starts and ends at (0, 0)",
+            ],
+            messages(&file)
+        );
+    }
+
+    #[test]
+    fn test_endpoints_off_the_pins() {
+        let file = file(|value| {
+            value["line_segments"][0]["b"] = serde_json::json!({"x": 5, "y": 5});
+            value["line_segments"][0]["pin_b"] = serde_json::Value::Null;
+        });
+        assert_eq!(
+            vec!["warning: String 0 ends at (5, 5), which is not a pin"],
+            messages(&file)
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use crate::cli_app::Args;
+use crate::geometry::Point;
+use crate::optimizer::Strategy;
+use crate::pins::Arrangement;
+use crate::style::Data;
+use crate::term;
+
+/// Above this alpha, a single string changes a pixel by more than half of the color range
+const MAX_REASONABLE_ALPHA: f64 = 0.5;
+
+/// Colors with less than this fraction of the strings are barely used
+const NEGLIGIBLE_COLOR_FRACTION: f64 = 0.01;
+
+/// Print a warning to stderr and return it for storing in the `Data`
+pub fn emit(message: String) -> String {
+    term::warn(&message);
+    message
+}
+
+/// Problems with the arguments that are known before creating the string art
+pub fn check_args(args: &Args) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if args.foreground_colors.contains(&args.background_color) {
+        warnings.push(format!(
+            "The foreground color {} is the same as the background color, so it can't be seen",
+            args.background_color
+        ));
+    }
+    if args.dither && args.foreground_colors.len() > 1 {
+        warnings.push(
+            "--dither only works with a single foreground color, so the image was not dithered"
+                .to_owned(),
+        );
+    }
+    if args.string_alpha > MAX_REASONABLE_ALPHA {
+        warnings.push(format!(
+            "With a string alpha of {}, a single string saturates the pixels it crosses, so \
+             strings can't blend. Consider a lower --string-alpha",
+            args.string_alpha
+        ));
+    }
+    if args.perturb_rounds.is_some() && args.optimizer == Strategy::GreedyWalk {
+        warnings.push(
+            "--perturb-rounds would break the greedy-walk thread into pieces, so it was ignored"
+                .to_owned(),
+        );
+    }
+    warnings
+}
+
+/// Fewer pins than asked for, because some would have been too close to others. A grid is left
+/// alone, since it rounds to whole rows and columns.
+pub fn check_pins(args: &Args, pin_locations: &[Point]) -> Option<String> {
+    let placed = pin_locations.len();
+    (args.pin_arrangement != Arrangement::Grid && placed < args.pin_count as usize).then(|| {
+        format!(
+            "Only {} of the {} pins were placed, since the rest would have been too close to \
+             others. Consider a lower --pin-count",
+            placed, args.pin_count
+        )
+    })
+}
+
+/// Foreground colors that ended up with no strings, or with very few
+pub fn check_colors(data: &Data) -> Vec<String> {
+    let total = data.line_segments.len();
+    let mut colors: Vec<_> = data.args.foreground_colors.iter().collect();
+    colors.sort_by_key(|rgb| rgb.to_string());
+    colors
+        .into_iter()
+        .filter_map(|rgb| {
+            let count = data
+                .line_segments
+                .iter()
+                .filter(|(_, _, c)| c == rgb)
+                .count();
+            if count == 0 {
+                Some(format!("The foreground color {} was never used", rgb))
+            } else if (count as f64) < total as f64 * NEGLIGIBLE_COLOR_FRACTION {
+                Some(format!(
+                    "The foreground color {} was barely used ({} of {} strings)",
+                    rgb, count, total
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use super::*;
+    use crate::imagery::Rgb;
+
+    #[test]
+    fn test_check_args_without_problems() {
+        assert!(check_args(&Data::example(&[]).args).is_empty());
+    }
+
+    #[test]
+    fn test_check_args_foreground_is_background() {
+        let args = Data::example(&["-f", "#000000", "-f", "#FFFFFF"]).args;
+        assert_eq!(1, check_args(&args).len());
+    }
+
+    #[test]
+    fn test_check_args_alpha_too_high() {
+        let args = Data::example(&["--string-alpha", "0.9"]).args;
+        assert_eq!(1, check_args(&args).len());
+    }
+
+    #[test]
+    fn test_check_args_dither_with_several_colors() {
+        let args = Data::example(&["--dither", "-f", "#FFFFFF", "-f", "#FF0000"]).args;
+        assert_eq!(1, check_args(&args).len());
+        assert!(check_args(&Data::example(&["--dither"]).args).is_empty());
+    }
+
+    #[test]
+    fn test_check_args_perturb_with_greedy_walk() {
+        let args = Data::example(&["--optimizer", "greedy-walk", "--perturb-rounds", "3"]).args;
+        assert_eq!(1, check_args(&args).len());
+        let args = Data::example(&["--perturb-rounds", "3"]).args;
+        assert!(check_args(&args).is_empty());
+    }
+
+    #[test]
+    fn test_check_pins() {
+        let args = Data::example(&["-c", "4"]).args;
+        let pins = [Point::new(0, 0), Point::new(9, 0), Point::new(9, 9)];
+        assert!(check_pins(&args, &pins)
+            .unwrap()
+            .starts_with("Only 3 of the 4 pins"));
+        let all = [pins.as_slice(), &[Point::new(0, 9)]].concat();
+        assert_eq!(None, check_pins(&args, &all));
+        let args = Data::example(&["-c", "4", "-r", "grid"]).args;
+        assert_eq!(None, check_pins(&args, &pins));
+    }
+
+    #[test]
+    fn test_check_colors_all_used() {
+        assert!(check_colors(&Data::example(&[])).is_empty());
+    }
+
+    #[test]
+    fn test_check_colors_unused() {
+        let data = Data::example(&["-f", "#FFFFFF", "-f", "#FF0000"]);
+        assert_eq!(
+            vec!["The foreground color #FF0000 was never used".to_owned()],
+            check_colors(&data)
+        );
+    }
+
+    #[test]
+    fn test_check_colors_barely_used() {
+        let mut data = Data::example(&["-f", "#FFFFFF", "-f", "#FF0000"]);
+        let white = data.line_segments[0];
+        data.line_segments = vec![white; 200];
+        data.line_segments
+            .push((Point::new(0, 0), Point::new(0, 9), Rgb::new(255, 0, 0)));
+        assert_eq!(1, check_colors(&data).len());
+        assert!(check_colors(&data)[0].contains("barely"));
+    }
+}